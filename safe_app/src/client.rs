@@ -11,7 +11,7 @@ use crate::errors::AppError;
 use crate::{AppContext, AppMsgTx};
 use lru_cache::LruCache;
 use rust_sodium::crypto::{box_, sign};
-use safe_core::client::{ClientInner, SafeKey, IMMUT_DATA_CACHE_SIZE};
+use safe_core::client::{ClientInner, SafeKey, IMMUT_DATA_CACHE_SIZE, REQUEST_TIMEOUT_SECS};
 use safe_core::config_handler::Config;
 use safe_core::crypto::{shared_box, shared_secretbox, shared_sign};
 use safe_core::ipc::BootstrapConfig;
@@ -44,16 +44,17 @@ impl AppClient {
         let client_keys = ClientKeys::new(None);
         let client_pk = PublicKey::from(client_keys.bls_pk);
 
-        let mut qp2p_config = Config::new().quic_p2p;
+        let mut core_config = Config::new();
         if let Some(additional_contacts) = config.clone() {
-            qp2p_config.hard_coded_contacts = qp2p_config
+            core_config.quic_p2p.hard_coded_contacts = core_config
+                .quic_p2p
                 .hard_coded_contacts
                 .union(&additional_contacts)
                 .cloned()
                 .collect();
         }
 
-        let mut connection_manager = ConnectionManager::new(qp2p_config, &net_tx.clone())?;
+        let mut connection_manager = ConnectionManager::new(core_config, &net_tx.clone())?;
         block_on_all(connection_manager.bootstrap(client_keys.app_safe_key(client_pk)))?;
 
         Ok(Self {
@@ -61,7 +62,7 @@ impl AppClient {
                 el_handle,
                 connection_manager,
                 LruCache::new(IMMUT_DATA_CACHE_SIZE),
-                Duration::from_secs(180), // REQUEST_TIMEOUT_SECS), // FIXMe
+                Duration::from_secs(REQUEST_TIMEOUT_SECS),
                 core_tx,
                 net_tx,
             ))),
@@ -126,14 +127,15 @@ impl AppClient {
     {
         trace!("Attempting to log into an acc using client keys.");
 
-        let mut qp2p_config = Config::new().quic_p2p;
-        qp2p_config.hard_coded_contacts = qp2p_config
+        let mut core_config = Config::new();
+        core_config.quic_p2p.hard_coded_contacts = core_config
+            .quic_p2p
             .hard_coded_contacts
             .union(&config)
             .cloned()
             .collect();
 
-        let mut connection_manager = ConnectionManager::new(qp2p_config, &net_tx.clone())?;
+        let mut connection_manager = ConnectionManager::new(core_config, &net_tx.clone())?;
         let _ = block_on_all(connection_manager.bootstrap(keys.clone().app_safe_key(owner)));
 
         connection_manager = connection_manager_wrapper_fn(connection_manager);
@@ -143,7 +145,7 @@ impl AppClient {
                 el_handle,
                 connection_manager,
                 LruCache::new(IMMUT_DATA_CACHE_SIZE),
-                Duration::from_secs(180), // REQUEST_TIMEOUT_SECS), // FIXME
+                Duration::from_secs(REQUEST_TIMEOUT_SECS),
                 core_tx,
                 net_tx,
             ))),
@@ -34,23 +34,44 @@ pub mod object_cache;
 /// Testing utilities.
 #[cfg(any(test, feature = "testing"))]
 pub mod test_utils;
+/// Coin wallet of the app.
+pub mod wallet;
+/// `safe://` XOR-URLs.
+pub mod xor_url;
 
 mod helper;
 #[cfg(test)]
 mod tests;
 
+use super::client::AppClient;
 use super::errors::AppError;
 use super::App;
 use config_file_handler;
 use ffi_utils::{catch_unwind_cb, from_c_str, FfiResult, OpaqueCtx, ReprC, FFI_RESULT_OK};
+use futures::{Future, Stream};
 use maidsafe_utilities::serialisation::deserialise;
+use safe_core::config_handler::{self, IDataCacheConfig};
 use safe_core::ffi::ipc::resp::AuthGranted;
+use safe_core::ffi::{BootstrapContact, FfiHost};
 use safe_core::ipc::{AuthGranted as NativeAuthGranted, BootstrapConfig};
-use safe_core::{self, Client};
+use safe_core::{self, Client, FutureExt, NetworkNotification};
 use std::ffi::{CStr, CString, OsStr};
 use std::os::raw::{c_char, c_void};
+use std::path::PathBuf;
 use std::slice;
 
+impl FfiHost for App {
+    type Client = AppClient;
+    type Error = AppError;
+
+    fn send<F>(&self, f: F) -> Result<(), AppError>
+    where
+        F: FnOnce(&AppClient) -> Option<Box<dyn Future<Item = (), Error = ()>>> + Send + 'static,
+    {
+        App::send(self, move |client, _| f(client))
+    }
+}
+
 /// Create unregistered app.
 /// The `user_data` parameter corresponds to the first parameter of the
 /// `o_cb` and `o_disconnect_notifier_cb` callbacks.
@@ -116,17 +137,76 @@ pub unsafe extern "C" fn app_reconnect(
     catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
         let user_data = OpaqueCtx(user_data);
         (*app).send(move |client, _| {
-            try_cb!(
-                client.restart_network().map_err(AppError::from),
-                user_data.0,
-                o_cb
-            );
+            Some(
+                client
+                    .restart_network()
+                    .map_err(AppError::from)
+                    .then(move |res| {
+                        call_result_cb!(res, user_data, o_cb);
+                        Ok(())
+                    })
+                    .into_box(),
+            )
+        })
+    })
+}
+
+/// Enable or disable automatic reconnection after the connection with the network has been lost.
+#[no_mangle]
+pub unsafe extern "C" fn app_set_auto_reconnect(
+    app: *mut App,
+    enabled: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        (*app).send(move |client, _| {
+            client.set_auto_reconnect(enabled);
             o_cb(user_data.0, FFI_RESULT_OK);
             None
         })
     })
 }
 
+/// Subscribe to the notifications the network sends to the app, such as incoming coin
+/// transfers. `o_notification_cb` is called with the transaction ID and the amount (in nano
+/// coins) of every transfer received. `o_cb` is called once the subscription is set up.
+#[no_mangle]
+pub unsafe extern "C" fn app_subscribe_notifications(
+    app: *mut App,
+    user_data: *mut c_void,
+    o_notification_cb: extern "C" fn(user_data: *mut c_void, transaction_id: u64, amount: u64),
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        (*app).send(move |client, _| {
+            client
+                .subscribe_notifications()
+                .map_err(AppError::from)
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .and_then(move |notifications| {
+                    o_cb(user_data.0, FFI_RESULT_OK);
+
+                    notifications.for_each(move |notification| {
+                        match notification {
+                            NetworkNotification::TransferReceived {
+                                transaction_id,
+                                amount,
+                            } => o_notification_cb(user_data.0, transaction_id, amount.as_nano()),
+                        }
+                        Ok(())
+                    })
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
 /// Returns the expected name for the application executable without an extension
 #[no_mangle]
 pub unsafe extern "C" fn app_exe_file_stem(
@@ -165,6 +245,81 @@ pub unsafe extern "C" fn app_set_additional_search_path(
     });
 }
 
+/// Returns the contacts in the bootstrap cache, most recent first.
+#[no_mangle]
+pub unsafe extern "C" fn app_bootstrap_cache(
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        contacts: *const BootstrapContact,
+        contacts_len: usize,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let cache = config_handler::read_bootstrap_cache()?;
+        let peer_addrs = cache
+            .contacts()
+            .iter()
+            .map(|contact| CString::new(contact.node_info.peer_addr.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let contacts: Vec<_> = cache
+            .contacts()
+            .iter()
+            .zip(&peer_addrs)
+            .map(|(contact, peer_addr)| BootstrapContact {
+                peer_addr: peer_addr.as_ptr(),
+                last_success: contact.last_success.timestamp(),
+            })
+            .collect();
+
+        o_cb(user_data, FFI_RESULT_OK, contacts.as_ptr(), contacts.len());
+        Ok(())
+    })
+}
+
+/// Removes all contacts from the bootstrap cache.
+#[no_mangle]
+pub unsafe extern "C" fn app_clear_bootstrap_cache(
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        config_handler::clear_bootstrap_cache()?;
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
+/// Configures the on-disk cache of immutable data shared by the apps of the current user.
+/// A null `path` selects the default location in the user's cache directory. The new options
+/// apply to the apps created afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn app_set_idata_cache_config(
+    enabled: bool,
+    path: *const c_char,
+    max_size: u64,
+    published_only: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let path = if path.is_null() {
+            None
+        } else {
+            Some(PathBuf::from(from_c_str(path)?))
+        };
+        let _ = config_handler::write_idata_cache_config(&IDataCacheConfig {
+            enabled,
+            path,
+            max_size,
+            published_only,
+        })?;
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
 /// Discard and clean up the previously allocated app instance.
 /// Use this only if the app is obtained from one of the auth
 /// functions in this crate. Using `app` after a call to this
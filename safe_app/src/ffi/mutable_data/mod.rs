@@ -23,7 +23,8 @@ use crate::ffi::object_cache::{
 };
 use crate::App;
 use ffi_utils::{
-    catch_unwind_cb, vec_clone_from_raw_parts, FfiResult, OpaqueCtx, ReprC, SafePtr, FFI_RESULT_OK,
+    catch_unwind_cb, vec_clone_from_raw_parts, ErrorCode, FfiResult, OpaqueCtx, ReprC, SafePtr,
+    FFI_RESULT_OK,
 };
 use futures::Future;
 use maidsafe_utilities::serialisation::serialised_size;
@@ -35,8 +36,9 @@ use safe_core::ipc::req::{permission_set_clone_from_repr_c, permission_set_into_
 use safe_core::ipc::resp::{MDataKey as NativeMDataKey, MDataValue as NativeMDataValue};
 use safe_core::Client;
 use safe_core::{FutureExt, MDataInfo as NativeMDataInfo};
-use safe_nd::SeqMutableData;
+use safe_nd::{MDataAddress, MDataEntryActions, SeqMutableData};
 use std::os::raw::c_void;
+use std::slice;
 
 /// Special value that represents an empty permission set.
 #[no_mangle]
@@ -359,6 +361,81 @@ pub unsafe extern "C" fn mdata_mutate_entries(
     })
 }
 
+/// Mutate the entries of several mutable data at once, applying the actions of `actions_hs[i]` to
+/// `infos[i]`. The requests are pipelined. The callback receives the error code of each mutation,
+/// in the same order, with 0 for the mutations which succeeded.
+#[no_mangle]
+pub unsafe extern "C" fn mdata_mutate_entries_batch(
+    app: *const App,
+    infos: *const MDataInfo,
+    actions_hs: *const MDataEntryActionsHandle,
+    len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        error_codes: *const i32,
+        error_codes_len: usize,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let infos = slice::from_raw_parts(infos, len)
+            .iter()
+            .map(|info| NativeMDataInfo::clone_from_repr_c(info))
+            .collect::<Result<Vec<_>, _>>()?;
+        let actions_hs = vec_clone_from_raw_parts(actions_hs, len);
+
+        (*app).send(move |client, context| {
+            let mut mutations = Vec::with_capacity(infos.len());
+            for (info, actions_h) in infos.into_iter().zip(actions_hs) {
+                let actions = try_cb!(
+                    context
+                        .object_cache()
+                        .get_seq_mdata_entry_actions(actions_h),
+                    user_data,
+                    o_cb
+                );
+                let address = MDataAddress::Seq {
+                    name: info.name(),
+                    tag: info.type_tag(),
+                };
+                mutations.push((address, MDataEntryActions::Seq(actions.clone())));
+            }
+
+            client
+                .mutate_mdata_entries_batch(mutations)
+                .map_err(AppError::from)
+                .then(move |result| {
+                    match result {
+                        Ok(results) => {
+                            let error_codes: Vec<i32> = results
+                                .into_iter()
+                                .map(|result| match result {
+                                    Ok(()) => 0,
+                                    Err(error) => AppError::from(error).error_code(),
+                                })
+                                .collect();
+
+                            o_cb(
+                                user_data.0,
+                                FFI_RESULT_OK,
+                                error_codes.as_safe_ptr(),
+                                error_codes.len(),
+                            )
+                        }
+                        Err(..) => {
+                            call_result_cb!(result, user_data, o_cb);
+                        }
+                    }
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
 /// Get list of all permissions set on the mutable data.
 #[no_mangle]
 pub unsafe extern "C" fn mdata_list_permissions(
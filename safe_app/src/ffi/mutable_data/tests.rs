@@ -631,3 +631,105 @@ fn entries_crud_ffi() {
         }
     }
 }
+
+// Test mutating the entries of several mutable data in a single batch.
+// 1. Put two public mutable data.
+// 2. Insert an entry into both of them and into a third one which doesn't exist.
+// 3. Verify that the error codes are in the order of the mutations, and only the last one failed.
+#[test]
+fn mutate_entries_batch_ffi() {
+    let app = create_app();
+
+    let perm_set = MDataPermissionSet::new()
+        .allow(MDataAction::Read)
+        .allow(MDataAction::Insert);
+    let perms_h: MDataPermissionsHandle =
+        unsafe { unwrap!(call_1(|ud, cb| mdata_permissions_new(&app, ud, cb))) };
+    let app_pk_handle = unwrap!(run(&app, move |client, context| {
+        Ok(context.object_cache().insert_pub_key(client.public_key()))
+    }));
+    unsafe {
+        unwrap!(call_0(|ud, cb| mdata_permissions_insert(
+            &app,
+            perms_h,
+            app_pk_handle,
+            &permission_set_into_repr_c(perm_set),
+            ud,
+            cb,
+        )))
+    }
+
+    let infos: Vec<MDataInfo> = (0..3)
+        .map(|_| {
+            let info: NativeMDataInfo = unsafe {
+                unwrap!(call_1(|ud, cb| mdata_info_random_public(
+                    true, 10_000, ud, cb
+                )))
+            };
+            info.into_repr_c()
+        })
+        .collect();
+    for info in &infos[..2] {
+        unsafe {
+            unwrap!(call_0(|ud, cb| mdata_put(
+                &app,
+                info,
+                perms_h,
+                ENTRIES_EMPTY,
+                ud,
+                cb
+            )))
+        };
+    }
+
+    let actions_h: MDataEntryActionsHandle =
+        unsafe { unwrap!(call_1(|ud, cb| mdata_entry_actions_new(&app, ud, cb))) };
+    unsafe {
+        unwrap!(call_0(|ud, cb| mdata_entry_actions_insert(
+            &app,
+            actions_h,
+            b"key".as_ptr(),
+            3,
+            b"value".as_ptr(),
+            5,
+            ud,
+            cb,
+        )))
+    };
+    let actions_hs = vec![actions_h; infos.len()];
+
+    let (tx, rx) = mpsc::channel::<Result<Vec<i32>, i32>>();
+    let mut ud = Default::default();
+    unsafe {
+        mdata_mutate_entries_batch(
+            &app,
+            infos.as_ptr(),
+            actions_hs.as_ptr(),
+            infos.len(),
+            sender_as_user_data(&tx, &mut ud),
+            error_codes_cb,
+        )
+    };
+
+    let error_codes = unwrap!(unwrap!(rx.recv()));
+    assert_eq!(error_codes.len(), 3);
+    assert_eq!(&error_codes[..2], &[0, 0]);
+    assert_ne!(error_codes[2], 0);
+
+    extern "C" fn error_codes_cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        error_codes: *const i32,
+        len: usize,
+    ) {
+        unsafe {
+            let result: Result<Vec<i32>, i32> = if (*res).error_code == 0 {
+                Ok(vec_clone_from_raw_parts(error_codes, len))
+            } else {
+                Err((*res).error_code)
+            };
+
+            send_via_user_data(user_data, result);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::App;
+use ffi_utils::FfiResult;
+use safe_core::ffi::arrays::{BlsPublicKey, XorNameArray};
+use safe_core::ffi::wallet::{self, TransferRecord, WalletBalance};
+use std::os::raw::{c_char, c_void};
+
+/// Add a balance to the app's wallet under the given name. `secret_key` is the
+/// serialised BLS secret key controlling the balance; if it's null, a new key is generated.
+/// If `store_path` is null, the wallet is kept on the network, otherwise in the given file.
+#[no_mangle]
+pub unsafe extern "C" fn app_wallet_add_balance(
+    app: *const App,
+    store_path: *const c_char,
+    name: *const c_char,
+    secret_key: *const u8,
+    secret_key_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        public_key: *const BlsPublicKey,
+    ),
+) {
+    wallet::add_balance(
+        app,
+        store_path,
+        name,
+        secret_key,
+        secret_key_len,
+        user_data,
+        o_cb,
+    );
+}
+
+/// List the balances of the app's wallet.
+#[no_mangle]
+pub unsafe extern "C" fn app_wallet_balances(
+    app: *const App,
+    store_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        balances: *const WalletBalance,
+        balances_len: usize,
+    ),
+) {
+    wallet::balances(app, store_path, user_data, o_cb);
+}
+
+/// Get the amount of nano coins held by the named balance of the app's wallet.
+#[no_mangle]
+pub unsafe extern "C" fn app_wallet_get_balance(
+    app: *const App,
+    store_path: *const c_char,
+    name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, amount: u64),
+) {
+    wallet::get_balance(app, store_path, name, user_data, o_cb);
+}
+
+/// Transfer `amount` nano coins from the named balance of the app's wallet to
+/// `destination`. The transfer is recorded in the wallet's history as pending before being sent.
+/// `memo` can be null.
+#[no_mangle]
+pub unsafe extern "C" fn app_wallet_transfer_coins(
+    app: *const App,
+    store_path: *const c_char,
+    from: *const c_char,
+    destination: *const XorNameArray,
+    amount: u64,
+    memo: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, transaction_id: u64),
+) {
+    wallet::transfer_coins(
+        app,
+        store_path,
+        from,
+        destination,
+        amount,
+        memo,
+        user_data,
+        o_cb,
+    );
+}
+
+/// Record `amount` nano coins received by the named balance of the app's wallet, such
+/// as those announced by a transfer notification. `memo` can be null.
+#[no_mangle]
+pub unsafe extern "C" fn app_wallet_record_received(
+    app: *const App,
+    store_path: *const c_char,
+    balance: *const c_char,
+    transaction_id: u64,
+    amount: u64,
+    memo: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    wallet::record_received(
+        app,
+        store_path,
+        balance,
+        transaction_id,
+        amount,
+        memo,
+        user_data,
+        o_cb,
+    );
+}
+
+/// Resolve the pending transfers of the app's wallet, which were sent by
+/// `app_wallet_transfer_coins` but whose outcome isn't known, by sending them again. Transfers
+/// which went through are marked as completed, and those rejected by the network are removed from
+/// the history. Should be called at startup, before making new transfers.
+#[no_mangle]
+pub unsafe extern "C" fn app_wallet_reconcile(
+    app: *const App,
+    store_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    wallet::reconcile(app, store_path, user_data, o_cb);
+}
+
+/// Get all the transfers recorded in the app's wallet, oldest first.
+#[no_mangle]
+pub unsafe extern "C" fn app_wallet_history(
+    app: *const App,
+    store_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        transfers: *const TransferRecord,
+        transfers_len: usize,
+    ),
+) {
+    wallet::history(app, store_path, user_data, o_cb);
+}
+
+/// Export all the transfers recorded in the app's wallet as JSON if `json` is
+/// `true`, or as CSV otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn app_wallet_export_history(
+    app: *const App,
+    store_path: *const c_char,
+    json: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, history: *const c_char),
+) {
+    wallet::export_history(app, store_path, json, user_data, o_cb);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_app;
+    use ffi_utils::test_utils::{call_0, call_1, send_via_user_data, sender_as_user_data};
+    use maidsafe_utilities::serialisation::serialise;
+    use safe_core::client::test_create_balance;
+    use safe_nd::{Coins, PublicKey, XorName};
+    use std::ffi::CString;
+    use std::str::FromStr;
+    use std::sync::mpsc;
+    use std::{ptr, slice};
+    use threshold_crypto::serde_impl::SerdeSecret;
+    use threshold_crypto::SecretKey as BlsSecretKey;
+
+    // Test adding a balance, transferring coins from it and getting the history back through the
+    // FFI.
+    #[test]
+    fn transfer_and_history() {
+        let app = create_app();
+
+        let secret_key = BlsSecretKey::random();
+        let destination_sk = BlsSecretKey::random();
+        unwrap!(test_create_balance(
+            &secret_key,
+            unwrap!(Coins::from_str("50"))
+        ));
+        unwrap!(test_create_balance(
+            &destination_sk,
+            unwrap!(Coins::from_str("1"))
+        ));
+        let destination = XorName::from(PublicKey::from(destination_sk.public_key()));
+
+        let name = unwrap!(CString::new("main"));
+        let memo = unwrap!(CString::new("rent"));
+        let serialised_sk = unwrap!(serialise(&SerdeSecret(&secret_key)));
+
+        let (tx, rx) = mpsc::channel::<Result<Vec<u8>, i32>>();
+        let mut ud = Default::default();
+        unsafe {
+            app_wallet_add_balance(
+                &app,
+                ptr::null(),
+                name.as_ptr(),
+                serialised_sk.as_ptr(),
+                serialised_sk.len(),
+                sender_as_user_data(&tx, &mut ud),
+                add_balance_cb,
+            )
+        };
+        let public_key = unwrap!(unwrap!(rx.recv()));
+        assert_eq!(&public_key[..], &secret_key.public_key().to_bytes()[..]);
+
+        let balance: u64 = unsafe {
+            unwrap!(call_1(|ud, cb| app_wallet_get_balance(
+                &app,
+                ptr::null(),
+                name.as_ptr(),
+                ud,
+                cb
+            )))
+        };
+        assert_eq!(balance, unwrap!(Coins::from_str("50")).as_nano());
+
+        let transaction_id: u64 = unsafe {
+            unwrap!(call_1(|ud, cb| app_wallet_transfer_coins(
+                &app,
+                ptr::null(),
+                name.as_ptr(),
+                &destination.0,
+                unwrap!(Coins::from_str("10")).as_nano(),
+                memo.as_ptr(),
+                ud,
+                cb
+            )))
+        };
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| app_wallet_reconcile(
+                &app,
+                ptr::null(),
+                ud,
+                cb
+            )))
+        };
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| app_wallet_record_received(
+                &app,
+                ptr::null(),
+                name.as_ptr(),
+                1,
+                10,
+                ptr::null(),
+                ud,
+                cb
+            )))
+        };
+
+        let (tx, rx) = mpsc::channel::<Vec<(u64, bool, bool)>>();
+        let mut ud = Default::default();
+        unsafe {
+            app_wallet_history(
+                &app,
+                ptr::null(),
+                sender_as_user_data(&tx, &mut ud),
+                history_cb,
+            )
+        };
+        assert_eq!(
+            unwrap!(rx.recv()),
+            vec![(transaction_id, false, false), (1, true, false)]
+        );
+
+        let csv: String = unsafe {
+            unwrap!(call_1(|ud, cb| app_wallet_export_history(
+                &app,
+                ptr::null(),
+                false,
+                ud,
+                cb
+            )))
+        };
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with(&format!("{},sent,main,", transaction_id)));
+        assert!(lines[1].ends_with(",false"));
+    }
+
+    extern "C" fn add_balance_cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        public_key: *const BlsPublicKey,
+    ) {
+        unsafe {
+            let result: Result<Vec<u8>, i32> = if (*res).error_code == 0 {
+                Ok((*public_key).to_vec())
+            } else {
+                Err((*res).error_code)
+            };
+
+            send_via_user_data(user_data, result);
+        }
+    }
+
+    // Sends the ID, direction and state of every transfer.
+    extern "C" fn history_cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        transfers: *const TransferRecord,
+        transfers_len: usize,
+    ) {
+        unsafe {
+            assert_eq!((*res).error_code, 0);
+            let transfers: Vec<_> = slice::from_raw_parts(transfers, transfers_len)
+                .iter()
+                .map(|transfer| (transfer.transaction_id, transfer.received, transfer.pending))
+                .collect();
+
+            send_via_user_data(user_data, transfers);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::errors::AppError;
+use crate::App;
+use ffi_utils::FfiResult;
+use safe_core::ffi::xor_url::{self, XorUrl};
+use std::os::raw::{c_char, c_void};
+
+/// Encode the address of data into a `safe://` XOR-URL.
+#[no_mangle]
+pub unsafe extern "C" fn encode_xor_url(
+    xor_url: *const XorUrl,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, encoded: *const c_char),
+) {
+    xor_url::encode::<AppError>(xor_url, user_data, o_cb);
+}
+
+/// Decode a `safe://` XOR-URL into the address of the data it references.
+#[no_mangle]
+pub unsafe extern "C" fn decode_xor_url(
+    encoded: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, xor_url: *const XorUrl),
+) {
+    xor_url::decode::<AppError>(encoded, user_data, o_cb);
+}
+
+/// Fetch the data referenced by a `safe://` XOR-URL. An unregistered app can resolve the URLs of
+/// published data.
+///
+/// Callback parameters: user data, error code, decoded URL, data, data length. The data is the
+/// value of immutable data, or the serialised mutable or append-only data.
+#[no_mangle]
+pub unsafe extern "C" fn app_resolve_xor_url(
+    app: *const App,
+    encoded: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        xor_url: *const XorUrl,
+        data: *const u8,
+        data_len: usize,
+    ),
+) {
+    xor_url::resolve(app, encoded, user_data, o_cb);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::test_utils::call_1;
+    use ffi_utils::ErrorCode;
+    use safe_core::ipc::IpcError;
+    use safe_core::xor_url::XorUrl as NativeXorUrl;
+    use safe_nd::{ADataAddress, XorName};
+    use std::ffi::CString;
+
+    // Test encoding and decoding XOR-URLs through the FFI.
+    #[test]
+    fn encode_decode() {
+        let native_url = NativeXorUrl::new(
+            ADataAddress::PubUnseq {
+                name: XorName(rand::random()),
+                tag: 42,
+            },
+            Some("text/html".to_string()),
+        );
+        let xor_url = unwrap!(native_url.clone().into_repr_c());
+
+        let encoded: String = unsafe { unwrap!(call_1(|ud, cb| encode_xor_url(&xor_url, ud, cb))) };
+        assert_eq!(encoded, unwrap!(native_url.encode()));
+
+        let encoded = unwrap!(CString::new(encoded));
+        let decoded: NativeXorUrl =
+            unsafe { unwrap!(call_1(|ud, cb| decode_xor_url(encoded.as_ptr(), ud, cb))) };
+        assert_eq!(decoded, native_url);
+
+        let invalid = unwrap!(CString::new("safe://invalid"));
+        let res: Result<NativeXorUrl, i32> =
+            unsafe { call_1(|ud, cb| decode_xor_url(invalid.as_ptr(), ud, cb)) };
+        match res {
+            Err(code) => assert_eq!(
+                code,
+                AppError::from(IpcError::EncodeDecodeError).error_code()
+            ),
+            Ok(_) => panic!("Unexpected success"),
+        }
+    }
+}
@@ -49,6 +49,8 @@ use safe_core::ffi::ipc::resp::{
     MDataEntry, MDataKey, MDataValue, MetadataResponse,
 };
 use safe_core::ffi::nfs::File;
+use safe_core::ffi::wallet::{TransferRecord, WalletBalance};
+use safe_core::ffi::xor_url::XorUrl;
 use safe_core::ffi::*;
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_void};
@@ -277,6 +279,21 @@ gen_object_array_converter!(
     ContainerPermissions,
     "net/maidsafe/safe_app/ContainerPermissions"
 );
+gen_object_array_converter!(
+    find_class,
+    WalletBalance,
+    "net/maidsafe/safe_app/WalletBalance"
+);
+gen_object_array_converter!(
+    find_class,
+    TransferRecord,
+    "net/maidsafe/safe_app/TransferRecord"
+);
+gen_object_array_converter!(
+    find_class,
+    BootstrapContact,
+    "net/maidsafe/safe_app/BootstrapContact"
+);
 
 extern "C" fn call_app_disconnect_cb(ctx: *mut c_void) {
     unsafe {
@@ -83,6 +83,10 @@ mod bindings {
             "XorNameArray",
             JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
         );
+        type_map.insert(
+            "BlsPublicKey",
+            JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
+        );
         type_map.insert(
             "SignSecretKey",
             JavaType::Array(Box::new(JavaType::Primitive(Primitive::Byte))),
@@ -17,7 +17,9 @@ use new_rand::SeedableRng;
 use rust_sodium::crypto::sign::Seed;
 use rust_sodium::crypto::{box_, sign};
 use safe_core::client::account::Account;
-use safe_core::client::{req, AuthActions, ClientInner, SafeKey, IMMUT_DATA_CACHE_SIZE};
+use safe_core::client::{
+    req, AuthActions, ClientInner, SafeKey, IMMUT_DATA_CACHE_SIZE, REQUEST_TIMEOUT_SECS,
+};
 use safe_core::config_handler::Config;
 use safe_core::crypto::{shared_box, shared_secretbox, shared_sign};
 use safe_core::ipc::BootstrapConfig;
@@ -162,8 +164,8 @@ where {
         let new_login_packet = LoginPacket::new(acc_locator, *transient_pk, acc_ciphertext, sig)?;
 
         // Create the connection manager
-        let mut connection_manager =
-            ConnectionManager::new(Config::new().quic_p2p, &net_tx.clone())?;
+        let timeout = Duration::from_secs(REQUEST_TIMEOUT_SECS);
+        let mut connection_manager = ConnectionManager::new(Config::new(), &net_tx.clone())?;
 
         connection_manager = connection_manager_wrapper_fn(connection_manager);
 
@@ -176,6 +178,7 @@ where {
                 &mut connection_manager,
                 Request::CreateLoginPacket(new_login_packet),
                 &balance_full_id,
+                timeout,
             )?;
 
             match response {
@@ -193,7 +196,7 @@ where {
                 el_handle,
                 connection_manager,
                 LruCache::new(IMMUT_DATA_CACHE_SIZE),
-                Duration::from_secs(180), // FIXME //(REQUEST_TIMEOUT_SECS),
+                timeout,
                 core_tx,
                 net_tx,
             ))),
@@ -285,8 +288,8 @@ where {
         let user_cred = UserCred::new(password, pin);
 
         // Create the connection manager
-        let mut connection_manager =
-            ConnectionManager::new(Config::new().quic_p2p, &net_tx.clone())?;
+        let timeout = Duration::from_secs(REQUEST_TIMEOUT_SECS);
+        let mut connection_manager = ConnectionManager::new(Config::new(), &net_tx.clone())?;
         connection_manager = connection_manager_wrapper_fn(connection_manager);
 
         let (account_buffer, signature) = {
@@ -298,6 +301,7 @@ where {
                 &mut connection_manager,
                 Request::GetLoginPacket(acc_locator),
                 &client_full_id,
+                timeout,
             )?;
 
             block_on_all(connection_manager.disconnect(&client_full_id.public_id()))?;
@@ -326,7 +330,7 @@ where {
                 el_handle,
                 connection_manager,
                 LruCache::new(IMMUT_DATA_CACHE_SIZE),
-                Duration::from_secs(180), // REQUEST_TIMEOUT_SECS), //FIXME
+                timeout,
                 core_tx,
                 net_tx,
             ))),
@@ -802,7 +806,6 @@ mod tests {
 
     // Test restarting routing after a network disconnect.
     #[cfg(feature = "mock-network")]
-    #[ignore] // FIXME: ignoring this temporarily until we figure out the disconnection semantics
     #[test]
     fn restart_network() {
         use crate::test_utils::random_client_with_net_obs;
@@ -830,15 +833,16 @@ mod tests {
             move |net_event| unwrap!(tx.send(net_event)),
             move |client| {
                 client.simulate_network_disconnect();
-                unwrap!(client.restart_network());
-                keep_alive
+                client.restart_network().then(move |res| {
+                    unwrap!(res);
+                    keep_alive
+                })
             },
         );
     }
 
     // Test that a `RequestTimeout` error is returned on network timeout.
     #[cfg(feature = "mock-network")]
-    #[ignore]
     #[test]
     fn timeout() {
         use crate::test_utils::random_client;
@@ -44,6 +44,8 @@ mod codes {
     pub const ERR_REQUEST_TIMEOUT: i32 = -14;
     pub const ERR_CONFIG_FILE: i32 = -15;
     pub const ERR_IO: i32 = -16;
+    pub const ERR_ELDER_DISAGREEMENT: i32 = -17;
+    pub const ERR_ELDERS_UNREACHABLE: i32 = -18;
 
     // Data type errors
     pub const ERR_ACCESS_DENIED: i32 = -100;
@@ -355,6 +357,8 @@ fn core_error_code(err: &CoreError) -> i32 {
         CoreError::RequestTimeout => ERR_REQUEST_TIMEOUT,
         CoreError::ConfigError(_) => ERR_CONFIG_FILE,
         CoreError::IoError(_) => ERR_IO,
+        CoreError::ElderDisagreement(_) => ERR_ELDER_DISAGREEMENT,
+        CoreError::EldersUnreachable => ERR_ELDERS_UNREACHABLE,
         CoreError::Unexpected(_) => ERR_UNEXPECTED,
     }
 }
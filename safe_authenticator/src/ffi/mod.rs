@@ -14,17 +14,36 @@ pub mod apps;
 pub mod ipc;
 /// Logging utilities
 pub mod logging;
+/// Coin wallet of the authenticator
+pub mod wallet;
+/// `safe://` XOR-URLs
+pub mod xor_url;
 
+use crate::client::AuthClient;
 use crate::errors::AuthError;
 use crate::Authenticator;
 use config_file_handler;
 use ffi_utils::{catch_unwind_cb, from_c_str, FfiResult, OpaqueCtx, FFI_RESULT_OK};
-use safe_core::{test_create_balance, Client};
+use futures::{Future, Stream};
+use safe_core::ffi::{BootstrapContact, FfiHost};
+use safe_core::{config_handler, test_create_balance, Client, FutureExt, NetworkNotification};
 use safe_nd::Coins;
 use std::ffi::{CStr, CString, OsStr};
 use std::os::raw::{c_char, c_void};
 use std::str::FromStr;
 
+impl FfiHost for Authenticator {
+    type Client = AuthClient;
+    type Error = AuthError;
+
+    fn send<F>(&self, f: F) -> Result<(), AuthError>
+    where
+        F: FnOnce(&AuthClient) -> Option<Box<dyn Future<Item = (), Error = ()>>> + Send + 'static,
+    {
+        Authenticator::send(self, f)
+    }
+}
+
 /// Create a registered client. This or any one of the other companion
 /// functions to get an authenticator instance must be called before initiating any
 /// operation allowed by this module. The `user_data` parameter corresponds to the
@@ -118,17 +137,76 @@ pub unsafe extern "C" fn auth_reconnect(
     catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
         let user_data = OpaqueCtx(user_data);
         (*auth).send(move |client| {
-            try_cb!(
-                client.restart_network().map_err(AuthError::from),
-                user_data.0,
-                o_cb
-            );
+            Some(
+                client
+                    .restart_network()
+                    .map_err(AuthError::from)
+                    .then(move |res| {
+                        call_result_cb!(res, user_data, o_cb);
+                        Ok(())
+                    })
+                    .into_box(),
+            )
+        })
+    })
+}
+
+/// Enable or disable automatic reconnection after the connection with the network has been lost.
+#[no_mangle]
+pub unsafe extern "C" fn auth_set_auto_reconnect(
+    auth: *mut Authenticator,
+    enabled: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let user_data = OpaqueCtx(user_data);
+        (*auth).send(move |client| {
+            client.set_auto_reconnect(enabled);
             o_cb(user_data.0, FFI_RESULT_OK);
             None
         })
     })
 }
 
+/// Subscribe to the notifications the network sends to the auth, such as incoming coin
+/// transfers. `o_notification_cb` is called with the transaction ID and the amount (in nano
+/// coins) of every transfer received. `o_cb` is called once the subscription is set up.
+#[no_mangle]
+pub unsafe extern "C" fn auth_subscribe_notifications(
+    auth: *mut Authenticator,
+    user_data: *mut c_void,
+    o_notification_cb: extern "C" fn(user_data: *mut c_void, transaction_id: u64, amount: u64),
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let user_data = OpaqueCtx(user_data);
+        (*auth).send(move |client| {
+            client
+                .subscribe_notifications()
+                .map_err(AuthError::from)
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .and_then(move |notifications| {
+                    o_cb(user_data.0, FFI_RESULT_OK);
+
+                    notifications.for_each(move |notification| {
+                        match notification {
+                            NetworkNotification::TransferReceived {
+                                transaction_id,
+                                amount,
+                            } => o_notification_cb(user_data.0, transaction_id, amount.as_nano()),
+                        }
+                        Ok(())
+                    })
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
 /// Returns the expected name for the application executable without an extension.
 #[no_mangle]
 pub unsafe extern "C" fn auth_exe_file_stem(
@@ -167,6 +245,52 @@ pub unsafe extern "C" fn auth_set_additional_search_path(
     });
 }
 
+/// Returns the contacts in the bootstrap cache, most recent first.
+#[no_mangle]
+pub unsafe extern "C" fn auth_bootstrap_cache(
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        contacts: *const BootstrapContact,
+        contacts_len: usize,
+    ),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let cache = config_handler::read_bootstrap_cache()?;
+        let peer_addrs = cache
+            .contacts()
+            .iter()
+            .map(|contact| CString::new(contact.node_info.peer_addr.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let contacts: Vec<_> = cache
+            .contacts()
+            .iter()
+            .zip(&peer_addrs)
+            .map(|(contact, peer_addr)| BootstrapContact {
+                peer_addr: peer_addr.as_ptr(),
+                last_success: contact.last_success.timestamp(),
+            })
+            .collect();
+
+        o_cb(user_data, FFI_RESULT_OK, contacts.as_ptr(), contacts.len());
+        Ok(())
+    })
+}
+
+/// Removes all contacts from the bootstrap cache.
+#[no_mangle]
+pub unsafe extern "C" fn auth_clear_bootstrap_cache(
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        config_handler::clear_bootstrap_cache()?;
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
 /// Discard and clean up the previously allocated authenticator instance.
 /// Use this only if the authenticator is obtained from one of the auth
 /// functions in this crate (`create_acc` or `login`).
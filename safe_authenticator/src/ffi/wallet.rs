@@ -0,0 +1,284 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::Authenticator;
+use ffi_utils::FfiResult;
+use safe_core::ffi::arrays::{BlsPublicKey, XorNameArray};
+use safe_core::ffi::wallet::{self, TransferRecord, WalletBalance};
+use std::os::raw::{c_char, c_void};
+
+/// Add a balance to the authenticator's wallet under the given name. `secret_key` is the
+/// serialised BLS secret key controlling the balance; if it's null, a new key is generated.
+/// If `store_path` is null, the wallet is kept on the network, otherwise in the given file.
+#[no_mangle]
+pub unsafe extern "C" fn auth_wallet_add_balance(
+    auth: *const Authenticator,
+    store_path: *const c_char,
+    name: *const c_char,
+    secret_key: *const u8,
+    secret_key_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        public_key: *const BlsPublicKey,
+    ),
+) {
+    wallet::add_balance(
+        auth,
+        store_path,
+        name,
+        secret_key,
+        secret_key_len,
+        user_data,
+        o_cb,
+    );
+}
+
+/// List the balances of the authenticator's wallet.
+#[no_mangle]
+pub unsafe extern "C" fn auth_wallet_balances(
+    auth: *const Authenticator,
+    store_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        balances: *const WalletBalance,
+        balances_len: usize,
+    ),
+) {
+    wallet::balances(auth, store_path, user_data, o_cb);
+}
+
+/// Get the amount of nano coins held by the named balance of the authenticator's wallet.
+#[no_mangle]
+pub unsafe extern "C" fn auth_wallet_get_balance(
+    auth: *const Authenticator,
+    store_path: *const c_char,
+    name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, amount: u64),
+) {
+    wallet::get_balance(auth, store_path, name, user_data, o_cb);
+}
+
+/// Transfer `amount` nano coins from the named balance of the authenticator's wallet to
+/// `destination`. The transfer is recorded in the wallet's history as pending before being sent.
+/// `memo` can be null.
+#[no_mangle]
+pub unsafe extern "C" fn auth_wallet_transfer_coins(
+    auth: *const Authenticator,
+    store_path: *const c_char,
+    from: *const c_char,
+    destination: *const XorNameArray,
+    amount: u64,
+    memo: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, transaction_id: u64),
+) {
+    wallet::transfer_coins(
+        auth,
+        store_path,
+        from,
+        destination,
+        amount,
+        memo,
+        user_data,
+        o_cb,
+    );
+}
+
+/// Record `amount` nano coins received by the named balance of the authenticator's wallet, such
+/// as those announced by a transfer notification. `memo` can be null.
+#[no_mangle]
+pub unsafe extern "C" fn auth_wallet_record_received(
+    auth: *const Authenticator,
+    store_path: *const c_char,
+    balance: *const c_char,
+    transaction_id: u64,
+    amount: u64,
+    memo: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    wallet::record_received(
+        auth,
+        store_path,
+        balance,
+        transaction_id,
+        amount,
+        memo,
+        user_data,
+        o_cb,
+    );
+}
+
+/// Resolve the pending transfers of the authenticator's wallet, which were sent by
+/// `auth_wallet_transfer_coins` but whose outcome isn't known, by sending them again. Transfers
+/// which went through are marked as completed, and those rejected by the network are removed from
+/// the history. Should be called at startup, before making new transfers.
+#[no_mangle]
+pub unsafe extern "C" fn auth_wallet_reconcile(
+    auth: *const Authenticator,
+    store_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    wallet::reconcile(auth, store_path, user_data, o_cb);
+}
+
+/// Get all the transfers recorded in the authenticator's wallet, oldest first.
+#[no_mangle]
+pub unsafe extern "C" fn auth_wallet_history(
+    auth: *const Authenticator,
+    store_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        transfers: *const TransferRecord,
+        transfers_len: usize,
+    ),
+) {
+    wallet::history(auth, store_path, user_data, o_cb);
+}
+
+/// Export all the transfers recorded in the authenticator's wallet as JSON if `json` is
+/// `true`, or as CSV otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn auth_wallet_export_history(
+    auth: *const Authenticator,
+    store_path: *const c_char,
+    json: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, history: *const c_char),
+) {
+    wallet::export_history(auth, store_path, json, user_data, o_cb);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_account_and_login;
+    use ffi_utils::from_c_str;
+    use ffi_utils::test_utils::{call_0, send_via_user_data, sender_as_user_data};
+    use std::ffi::CString;
+    use std::sync::mpsc;
+    use std::{ptr, slice};
+
+    // Test adding a balance with a new key and recording a transfer through the FFI.
+    #[test]
+    fn add_balance_and_record() {
+        let auth = create_account_and_login();
+        let name = unwrap!(CString::new("main"));
+
+        let (tx, rx) = mpsc::channel::<Result<(), i32>>();
+        let mut ud = Default::default();
+        unsafe {
+            auth_wallet_add_balance(
+                &auth,
+                ptr::null(),
+                name.as_ptr(),
+                ptr::null(),
+                0,
+                sender_as_user_data(&tx, &mut ud),
+                add_balance_cb,
+            )
+        };
+        unwrap!(unwrap!(rx.recv()));
+
+        let (tx, rx) = mpsc::channel::<Vec<String>>();
+        let mut ud = Default::default();
+        unsafe {
+            auth_wallet_balances(
+                &auth,
+                ptr::null(),
+                sender_as_user_data(&tx, &mut ud),
+                balances_cb,
+            )
+        };
+        assert_eq!(unwrap!(rx.recv()), vec!["main".to_string()]);
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| auth_wallet_record_received(
+                &auth,
+                ptr::null(),
+                name.as_ptr(),
+                1,
+                10,
+                ptr::null(),
+                ud,
+                cb
+            )))
+        };
+
+        let (tx, rx) = mpsc::channel::<Vec<(u64, u64)>>();
+        let mut ud = Default::default();
+        unsafe {
+            auth_wallet_history(
+                &auth,
+                ptr::null(),
+                sender_as_user_data(&tx, &mut ud),
+                history_cb,
+            )
+        };
+        assert_eq!(unwrap!(rx.recv()), vec![(1, 10)]);
+    }
+
+    extern "C" fn add_balance_cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        _public_key: *const BlsPublicKey,
+    ) {
+        unsafe {
+            let result = if (*res).error_code == 0 {
+                Ok(())
+            } else {
+                Err((*res).error_code)
+            };
+
+            send_via_user_data(user_data, result);
+        }
+    }
+
+    extern "C" fn balances_cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        balances: *const WalletBalance,
+        balances_len: usize,
+    ) {
+        unsafe {
+            assert_eq!((*res).error_code, 0);
+            let names: Vec<_> = slice::from_raw_parts(balances, balances_len)
+                .iter()
+                .map(|balance| unwrap!(from_c_str(balance.name)))
+                .collect();
+
+            send_via_user_data(user_data, names);
+        }
+    }
+
+    // Sends the ID and amount of every transfer.
+    extern "C" fn history_cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        transfers: *const TransferRecord,
+        transfers_len: usize,
+    ) {
+        unsafe {
+            assert_eq!((*res).error_code, 0);
+            let transfers: Vec<_> = slice::from_raw_parts(transfers, transfers_len)
+                .iter()
+                .map(|transfer| (transfer.transaction_id, transfer.amount))
+                .collect();
+
+            send_via_user_data(user_data, transfers);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{AuthError, Authenticator};
+use ffi_utils::FfiResult;
+use safe_core::ffi::xor_url::{self, XorUrl};
+use std::os::raw::{c_char, c_void};
+
+/// Encode the address of data into a `safe://` XOR-URL.
+#[no_mangle]
+pub unsafe extern "C" fn auth_encode_xor_url(
+    xor_url: *const XorUrl,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, encoded: *const c_char),
+) {
+    xor_url::encode::<AuthError>(xor_url, user_data, o_cb);
+}
+
+/// Decode a `safe://` XOR-URL into the address of the data it references.
+#[no_mangle]
+pub unsafe extern "C" fn auth_decode_xor_url(
+    encoded: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, xor_url: *const XorUrl),
+) {
+    xor_url::decode::<AuthError>(encoded, user_data, o_cb);
+}
+
+/// Fetch the data referenced by a `safe://` XOR-URL.
+///
+/// Callback parameters: user data, error code, decoded URL, data, data length. The data is the
+/// value of immutable data, or the serialised mutable or append-only data.
+#[no_mangle]
+pub unsafe extern "C" fn auth_resolve_xor_url(
+    auth: *const Authenticator,
+    encoded: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        xor_url: *const XorUrl,
+        data: *const u8,
+        data_len: usize,
+    ),
+) {
+    xor_url::resolve(auth, encoded, user_data, o_cb);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run;
+    use crate::test_utils::create_account_and_login;
+    use ffi_utils::test_utils::{call_1, send_via_user_data, sender_as_user_data};
+    use futures::Future;
+    use safe_core::immutable_data;
+    use safe_core::xor_url::XorUrl as NativeXorUrl;
+    use safe_core::Client;
+    use std::ffi::CString;
+    use std::slice;
+    use std::sync::mpsc;
+
+    // Test encoding an XOR-URL of immutable data and resolving it through the FFI.
+    #[test]
+    fn encode_and_resolve() {
+        let auth = create_account_and_login();
+        let value = b"XOR-URL".to_vec();
+
+        let value2 = value.clone();
+        let address = unwrap!(run(&auth, move |client| {
+            let client2 = client.clone();
+            immutable_data::create(client, &value2, true, None)
+                .and_then(move |data| {
+                    let address = *data.address();
+                    client2.put_idata(data).map(move |_| address)
+                })
+                .map_err(AuthError::from)
+        }));
+
+        let native_url = NativeXorUrl::new(address, None);
+        let xor_url = unwrap!(native_url.clone().into_repr_c());
+        let encoded: String =
+            unsafe { unwrap!(call_1(|ud, cb| auth_encode_xor_url(&xor_url, ud, cb))) };
+        let encoded = unwrap!(CString::new(encoded));
+
+        let decoded: NativeXorUrl = unsafe {
+            unwrap!(call_1(|ud, cb| auth_decode_xor_url(
+                encoded.as_ptr(),
+                ud,
+                cb
+            )))
+        };
+        assert_eq!(decoded, native_url);
+
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+        let mut ud = Default::default();
+        unsafe {
+            auth_resolve_xor_url(
+                &auth,
+                encoded.as_ptr(),
+                sender_as_user_data(&tx, &mut ud),
+                resolve_cb,
+            )
+        };
+        assert_eq!(unwrap!(rx.recv()), value);
+    }
+
+    extern "C" fn resolve_cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        _xor_url: *const XorUrl,
+        data: *const u8,
+        data_len: usize,
+    ) {
+        unsafe {
+            assert_eq!((*res).error_code, 0);
+            send_via_user_data(user_data, slice::from_raw_parts(data, data_len).to_vec());
+        }
+    }
+}
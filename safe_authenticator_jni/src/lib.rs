@@ -48,6 +48,8 @@ use safe_core::ffi::ipc::resp::{
     MDataEntry, MDataKey, MDataValue, MetadataResponse,
 };
 use safe_core::ffi::nfs::File;
+use safe_core::ffi::wallet::{TransferRecord, WalletBalance};
+use safe_core::ffi::xor_url::XorUrl;
 use safe_core::ffi::*;
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_void};
@@ -298,6 +300,21 @@ gen_object_array_converter!(
     MetadataResponse,
     "net/maidsafe/safe_authenticator/MetadataResponse"
 );
+gen_object_array_converter!(
+    find_class,
+    WalletBalance,
+    "net/maidsafe/safe_authenticator/WalletBalance"
+);
+gen_object_array_converter!(
+    find_class,
+    TransferRecord,
+    "net/maidsafe/safe_authenticator/TransferRecord"
+);
+gen_object_array_converter!(
+    find_class,
+    BootstrapContact,
+    "net/maidsafe/safe_authenticator/BootstrapContact"
+);
 
 extern "C" fn call_auth_disconnect_cb(ctx: *mut c_void) {
     unsafe {
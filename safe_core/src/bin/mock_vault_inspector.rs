@@ -0,0 +1,232 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Lists, dumps and deletes the contents of the mock vault store file.
+
+#![forbid(unsafe_code, warnings)]
+#![deny(missing_docs, clippy::all)]
+#![warn(unused_results)]
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use data_encoding::HEXLOWER_PERMISSIVE;
+use safe_core::client::{
+    ADataContents, DataInfo, EntryInfo, MDataContents, VaultContents, VaultInspector,
+};
+use safe_nd::{XorName, XOR_NAME_LEN};
+use serde::Serialize;
+use std::process;
+
+fn main() {
+    let name_arg = || {
+        Arg::with_name("NAME")
+            .required(true)
+            .help("Name of the item, in hex")
+    };
+    let tag_arg = || Arg::with_name("TAG").help("Type tag of the data");
+
+    let matches = App::new("mock_vault_inspector")
+        .about("Inspects the mock vault store file")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .global(true)
+                .help("Print the output as JSON"),
+        )
+        .subcommand(SubCommand::with_name("list").about("Lists the contents of the vault"))
+        .subcommand(
+            SubCommand::with_name("mdata")
+                .about("Dumps the entries and permissions of a mutable data")
+                .arg(name_arg())
+                .arg(tag_arg().required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("adata")
+                .about("Dumps the entries of an append-only data")
+                .arg(name_arg())
+                .arg(tag_arg().required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("delete")
+                .about("Deletes an item from the vault")
+                .arg(Arg::with_name("KIND").required(true).possible_values(&[
+                    "account",
+                    "balance",
+                    "login-packet",
+                    "data",
+                ]))
+                .arg(name_arg())
+                .arg(tag_arg()),
+        )
+        .get_matches();
+
+    if let Err(error) = run(&matches) {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<(), String> {
+    let json = matches.is_present("json");
+    let inspector = VaultInspector::open();
+
+    match matches.subcommand() {
+        ("list", Some(_)) => {
+            let contents = inspector.contents();
+            if json {
+                print_json(&contents)
+            } else {
+                println!("Mock vault: {}", inspector.path().display());
+                print_contents(&contents);
+                Ok(())
+            }
+        }
+        ("mdata", Some(args)) => {
+            let contents = inspector
+                .mdata(&parse_name(args)?, parse_tag(args)?.unwrap_or_default())
+                .ok_or("No such mutable data")?;
+            if json {
+                print_json(&contents)
+            } else {
+                print_mdata(&contents);
+                Ok(())
+            }
+        }
+        ("adata", Some(args)) => {
+            let contents = inspector
+                .adata(&parse_name(args)?, parse_tag(args)?.unwrap_or_default())
+                .ok_or("No such append-only data")?;
+            if json {
+                print_json(&contents)
+            } else {
+                print_adata(&contents);
+                Ok(())
+            }
+        }
+        ("delete", Some(args)) => {
+            let name = parse_name(args)?;
+            let deleted = match args.value_of("KIND") {
+                Some("account") => usize::from(inspector.delete_account(&name)),
+                Some("balance") => usize::from(inspector.delete_balance(&name)),
+                Some("login-packet") => usize::from(inspector.delete_login_packet(&name)),
+                _ => inspector.delete_data(&name, parse_tag(args)?),
+            };
+            if json {
+                print_json(&deleted)
+            } else {
+                println!("Deleted {} item(s)", deleted);
+                Ok(())
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_name(args: &ArgMatches) -> Result<XorName, String> {
+    let hex = args.value_of("NAME").unwrap_or_default();
+    let bytes = HEXLOWER_PERMISSIVE
+        .decode(hex.as_bytes())
+        .map_err(|error| format!("Invalid name: {}", error))?;
+    if bytes.len() != XOR_NAME_LEN {
+        return Err(format!("Names must be {} bytes long", XOR_NAME_LEN));
+    }
+
+    let mut name = XorName([0; XOR_NAME_LEN]);
+    name.0.copy_from_slice(&bytes);
+    Ok(name)
+}
+
+fn parse_tag(args: &ArgMatches) -> Result<Option<u64>, String> {
+    args.value_of("TAG")
+        .map(|tag| {
+            tag.parse()
+                .map_err(|_| format!("Invalid type tag: {}", tag))
+        })
+        .transpose()
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|error| error.to_string())?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn print_contents(contents: &VaultContents) {
+    println!("\nAccounts ({}):", contents.accounts.len());
+    for account in &contents.accounts {
+        println!("  {} (version {})", account.name, account.version);
+        for auth_key in &account.auth_keys {
+            println!(
+                "    app {} (transfer coins: {})",
+                auth_key.key, auth_key.transfer_coins
+            );
+        }
+    }
+
+    println!("\nCoin balances ({}):", contents.balances.len());
+    for balance in &contents.balances {
+        println!(
+            "  {}: {} (owner {})",
+            balance.name, balance.coins, balance.owner
+        );
+    }
+
+    println!("\nLogin packets ({}):", contents.login_packets.len());
+    for login_packet in &contents.login_packets {
+        println!(
+            "  {}: {} bytes (getter {})",
+            login_packet.name, login_packet.size, login_packet.authorised_getter
+        );
+    }
+
+    print_data("Immutable data", &contents.immutable_data);
+    print_data("Mutable data", &contents.mutable_data);
+    print_data("Append-only data", &contents.append_only_data);
+}
+
+fn print_data(title: &str, data: &[DataInfo]) {
+    println!("\n{} ({}):", title, data.len());
+    for info in data {
+        println!("  {}", format_data_info(info));
+    }
+}
+
+fn print_mdata(contents: &MDataContents) {
+    println!("Mutable data {}", format_data_info(&contents.info));
+    println!("Owner: {}", contents.owner);
+    println!("Version: {}", contents.version);
+
+    print_entries(&contents.entries);
+
+    println!("\nPermissions ({}):", contents.permissions.len());
+    for permission in &contents.permissions {
+        println!("  {}: {}", permission.user, permission.allowed.join(", "));
+    }
+}
+
+fn print_adata(contents: &ADataContents) {
+    println!("Append-only data {}", format_data_info(&contents.info));
+    print_entries(&contents.entries);
+}
+
+fn print_entries(entries: &[EntryInfo]) {
+    println!("\nEntries ({}):", entries.len());
+    for entry in entries {
+        match entry.version {
+            Some(version) => println!("  {} = {} (version {})", entry.key, entry.value, version),
+            None => println!("  {} = {}", entry.key, entry.value),
+        }
+    }
+}
+
+fn format_data_info(info: &DataInfo) -> String {
+    match info.tag {
+        Some(tag) => format!("{} (tag {}, {})", info.name, tag, info.kind),
+        None => format!("{} ({})", info.name, info.kind),
+    }
+}
@@ -0,0 +1,51 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Serves the mock vault to other processes over a Unix domain socket. Point the clients at it
+//! with the `SAFE_MOCK_VAULT_SOCKET` env var or the `mock_vault_socket` dev config option.
+
+#![forbid(unsafe_code, warnings)]
+#![deny(missing_docs, clippy::all)]
+#![warn(unused_results)]
+
+use std::process;
+
+#[cfg(unix)]
+fn main() {
+    use clap::{App, Arg};
+    use safe_core::config_handler::get_config;
+    use safe_core::MockVaultServer;
+    use std::path::Path;
+
+    let matches = App::new("mock_vault_server")
+        .about("Serves the mock vault over a Unix domain socket")
+        .arg(
+            Arg::with_name("SOCKET")
+                .required(true)
+                .help("Path of the socket to listen on"),
+        )
+        .get_matches();
+
+    let path = Path::new(matches.value_of("SOCKET").unwrap_or_default());
+    match MockVaultServer::start(path, get_config()) {
+        Ok(server) => {
+            println!("Mock vault server listening on {}", server.path().display());
+            server.wait();
+        }
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("Error: the mock vault server requires Unix domain sockets");
+    process::exit(1);
+}
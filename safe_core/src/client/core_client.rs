@@ -9,7 +9,9 @@
 use crate::client::account::{Account as ClientAccount, ClientKeys};
 #[cfg(feature = "mock-network")]
 use crate::client::mock::ConnectionManager;
-use crate::client::{req, AuthActions, Client, ClientInner, SafeKey, IMMUT_DATA_CACHE_SIZE};
+use crate::client::{
+    req, AuthActions, Client, ClientInner, SafeKey, IMMUT_DATA_CACHE_SIZE, REQUEST_TIMEOUT_SECS,
+};
 use crate::config_handler::Config;
 #[cfg(not(feature = "mock-network"))]
 use crate::connection_manager::ConnectionManager;
@@ -117,8 +119,9 @@ impl CoreClient {
         let balance_pub_id = balance_client_id.public_id();
 
         // Create the connection manager
-        let mut connection_manager =
-            ConnectionManager::new(Config::new().quic_p2p, &net_tx.clone())?;
+        let config = Config::new();
+        let timeout = Duration::from_secs(REQUEST_TIMEOUT_SECS);
+        let mut connection_manager = ConnectionManager::new(config.clone(), &net_tx.clone())?;
 
         connection_manager = connection_manager_wrapper_fn(connection_manager);
 
@@ -134,6 +137,7 @@ impl CoreClient {
                     transaction_id: new_rand::random(),
                 },
                 &balance_client_id,
+                timeout,
             )?;
             let _ = match response {
                 Response::Transaction(res) => res?,
@@ -144,6 +148,7 @@ impl CoreClient {
                 &mut connection_manager,
                 Request::CreateLoginPacket(new_login_packet),
                 &balance_client_id,
+                timeout,
             )?;
 
             match response {
@@ -157,14 +162,14 @@ impl CoreClient {
         block_on_all(connection_manager.bootstrap(maid_keys.client_safe_key()))?;
 
         Ok(Self {
-            inner: Rc::new(RefCell::new(ClientInner {
+            inner: Rc::new(RefCell::new(ClientInner::new(
                 el_handle,
                 connection_manager,
-                cache: LruCache::new(IMMUT_DATA_CACHE_SIZE),
-                timeout: Duration::from_secs(180), // REQUEST_TIMEOUT_SECS), // FIXME
-                net_tx,
+                LruCache::new(IMMUT_DATA_CACHE_SIZE),
+                timeout,
                 core_tx,
-            })),
+                net_tx,
+            ))),
             keys: maid_keys,
         })
     }
@@ -0,0 +1,445 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config_handler::DataCacheConfig;
+use lru_cache::LruCache;
+use safe_nd::{
+    ADataAddress, ADataEntry, ADataIndices, MDataAddress, MDataSeqEntries, MDataSeqValue,
+    SeqMutableData,
+};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Statistics of the cache of mutable and append-only data.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DataCacheStats {
+    /// Number of reads served from the cache.
+    pub hits: u64,
+    /// Number of reads which had to fetch the data from the network.
+    pub misses: u64,
+    /// Number of cached data dropped because the client mutated them.
+    pub invalidations: u64,
+}
+
+/// Parts of a sequenced mutable data cached at a given version. The entries are cached apart, as
+/// mutating them doesn't change the version of the data.
+#[derive(Default)]
+pub struct CachedMData {
+    pub shell: Option<SeqMutableData>,
+}
+
+/// Entries of a sequenced mutable data, along with the time they were fetched. As the version of
+/// the data can't tell whether they've changed, they're only used until they're older than the
+/// maximum age.
+#[derive(Default)]
+pub struct CachedEntries {
+    pub all: Option<(Instant, MDataSeqEntries)>,
+    pub values: BTreeMap<Vec<u8>, (Instant, MDataSeqValue)>,
+}
+
+/// Parts of an append-only data cached at given indices.
+#[derive(Default)]
+pub struct CachedAData {
+    pub values: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub last_entry: Option<ADataEntry>,
+}
+
+// Append-only data can't change without one of its indices changing.
+type Indices = (u64, u64, u64);
+
+fn indices(indices: &ADataIndices) -> Indices {
+    (
+        indices.entries_index(),
+        indices.owners_index(),
+        indices.permissions_index(),
+    )
+}
+
+// Data cached at a given version, along with the time the version was last checked against the
+// network.
+struct Entry<V, D> {
+    version: V,
+    checked: Instant,
+    data: D,
+}
+
+/// Part of a data found in the cache.
+pub struct Cached<V, T> {
+    /// Version the data was cached at.
+    pub version: V,
+    /// Whether the version was checked recently enough for the data to be used as it is.
+    pub fresh: bool,
+    /// The part selected from the cached data.
+    pub value: T,
+}
+
+/// Cache of the mutable and append-only data read by a client.
+pub struct DataCache {
+    config: DataCacheConfig,
+    mdata: LruCache<MDataAddress, Entry<u64, CachedMData>>,
+    mdata_entries: LruCache<MDataAddress, CachedEntries>,
+    // Bumped whenever a mutable data is invalidated, so that entries fetched before aren't cached
+    // after it.
+    mdata_generation: u64,
+    adata: LruCache<ADataAddress, Entry<Indices, CachedAData>>,
+    stats: DataCacheStats,
+}
+
+impl DataCache {
+    /// Create an empty cache.
+    pub fn new(config: DataCacheConfig) -> Self {
+        Self {
+            config,
+            mdata: LruCache::new(config.max_mdata),
+            mdata_entries: LruCache::new(config.max_mdata),
+            mdata_generation: 0,
+            adata: LruCache::new(config.max_adata),
+            stats: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn stats(&self) -> DataCacheStats {
+        self.stats
+    }
+
+    pub fn set_stats(&mut self, stats: DataCacheStats) {
+        self.stats = stats;
+    }
+
+    /// Drop all the cached data, keeping the statistics.
+    pub fn clear(&mut self) {
+        self.mdata.clear();
+        self.mdata_entries.clear();
+        self.mdata_generation += 1;
+        self.adata.clear();
+    }
+
+    pub fn record_hit(&mut self) {
+        self.stats.hits += 1;
+    }
+
+    pub fn record_miss(&mut self) {
+        self.stats.misses += 1;
+    }
+
+    /// Returns the part of the mutable data selected by `get`, along with the version it was
+    /// cached at.
+    pub fn mdata<T, F>(&mut self, address: &MDataAddress, get: F) -> Option<Cached<u64, T>>
+    where
+        F: FnOnce(&CachedMData) -> Option<T>,
+    {
+        let max_age = self.max_age();
+        let entry = self.mdata.get_mut(address)?;
+        get(&entry.data).map(|value| Cached {
+            version: entry.version,
+            fresh: entry.checked.elapsed() < max_age,
+            value,
+        })
+    }
+
+    /// Record that the mutable data is still at the version it was cached at.
+    pub fn checked_mdata(&mut self, address: &MDataAddress, version: u64) {
+        if let Some(entry) = self.mdata.get_mut(address) {
+            if entry.version == version {
+                entry.checked = Instant::now();
+            }
+        }
+    }
+
+    /// Update the cached mutable data at the given version, discarding whatever was cached at
+    /// another version.
+    pub fn update_mdata<F>(&mut self, address: MDataAddress, version: u64, update: F)
+    where
+        F: FnOnce(&mut CachedMData),
+    {
+        if !self.config.enabled {
+            return;
+        }
+        let stale = self
+            .mdata
+            .get_mut(&address)
+            .map_or(true, |entry| entry.version != version);
+        if stale {
+            let _ = self.mdata.insert(address, Entry::new(version));
+        }
+        if let Some(entry) = self.mdata.get_mut(&address) {
+            update(&mut entry.data);
+        }
+    }
+
+    pub fn invalidate_mdata(&mut self, address: &MDataAddress) {
+        self.mdata_generation += 1;
+        let shell = self.mdata.remove(address).is_some();
+        let entries = self.mdata_entries.remove(address).is_some();
+        if shell || entries {
+            self.stats.invalidations += 1;
+        }
+    }
+
+    /// Returns the entries of the sequenced mutable data selected by `get`, if they were fetched
+    /// within the maximum age.
+    pub fn mdata_entries<T, F>(&mut self, address: &MDataAddress, get: F) -> Option<T>
+    where
+        F: FnOnce(&CachedEntries, Duration) -> Option<T>,
+    {
+        let max_age = self.max_age();
+        self.mdata_entries
+            .get_mut(address)
+            .and_then(|entries| get(entries, max_age))
+    }
+
+    /// Number of mutable data invalidated so far, to be passed to `update_mdata_entries`.
+    pub fn mdata_generation(&self) -> u64 {
+        self.mdata_generation
+    }
+
+    /// Update the cached entries of the sequenced mutable data, unless a mutable data has been
+    /// invalidated since `generation` was read, in which case they may be out of date.
+    pub fn update_mdata_entries<F>(&mut self, address: MDataAddress, generation: u64, update: F)
+    where
+        F: FnOnce(&mut CachedEntries),
+    {
+        if !self.config.enabled || generation != self.mdata_generation {
+            return;
+        }
+        if self.mdata_entries.get_mut(&address).is_none() {
+            let _ = self.mdata_entries.insert(address, Default::default());
+        }
+        if let Some(entries) = self.mdata_entries.get_mut(&address) {
+            update(entries);
+        }
+    }
+
+    /// Returns the part of the append-only data selected by `get`, along with the indices it was
+    /// cached at.
+    pub fn adata<T, F>(&mut self, address: &ADataAddress, get: F) -> Option<Cached<Indices, T>>
+    where
+        F: FnOnce(&CachedAData) -> Option<T>,
+    {
+        let max_age = self.max_age();
+        let entry = self.adata.get_mut(address)?;
+        get(&entry.data).map(|value| Cached {
+            version: entry.version,
+            fresh: entry.checked.elapsed() < max_age,
+            value,
+        })
+    }
+
+    /// Record that the append-only data is still at the indices it was cached at.
+    pub fn checked_adata(&mut self, address: &ADataAddress, data_indices: &ADataIndices) {
+        if let Some(entry) = self.adata.get_mut(address) {
+            if entry.version == indices(data_indices) {
+                entry.checked = Instant::now();
+            }
+        }
+    }
+
+    /// Update the cached append-only data at the given indices, discarding whatever was cached
+    /// at other indices.
+    pub fn update_adata<F>(&mut self, address: ADataAddress, data_indices: &ADataIndices, update: F)
+    where
+        F: FnOnce(&mut CachedAData),
+    {
+        if !self.config.enabled {
+            return;
+        }
+        let data_indices = indices(data_indices);
+        let stale = self
+            .adata
+            .get_mut(&address)
+            .map_or(true, |entry| entry.version != data_indices);
+        if stale {
+            let _ = self.adata.insert(address, Entry::new(data_indices));
+        }
+        if let Some(entry) = self.adata.get_mut(&address) {
+            update(&mut entry.data);
+        }
+    }
+
+    pub fn invalidate_adata(&mut self, address: &ADataAddress) {
+        if self.adata.remove(address).is_some() {
+            self.stats.invalidations += 1;
+        }
+    }
+
+    fn max_age(&self) -> Duration {
+        Duration::from_secs(self.config.max_age_secs)
+    }
+}
+
+impl<V, D: Default> Entry<V, D> {
+    fn new(version: V) -> Self {
+        Self {
+            version,
+            checked: Instant::now(),
+            data: Default::default(),
+        }
+    }
+}
+
+impl CachedEntries {
+    /// Returns the value of the entry with the given key, if it was fetched within `max_age`,
+    /// either on its own or along with all the entries.
+    pub fn value(&self, key: &[u8], max_age: Duration) -> Option<MDataSeqValue> {
+        let value = self
+            .values
+            .get(key)
+            .filter(|(fetched, _)| fetched.elapsed() < max_age)
+            .map(|(_, value)| value);
+        let from_all = || {
+            self.all
+                .as_ref()
+                .filter(|(fetched, _)| fetched.elapsed() < max_age)
+                .and_then(|(_, entries)| entries.get(key))
+        };
+        value.or_else(from_all).cloned()
+    }
+
+    /// Returns all the entries, if they were fetched within `max_age`.
+    pub fn all(&self, max_age: Duration) -> Option<MDataSeqEntries> {
+        self.all
+            .as_ref()
+            .filter(|(fetched, _)| fetched.elapsed() < max_age)
+            .map(|(_, entries)| entries.clone())
+    }
+}
+
+/// Returns whether the data is still at the indices it was cached at.
+pub fn same_indices(cached: Indices, data_indices: &ADataIndices) -> bool {
+    cached == indices(data_indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use safe_nd::{PublicKey, XorName};
+    use threshold_crypto::SecretKey as BlsSecretKey;
+
+    fn shell(tag: u64) -> SeqMutableData {
+        let owner = PublicKey::from(BlsSecretKey::random().public_key());
+        SeqMutableData::new(XorName(rand::random()), tag, owner)
+    }
+
+    // Test that the cached parts are discarded once a newer version is cached or the data is
+    // invalidated, and that the least recently used data is evicted.
+    #[test]
+    fn versions_and_eviction() {
+        let mut cache = DataCache::new(DataCacheConfig {
+            enabled: true,
+            max_mdata: 2,
+            max_age_secs: 60,
+            ..Default::default()
+        });
+        let addresses: Vec<_> = (0..3)
+            .map(|_| MDataAddress::Seq {
+                name: XorName(rand::random()),
+                tag: 15_000,
+            })
+            .collect();
+        let get_shell = |cached: &CachedMData| cached.shell.clone();
+        let cached_shell =
+            |cache: &mut DataCache, address| cache.mdata(address, get_shell).map(|c| c.value);
+
+        let data = shell(1);
+        cache.update_mdata(addresses[0], 1, |cached| cached.shell = Some(data.clone()));
+        let cached = unwrap!(cache.mdata(&addresses[0], get_shell));
+        assert_eq!((cached.version, cached.fresh), (1, true));
+        assert_eq!(cached.value, data);
+
+        // A new version replaces whatever was cached.
+        cache.update_mdata(addresses[0], 2, |_| ());
+        assert_eq!(cached_shell(&mut cache, &addresses[0]), None);
+
+        cache.update_mdata(addresses[0], 2, |cached| cached.shell = Some(shell(2)));
+        cache.invalidate_mdata(&addresses[0]);
+        assert_eq!(cached_shell(&mut cache, &addresses[0]), None);
+        assert_eq!(cache.stats().invalidations, 1);
+
+        for address in &addresses {
+            cache.update_mdata(*address, 0, |cached| cached.shell = Some(shell(0)));
+        }
+        assert_eq!(cached_shell(&mut cache, &addresses[0]), None);
+        assert!(cached_shell(&mut cache, &addresses[2]).is_some());
+
+        // Nothing is cached while the cache is disabled.
+        let mut cache = DataCache::new(Default::default());
+        cache.update_mdata(addresses[0], 0, |cached| cached.shell = Some(shell(0)));
+        assert_eq!(cached_shell(&mut cache, &addresses[0]), None);
+    }
+
+    // Test that cached entries are used until they're older than the maximum age, and that they
+    // aren't cached if the data was invalidated while they were fetched.
+    #[test]
+    fn entries() {
+        let mut cache = DataCache::new(DataCacheConfig {
+            enabled: true,
+            max_age_secs: 60,
+            ..Default::default()
+        });
+        let address = MDataAddress::Seq {
+            name: XorName(rand::random()),
+            tag: 15_000,
+        };
+        let value = MDataSeqValue {
+            data: b"value".to_vec(),
+            version: 0,
+        };
+        let get_value = |entries: &CachedEntries, max_age| entries.value(b"key", max_age);
+
+        let generation = cache.mdata_generation();
+        cache.update_mdata_entries(address, generation, |entries| {
+            let _ = entries
+                .values
+                .insert(b"key".to_vec(), (Instant::now(), value.clone()));
+        });
+        assert_eq!(
+            cache.mdata_entries(&address, get_value),
+            Some(value.clone())
+        );
+
+        cache.config.max_age_secs = 0;
+        assert_eq!(cache.mdata_entries(&address, get_value), None);
+        cache.config.max_age_secs = 60;
+
+        cache.invalidate_mdata(&address);
+        assert_eq!(cache.mdata_entries(&address, get_value), None);
+        assert_eq!(cache.stats().invalidations, 1);
+
+        cache.update_mdata_entries(address, generation, |entries| {
+            let _ = entries
+                .values
+                .insert(b"key".to_vec(), (Instant::now(), value.clone()));
+        });
+        assert_eq!(cache.mdata_entries(&address, get_value), None);
+    }
+
+    // Test that cached data older than the maximum age has to be checked again before it's used.
+    #[test]
+    fn max_age() {
+        let mut cache = DataCache::new(DataCacheConfig {
+            enabled: true,
+            max_age_secs: 0,
+            ..Default::default()
+        });
+        let address = MDataAddress::Seq {
+            name: XorName(rand::random()),
+            tag: 15_000,
+        };
+        let get_shell = |cached: &CachedMData| cached.shell.clone();
+
+        cache.update_mdata(address, 0, |cached| cached.shell = Some(shell(0)));
+        assert!(!unwrap!(cache.mdata(&address, get_shell)).fresh);
+
+        cache.config.max_age_secs = 60;
+        assert!(unwrap!(cache.mdata(&address, get_shell)).fresh);
+    }
+}
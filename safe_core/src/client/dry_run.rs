@@ -0,0 +1,203 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config_handler::Pricing;
+use crate::cost::{CostEstimate, Operation};
+use crate::errors::CoreError;
+use crate::utils::request_name;
+use maidsafe_utilities::serialisation::serialise;
+use safe_nd::{Coins, IData, IDataAddress, Request, RequestType, Response, Transaction};
+use std::collections::BTreeMap;
+
+/// Requests tallied by a client in dry-run mode.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DryRunTally {
+    /// Number of requests made, by the name of their `Request` variant such as `"PutIData"`.
+    pub requests: BTreeMap<String, u64>,
+    /// Number of charged mutations and transactions, none of which were sent to the network.
+    pub mutations: u64,
+    // Pricing of the mutations, and what they add up to in nano coins.
+    pricing: Pricing,
+    cost: u64,
+    // Immutable data put during the dry run, which can be read back.
+    idata: BTreeMap<IDataAddress, IData>,
+}
+
+impl DryRunTally {
+    /// Create an empty tally, which prices the mutations with `pricing`. The fullness of the
+    /// network isn't known to the client, so they're priced as if the network was empty.
+    pub fn new(pricing: Pricing) -> Self {
+        Self {
+            pricing,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the mutations tallied along with what they would have cost.
+    pub fn estimate(&self) -> Result<CostEstimate, CoreError> {
+        Ok(CostEstimate {
+            mutations: self.mutations,
+            cost: Coins::from_nano(self.cost)?,
+        })
+    }
+
+    /// Tally the request, and return the response the network would give if it's a mutation or
+    /// a transaction and succeeded, or if it reads immutable data put during the dry run. Other
+    /// requests have to be sent as usual, so mutable and append-only data still read as they
+    /// were before the dry run.
+    pub fn record(&mut self, request: &Request) -> Option<Response> {
+        *self
+            .requests
+            .entry(request_name(request).to_string())
+            .or_insert(0) += 1;
+
+        match request.get_type() {
+            RequestType::PublicGet | RequestType::PrivateGet => return self.read(request),
+            RequestType::Mutation | RequestType::Transaction => (),
+        }
+        if !is_free(request) {
+            self.mutations += 1;
+            self.cost = self.cost.saturating_add(self.price(request));
+        }
+
+        Some(match *request {
+            Request::PutIData(ref data) => {
+                let _ = self.idata.insert(*data.address(), data.clone());
+                Response::Mutation(Ok(()))
+            }
+            Request::DeleteUnpubIData(address) => {
+                let _ = self.idata.remove(&address);
+                Response::Mutation(Ok(()))
+            }
+            Request::TransferCoins {
+                amount,
+                transaction_id,
+                ..
+            }
+            | Request::CreateBalance {
+                amount,
+                transaction_id,
+                ..
+            }
+            | Request::CreateLoginPacketFor {
+                amount,
+                transaction_id,
+                ..
+            } => Response::Transaction(Ok(Transaction {
+                id: transaction_id,
+                amount,
+            })),
+            _ => Response::Mutation(Ok(())),
+        })
+    }
+
+    // Returns the immutable data put during the dry run if that's what the request reads.
+    fn read(&self, request: &Request) -> Option<Response> {
+        match *request {
+            Request::GetIData(address) => self
+                .idata
+                .get(&address)
+                .map(|data| Response::GetIData(Ok(data.clone()))),
+            _ => None,
+        }
+    }
+
+    // Returns what the mutation would cost in nano coins.
+    fn price(&self, request: &Request) -> u64 {
+        let size = serialise(request).map_or(0, |raw_request| raw_request.len() as u64);
+        let operation = Operation {
+            request: request_name(request),
+            size,
+            stored_data: 0,
+        };
+        self.pricing
+            .cost_of(&operation)
+            .map_or(u64::max_value(), |cost| cost.as_nano())
+    }
+}
+
+// Deleting data isn't charged.
+fn is_free(request: &Request) -> bool {
+    match request {
+        Request::DeleteUnpubIData(_) | Request::DeleteMData(_) | Request::DeleteAData(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use safe_nd::{PubImmutableData, XorName};
+
+    // Test that mutations are answered without being sent, unlike reads other than those of the
+    // immutable data put during the dry run, and that deletions are tallied without being charged.
+    #[test]
+    fn record() {
+        let mut tally = DryRunTally::new(Default::default());
+        let data: IData = PubImmutableData::new(vec![1, 2, 3]).into();
+        let address = *data.address();
+
+        let other_address = *PubImmutableData::new(vec![4, 5, 6]).address();
+        assert!(tally.record(&Request::GetIData(address)).is_none());
+        match tally.record(&Request::PutIData(data.clone())) {
+            Some(Response::Mutation(Ok(()))) => (),
+            response => panic!("Unexpected response {:?}", response),
+        }
+        match tally.record(&Request::GetIData(address)) {
+            Some(Response::GetIData(Ok(fetched_data))) => assert_eq!(fetched_data, data),
+            response => panic!("Unexpected response {:?}", response),
+        }
+        assert!(tally.record(&Request::GetIData(other_address)).is_none());
+        let _ = tally.record(&Request::DeleteUnpubIData(IDataAddress::Unpub(
+            *address.name(),
+        )));
+
+        let amount = unwrap!(Coins::from_nano(10));
+        match tally.record(&Request::TransferCoins {
+            destination: XorName(rand::random()),
+            amount,
+            transaction_id: 1,
+        }) {
+            Some(Response::Transaction(Ok(transaction))) => {
+                assert_eq!(transaction.id, 1);
+                assert_eq!(transaction.amount, amount);
+            }
+            response => panic!("Unexpected response {:?}", response),
+        }
+
+        assert_eq!(tally.mutations, 2);
+        assert_eq!(tally.requests.get("PutIData"), Some(&1));
+        assert_eq!(tally.requests.get("GetIData"), Some(&3));
+        assert_eq!(tally.requests.get("DeleteUnpubIData"), Some(&1));
+        assert_eq!(unwrap!(tally.estimate()).mutations, 2);
+    }
+
+    // Test that the mutations are priced with the given pricing.
+    #[test]
+    fn pricing() {
+        let mut request_costs = BTreeMap::new();
+        let _ = request_costs.insert("PutIData".to_string(), 10);
+        let mut tally = DryRunTally::new(Pricing {
+            base_cost: 2,
+            request_costs,
+            cost_per_kib: 1,
+            network_capacity: Some(100),
+        });
+
+        let data = PubImmutableData::new(vec![0; 2000]);
+        let _ = tally.record(&Request::PutIData(data.into()));
+        let _ = tally.record(&Request::TransferCoins {
+            destination: XorName(rand::random()),
+            amount: unwrap!(Coins::from_nano(10)),
+            transaction_id: 1,
+        });
+
+        // 10 plus 2 KiBs for the put, 2 plus 1 KiB for the transfer.
+        assert_eq!(unwrap!(tally.estimate()).cost.as_nano(), 15);
+    }
+}
@@ -0,0 +1,400 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config_handler::{default_idata_cache_dir, IDataCacheConfig};
+use crate::errors::CoreError;
+use data_encoding::HEXLOWER;
+use filetime::FileTime;
+use fs2::FileExt;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use safe_nd::{IData, IDataAddress, PubImmutableData, PublicKey, UnpubImmutableData};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const LOCK_FILE_NAME: &str = ".lock";
+const SIZE_FILE_NAME: &str = ".size";
+const TEMP_FILE_EXTENSION: &str = "tmp";
+// Temporary files older than this are left over by processes which died while writing them.
+const TEMP_FILE_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Immutable data cached on disk and shared by the processes of the same user. Every chunk is
+/// stored in its own file, named after its address, and checked against its address when read.
+/// Reading a chunk marks it as recently used, and the least recently used chunks are evicted once
+/// the cache exceeds its maximum size. The total size of the chunks is kept in an index file, so
+/// that the cache only has to be scanned when it's full.
+pub struct IDataDiskCache {
+    dir: PathBuf,
+    max_size: u64,
+    published_only: bool,
+}
+
+impl IDataDiskCache {
+    /// Open the cache described by `config`, or return `None` if it's disabled.
+    pub fn open(config: &IDataCacheConfig) -> Result<Option<Self>, CoreError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let dir = match config.path {
+            Some(ref path) => path.clone(),
+            None => default_idata_cache_dir()?,
+        };
+        fs::create_dir_all(&dir)?;
+
+        Ok(Some(Self {
+            dir,
+            max_size: config.max_size,
+            published_only: config.published_only,
+        }))
+    }
+
+    /// Returns the cached chunk at `address`. As the cache is shared by all the accounts of the
+    /// user, an unpublished chunk is only returned to its owner, like the network would.
+    /// A chunk which doesn't match its address is removed from the cache.
+    pub fn get(&self, address: &IDataAddress, requester: &PublicKey) -> Option<IData> {
+        if self.published_only && address.is_unpub() {
+            return None;
+        }
+
+        let path = self.path(address);
+        let mut raw_data = Vec::new();
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut raw_data))
+            .ok()?;
+
+        let data = match deserialise(&raw_data) {
+            Ok(data) if verify(address, &data) => data,
+            _ => {
+                warn!("Removing corrupted chunk {} from the cache", path.display());
+                if let Err(error) = self.remove(address) {
+                    warn!("Could not remove the chunk: {:?}", error);
+                }
+                return None;
+            }
+        };
+        if let IData::Unpub(ref unpub_data) = data {
+            if unpub_data.owner() != requester {
+                return None;
+            }
+        }
+
+        // Mark the chunk as recently used. It may have been evicted in the meantime, in which
+        // case there's nothing to mark.
+        let _ = filetime::set_file_mtime(&path, FileTime::now());
+        Some(data)
+    }
+
+    /// Cache the chunk, then evict the least recently used chunks if the cache has grown too
+    /// large.
+    pub fn insert(&self, data: &IData) -> Result<(), CoreError> {
+        let address = *data.address();
+        if self.published_only && address.is_unpub() {
+            return Ok(());
+        }
+
+        let path = self.path(&address);
+        if path.exists() {
+            return Ok(());
+        }
+
+        let raw_data = serialise(data)?;
+        let len = raw_data.len() as u64;
+        if len > self.max_size {
+            return Ok(());
+        }
+
+        // Other processes may be reading the cache, so the chunk is written to a temporary file
+        // first and only then moved into place.
+        let temp_path = path.with_extension(format!(
+            "{:016x}.{}",
+            new_rand::random::<u64>(),
+            TEMP_FILE_EXTENSION
+        ));
+        File::create(&temp_path)?.write_all(&raw_data)?;
+
+        let lock = self.lock()?;
+        let result = if path.exists() {
+            // Another process cached the chunk first.
+            fs::remove_file(&temp_path).map_err(CoreError::from)
+        } else {
+            match fs::rename(&temp_path, &path) {
+                Ok(()) => self
+                    .read_size()
+                    .and_then(|size| self.evict(size.saturating_add(len))),
+                Err(error) => {
+                    let _ = fs::remove_file(&temp_path);
+                    Err(error.into())
+                }
+            }
+        };
+        lock.unlock()?;
+        result
+    }
+
+    /// Remove the chunk at `address` from the cache.
+    pub fn remove(&self, address: &IDataAddress) -> Result<(), CoreError> {
+        let path = self.path(address);
+        let lock = self.lock()?;
+        let result = match fs::metadata(&path) {
+            Ok(metadata) => fs::remove_file(&path)
+                .map_err(CoreError::from)
+                .and_then(|()| self.read_size())
+                .and_then(|size| self.write_size(size.saturating_sub(metadata.len()))),
+            Err(_) => Ok(()),
+        };
+        lock.unlock()?;
+        result
+    }
+
+    /// Remove all the chunks from the cache, along with the temporary files left over by other
+    /// processes.
+    pub fn clear(&self) -> Result<(), CoreError> {
+        let lock = self.lock()?;
+        for (_, _, path) in self.chunks()? {
+            let _ = fs::remove_file(path);
+        }
+        self.write_size(0)?;
+        Ok(lock.unlock()?)
+    }
+
+    // Remove the least recently used chunks if the cache is larger than its maximum size, and
+    // record the new size. The cache is shrunk to 90% of its maximum size so that it isn't
+    // scanned again on the next insert. Must be called with the cache locked.
+    fn evict(&self, size: u64) -> Result<(), CoreError> {
+        if size <= self.max_size {
+            return self.write_size(size);
+        }
+
+        let target = self.max_size - self.max_size / 10;
+        let mut chunks = self.chunks()?;
+        let mut size: u64 = chunks.iter().map(|(_, len, _)| len).sum();
+        chunks.sort();
+        for (_, len, path) in chunks {
+            if size <= target {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                size -= len;
+            }
+        }
+        self.write_size(size)
+    }
+
+    // Returns the modification time, size and path of every cached chunk, removing the orphaned
+    // temporary files along the way. Must be called with the cache locked.
+    fn chunks(&self) -> Result<Vec<(u128, u64, PathBuf)>, CoreError> {
+        let mut chunks = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+
+            if is_temp_file(&path) {
+                let orphaned = SystemTime::now()
+                    .duration_since(modified)
+                    .map(|age| age > TEMP_FILE_MAX_AGE)
+                    .unwrap_or(false);
+                if orphaned {
+                    let _ = fs::remove_file(&path);
+                }
+                continue;
+            }
+            if !is_chunk(&path) {
+                continue;
+            }
+
+            let modified = modified
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_nanos());
+            chunks.push((modified, metadata.len(), path));
+        }
+        Ok(chunks)
+    }
+
+    // Returns the total size of the cached chunks from the index, rebuilding the index if it's
+    // missing or unreadable. Must be called with the cache locked.
+    fn read_size(&self) -> Result<u64, CoreError> {
+        let size = fs::read_to_string(self.dir.join(SIZE_FILE_NAME))
+            .ok()
+            .and_then(|size| size.trim().parse().ok());
+        match size {
+            Some(size) => Ok(size),
+            None => Ok(self.chunks()?.iter().map(|(_, len, _)| len).sum()),
+        }
+    }
+
+    // Must be called with the cache locked.
+    fn write_size(&self, size: u64) -> Result<(), CoreError> {
+        Ok(fs::write(self.dir.join(SIZE_FILE_NAME), size.to_string())?)
+    }
+
+    // Lock the cache against changes by other processes.
+    fn lock(&self) -> Result<File, CoreError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(self.dir.join(LOCK_FILE_NAME))?;
+        file.lock_exclusive()?;
+        Ok(file)
+    }
+
+    fn path(&self, address: &IDataAddress) -> PathBuf {
+        let kind = if address.is_unpub() { "unpub" } else { "pub" };
+        self.dir
+            .join(format!("{}-{}", kind, HEXLOWER.encode(&address.name().0)))
+    }
+}
+
+// Chunks are the files without an extension, which excludes the lock, index and temporary files.
+fn is_chunk(path: &Path) -> bool {
+    path.extension().is_none()
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| {
+                name.starts_with("pub-") || name.starts_with("unpub-")
+            })
+}
+
+fn is_temp_file(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |extension| extension == TEMP_FILE_EXTENSION)
+}
+
+// The name of a chunk is derived from its content, so the chunk rebuilt from its content is at
+// the same address only if the content is intact.
+fn verify(address: &IDataAddress, data: &IData) -> bool {
+    let rebuilt: IData = match data {
+        IData::Pub(data) => PubImmutableData::new(data.value().clone()).into(),
+        IData::Unpub(data) => UnpubImmutableData::new(data.value().clone(), *data.owner()).into(),
+    };
+    rebuilt.address() == address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::generate_random_vector;
+    use threshold_crypto::SecretKey;
+
+    fn open(dir: &Path, max_size: u64, published_only: bool) -> IDataDiskCache {
+        unwrap!(unwrap!(IDataDiskCache::open(&IDataCacheConfig {
+            enabled: true,
+            path: Some(dir.to_path_buf()),
+            max_size,
+            published_only,
+        })))
+    }
+
+    fn random_pub_data(len: usize) -> IData {
+        PubImmutableData::new(unwrap!(generate_random_vector(len))).into()
+    }
+
+    fn random_key() -> PublicKey {
+        PublicKey::from(SecretKey::random().public_key())
+    }
+
+    fn total_size(cache: &IDataDiskCache) -> u64 {
+        unwrap!(cache.chunks()).iter().map(|(_, len, _)| len).sum()
+    }
+
+    // Test that chunks are shared between instances of the cache, unpublished chunks are only
+    // returned to their owner, and corrupted chunks are discarded.
+    #[test]
+    fn shared_and_verified() {
+        let dir = unwrap!(tempfile::tempdir());
+        let cache = open(dir.path(), 1024 * 1024, false);
+
+        let data = random_pub_data(100);
+        let owner = random_key();
+        let unpub_data: IData =
+            UnpubImmutableData::new(unwrap!(generate_random_vector(100)), owner).into();
+        unwrap!(cache.insert(&data));
+        unwrap!(cache.insert(&unpub_data));
+
+        let other_cache = open(dir.path(), 1024 * 1024, false);
+        let other_key = random_key();
+        assert_eq!(
+            other_cache.get(data.address(), &other_key),
+            Some(data.clone())
+        );
+        assert_eq!(
+            other_cache.get(unpub_data.address(), &owner),
+            Some(unpub_data.clone())
+        );
+        assert_eq!(other_cache.get(unpub_data.address(), &other_key), None);
+
+        // Replace the content of the chunk with another chunk.
+        let path = cache.path(data.address());
+        unwrap!(fs::write(&path, unwrap!(serialise(&random_pub_data(100)))));
+        assert_eq!(cache.get(data.address(), &owner), None);
+        assert!(!path.exists());
+        assert_eq!(unwrap!(cache.read_size()), total_size(&cache));
+
+        // Unpublished data isn't cached if the cache is limited to published data.
+        let cache = open(dir.path(), 1024 * 1024, true);
+        let unpub_data: IData =
+            UnpubImmutableData::new(unwrap!(generate_random_vector(100)), owner).into();
+        unwrap!(cache.insert(&unpub_data));
+        assert!(!cache.path(unpub_data.address()).exists());
+    }
+
+    // Test that the cache never grows larger than its maximum size, that the least recently
+    // used chunks are evicted first, and that the size index matches the cached chunks.
+    #[test]
+    fn eviction() {
+        let dir = unwrap!(tempfile::tempdir());
+        let cache = open(dir.path(), 1000, true);
+        let requester = random_key();
+
+        let first = random_pub_data(300);
+        unwrap!(cache.insert(&first));
+        let old_time = FileTime::from_unix_time(1, 0);
+        for _ in 0..10 {
+            // Make the other chunks look older than the first one, which is read every time.
+            for (_, _, path) in unwrap!(cache.chunks()) {
+                unwrap!(filetime::set_file_mtime(&path, old_time));
+            }
+            assert_eq!(cache.get(first.address(), &requester), Some(first.clone()));
+
+            unwrap!(cache.insert(&random_pub_data(300)));
+            let size = total_size(&cache);
+            assert!(size <= 1000);
+            assert_eq!(unwrap!(cache.read_size()), size);
+        }
+        assert!(cache.path(first.address()).exists());
+
+        unwrap!(cache.clear());
+        assert!(unwrap!(cache.chunks()).is_empty());
+        assert_eq!(unwrap!(cache.read_size()), 0);
+    }
+
+    // Test that temporary files left over by dead processes are removed, while the recent ones
+    // are kept.
+    #[test]
+    fn orphaned_temp_files() {
+        let dir = unwrap!(tempfile::tempdir());
+        let cache = open(dir.path(), 1000, true);
+
+        let orphaned = dir.path().join(format!("pub-00.1.{}", TEMP_FILE_EXTENSION));
+        let recent = dir.path().join(format!("pub-00.2.{}", TEMP_FILE_EXTENSION));
+        unwrap!(fs::write(&orphaned, b"orphaned"));
+        unwrap!(fs::write(&recent, b"recent"));
+        unwrap!(filetime::set_file_mtime(
+            &orphaned,
+            FileTime::from_unix_time(1, 0)
+        ));
+
+        unwrap!(cache.clear());
+        assert!(!orphaned.exists());
+        assert!(recent.exists());
+    }
+}
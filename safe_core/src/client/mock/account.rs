@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::config_handler::Config;
-use safe_nd::{AppPermissions, Coins, Error, PublicKey};
+use safe_nd::{AppPermissions, Coins, Error, PublicKey, XorName};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
 
@@ -19,6 +19,9 @@ pub struct CoinBalance {
     owner: PublicKey,
     value: Coins,
     credits: VecDeque<Credit>,
+    // Transfers sent from the balance, by transaction ID. Unlike the credits, they're all kept,
+    // so that a transaction ID is never reused by the sender.
+    debits: BTreeMap<u64, Debit>,
 }
 
 impl CoinBalance {
@@ -27,6 +30,7 @@ impl CoinBalance {
             owner,
             value,
             credits: VecDeque::new(),
+            debits: BTreeMap::new(),
         }
     }
 
@@ -53,6 +57,23 @@ impl CoinBalance {
         self.value
     }
 
+    pub fn add_debit(&mut self, transaction_id: u64, destination: XorName, amount: Coins) {
+        let _ = self.debits.insert(
+            transaction_id,
+            Debit {
+                destination,
+                amount,
+            },
+        );
+    }
+
+    // Returns the destination and amount of the transfer sent under the given transaction ID.
+    pub fn debit(&self, transaction_id: u64) -> Option<(XorName, Coins)> {
+        self.debits
+            .get(&transaction_id)
+            .map(|debit| (debit.destination, debit.amount))
+    }
+
     fn add_transaction(&mut self, amount: Coins, transaction_id: u64) {
         if self.credits.len() == DEFAULT_MAX_CREDITS {
             let _ = self.credits.pop_back();
@@ -75,8 +96,17 @@ pub struct Credit {
     transaction_id: u64, // TODO: use Uuid
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct Debit {
+    destination: XorName,
+    amount: Coins,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct Account {
+    // Serialised as a list of pairs, so that accounts can be exported as JSON. The binary encoding
+    // is the same as for the map.
+    #[serde(with = "key_value_pairs")]
     auth_keys: BTreeMap<PublicKey, AppPermissions>,
     version: u64,
     config: Config,
@@ -135,3 +165,27 @@ impl Account {
         }
     }
 }
+
+mod key_value_pairs {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<K, V, S>(map: &BTreeMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let pairs: Vec<(K, V)> = Deserialize::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
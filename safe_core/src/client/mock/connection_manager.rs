@@ -6,21 +6,41 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::faults::{init_fault_profile, Fault, FaultInjector};
+use super::pricing::{Charge, PricingModel};
+use super::recording::{Recorder, Recording};
+use super::sections::{init_sections_profile, Routing, SectionSimulation};
 use super::vault::{self, Vault};
-use crate::config_handler::{get_config, Config};
+#[cfg(unix)]
+use super::vault_server::{init_vault_socket, VaultConnection};
+use crate::config_handler::{get_config, Config, FaultProfile, SectionEvent, SectionsProfile};
 use crate::{
     client::SafeKey,
-    event::{NetworkEvent, NetworkTx},
-    CoreError, CoreFuture,
+    event::{NetworkEvent, NetworkTx, NotificationRx},
+    section_map::data_name,
+    CoreError, CoreFuture, Quorum,
 };
-use quic_p2p::{self, Config as QuicP2pConfig};
-use safe_nd::{Coins, Message, PublicId, PublicKey, Request, RequestType, Response, XorName};
-use std::collections::HashSet;
-use std::env;
+use futures::{future, sync::mpsc, Future};
+use safe_nd::{
+    Coins, Error as SndError, Message, PublicId, PublicKey, Request, RequestType, Response, XorName,
+};
+use std::collections::{HashMap, HashSet};
+#[cfg(unix)]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::prelude::FutureExt;
+use tokio::timer::Delay;
+
+// Value of the operations countdown when the number of operations isn't limited.
+const UNLIMITED_OPS: u64 = std::u64::MAX;
 
 lazy_static! {
-    static ref VAULT: Arc<Mutex<Vault>> = Arc::new(Mutex::new(Vault::new(get_config())));
+    // Vaults of this process by the path they're stored at, so that the connection managers whose
+    // configs point at the same path share a vault.
+    static ref VAULTS: Mutex<HashMap<PathBuf, Arc<Mutex<Vault>>>> = Mutex::new(HashMap::new());
 }
 
 /// Function that is used to tap into routing requests and return preconditioned responses.
@@ -35,21 +55,65 @@ pub type ResponseHookFn = dyn FnMut(Response) -> Response + 'static;
 #[derive(Clone)]
 pub struct ConnectionManager {
     vault: Arc<Mutex<Vault>>,
-    request_hook: Option<Arc<RequestHookFn>>,
-    response_hook: Option<Arc<ResponseHookFn>>,
+    request_hook: Option<Arc<Mutex<RequestHookFn>>>,
+    response_hook: Option<Arc<Mutex<ResponseHookFn>>>,
     groups: Arc<Mutex<HashSet<PublicId>>>,
+    // Groups we've lost the connection to and which will be restored by `restart_network`.
+    disconnected_groups: Arc<Mutex<HashSet<PublicId>>>,
+    auto_reconnect: Arc<AtomicBool>,
+    // There's only one mock vault to agree on a response, but the setting is kept so that clients
+    // behave the same with both connection managers.
+    quorum: Arc<Mutex<Quorum>>,
     net_tx: NetworkTx,
+    // Number of requests whose response future hasn't completed or been dropped yet.
+    in_flight: Arc<AtomicUsize>,
+    // The settings below are shared with the clones, such as the ones sending requests on behalf
+    // of other identities.
+    timeout_simulation: Arc<AtomicBool>,
+    // Number of requests which will still be processed, or `UNLIMITED_OPS`.
+    max_ops_countdown: Arc<AtomicU64>,
+    faults: Arc<Mutex<Option<FaultInjector>>>,
+    recorder: Arc<Mutex<Option<Recorder>>>,
+    sections: Arc<Mutex<Option<SectionSimulation>>>,
+    // Connection to a mock vault server, used instead of the vault of this process if set.
+    #[cfg(unix)]
+    remote: Option<Arc<VaultConnection>>,
+    config: Arc<Config>,
 }
 
 impl ConnectionManager {
-    /// Create a new connection manager.
-    pub fn new(_config: QuicP2pConfig, net_tx: &NetworkTx) -> Result<Self, CoreError> {
+    /// Create a new connection manager. The developer options of `config` decide which vault the
+    /// requests are sent to, and how the network is simulated.
+    pub fn new(config: Config, net_tx: &NetworkTx) -> Result<Self, CoreError> {
+        let faults = init_fault_profile(&config).map(FaultInjector::new);
+        let sections = init_sections_profile(&config).map(SectionSimulation::new);
+        #[cfg(unix)]
+        let remote = match init_vault_socket(&config) {
+            Some(path) => Some(connect_to_vault_server(&path, &config)?),
+            None => None,
+        };
+        let dev_config = config.dev.clone().unwrap_or_default();
+
         Ok(Self {
-            vault: clone_vault(),
+            vault: vault_for(&config),
             request_hook: None,
             response_hook: None,
             groups: Arc::new(Mutex::new(HashSet::default())),
+            disconnected_groups: Arc::new(Mutex::new(HashSet::default())),
+            auto_reconnect: Arc::new(AtomicBool::new(false)),
+            quorum: Arc::new(Mutex::new(Quorum::default())),
             net_tx: net_tx.clone(),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            timeout_simulation: Arc::new(AtomicBool::new(dev_config.mock_simulate_timeout)),
+            max_ops_countdown: Arc::new(AtomicU64::new(
+                dev_config.mock_max_ops_count.unwrap_or(UNLIMITED_OPS),
+            )),
+            faults: Arc::new(Mutex::new(faults)),
+            recorder: Arc::new(Mutex::new(None)),
+            sections: Arc::new(Mutex::new(sections)),
+            #[cfg(unix)]
+            remote,
+            config: Arc::new(config),
         })
     }
 
@@ -60,55 +124,235 @@ impl ConnectionManager {
     }
 
     /// Send `message` via the `ConnectionGroup` specified by our given `pub_id`.
-    pub fn send(&mut self, pub_id: &PublicId, msg: &Message) -> Box<CoreFuture<Response>> {
+    pub fn send(
+        &mut self,
+        pub_id: &PublicId,
+        msg: &Message,
+        timeout: Duration,
+    ) -> Box<CoreFuture<Response>> {
+        let response = self.send_request(pub_id, msg, timeout);
+        let in_flight = InFlight::new(&self.in_flight);
+
+        Box::new(response.then(move |result| {
+            drop(in_flight);
+            result
+        }))
+    }
+
+    fn send_request(
+        &mut self,
+        pub_id: &PublicId,
+        msg: &Message,
+        timeout: Duration,
+    ) -> Box<CoreFuture<Response>> {
+        let sent_at = Instant::now();
+
+        if self.timeout_simulation.load(Ordering::SeqCst) {
+            return no_response(timeout);
+        }
+
+        if let Message::Request { request, .. } = msg {
+            if self.network_limits_reached() {
+                info!("Mock request {:?}: max operations exhausted", request);
+                let response = request.error_response(SndError::NetworkOther(
+                    "Max operations exhausted".to_string(),
+                ));
+                self.record(pub_id, msg, &response, false, sent_at);
+                return ok!(response);
+            }
+        }
+
         #[cfg(any(feature = "testing", test))]
         {
             if let Some(resp) = self.intercept_request(msg.clone()) {
+                self.record(pub_id, msg, &resp, false, sent_at);
                 return ok!(resp);
             }
         }
 
-        let msg: Message = {
-            let writing = match msg {
-                Message::Request { request, .. } => {
-                    let req_type = request.get_type();
-                    req_type == RequestType::Mutation || req_type == RequestType::Transaction
+        match self.route(pub_id, msg) {
+            Routing::Delivered => (),
+            Routing::Lost => return no_response(timeout),
+            Routing::Failed(error) => {
+                if let Message::Request { request, .. } = msg {
+                    let response = request.error_response(error);
+                    self.record(pub_id, msg, &response, false, sent_at);
+                    return ok!(response);
                 }
-                _ => false,
-            };
-            let mut vault = vault::lock(&self.vault, writing);
-            unwrap!(vault.process_request(pub_id.clone(), &msg))
+            }
+        }
+
+        let fault = match (msg, &mut *unwrap!(self.faults.lock())) {
+            (Message::Request { request, .. }, Some(faults)) => faults.next_fault(request),
+            _ => Fault::default(),
+        };
+        if fault != Fault::default() {
+            trace!("Mock network: injecting {:?}", fault);
+        }
+
+        let (reply, processed) = match (msg, fault.error) {
+            (
+                Message::Request {
+                    request,
+                    message_id,
+                    ..
+                },
+                Some(error),
+            ) => (
+                Message::Response {
+                    response: request.error_response(error),
+                    message_id: *message_id,
+                },
+                false,
+            ),
+            _ => (
+                fry!(self.process_request(pub_id, msg, fault.duplicate, timeout)),
+                true,
+            ),
         };
 
         // Send response back to a client
-        if let Message::Response { response, .. } = msg {
-            ok!(response)
+        let response = if let Message::Response { response, .. } = reply {
+            response
         } else {
-            err!(CoreError::Unexpected(
+            return err!(CoreError::Unexpected(
                 "Logic error: Vault error returned invalid response".to_string()
-            ))
+            ));
+        };
+
+        #[cfg(any(feature = "testing", test))]
+        let response = self.apply_response_hook(response);
+
+        self.record(pub_id, msg, &response, processed, sent_at);
+
+        if fault.drop {
+            no_response(timeout)
+        } else if fault.latency > Duration::from_millis(0) {
+            Box::new(
+                Delay::new(Instant::now() + fault.latency)
+                    .map_err(|err| CoreError::Unexpected(err.to_string()))
+                    .map(move |()| response)
+                    .timeout(timeout)
+                    .map_err(|err| err.into_inner().unwrap_or(CoreError::RequestTimeout)),
+            )
+        } else {
+            ok!(response)
         }
     }
 
+    /// Connect to the mock vault server listening on the socket at `path`, so that the requests
+    /// are processed by the vault it shares with other processes rather than by the vault of this
+    /// process. The connection is shared by the clones of this connection manager.
+    #[cfg(unix)]
+    pub fn connect_to_vault_server(&mut self, path: &Path) -> Result<(), CoreError> {
+        self.remote = Some(connect_to_vault_server(path, &self.config)?);
+        Ok(())
+    }
+
+    // Have the vault process the message, twice if it's duplicated.
+    fn process_request(
+        &self,
+        pub_id: &PublicId,
+        msg: &Message,
+        duplicate: bool,
+        timeout: Duration,
+    ) -> Result<Message, CoreError> {
+        #[cfg(not(unix))]
+        let _ = timeout;
+        #[cfg(unix)]
+        {
+            if let Some(remote) = &self.remote {
+                if duplicate {
+                    let _ = remote.process_request(pub_id, msg, timeout);
+                }
+                return remote.process_request(pub_id, msg, timeout);
+            }
+        }
+
+        let writing = match msg {
+            Message::Request { request, .. } => {
+                let req_type = request.get_type();
+                req_type == RequestType::Mutation || req_type == RequestType::Transaction
+            }
+            _ => false,
+        };
+        let mut vault = vault::lock(&self.vault, writing);
+        if duplicate {
+            let _ = vault.process_request(pub_id.clone(), msg);
+        }
+        Ok(vault.process_request(pub_id.clone(), msg)?)
+    }
+
+    /// Subscribe to the notifications concerning the coin balance of the given client, such as
+    /// incoming coin transfers.
+    pub fn subscribe(&mut self, pub_id: &PublicId) -> Result<NotificationRx, CoreError> {
+        let coin_balance_id = match pub_id {
+            PublicId::Client(client_id) => XorName::from(*client_id.public_key()),
+            PublicId::App(app_id) => XorName::from(*app_id.owner().public_key()),
+            PublicId::Node(_) => {
+                return Err(CoreError::Unexpected(
+                    "Nodes can't subscribe to notifications".to_string(),
+                ))
+            }
+        };
+
+        let (notification_tx, notification_rx) = mpsc::unbounded();
+        #[cfg(unix)]
+        {
+            if let Some(remote) = &self.remote {
+                remote.subscribe(coin_balance_id, notification_tx)?;
+                return Ok(notification_rx);
+            }
+        }
+        vault::lock(&self.vault, false).subscribe(coin_balance_id, notification_tx);
+        Ok(notification_rx)
+    }
+
+    /// Returns the number of requests which are still waiting for a response: those whose
+    /// response future hasn't completed or been dropped yet.
+    pub fn requests_in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
     /// Bootstrap to any known contact.
     pub fn bootstrap(&mut self, full_id: SafeKey) -> Box<CoreFuture<()>> {
         let _ = unwrap!(self.groups.lock()).insert(full_id.public_id());
         ok!(())
     }
 
-    /// Restart the connection to the groups.
-    pub fn restart_network(&mut self) {
-        // Do nothing
+    /// Restart the connection to the groups. `NetworkEvent::Connected` is sent once they're all
+    /// connected again, like it is by the real connection manager.
+    pub fn restart_network(&mut self) -> Box<CoreFuture<()>> {
+        let mut groups = unwrap!(self.groups.lock());
+        let mut disconnected_groups = unwrap!(self.disconnected_groups.lock());
+
+        trace!("Reconnecting to groups: {:?}", disconnected_groups);
+        groups.extend(disconnected_groups.drain());
+        let _ = self.net_tx.unbounded_send(NetworkEvent::Connected);
+
+        ok!(())
+    }
+
+    /// Enable or disable automatic reconnection after the connection has been lost.
+    pub fn set_auto_reconnect(&mut self, enabled: bool) {
+        self.auto_reconnect.store(enabled, Ordering::SeqCst);
     }
 
-    /// Disconnect from a group.
+    /// Set the number of elders that have to agree on a response before it's accepted. The mock
+    /// vault is the only one responding, so this doesn't affect the responses.
+    pub fn set_quorum(&mut self, quorum: Quorum) {
+        *unwrap!(self.quorum.lock()) = quorum;
+    }
+
+    /// Returns the number of elders that have to agree on a response before it's accepted.
+    pub fn quorum(&self) -> Quorum {
+        *unwrap!(self.quorum.lock())
+    }
+
+    /// Disconnect from a group. Like the real connection manager, this doesn't send
+    /// `NetworkEvent::Disconnected`, as the connection hasn't been lost.
     pub fn disconnect(&mut self, pub_id: &PublicId) -> Box<CoreFuture<()>> {
-        let mut groups = unwrap!(self.groups.lock());
-        let _ = groups.remove(pub_id);
-        if groups.is_empty() {
-            trace!("Disconnected from the network; sending the notification.");
-            let _ = self.net_tx.unbounded_send(NetworkEvent::Disconnected);
-        }
+        let _ = unwrap!(self.groups.lock()).remove(pub_id);
         ok!(())
     }
 
@@ -118,38 +362,197 @@ impl ConnectionManager {
         coin_balance_name: &XorName,
         amount: Coins,
     ) -> Result<(), safe_nd::Error> {
+        #[cfg(unix)]
+        {
+            if let Some(remote) = &self.remote {
+                return remote
+                    .increment_balance(coin_balance_name, amount)
+                    .unwrap_or_else(|error| Err(SndError::NetworkOther(error.to_string())));
+            }
+        }
         let mut vault = vault::lock(&self.vault, true);
         vault.mock_increment_balance(coin_balance_name, amount)
     }
 
     /// Create coin balance in the mock network arbitrarily.
-    pub fn create_balance(&self, owner: PublicKey, amount: Coins) {
+    pub fn create_balance(&self, owner: PublicKey, amount: Coins) -> Result<(), CoreError> {
+        #[cfg(unix)]
+        {
+            if let Some(remote) = &self.remote {
+                return remote.create_balance(owner, amount);
+            }
+        }
         let mut vault = vault::lock(&self.vault, true);
         vault.mock_create_balance(owner, amount);
+        Ok(())
+    }
+
+    /// Replace the model deciding how much the mock vault charges for the mutations. The vault, and
+    /// so the model, is shared by all the connection managers. The model of a mock vault server
+    /// can't be replaced.
+    pub fn set_pricing_model(&self, pricing: Box<dyn PricingModel>) -> Result<(), CoreError> {
+        #[cfg(unix)]
+        {
+            if self.remote.is_some() {
+                return Err(CoreError::Unexpected(
+                    "The pricing model of a mock vault server can't be replaced".to_string(),
+                ));
+            }
+        }
+        vault::lock(&self.vault, false).set_pricing_model(pricing);
+        Ok(())
+    }
+
+    /// Returns the charges made by the mock vault to the given account, oldest first.
+    pub fn charges(&self, account: &XorName) -> Result<Vec<Charge>, CoreError> {
+        #[cfg(unix)]
+        {
+            if let Some(remote) = &self.remote {
+                return remote.charges(account);
+            }
+        }
+        Ok(vault::lock(&self.vault, false).charges(account))
     }
 
     /// Simulates network disconnect
-    pub fn simulate_disconnect(&self) {
-        let mut groups = unwrap!(self.groups.lock());
-        trace!("Simulating disconnect. Connected groups: {:?}", groups);
+    pub fn simulate_disconnect(&mut self) {
+        {
+            let mut groups = unwrap!(self.groups.lock());
+            trace!("Simulating disconnect. Connected groups: {:?}", groups);
+
+            if groups.is_empty() {
+                return;
+            }
 
-        if !groups.is_empty() {
             trace!("Disconnecting everyone");
-            groups.clear();
+            unwrap!(self.disconnected_groups.lock()).extend(groups.drain());
             let _ = self.net_tx.unbounded_send(NetworkEvent::Disconnected);
         }
+
+        if self.auto_reconnect.load(Ordering::SeqCst) {
+            let _ = self.restart_network();
+        }
+    }
+
+    /// Simulates network timeouts: requests never get a response, so they fail with
+    /// `CoreError::RequestTimeout` once their timeout expires.
+    pub fn set_simulate_timeout(&mut self, enable: bool) {
+        self.timeout_simulation.store(enable, Ordering::SeqCst);
+    }
+
+    /// Sets a maximum number of operations, after which every request fails with
+    /// `safe_nd::Error::NetworkOther`. `None` removes the limit.
+    pub fn set_network_limits(&mut self, max_ops_count: Option<u64>) {
+        self.max_ops_countdown
+            .store(max_ops_count.unwrap_or(UNLIMITED_OPS), Ordering::SeqCst);
+    }
+
+    /// Inject faults into the requests following the given profile, or stop injecting them if
+    /// `None`. Applies to the clones of this connection manager as well.
+    pub fn set_fault_profile(&mut self, profile: Option<FaultProfile>) {
+        *unwrap!(self.faults.lock()) = profile.map(FaultInjector::new);
+    }
+
+    /// Simulate a network of several sections following the given profile, or stop simulating
+    /// them if `None`. Applies to the clones of this connection manager as well.
+    pub fn set_sections_profile(&mut self, profile: Option<SectionsProfile>) {
+        *unwrap!(self.sections.lock()) = profile.map(SectionSimulation::new);
+    }
+
+    /// Apply an event to the simulated sections. Fails if no sections are simulated or the event
+    /// is about a section which doesn't exist.
+    pub fn simulate_section_event(&self, event: SectionEvent) -> Result<(), CoreError> {
+        match &mut *unwrap!(self.sections.lock()) {
+            Some(sections) => sections.apply(event),
+            None => Err(CoreError::Unexpected(
+                "No sections are simulated".to_string(),
+            )),
+        }
+    }
+
+    // Route the message through the simulated sections, to the section managing the data it's
+    // about or the section of the client.
+    fn route(&self, pub_id: &PublicId, msg: &Message) -> Routing {
+        match &mut *unwrap!(self.sections.lock()) {
+            Some(sections) => {
+                let name = data_name(msg).unwrap_or_else(|| *pub_id.name());
+                sections.route(&name)
+            }
+            None => Routing::Delivered,
+        }
+    }
+
+    /// Start recording the requests sent through this connection manager and its clones,
+    /// together with the responses they receive and the current state of the vault. Discards any
+    /// previous recording.
+    pub fn start_recording(&mut self) {
+        let initial_state = self.vault_state();
+        *unwrap!(self.recorder.lock()) = Some(Recorder::start(initial_state));
+    }
+
+    // Returns the state of the vault the requests are sent to, if it's the vault of this process.
+    fn vault_state(&self) -> Option<Vec<u8>> {
+        #[cfg(unix)]
+        {
+            if self.remote.is_some() {
+                return None;
+            }
+        }
+
+        match vault::lock(&self.vault, false).state() {
+            Ok(state) => Some(state),
+            Err(error) => {
+                warn!("Failed to save the state of the mock vault: {:?}", error);
+                None
+            }
+        }
     }
 
-    /// Simulates network timeouts
-    pub fn set_simulate_timeout(&mut self, _enable: bool) {
-        unimplemented!()
-        // self.timeout_simulation = enable;
+    /// Stop recording and return the recorded exchanges, or `None` if not recording.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        unwrap!(self.recorder.lock())
+            .take()
+            .map(|mut recorder| recorder.finish())
     }
 
-    /// Sets a maximum number of operations
-    pub fn set_network_limits(&mut self, _max_ops_count: Option<u64>) {
-        unimplemented!()
-        // self.max_ops_countdown = max_ops_count.map(Cell::new)
+    fn record(
+        &self,
+        pub_id: &PublicId,
+        msg: &Message,
+        response: &Response,
+        processed: bool,
+        sent_at: Instant,
+    ) {
+        if let Some(recorder) = &mut *unwrap!(self.recorder.lock()) {
+            recorder.record(
+                pub_id.clone(),
+                msg.clone(),
+                response.clone(),
+                processed,
+                sent_at,
+            );
+        }
+    }
+
+    // Counts the request against the network limits. Returns `true` if they've been reached.
+    fn network_limits_reached(&self) -> bool {
+        let mut count = self.max_ops_countdown.load(Ordering::SeqCst);
+        loop {
+            match count {
+                0 => return true,
+                UNLIMITED_OPS => return false,
+                _ => (),
+            }
+            match self.max_ops_countdown.compare_exchange(
+                count,
+                count - 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return false,
+                Err(current) => count = current,
+            }
+        }
     }
 }
 
@@ -157,21 +560,25 @@ impl ConnectionManager {
 impl ConnectionManager {
     fn intercept_request(&mut self, message: Message) -> Option<Response> {
         if let Message::Request { request, .. } = message {
-            if let Some(hook) = Arc::get_mut(self.request_hook.as_mut()?) {
-                if let Some(response) = hook(&request) {
-                    return Some(response);
-                }
-            }
+            let mut hook = unwrap!(self.request_hook.as_ref()?.lock());
+            return (&mut *hook)(&request);
         }
         None
     }
 
+    fn apply_response_hook(&mut self, response: Response) -> Response {
+        match self.response_hook {
+            Some(ref hook) => (&mut *unwrap!(hook.lock()))(response),
+            None => response,
+        }
+    }
+
     /// Set hook function to override response before request is processed, for test purposes.
     pub fn set_request_hook<F>(&mut self, hook: F)
     where
         F: FnMut(&Request) -> Option<Response> + 'static,
     {
-        let hook: Arc<RequestHookFn> = Arc::new(hook);
+        let hook: Arc<Mutex<RequestHookFn>> = Arc::new(Mutex::new(hook));
         self.request_hook = Some(hook);
     }
 
@@ -180,7 +587,7 @@ impl ConnectionManager {
     where
         F: FnMut(Response) -> Response + 'static,
     {
-        let hook: Arc<ResponseHookFn> = Arc::new(hook);
+        let hook: Arc<Mutex<ResponseHookFn>> = Arc::new(Mutex::new(hook));
         self.response_hook = Some(hook);
     }
 
@@ -188,19 +595,64 @@ impl ConnectionManager {
     pub fn remove_request_hook(&mut self) {
         self.request_hook = None;
     }
+
+    /// Removes hook function to modify responses after requests are processed.
+    pub fn remove_response_hook(&mut self) {
+        self.response_hook = None;
+    }
 }
 
-/// Creates a thread-safe reference-counted pointer to the global vault.
-pub fn clone_vault() -> Arc<Mutex<Vault>> {
-    VAULT.clone()
+// Counts a request as in flight until it's dropped along with the request's response future.
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn new(in_flight: &Arc<AtomicUsize>) -> Self {
+        let _ = in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(in_flight.clone())
+    }
 }
 
-pub fn unlimited_muts(config: &Config) -> bool {
-    match env::var("SAFE_MOCK_UNLIMITED_MUTATIONS") {
-        Ok(_) => true,
-        Err(_) => match config.dev {
-            Some(ref dev) => dev.mock_unlimited_mutations,
-            None => false,
-        },
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let _ = self.0.fetch_sub(1, Ordering::SeqCst);
     }
 }
+
+// Response future which never resolves, failing once the timeout expires.
+fn no_response(timeout: Duration) -> Box<CoreFuture<Response>> {
+    Box::new(
+        future::empty::<Response, CoreError>()
+            .timeout(timeout)
+            .map_err(|_| CoreError::RequestTimeout),
+    )
+}
+
+// Connect to a mock vault server. The server decides whether mutations are unlimited, so the
+// connection is refused if `config` asks for unlimited mutations but the server doesn't allow them.
+#[cfg(unix)]
+fn connect_to_vault_server(
+    path: &Path,
+    config: &Config,
+) -> Result<Arc<VaultConnection>, CoreError> {
+    let remote = VaultConnection::connect(path)?;
+    if vault::unlimited_muts(config) && !remote.unlimited_muts()? {
+        return Err(CoreError::Unexpected(format!(
+            "The mock vault server on {} doesn't allow unlimited mutations",
+            path.display()
+        )));
+    }
+    Ok(Arc::new(remote))
+}
+
+/// Creates a thread-safe reference-counted pointer to the vault used with the default config.
+pub fn clone_vault() -> Arc<Mutex<Vault>> {
+    vault_for(&get_config())
+}
+
+// Returns the vault stored at the path `config` points at, opening it the first time.
+fn vault_for(config: &Config) -> Arc<Mutex<Vault>> {
+    unwrap!(VAULTS.lock())
+        .entry(vault::mock_vault_path(config))
+        .or_insert_with(|| Arc::new(Mutex::new(Vault::new(config.clone()))))
+        .clone()
+}
@@ -0,0 +1,180 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{load_profile, read_json_file};
+use crate::config_handler::{Config, FaultProfile, Latency};
+use crate::utils::request_name;
+use new_rand::distributions::Exp;
+use new_rand::rngs::StdRng;
+use new_rand::seq::SliceRandom;
+use new_rand::{Rng, SeedableRng};
+use safe_nd::{Error as SndError, Request};
+use std::time::Duration;
+
+/// Faults injected into a single request.
+#[derive(Debug, Default, PartialEq)]
+pub struct Fault {
+    /// Error the request fails with instead of being processed.
+    pub error: Option<SndError>,
+    /// Whether the request is processed twice.
+    pub duplicate: bool,
+    /// Whether the response is lost.
+    pub drop: bool,
+    /// Delay before the response is received.
+    pub latency: Duration,
+}
+
+/// Decides which faults are injected into the requests, following a `FaultProfile`.
+pub struct FaultInjector {
+    profile: FaultProfile,
+    rng: StdRng,
+}
+
+impl FaultInjector {
+    /// Create an injector for the given profile.
+    pub fn new(profile: FaultProfile) -> Self {
+        let seed = profile.seed.unwrap_or_else(new_rand::random);
+        info!("Mock network: injecting faults with seed {}", seed);
+
+        Self {
+            profile,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Returns the faults to inject into the given request. The same number of random values is
+    /// drawn for every request, so that a given seed always results in the same faults.
+    pub fn next_fault(&mut self, request: &Request) -> Fault {
+        let name = request_name(request);
+        let mut error = None;
+
+        for rule in &self.profile.failures {
+            let fails = self.rng.gen_bool(probability(rule.probability));
+            let chosen = rule.errors.choose(&mut self.rng).cloned();
+
+            let matches = rule.requests.is_empty() || rule.requests.iter().any(|req| *req == name);
+            if matches && fails && error.is_none() {
+                error = Some(
+                    chosen
+                        .unwrap_or_else(|| SndError::NetworkOther("Injected failure".to_string())),
+                );
+            }
+        }
+
+        let duplicate = self
+            .rng
+            .gen_bool(probability(self.profile.duplicate_probability));
+        let drop = self
+            .rng
+            .gen_bool(probability(self.profile.drop_probability));
+        let latency = self.latency();
+
+        Fault {
+            error,
+            duplicate,
+            drop,
+            latency,
+        }
+    }
+
+    fn latency(&mut self) -> Duration {
+        let millis = match self.profile.latency {
+            Latency::None => 0,
+            Latency::Fixed { millis } => millis,
+            Latency::Uniform {
+                min_millis,
+                max_millis,
+            } => self
+                .rng
+                .gen_range(min_millis, max_millis.max(min_millis) + 1),
+            Latency::Exponential { mean_millis } => {
+                let exp = Exp::new(1.0 / (mean_millis.max(1) as f64));
+                self.rng.sample(exp) as u64
+            }
+        };
+        Duration::from_millis(millis)
+    }
+}
+
+// Initializes the fault profile with the following precedence:
+// 1. "SAFE_MOCK_FAULT_PROFILE" env var, the path to a JSON file containing the profile
+// 2. DevConfig `mock_fault_profile` option
+pub fn init_fault_profile(config: &Config) -> Option<FaultProfile> {
+    load_profile(config, "SAFE_MOCK_FAULT_PROFILE", read_json_file, |dev| {
+        dev.mock_fault_profile.clone()
+    })
+}
+
+fn probability(value: f64) -> f64 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.max(0.0).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_handler::FailureRule;
+    use safe_nd::IDataAddress;
+
+    fn get_idata() -> Request {
+        Request::GetIData(IDataAddress::Pub(new_rand::random()))
+    }
+
+    // Test that failures only apply to the requests they're configured for.
+    #[test]
+    fn failures() {
+        let mut injector = FaultInjector::new(FaultProfile {
+            failures: vec![FailureRule {
+                requests: vec!["GetIData".to_string()],
+                probability: 1.0,
+                errors: vec![SndError::NoSuchData],
+            }],
+            ..Default::default()
+        });
+
+        assert_eq!(request_name(&get_idata()), "GetIData");
+        assert_eq!(
+            injector.next_fault(&get_idata()).error,
+            Some(SndError::NoSuchData)
+        );
+        assert_eq!(injector.next_fault(&Request::GetBalance).error, None);
+    }
+
+    // Test that the same seed results in the same faults.
+    #[test]
+    fn deterministic() {
+        let profile = FaultProfile {
+            seed: Some(42),
+            failures: vec![FailureRule {
+                requests: vec![],
+                probability: 0.5,
+                errors: vec![],
+            }],
+            latency: Latency::Uniform {
+                min_millis: 10,
+                max_millis: 1000,
+            },
+            drop_probability: 0.3,
+            duplicate_probability: 0.3,
+        };
+
+        let mut injector0 = FaultInjector::new(profile.clone());
+        let mut injector1 = FaultInjector::new(profile);
+        let request = get_idata();
+
+        for _ in 0..100 {
+            assert_eq!(
+                injector0.next_fault(&request),
+                injector1.next_fault(&request)
+            );
+        }
+    }
+}
@@ -9,16 +9,39 @@
 pub mod vault;
 
 mod account;
+#[cfg(feature = "mock-network")]
+mod faults;
+mod pricing;
+#[cfg(feature = "mock-network")]
+mod recording;
+#[cfg(feature = "mock-network")]
+mod sections;
+#[cfg(all(feature = "mock-network", unix))]
+mod vault_server;
+#[cfg(feature = "mock-network")]
 #[macro_use]
 mod routing;
 // #[cfg(test)]
 // mod tests;
+#[cfg(feature = "mock-network")]
 mod connection_manager;
 
 pub use self::account::{Account, CoinBalance};
-pub use self::connection_manager::{ConnectionManager, RequestHookFn};
+#[cfg(feature = "mock-network")]
+pub use self::connection_manager::{clone_vault, ConnectionManager, RequestHookFn, ResponseHookFn};
+pub use self::pricing::{Charge, Operation, PricingModel};
+#[cfg(feature = "mock-network")]
+pub use self::recording::{Exchange, Mismatch, Recording};
+#[cfg(all(feature = "mock-network", unix))]
+pub use self::vault_server::{init_vault_socket, MockVaultServer, VaultConnection};
+#[cfg(feature = "mock-network")]
+use crate::config_handler::{Config, DevConfig};
 use safe_nd::{ADataAddress, IDataAddress, MDataAddress};
+#[cfg(feature = "mock-network")]
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "mock-network")]
+use std::{env, fs::File, io::BufReader};
 
 /// Identifier for a data.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
@@ -30,3 +53,31 @@ pub enum DataId {
     /// Identifier of appendonly data.
     AppendOnly(ADataAddress),
 }
+
+// Loads a developer option of the mock network with the following precedence:
+// 1. The `env_var` env var, read by `from_env`
+// 2. The DevConfig option returned by `dev_option`
+#[cfg(feature = "mock-network")]
+fn load_profile<T, E, D>(config: &Config, env_var: &str, from_env: E, dev_option: D) -> Option<T>
+where
+    E: FnOnce(&str) -> Result<T, String>,
+    D: FnOnce(&DevConfig) -> Option<T>,
+{
+    match env::var(env_var) {
+        Ok(value) => match from_env(&value) {
+            Ok(profile) => Some(profile),
+            Err(error) => {
+                warn!("Could not read {} from {}: {}", env_var, value, error);
+                None
+            }
+        },
+        Err(_) => config.dev.as_ref().and_then(dev_option),
+    }
+}
+
+// Reads the JSON file at `path`.
+#[cfg(feature = "mock-network")]
+fn read_json_file<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|err| err.to_string())
+}
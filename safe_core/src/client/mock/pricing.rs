@@ -0,0 +1,88 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config_handler::Pricing;
+pub use crate::cost::Operation;
+use safe_nd::{Coins, Result as SndResult};
+use serde::{Deserialize, Serialize};
+
+/// Decides how much the mock vault charges for the mutations.
+pub trait PricingModel: Send {
+    /// Returns the cost of the given mutation, or an error if it exceeds the coins in existence.
+    fn cost(&self, operation: &Operation) -> SndResult<Coins>;
+}
+
+impl PricingModel for Pricing {
+    fn cost(&self, operation: &Operation) -> SndResult<Coins> {
+        self.cost_of(operation)
+    }
+}
+
+/// Name and cost of a request, decided before it's processed.
+#[derive(Clone, Debug)]
+pub struct Price {
+    /// Name of the `Request` variant, such as `"PutIData"`.
+    pub request: String,
+    /// Amount charged if the request is a mutation.
+    pub cost: Coins,
+}
+
+/// Charge made to an account by the mock vault.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Charge {
+    /// Name of the `Request` variant charged for, such as `"PutIData"`.
+    pub request: String,
+    /// Amount charged.
+    pub cost: Coins,
+    /// Balance of the account after the charge.
+    pub balance: Coins,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn nano(cost: SndResult<Coins>) -> u64 {
+        unwrap!(cost).as_nano()
+    }
+
+    // Test that the cost depends on the request type, the size of the request and the fullness of
+    // the network.
+    #[test]
+    fn pricing() {
+        let mut request_costs = BTreeMap::new();
+        let _ = request_costs.insert("PutIData".to_string(), 10);
+        let pricing = Pricing {
+            base_cost: 2,
+            request_costs,
+            cost_per_kib: 1,
+            network_capacity: Some(100),
+        };
+
+        let operation = |request, size, stored_data| Operation {
+            request,
+            size,
+            stored_data,
+        };
+
+        assert_eq!(nano(pricing.cost(&operation("PutMData", 0, 0))), 2);
+        assert_eq!(nano(pricing.cost(&operation("PutIData", 0, 0))), 10);
+        assert_eq!(nano(pricing.cost(&operation("PutIData", 1, 0))), 11);
+        assert_eq!(nano(pricing.cost(&operation("PutIData", 2048, 0))), 12);
+        assert_eq!(nano(pricing.cost(&operation("PutIData", 2048, 50))), 24);
+        assert_eq!(nano(pricing.cost(&operation("PutIData", 2048, 1000))), 1200);
+
+        // The default pricing charges a flat `COST_OF_PUT`.
+        let pricing = Pricing::default();
+        assert_eq!(
+            nano(pricing.cost(&operation("PutIData", 2048, 1000))),
+            crate::client::COST_OF_PUT.as_nano()
+        );
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::vault::Vault;
+use crate::config_handler::get_config;
+use crate::errors::CoreError;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use safe_nd::{Message, PublicId, Response};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::mem;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// A request sent by a client together with the response it received.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Exchange {
+    /// Public ID of the client sending the request.
+    pub public_id: PublicId,
+    /// The request message, including its message ID and signature.
+    pub request: Message,
+    /// The response received by the client.
+    pub response: Response,
+    /// Whether the request was processed by the vault. Requests answered by a request hook or
+    /// failed by an injected fault never reached it, so they're not replayed.
+    pub processed: bool,
+    /// Time the request was sent at, since the start of the recording.
+    pub sent_at: Duration,
+    /// Time the vault took to process the request.
+    pub duration: Duration,
+}
+
+/// Response of a replayed request which differs from the recorded one.
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    /// Position of the exchange in the recording.
+    pub index: usize,
+    /// The recorded response.
+    pub expected: Response,
+    /// The response returned during the replay.
+    pub actual: Response,
+}
+
+/// Ordered log of the requests sent by a client and of the responses they received, along with
+/// the state of the vault when the recording started.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Recording {
+    initial_state: Option<Vec<u8>>,
+    exchanges: Vec<Exchange>,
+}
+
+impl Recording {
+    /// The recorded exchanges, in the order the requests were sent.
+    pub fn exchanges(&self) -> &[Exchange] {
+        &self.exchanges
+    }
+
+    /// Write the recording to a file.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), CoreError> {
+        fs::write(path, serialise(self)?)?;
+        Ok(())
+    }
+
+    /// Read a recording previously written with `write_to_file`.
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> Result<Self, CoreError> {
+        Ok(deserialise(&fs::read(path)?)?)
+    }
+
+    /// Send the recorded requests again, in order, to an in-memory mock vault restored to the
+    /// state of the vault when the recording started, and return the responses which differ from
+    /// the recorded ones. The vault starts out empty if its state wasn't available, as when
+    /// recording the requests sent to a mock vault server.
+    pub fn replay(&self) -> Result<Vec<Mismatch>, CoreError> {
+        let mut config = get_config();
+        let mut dev_config = config.dev.unwrap_or_default();
+        dev_config.mock_in_memory_storage = true;
+        config.dev = Some(dev_config);
+
+        let mut vault = Vault::new(config);
+        if let Some(ref state) = self.initial_state {
+            vault.restore_state(state)?;
+        }
+        let mut mismatches = Vec::new();
+
+        for (index, exchange) in self.exchanges.iter().enumerate() {
+            if !exchange.processed {
+                continue;
+            }
+
+            let actual =
+                match vault.process_request(exchange.public_id.clone(), &exchange.request)? {
+                    Message::Response { response, .. } => response,
+                    _ => {
+                        return Err(CoreError::Unexpected(
+                            "Logic error: Vault error returned invalid response".to_string(),
+                        ))
+                    }
+                };
+
+            if actual != exchange.response {
+                mismatches.push(Mismatch {
+                    index,
+                    expected: exchange.response.clone(),
+                    actual,
+                });
+            }
+        }
+
+        Ok(mismatches)
+    }
+}
+
+// Records the exchanges of a connection manager.
+pub struct Recorder {
+    started: Instant,
+    recording: Recording,
+}
+
+impl Recorder {
+    pub fn start(initial_state: Option<Vec<u8>>) -> Self {
+        Self {
+            started: Instant::now(),
+            recording: Recording {
+                initial_state,
+                exchanges: Vec::new(),
+            },
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        public_id: PublicId,
+        request: Message,
+        response: Response,
+        processed: bool,
+        sent_at: Instant,
+    ) {
+        self.recording.exchanges.push(Exchange {
+            public_id,
+            request,
+            response,
+            processed,
+            sent_at: sent_at.duration_since(self.started),
+            duration: sent_at.elapsed(),
+        });
+    }
+
+    pub fn finish(&mut self) -> Recording {
+        mem::replace(&mut self.recording, Recording::default())
+    }
+}
@@ -0,0 +1,334 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{load_profile, read_json_file};
+use crate::config_handler::{Config, ScriptedSectionEvent, SectionEvent, SectionsProfile};
+use crate::errors::CoreError;
+use crate::section_map::Prefix;
+use safe_nd::{Error as SndError, XorName};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::str::FromStr;
+
+// Longest prefix of the initial sections, so that there are at most 65536 of them.
+const MAX_INITIAL_PREFIX_LEN: u8 = 16;
+
+/// What happens to a request routed to a simulated section.
+#[derive(Debug, PartialEq)]
+pub enum Routing {
+    /// The request is handled.
+    Delivered,
+    /// The request is lost, so that it times out.
+    Lost,
+    /// The request fails with the given error.
+    Failed(SndError),
+}
+
+// Elders are identified by the order in which they were created.
+type Elder = u64;
+
+struct Section {
+    elders: BTreeSet<Elder>,
+    // Number of requests the section won't handle, and the error they fail with.
+    outage: Option<(u64, Option<SndError>)>,
+}
+
+/// Sections of the mock network, along with the elders of those sections the client knows about.
+///
+/// A request only reaches a section if the client knows a majority of its current elders. When
+/// it doesn't, following a split or churn, the request is lost and the client learns the new
+/// elders, as it would by reconnecting to the section.
+pub struct SectionSimulation {
+    sections: BTreeMap<Prefix, Section>,
+    known_elders: BTreeMap<Prefix, BTreeSet<Elder>>,
+    events: VecDeque<ScriptedSectionEvent>,
+    elder_count: usize,
+    next_elder: Elder,
+    requests: u64,
+}
+
+impl SectionSimulation {
+    /// Create the simulation described by the given profile.
+    pub fn new(profile: SectionsProfile) -> Self {
+        let mut events = profile.events;
+        events.sort_by_key(|event| event.after_requests);
+
+        let mut simulation = Self {
+            sections: BTreeMap::new(),
+            known_elders: BTreeMap::new(),
+            events: events.into_iter().collect(),
+            elder_count: profile.elder_count.max(1),
+            next_elder: 0,
+            requests: 0,
+        };
+
+        let mut prefixes = vec![Prefix::default()];
+        for _ in 0..profile.prefix_len.min(MAX_INITIAL_PREFIX_LEN) {
+            prefixes = prefixes
+                .into_iter()
+                .flat_map(|prefix| vec![prefix.pushed(false), prefix.pushed(true)])
+                .collect();
+        }
+        for prefix in prefixes {
+            let elders = simulation.new_elders(BTreeSet::new());
+            let _ = simulation.known_elders.insert(prefix, elders.clone());
+            let _ = simulation.sections.insert(
+                prefix,
+                Section {
+                    elders,
+                    outage: None,
+                },
+            );
+        }
+
+        simulation
+    }
+
+    /// Returns the prefixes of the current sections.
+    pub fn prefixes(&self) -> Vec<Prefix> {
+        self.sections.keys().cloned().collect()
+    }
+
+    /// Route a request to the section managing `name`, applying the scripted events which are
+    /// due first.
+    pub fn route(&mut self, name: &XorName) -> Routing {
+        self.requests += 1;
+        while self
+            .events
+            .front()
+            .map_or(false, |event| event.after_requests < self.requests)
+        {
+            if let Some(ScriptedSectionEvent { event, .. }) = self.events.pop_front() {
+                if let Err(error) = self.apply(event) {
+                    warn!(
+                        "Mock network: can't apply scripted section event: {:?}",
+                        error
+                    );
+                }
+            }
+        }
+
+        let (prefix, section) = match self
+            .sections
+            .iter_mut()
+            .find(|(prefix, _)| prefix.matches(name))
+        {
+            Some((prefix, section)) => (*prefix, section),
+            None => return Routing::Lost,
+        };
+
+        if let Some((requests, error)) = section.outage.take() {
+            if requests > 1 {
+                section.outage = Some((requests - 1, error.clone()));
+            }
+            trace!("Mock network: section {:?} is unavailable", prefix);
+            return error.map_or(Routing::Lost, Routing::Failed);
+        }
+
+        // The client knows the elders of the section itself or, following a split, of the section
+        // it split from.
+        let known = self
+            .known_elders
+            .iter()
+            .filter(|(known_prefix, _)| known_prefix.is_ancestor_of(&prefix))
+            .max_by_key(|(known_prefix, _)| known_prefix.bit_count())
+            .map(|(_, elders)| elders.intersection(&section.elders).count())
+            .unwrap_or(0);
+        let _ = self.known_elders.insert(prefix, section.elders.clone());
+
+        if 2 * known > section.elders.len() {
+            Routing::Delivered
+        } else {
+            trace!(
+                "Mock network: lost contact with the elders of section {:?}",
+                prefix
+            );
+            Routing::Lost
+        }
+    }
+
+    /// Apply an event to the sections.
+    pub fn apply(&mut self, event: SectionEvent) -> Result<(), CoreError> {
+        trace!("Mock network: {:?}", event);
+
+        match event {
+            SectionEvent::Split { prefix } => {
+                let prefix = parse_prefix(&prefix)?;
+                let section = self.remove_section(&prefix)?;
+
+                let (left, right): (Vec<_>, Vec<_>) = section
+                    .elders
+                    .into_iter()
+                    .enumerate()
+                    .partition(|(index, _)| index % 2 == 0);
+                for (bit, elders) in vec![(false, left), (true, right)] {
+                    let elders = elders.into_iter().map(|(_, elder)| elder).collect();
+                    let elders = self.new_elders(elders);
+                    let _ = self.sections.insert(
+                        prefix.pushed(bit),
+                        Section {
+                            elders,
+                            outage: None,
+                        },
+                    );
+                }
+            }
+            SectionEvent::Churn { prefix, count } => {
+                let prefix = parse_prefix(&prefix)?;
+                let mut section = self.remove_section(&prefix)?;
+
+                let elders = section.elders.into_iter().skip(count).collect();
+                section.elders = self.new_elders(elders);
+                let _ = self.sections.insert(prefix, section);
+            }
+            SectionEvent::Unavailable {
+                prefix,
+                requests,
+                error,
+            } => {
+                let prefix = parse_prefix(&prefix)?;
+                let section = self
+                    .sections
+                    .get_mut(&prefix)
+                    .ok_or_else(|| no_section(&prefix))?;
+                section.outage = if requests > 0 {
+                    Some((requests, error))
+                } else {
+                    None
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove_section(&mut self, prefix: &Prefix) -> Result<Section, CoreError> {
+        self.sections
+            .remove(prefix)
+            .ok_or_else(|| no_section(prefix))
+    }
+
+    // Add new elders to the given ones until there are `elder_count` of them.
+    fn new_elders(&mut self, mut elders: BTreeSet<Elder>) -> BTreeSet<Elder> {
+        while elders.len() < self.elder_count {
+            let _ = elders.insert(self.next_elder);
+            self.next_elder += 1;
+        }
+        elders
+    }
+}
+
+fn parse_prefix(prefix: &str) -> Result<Prefix, CoreError> {
+    Prefix::from_str(prefix).map_err(CoreError::Unexpected)
+}
+
+fn no_section(prefix: &Prefix) -> CoreError {
+    CoreError::Unexpected(format!("No simulated section with {:?}", prefix))
+}
+
+// Initializes the sections profile with the following precedence:
+// 1. "SAFE_MOCK_SECTIONS_PROFILE" env var, the path to a JSON file containing the profile
+// 2. DevConfig `mock_sections` option
+pub fn init_sections_profile(config: &Config) -> Option<SectionsProfile> {
+    load_profile(
+        config,
+        "SAFE_MOCK_SECTIONS_PROFILE",
+        read_json_file,
+        |dev| dev.mock_sections.clone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use safe_nd::XOR_NAME_LEN;
+
+    fn name(first_byte: u8) -> XorName {
+        let mut name = XorName([0; XOR_NAME_LEN]);
+        name.0[0] = first_byte;
+        name
+    }
+
+    fn new_simulation(events: Vec<ScriptedSectionEvent>) -> SectionSimulation {
+        SectionSimulation::new(SectionsProfile {
+            prefix_len: 1,
+            elder_count: 4,
+            events,
+        })
+    }
+
+    // Test that requests to a section time out or fail while it's unavailable.
+    #[test]
+    fn outages() {
+        let mut simulation = new_simulation(vec![]);
+        assert_eq!(simulation.prefixes().len(), 2);
+
+        unwrap!(simulation.apply(SectionEvent::Unavailable {
+            prefix: "1".to_string(),
+            requests: 2,
+            error: None,
+        }));
+        assert_eq!(simulation.route(&name(0b1000_0000)), Routing::Lost);
+        assert_eq!(simulation.route(&name(0b0000_0000)), Routing::Delivered);
+        assert_eq!(simulation.route(&name(0b1000_0000)), Routing::Lost);
+        assert_eq!(simulation.route(&name(0b1000_0000)), Routing::Delivered);
+
+        unwrap!(simulation.apply(SectionEvent::Unavailable {
+            prefix: "0".to_string(),
+            requests: 1,
+            error: Some(SndError::NetworkOther("Unavailable".to_string())),
+        }));
+        match simulation.route(&name(0b0000_0000)) {
+            Routing::Failed(SndError::NetworkOther(_)) => (),
+            routing => panic!("Unexpected {:?}", routing),
+        }
+        assert_eq!(simulation.route(&name(0b0000_0000)), Routing::Delivered);
+
+        assert!(simulation
+            .apply(SectionEvent::Unavailable {
+                prefix: "01".to_string(),
+                requests: 1,
+                error: None,
+            })
+            .is_err());
+    }
+
+    // Test that the first request to a section which has split or churned too much is lost, and
+    // the following ones get through.
+    #[test]
+    fn splits_and_churn() {
+        let mut simulation = new_simulation(vec![ScriptedSectionEvent {
+            after_requests: 1,
+            event: SectionEvent::Split {
+                prefix: "0".to_string(),
+            },
+        }]);
+
+        assert_eq!(simulation.route(&name(0b0000_0000)), Routing::Delivered);
+        assert_eq!(simulation.route(&name(0b0100_0000)), Routing::Lost);
+        assert_eq!(simulation.route(&name(0b0100_0000)), Routing::Delivered);
+        assert_eq!(simulation.prefixes().len(), 3);
+
+        // Losing a minority of the elders goes unnoticed.
+        unwrap!(simulation.apply(SectionEvent::Churn {
+            prefix: "1".to_string(),
+            count: 1,
+        }));
+        assert_eq!(simulation.route(&name(0b1000_0000)), Routing::Delivered);
+
+        unwrap!(simulation.apply(SectionEvent::Churn {
+            prefix: "1".to_string(),
+            count: 2,
+        }));
+        unwrap!(simulation.apply(SectionEvent::Churn {
+            prefix: "1".to_string(),
+            count: 1,
+        }));
+        assert_eq!(simulation.route(&name(0b1000_0000)), Routing::Lost);
+        assert_eq!(simulation.route(&name(0b1000_0000)), Routing::Delivered);
+    }
+}
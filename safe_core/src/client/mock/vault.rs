@@ -6,11 +6,21 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+#[cfg(feature = "mock-network")]
+pub mod inspect;
+mod journal_store;
+mod limits;
+#[cfg(feature = "mock-network")]
+mod snapshot;
+
+use self::journal_store::JournalStore;
+use super::pricing::{Charge, Operation, Price, PricingModel};
 use super::DataId;
 use super::{Account, CoinBalance};
-use crate::client::mock::connection_manager::unlimited_muts;
 use crate::client::COST_OF_PUT;
 use crate::config_handler::{Config, DevConfig};
+use crate::event::{NetworkNotification, NotificationTx};
+use crate::utils::request_name;
 use fs2::FileExt;
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use safe_nd::{
@@ -20,7 +30,7 @@ use safe_nd::{
     Result as SndResult, SeqAppendOnly, Transaction, UnseqAppendOnly, XorName,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fs::File;
 #[cfg(not(test))]
@@ -35,11 +45,20 @@ use std::time::SystemTime;
 use tempfile::tempfile;
 
 const FILE_NAME: &str = "SCL-Mock";
+const JOURNAL_FILE_NAME: &str = "SCL-Mock-Journal";
 
 pub struct Vault {
     cache: Cache,
     config: Config,
     store: Box<dyn Store>,
+    // Notification subscribers, by the name of the coin balance they're interested in.
+    subscribers: HashMap<XorName, Vec<NotificationTx>>,
+    // Serialised copies of the cache, by name.
+    #[cfg(feature = "mock-network")]
+    snapshots: HashMap<String, Vec<u8>>,
+    pricing: Box<dyn PricingModel>,
+    // Charges made to the accounts, oldest first.
+    ledger: HashMap<XorName, Vec<Charge>>,
 }
 
 // Initializes mock-vault path with the following precedence:
@@ -56,11 +75,22 @@ fn init_vault_path(devconfig: Option<&DevConfig>) -> PathBuf {
     }
 }
 
+// Whether the file storage is a journal, with the following precedence:
+// 1. "SAFE_MOCK_JOURNAL_STORAGE" env var => journal
+// 2. DevConfig `mock_journal_storage` option
+fn use_journal_storage(devconfig: Option<&DevConfig>) -> bool {
+    match env::var("SAFE_MOCK_JOURNAL_STORAGE") {
+        Ok(_) => true,
+        Err(_) => devconfig.map_or(false, |dev| dev.mock_journal_storage),
+    }
+}
+
 // Initializes vault storage. The type of storage is chosen with the following precedence:
 // 1.  "SAFE_MOCK_IN_MEMORY_STORAGE" env var => in-memory storage
 // 2.  DevConfig `mock_in_memory_storage` option => in-memory storage
 // 3a. Else (not test) => file storage, use path from `init_vault_path`
 // 3b. Else (test) => file storage, use random temporary file
+// The file storage is a journal if selected by `use_journal_storage`.
 fn init_vault_store(config: &Config) -> Box<dyn Store> {
     match env::var("SAFE_MOCK_IN_MEMORY_STORAGE") {
         Ok(_) => {
@@ -73,16 +103,30 @@ fn init_vault_store(config: &Config) -> Box<dyn Store> {
                 trace!("Mock vault: using memory store");
                 Box::new(MemoryStore)
             }
+            Some(ref dev) if use_journal_storage(Some(dev)) => {
+                trace!("Mock vault: using journal store");
+                Box::new(JournalStore::new(&init_vault_path(Some(dev))))
+            }
             Some(ref dev) => {
                 trace!("Mock vault: using file store");
                 Box::new(FileStore::new(&init_vault_path(Some(dev))))
             }
             #[cfg(not(test))]
+            None if use_journal_storage(None) => {
+                trace!("Mock vault: using journal store");
+                Box::new(JournalStore::new(&init_vault_path(None)))
+            }
+            #[cfg(not(test))]
             None => {
                 trace!("Mock vault: using file store");
                 Box::new(FileStore::new(&init_vault_path(None)))
             }
             #[cfg(test)]
+            None if use_journal_storage(None) => {
+                trace!("Mock vault: using temporary journal store");
+                Box::new(JournalStore::new_with_temp())
+            }
+            #[cfg(test)]
             None => {
                 trace!("Mock vault: using temporary file store");
                 Box::new(FileStore::new_with_temp())
@@ -149,19 +193,97 @@ fn check_perms_mdata(data: &MData, request: &Request, requester: PublicKey) -> S
     }
 }
 
+// Whether the request is a mutation which is charged by `commit_mutation` once applied, without
+// checking the balance beforehand.
+fn is_charged_on_commit(request: &Request) -> bool {
+    match request {
+        Request::SetMDataUserPermissions { .. }
+        | Request::DelMDataUserPermissions { .. }
+        | Request::MutateMDataEntries { .. }
+        | Request::AppendSeq { .. }
+        | Request::AppendUnseq(_)
+        | Request::AddPubADataPermissions { .. }
+        | Request::AddUnpubADataPermissions { .. }
+        | Request::SetADataOwner { .. } => true,
+        _ => false,
+    }
+}
+
 impl Vault {
     pub fn new(config: Config) -> Self {
         let store = init_vault_store(&config);
+        let pricing = config
+            .dev
+            .as_ref()
+            .and_then(|dev| dev.mock_pricing.clone())
+            .unwrap_or_default();
 
         Vault {
-            cache: Cache {
-                coin_balances: HashMap::new(),
-                client_manager: HashMap::new(),
-                login_packets: HashMap::new(),
-                nae_manager: HashMap::new(),
-            },
+            cache: Cache::default(),
             config,
             store,
+            subscribers: HashMap::new(),
+            #[cfg(feature = "mock-network")]
+            snapshots: HashMap::new(),
+            pricing: Box::new(pricing),
+            ledger: HashMap::new(),
+        }
+    }
+
+    // Replace the model deciding the cost of the mutations.
+    #[cfg(feature = "mock-network")]
+    pub fn set_pricing_model(&mut self, pricing: Box<dyn PricingModel>) {
+        self.pricing = pricing;
+    }
+
+    // Charges made to the given account, oldest first.
+    #[cfg(feature = "mock-network")]
+    pub fn charges(&self, account: &XorName) -> Vec<Charge> {
+        self.ledger.get(account).cloned().unwrap_or_default()
+    }
+
+    // Whether mutations are free, and so never fail for lack of coins.
+    #[cfg(feature = "mock-network")]
+    pub fn unlimited_muts(&self) -> bool {
+        unlimited_muts(&self.config)
+    }
+
+    // Decide the cost of the request about to be processed.
+    fn price_request(&self, request: &Request) -> SndResult<Price> {
+        let request_name = request_name(request);
+        let cost = match request.get_type() {
+            RequestType::Mutation | RequestType::Transaction => {
+                let raw_request = serialise(request)
+                    .map_err(|error| SndError::NetworkOther(error.to_string()))?;
+                self.pricing.cost(&Operation {
+                    request: request_name,
+                    size: raw_request.len() as u64,
+                    stored_data: self.cache.nae_manager.len() as u64,
+                })?
+            }
+            RequestType::PublicGet | RequestType::PrivateGet => *COST_OF_PUT,
+        };
+
+        Ok(Price {
+            request: request_name.to_string(),
+            cost,
+        })
+    }
+
+    // Subscribe to the notifications concerning the given coin balance.
+    #[cfg(feature = "mock-network")]
+    pub fn subscribe(&mut self, coin_balance_id: XorName, subscriber: NotificationTx) {
+        self.subscribers
+            .entry(coin_balance_id)
+            .or_insert_with(Vec::new)
+            .push(subscriber);
+    }
+
+    // Send the notification to everyone subscribed to the given coin balance.
+    fn notify(&mut self, coin_balance_id: &XorName, notification: NetworkNotification) {
+        if let Some(subscribers) = self.subscribers.get_mut(coin_balance_id) {
+            subscribers
+                .retain(|subscriber| subscriber.unbounded_send(notification.clone()).is_ok());
         }
     }
 
@@ -172,6 +294,7 @@ impl Vault {
 
     // Get mutable reference to account for the client manager name.
     pub fn get_account_mut(&mut self, name: &XorName) -> Option<&mut Account> {
+        let _ = self.cache.dirty.insert(Key::Account(*name));
         self.cache.client_manager.get_mut(name)
     }
 
@@ -182,11 +305,13 @@ impl Vault {
 
     // Get mutable reference to account for the client manager name.
     pub fn get_coin_balance_mut(&mut self, name: &XorName) -> Option<&mut CoinBalance> {
+        let _ = self.cache.dirty.insert(Key::CoinBalance(*name));
         self.cache.coin_balances.get_mut(name)
     }
 
     // Create account for the given client manager name.
     pub fn insert_account(&mut self, name: XorName) {
+        let _ = self.cache.dirty.insert(Key::Account(name));
         let _ = self
             .cache
             .client_manager
@@ -194,6 +319,10 @@ impl Vault {
     }
 
     pub fn insert_login_packet(&mut self, login_packet: LoginPacket) {
+        let _ = self
+            .cache
+            .dirty
+            .insert(Key::LoginPacket(*login_packet.destination()));
         let _ = self
             .cache
             .login_packets
@@ -205,7 +334,9 @@ impl Vault {
     }
 
     /// Instantly creates new balance.
+    #[cfg(feature = "mock-network")]
     pub fn mock_create_balance(&mut self, owner: PublicKey, amount: Coins) {
+        let _ = self.cache.dirty.insert(Key::CoinBalance(owner.into()));
         let _ = self
             .cache
             .coin_balances
@@ -213,6 +344,7 @@ impl Vault {
     }
 
     /// Increment coin balance for testing
+    #[cfg(feature = "mock-network")]
     pub fn mock_increment_balance(
         &mut self,
         coin_balance_name: &XorName,
@@ -275,6 +407,7 @@ impl Vault {
         &self,
         dst_name: &XorName,
         sign_pk: &PublicKey,
+        price: &Price,
     ) -> Result<(), SndError> {
         let account = self.get_account(&dst_name);
 
@@ -304,24 +437,52 @@ impl Vault {
             }
         }
 
+        self.check_cost(balance, price)
+    }
+
+    // Check that the balance covers the price of a request.
+    fn check_cost(&self, balance: Coins, price: &Price) -> SndResult<()> {
         let unlimited_mut = unlimited_muts(&self.config);
 
-        if !unlimited_mut && balance.checked_sub(*COST_OF_PUT).is_none() {
+        if !unlimited_mut && balance.checked_sub(price.cost).is_none() {
             return Err(SndError::InsufficientBalance);
         }
 
         Ok(())
     }
 
-    // Commit a mutation.
-    pub fn commit_mutation(&mut self, account: &XorName) {
+    // Commit a mutation, charging its price to the account unless mutations are unlimited.
+    pub fn commit_mutation(&mut self, account: &XorName, price: &Price) -> SndResult<()> {
         let unlimited_mut = unlimited_muts(&self.config);
-        if !unlimited_mut {
-            let balance = unwrap!(self.get_coin_balance_mut(account));
-            unwrap!(balance.debit_balance(*COST_OF_PUT));
+        if unlimited_mut {
+            Ok(())
+        } else {
+            self.charge(account, price)
         }
     }
 
+    // Debit the price of a request from the account and record it in the ledger.
+    fn charge(&mut self, account: &XorName, price: &Price) -> SndResult<()> {
+        let cost = price.cost;
+        let balance = match self.get_coin_balance_mut(account) {
+            Some(balance) => {
+                balance.debit_balance(cost)?;
+                balance.balance()
+            }
+            None => return Err(SndError::NoSuchBalance),
+        };
+
+        self.ledger
+            .entry(*account)
+            .or_insert_with(Vec::new)
+            .push(Charge {
+                request: price.request.clone(),
+                cost,
+                balance,
+            });
+        Ok(())
+    }
+
     // Check if data with the given name is in the storage.
     pub fn contains_data(&self, name: &DataId) -> bool {
         self.cache.nae_manager.contains_key(name)
@@ -334,11 +495,13 @@ impl Vault {
 
     // Save the data to the storage.
     pub fn insert_data(&mut self, name: DataId, data: Data) {
+        let _ = self.cache.dirty.insert(Key::Data(name));
         let _ = self.cache.nae_manager.insert(name, data);
     }
 
     // Delete the data from the storage.
     pub fn delete_data(&mut self, name: DataId) {
+        let _ = self.cache.dirty.insert(Key::Data(name));
         let _ = self.cache.nae_manager.remove(&name);
     }
 
@@ -346,6 +509,7 @@ impl Vault {
         if self.get_coin_balance(&destination).is_some() {
             return Err(SndError::BalanceExists);
         }
+        let _ = self.cache.dirty.insert(Key::CoinBalance(destination));
         let _ = self
             .cache
             .coin_balances
@@ -360,6 +524,18 @@ impl Vault {
         amount: Coins,
         transaction_id: u64,
     ) -> SndResult<Transaction> {
+        // A sender reusing a transaction ID is told the transfer already went through only if
+        // it's the same transfer. Any other transfer under that ID is rejected.
+        if let Some(debit) = self
+            .get_coin_balance(&source)
+            .and_then(|balance| balance.debit(transaction_id))
+        {
+            return if debit == (destination, amount) {
+                Err(SndError::TransactionIdExists)
+            } else {
+                Err(SndError::InvalidOperation)
+            };
+        }
         match self.get_coin_balance_mut(&source) {
             Some(balance) => balance.debit_balance(amount)?,
             None => return Err(SndError::NoSuchBalance),
@@ -368,6 +544,9 @@ impl Vault {
             Some(balance) => balance.credit_balance(amount, transaction_id)?,
             None => return Err(SndError::NoSuchBalance),
         };
+        if let Some(balance) = self.get_coin_balance_mut(&source) {
+            balance.add_debit(transaction_id, destination, amount);
+        }
         Ok(Transaction {
             id: transaction_id,
             amount,
@@ -430,11 +609,19 @@ impl Vault {
                 RequestType::PublicGet => (),
             }
 
-            Ok((requester_pk, owner_pk))
+            let price = self.price_request(request)?;
+
+            // Mutations charged after being applied are rejected up front if they can't be paid.
+            if is_charged_on_commit(request) && !unlimited_muts(&self.config) {
+                self.get_balance(&requester.name())
+                    .and_then(|balance| self.check_cost(balance, &price))?;
+            }
+
+            Ok((requester_pk, owner_pk, price))
         });
 
         // Return errors as a response message corresponding to the incoming request message.
-        let (requester_pk, owner_pk) = match result {
+        let (requester_pk, owner_pk, price) = match result {
             Ok(s) => s,
             Err(err) => {
                 let response = request.error_response(err);
@@ -478,6 +665,7 @@ impl Vault {
                         DataId::Immutable(*idata.address()),
                         Data::Immutable(idata),
                         requester,
+                        &price,
                     )
                 };
                 Response::Mutation(result)
@@ -533,6 +721,15 @@ impl Vault {
                             self.transfer_coins(source, destination, amount, transaction_id)
                         })
                 };
+                if let Ok(ref transaction) = result {
+                    self.notify(
+                        &destination,
+                        NetworkNotification::TransferReceived {
+                            transaction_id: transaction.id,
+                            amount: transaction.amount,
+                        },
+                    );
+                }
                 Response::Transaction(result)
             }
             Request::CreateBalance {
@@ -555,15 +752,15 @@ impl Vault {
                             self.get_balance(&source)
                                 .and_then(|source_balance| {
                                     let total_amount = amount
-                                        .checked_add(*COST_OF_PUT)
+                                        .checked_add(price.cost)
                                         .ok_or(SndError::ExcessiveValue)?;
                                     if source_balance.checked_sub(total_amount).is_none() {
                                         return Err(SndError::InsufficientBalance);
                                     }
                                     self.create_balance(destination, new_balance_owner)
                                 })
+                                .and_then(|()| self.commit_mutation(&source, &price))
                                 .and_then(|()| {
-                                    self.commit_mutation(&source);
                                     self.transfer_coins(source, destination, amount, transaction_id)
                                 })
                         })
@@ -601,7 +798,7 @@ impl Vault {
                 } else {
                     self.get_balance(&source)
                         .and_then(|source_balance| {
-                            let debit_amt = amount.checked_add(*COST_OF_PUT);
+                            let debit_amt = amount.checked_add(price.cost);
                             match debit_amt {
                                 Some(amt) => {
                                     // Check if the balance has sufficient coin for the transfer and
@@ -614,10 +811,7 @@ impl Vault {
                             }
 
                             // Debit the requester's wallet the cost of inserting a login packet
-                            match self.get_coin_balance_mut(&source) {
-                                Some(balance) => balance.debit_balance(*COST_OF_PUT)?,
-                                None => return Err(SndError::NoSuchBalance),
-                            };
+                            self.charge(&source, &price)?;
 
                             // Create the balance and transfer the mentioned amount of coins
                             self.create_balance(new_balance_dest, new_owner)
@@ -648,14 +842,10 @@ impl Vault {
                     let result = self
                         .get_balance(&source)
                         .and_then(|source_balance| {
-                            if source_balance.checked_sub(*COST_OF_PUT).is_none() {
+                            if source_balance.checked_sub(price.cost).is_none() {
                                 return Err(SndError::InsufficientBalance);
                             }
-                            match self.get_coin_balance_mut(&source) {
-                                Some(balance) => balance.debit_balance(*COST_OF_PUT)?,
-                                None => return Err(SndError::NoSuchBalance),
-                            };
-                            Ok(())
+                            self.charge(&source, &price)
                         })
                         .map(|_| self.insert_login_packet(account_data));
                     Response::Mutation(result)
@@ -716,6 +906,7 @@ impl Vault {
                         DataId::Mutable(address),
                         Data::Mutable(data.clone()),
                         requester,
+                        &price,
                     )
                 };
                 Response::Mutation(result)
@@ -845,8 +1036,9 @@ impl Vault {
 
                         let data_name = DataId::Mutable(address);
                         data.set_user_permissions(user, permissions, version)?;
+                        self.check_mdata_limits(&data)?;
+                        self.commit_mutation(requester.name(), &price)?;
                         self.insert_data(data_name, Data::Mutable(data));
-                        self.commit_mutation(requester.name());
 
                         Ok(())
                     });
@@ -868,8 +1060,8 @@ impl Vault {
 
                         let data_name = DataId::Mutable(address);
                         data.del_user_permissions(user, version)?;
+                        self.commit_mutation(requester.name(), &price)?;
                         self.insert_data(data_name, Data::Mutable(data));
-                        self.commit_mutation(requester.name());
 
                         Ok(())
                     });
@@ -914,8 +1106,9 @@ impl Vault {
 
                             let data_name = DataId::Mutable(address);
                             data.mutate_entries(actions.clone(), requester_pk)?;
+                            self.check_mdata_limits(&data)?;
+                            self.commit_mutation(requester.name(), &price)?;
                             self.insert_data(data_name, Data::Mutable(data));
-                            self.commit_mutation(requester.name());
 
                             Ok(())
                         });
@@ -937,6 +1130,7 @@ impl Vault {
                                 DataId::AppendOnly(address),
                                 Data::AppendOnly(adata),
                                 requester,
+                                &price,
                             )
                         }
                     }
@@ -1055,14 +1249,18 @@ impl Vault {
                     .and_then(move |data| match data {
                         AData::PubSeq(mut adata) => {
                             adata.append(append.values, index)?;
-                            self.commit_mutation(requester.name());
-                            self.insert_data(id, Data::AppendOnly(AData::PubSeq(adata)));
+                            let adata = AData::PubSeq(adata);
+                            self.check_adata_limits(&adata)?;
+                            self.commit_mutation(requester.name(), &price)?;
+                            self.insert_data(id, Data::AppendOnly(adata));
                             Ok(())
                         }
                         AData::UnpubSeq(mut adata) => {
                             adata.append(append.values, index)?;
-                            self.commit_mutation(requester.name());
-                            self.insert_data(id, Data::AppendOnly(AData::UnpubSeq(adata)));
+                            let adata = AData::UnpubSeq(adata);
+                            self.check_adata_limits(&adata)?;
+                            self.commit_mutation(requester.name(), &price)?;
+                            self.insert_data(id, Data::AppendOnly(adata));
                             Ok(())
                         }
                         _ => Err(SndError::NoSuchData),
@@ -1076,14 +1274,18 @@ impl Vault {
                     .and_then(move |data| match data {
                         AData::PubUnseq(mut adata) => {
                             adata.append(append.values)?;
-                            self.commit_mutation(requester.name());
-                            self.insert_data(id, Data::AppendOnly(AData::PubUnseq(adata)));
+                            let adata = AData::PubUnseq(adata);
+                            self.check_adata_limits(&adata)?;
+                            self.commit_mutation(requester.name(), &price)?;
+                            self.insert_data(id, Data::AppendOnly(adata));
                             Ok(())
                         }
                         AData::UnpubUnseq(mut adata) => {
                             adata.append(append.values)?;
-                            self.commit_mutation(requester.name());
-                            self.insert_data(id, Data::AppendOnly(AData::UnpubUnseq(adata)));
+                            let adata = AData::UnpubUnseq(adata);
+                            self.check_adata_limits(&adata)?;
+                            self.commit_mutation(requester.name(), &price)?;
+                            self.insert_data(id, Data::AppendOnly(adata));
                             Ok(())
                         }
                         _ => Err(SndError::NoSuchData),
@@ -1102,8 +1304,10 @@ impl Vault {
                         ADataAddress::PubSeq { .. } => match data {
                             AData::PubSeq(mut adata) => {
                                 adata.append_permissions(permissions, permissions_index)?;
-                                self.commit_mutation(requester.name());
-                                self.insert_data(id, Data::AppendOnly(AData::PubSeq(adata)));
+                                let adata = AData::PubSeq(adata);
+                                self.check_adata_limits(&adata)?;
+                                self.commit_mutation(requester.name(), &price)?;
+                                self.insert_data(id, Data::AppendOnly(adata));
                                 Ok(())
                             }
                             _ => Err(SndError::NoSuchData),
@@ -1111,8 +1315,10 @@ impl Vault {
                         ADataAddress::PubUnseq { .. } => match data {
                             AData::PubUnseq(mut adata) => {
                                 adata.append_permissions(permissions, permissions_index)?;
-                                self.commit_mutation(requester.name());
-                                self.insert_data(id, Data::AppendOnly(AData::PubUnseq(adata)));
+                                let adata = AData::PubUnseq(adata);
+                                self.check_adata_limits(&adata)?;
+                                self.commit_mutation(requester.name(), &price)?;
+                                self.insert_data(id, Data::AppendOnly(adata));
                                 Ok(())
                             }
                             _ => Err(SndError::NoSuchData),
@@ -1133,8 +1339,10 @@ impl Vault {
                         ADataAddress::UnpubSeq { .. } => match data.clone() {
                             AData::UnpubSeq(mut adata) => {
                                 adata.append_permissions(permissions, permissions_index)?;
-                                self.commit_mutation(requester.name());
-                                self.insert_data(id, Data::AppendOnly(AData::UnpubSeq(adata)));
+                                let adata = AData::UnpubSeq(adata);
+                                self.check_adata_limits(&adata)?;
+                                self.commit_mutation(requester.name(), &price)?;
+                                self.insert_data(id, Data::AppendOnly(adata));
                                 Ok(())
                             }
                             _ => Err(SndError::NoSuchData),
@@ -1142,8 +1350,10 @@ impl Vault {
                         ADataAddress::UnpubUnseq { .. } => match data {
                             AData::UnpubUnseq(mut adata) => {
                                 adata.append_permissions(permissions, permissions_index)?;
-                                self.commit_mutation(requester.name());
-                                self.insert_data(id, Data::AppendOnly(AData::UnpubUnseq(adata)));
+                                let adata = AData::UnpubUnseq(adata);
+                                self.check_adata_limits(&adata)?;
+                                self.commit_mutation(requester.name(), &price)?;
+                                self.insert_data(id, Data::AppendOnly(adata));
                                 Ok(())
                             }
                             _ => Err(SndError::NoSuchData),
@@ -1164,7 +1374,7 @@ impl Vault {
                         ADataAddress::PubSeq { .. } => match data {
                             AData::PubSeq(mut adata) => {
                                 adata.append_owner(owner, owners_index)?;
-                                self.commit_mutation(requester.name());
+                                self.commit_mutation(requester.name(), &price)?;
                                 self.insert_data(id, Data::AppendOnly(AData::PubSeq(adata)));
                                 Ok(())
                             }
@@ -1173,7 +1383,7 @@ impl Vault {
                         ADataAddress::PubUnseq { .. } => match data {
                             AData::PubUnseq(mut adata) => {
                                 adata.append_owner(owner, owners_index)?;
-                                self.commit_mutation(requester.name());
+                                self.commit_mutation(requester.name(), &price)?;
                                 self.insert_data(id, Data::AppendOnly(AData::PubUnseq(adata)));
                                 Ok(())
                             }
@@ -1182,7 +1392,7 @@ impl Vault {
                         ADataAddress::UnpubSeq { .. } => match data.clone() {
                             AData::UnpubSeq(mut adata) => {
                                 adata.append_owner(owner, owners_index)?;
-                                self.commit_mutation(requester.name());
+                                self.commit_mutation(requester.name(), &price)?;
                                 self.insert_data(id, Data::AppendOnly(AData::UnpubSeq(adata)));
                                 Ok(())
                             }
@@ -1191,7 +1401,7 @@ impl Vault {
                         ADataAddress::UnpubUnseq { .. } => match data {
                             AData::UnpubUnseq(mut adata) => {
                                 adata.append_owner(owner, owners_index)?;
-                                self.commit_mutation(requester.name());
+                                self.commit_mutation(requester.name(), &price)?;
                                 self.insert_data(id, Data::AppendOnly(AData::UnpubUnseq(adata)));
                                 Ok(())
                             }
@@ -1306,28 +1516,34 @@ impl Vault {
         data_name: DataId,
         data: Data,
         requester: PublicId,
+        price: &Price,
     ) -> SndResult<()> {
+        self.check_data_limits(&data)?;
+
         match requester.clone() {
-            PublicId::Client(client_public_id) => {
-                self.authorise_mutation(client_public_id.name(), client_public_id.public_key())?
-            }
-            PublicId::App(app_public_id) => {
-                self.authorise_mutation(app_public_id.owner_name(), app_public_id.public_key())?
-            }
+            PublicId::Client(client_public_id) => self.authorise_mutation(
+                client_public_id.name(),
+                client_public_id.public_key(),
+                price,
+            )?,
+            PublicId::App(app_public_id) => self.authorise_mutation(
+                app_public_id.owner_name(),
+                app_public_id.public_key(),
+                price,
+            )?,
             _ => return Err(SndError::AccessDenied),
         }
         if self.contains_data(&data_name) {
             // Published Immutable Data is de-duplicated
             if let DataId::Immutable(addr) = data_name {
                 if addr.is_pub() {
-                    self.commit_mutation(&requester.name());
-                    return Ok(());
+                    return self.commit_mutation(&requester.name(), price);
                 }
             }
             Err(SndError::DataExists)
         } else {
+            self.commit_mutation(&requester.name(), price)?;
             self.insert_data(data_name, data);
-            self.commit_mutation(&requester.name());
             Ok(())
         }
     }
@@ -1387,39 +1603,55 @@ impl<'a> DerefMut for VaultGuard<'a> {
 impl<'a> Drop for VaultGuard<'a> {
     fn drop(&mut self) {
         let vault = &mut *self.0;
-        vault.store.save(&vault.cache)
+        vault.store.save(&vault.cache);
+        vault.cache.dirty.clear();
+        vault.cache.replaced = false;
     }
 }
 
 pub fn lock(vault: &Mutex<Vault>, writing: bool) -> VaultGuard {
-    let mut inner = unwrap!(vault.lock());
+    let mut guard = unwrap!(vault.lock());
 
-    if let Some(cache) = inner.store.load(writing) {
-        inner.cache = cache;
-    }
+    let inner = &mut *guard;
+    inner.store.load(&mut inner.cache, writing);
 
-    VaultGuard(inner)
+    VaultGuard(guard)
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Default, Deserialize, Serialize)]
 struct Cache {
     coin_balances: HashMap<XorName, CoinBalance>,
     client_manager: HashMap<XorName, Account>,
     login_packets: HashMap<XorName, LoginPacket>,
     nae_manager: HashMap<DataId, Data>,
+    // Entries modified since the cache was last saved.
+    #[serde(skip)]
+    dirty: HashSet<Key>,
+    // Whether the whole cache has been replaced since it was last saved.
+    #[serde(skip)]
+    replaced: bool,
+}
+
+// Key of an entry of the cache.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Key {
+    CoinBalance(XorName),
+    Account(XorName),
+    LoginPacket(XorName),
+    Data(DataId),
 }
 
 trait Store: Send {
-    fn load(&mut self, writing: bool) -> Option<Cache>;
+    // Lock the store and bring the cache up to date with it.
+    fn load(&mut self, cache: &mut Cache, writing: bool);
+    // Write the changes to the cache (if locked for writing) and unlock the store.
     fn save(&mut self, cache: &Cache);
 }
 
 struct MemoryStore;
 
 impl Store for MemoryStore {
-    fn load(&mut self, _: bool) -> Option<Cache> {
-        None
-    }
+    fn load(&mut self, _: &mut Cache, _: bool) {}
 
     fn save(&mut self, _: &Cache) {}
 }
@@ -1474,7 +1706,7 @@ impl FileStore {
 }
 
 impl Store for FileStore {
-    fn load(&mut self, writing: bool) -> Option<Cache> {
+    fn load(&mut self, cache: &mut Cache, writing: bool) {
         let mut file = self.open_file();
 
         if writing {
@@ -1494,15 +1726,14 @@ impl Store for FileStore {
         };
 
         // Update vault only if it's not already synchronised
-        let mut result = None;
         if mtime_duration > Duration::new(0, 0) {
             let mut raw_data = Vec::with_capacity(metadata.len() as usize);
             match file.read_to_end(&mut raw_data) {
                 Ok(0) => (),
                 Ok(_) => match deserialise::<Cache>(&raw_data) {
-                    Ok(cache) => {
+                    Ok(loaded) => {
                         self.sync_time = Some(mtime);
-                        result = Some(cache);
+                        *cache = loaded;
                     }
                     Err(e) => {
                         warn!("Can't read the mock vault: {:?}", e);
@@ -1510,14 +1741,12 @@ impl Store for FileStore {
                 },
                 Err(e) => {
                     warn!("Can't read the mock vault: {:?}", e);
-                    return None;
+                    return;
                 }
             }
         }
 
         self.file = Some((file, writing));
-
-        result
     }
 
     fn save(&mut self, cache: &Cache) {
@@ -1541,6 +1770,25 @@ impl Store for FileStore {
 }
 
 /// Path to the mock vault store file.
+#[cfg(feature = "mock-network")]
 pub fn mock_vault_path(config: &Config) -> PathBuf {
-    init_vault_path(config.dev.as_ref()).join(FILE_NAME)
+    let file_name = if use_journal_storage(config.dev.as_ref()) {
+        JOURNAL_FILE_NAME
+    } else {
+        FILE_NAME
+    };
+    init_vault_path(config.dev.as_ref()).join(file_name)
+}
+
+// Whether the mutations are unlimited, with the following precedence:
+// 1. "SAFE_MOCK_UNLIMITED_MUTATIONS" env var
+// 2. DevConfig `mock_unlimited_mutations` option
+pub fn unlimited_muts(config: &Config) -> bool {
+    match env::var("SAFE_MOCK_UNLIMITED_MUTATIONS") {
+        Ok(_) => true,
+        Err(_) => match config.dev {
+            Some(ref dev) => dev.mock_unlimited_mutations,
+            None => false,
+        },
+    }
 }
@@ -0,0 +1,452 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{lock, mock_vault_path, Key, Vault};
+use crate::client::mock::DataId;
+use crate::config_handler::{get_config, Config};
+use data_encoding::HEXLOWER;
+use safe_nd::{AData, ADataIndex, Data, MData, MDataAction, XorName};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Contents of the mock vault.
+#[derive(Debug, Serialize)]
+pub struct VaultContents {
+    /// Client accounts and the apps authorised by them.
+    pub accounts: Vec<AccountInfo>,
+    /// Coin balances.
+    pub balances: Vec<BalanceInfo>,
+    /// Login packets.
+    pub login_packets: Vec<LoginPacketInfo>,
+    /// Immutable data.
+    pub immutable_data: Vec<DataInfo>,
+    /// Mutable data.
+    pub mutable_data: Vec<DataInfo>,
+    /// Append-only data.
+    pub append_only_data: Vec<DataInfo>,
+}
+
+/// Account of a client.
+#[derive(Debug, Serialize)]
+pub struct AccountInfo {
+    /// Name of the account, in hex.
+    pub name: String,
+    /// Version of the auth keys.
+    pub version: u64,
+    /// Keys of the authorised apps.
+    pub auth_keys: Vec<AuthKeyInfo>,
+}
+
+/// Key of an app authorised by a client.
+#[derive(Debug, Serialize)]
+pub struct AuthKeyInfo {
+    /// Public key of the app.
+    pub key: String,
+    /// Whether the app may transfer coins.
+    pub transfer_coins: bool,
+}
+
+/// Coin balance.
+#[derive(Debug, Serialize)]
+pub struct BalanceInfo {
+    /// Name of the balance, in hex.
+    pub name: String,
+    /// Owner of the balance.
+    pub owner: String,
+    /// Amount of coins.
+    pub coins: String,
+}
+
+/// Login packet.
+#[derive(Debug, Serialize)]
+pub struct LoginPacketInfo {
+    /// Name of the login packet, in hex.
+    pub name: String,
+    /// Key allowed to read the login packet.
+    pub authorised_getter: String,
+    /// Size of the encrypted account data, in bytes.
+    pub size: usize,
+}
+
+/// Stored data.
+#[derive(Debug, Serialize)]
+pub struct DataInfo {
+    /// Name of the data, in hex.
+    pub name: String,
+    /// Type tag of mutable and append-only data.
+    pub tag: Option<u64>,
+    /// Kind of the data, e.g. `Pub` or `Seq`.
+    pub kind: String,
+}
+
+/// Contents of a mutable data.
+#[derive(Debug, Serialize)]
+pub struct MDataContents {
+    /// Address of the data.
+    pub info: DataInfo,
+    /// Owner of the data.
+    pub owner: String,
+    /// Version of the data.
+    pub version: u64,
+    /// Entries of the data.
+    pub entries: Vec<EntryInfo>,
+    /// Permissions of the data.
+    pub permissions: Vec<PermissionInfo>,
+}
+
+/// Contents of an append-only data.
+#[derive(Debug, Serialize)]
+pub struct ADataContents {
+    /// Address of the data.
+    pub info: DataInfo,
+    /// Entries of the data, in order.
+    pub entries: Vec<EntryInfo>,
+}
+
+/// Entry of a mutable or append-only data. Keys and values are shown as text if they are valid
+/// UTF-8, and in hex otherwise.
+#[derive(Debug, Serialize)]
+pub struct EntryInfo {
+    /// Key of the entry.
+    pub key: String,
+    /// Value of the entry.
+    pub value: String,
+    /// Version of the entries of sequenced mutable data.
+    pub version: Option<u64>,
+}
+
+/// Permissions of a user of a mutable data.
+#[derive(Debug, Serialize)]
+pub struct PermissionInfo {
+    /// The user.
+    pub user: String,
+    /// Actions the user is allowed to perform.
+    pub allowed: Vec<String>,
+}
+
+/// Gives access to the contents of the mock vault stored on disk, for debugging.
+pub struct VaultInspector {
+    vault: Mutex<Vault>,
+    path: PathBuf,
+}
+
+impl VaultInspector {
+    /// Open the mock vault at `mock_vault_path`, using the file or journal store selected by the
+    /// config even if it asks for the in-memory store.
+    pub fn open() -> Self {
+        let mut config = get_config();
+        let mut dev_config = config.dev.unwrap_or_default();
+        dev_config.mock_in_memory_storage = false;
+        config.dev = Some(dev_config);
+
+        Self::with_config(config)
+    }
+
+    fn with_config(config: Config) -> Self {
+        Self {
+            path: mock_vault_path(&config),
+            vault: Mutex::new(Vault::new(config)),
+        }
+    }
+
+    /// Path of the mock vault store file.
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// List the contents of the vault.
+    pub fn contents(&self) -> VaultContents {
+        let vault = lock(&self.vault, false);
+        let cache = &vault.cache;
+
+        let mut accounts: Vec<_> = cache
+            .client_manager
+            .iter()
+            .map(|(name, account)| AccountInfo {
+                name: hex(name),
+                version: account.version(),
+                auth_keys: account
+                    .auth_keys()
+                    .iter()
+                    .map(|(key, permissions)| AuthKeyInfo {
+                        key: format!("{:?}", key),
+                        transfer_coins: permissions.transfer_coins,
+                    })
+                    .collect(),
+            })
+            .collect();
+        accounts.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut balances: Vec<_> = cache
+            .coin_balances
+            .iter()
+            .map(|(name, balance)| BalanceInfo {
+                name: hex(name),
+                owner: format!("{:?}", balance.owner()),
+                coins: balance.balance().to_string(),
+            })
+            .collect();
+        balances.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut login_packets: Vec<_> = cache
+            .login_packets
+            .iter()
+            .map(|(name, login_packet)| LoginPacketInfo {
+                name: hex(name),
+                authorised_getter: format!("{:?}", login_packet.authorised_getter()),
+                size: login_packet.data().len(),
+            })
+            .collect();
+        login_packets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut ids: Vec<_> = cache.nae_manager.keys().collect();
+        ids.sort();
+
+        let mut contents = VaultContents {
+            accounts,
+            balances,
+            login_packets,
+            immutable_data: Vec::new(),
+            mutable_data: Vec::new(),
+            append_only_data: Vec::new(),
+        };
+        for id in ids {
+            let info = data_info(id);
+            match id {
+                DataId::Immutable(_) => contents.immutable_data.push(info),
+                DataId::Mutable(_) => contents.mutable_data.push(info),
+                DataId::AppendOnly(_) => contents.append_only_data.push(info),
+            }
+        }
+        contents
+    }
+
+    /// Returns the contents of the mutable data with the given name and type tag.
+    pub fn mdata(&self, name: &XorName, tag: u64) -> Option<MDataContents> {
+        let vault = lock(&self.vault, false);
+        let (id, data) = find_data(&vault, name, Some(tag))
+            .into_iter()
+            .filter_map(|id| match vault.cache.nae_manager.get(&id) {
+                Some(Data::Mutable(data)) => Some((id, data)),
+                _ => None,
+            })
+            .next()?;
+
+        let entries = match data {
+            MData::Seq(data) => data
+                .entries()
+                .iter()
+                .map(|(key, value)| EntryInfo {
+                    key: display_bytes(key),
+                    value: display_bytes(&value.data),
+                    version: Some(value.version),
+                })
+                .collect(),
+            MData::Unseq(data) => data
+                .entries()
+                .iter()
+                .map(|(key, value)| EntryInfo {
+                    key: display_bytes(key),
+                    value: display_bytes(value),
+                    version: None,
+                })
+                .collect(),
+        };
+
+        let actions = [
+            MDataAction::Read,
+            MDataAction::Insert,
+            MDataAction::Update,
+            MDataAction::Delete,
+            MDataAction::ManagePermissions,
+        ];
+        let permissions = data
+            .permissions()
+            .iter()
+            .map(|(user, set)| PermissionInfo {
+                user: format!("{:?}", user),
+                allowed: actions
+                    .iter()
+                    .filter(|action| set.is_allowed(**action))
+                    .map(|action| format!("{:?}", action))
+                    .collect(),
+            })
+            .collect();
+
+        Some(MDataContents {
+            info: data_info(&id),
+            owner: format!("{:?}", data.owner()),
+            version: data.version(),
+            entries,
+            permissions,
+        })
+    }
+
+    /// Returns the contents of the append-only data with the given name and type tag.
+    pub fn adata(&self, name: &XorName, tag: u64) -> Option<ADataContents> {
+        let vault = lock(&self.vault, false);
+        let (id, data) = find_data(&vault, name, Some(tag))
+            .into_iter()
+            .filter_map(|id| match vault.cache.nae_manager.get(&id) {
+                Some(Data::AppendOnly(data)) => Some((id, data)),
+                _ => None,
+            })
+            .next()?;
+
+        let entries = adata_entries(data)
+            .into_iter()
+            .map(|entry| EntryInfo {
+                key: display_bytes(&entry.key),
+                value: display_bytes(&entry.value),
+                version: None,
+            })
+            .collect();
+
+        Some(ADataContents {
+            info: data_info(&id),
+            entries,
+        })
+    }
+
+    /// Delete the account with the given name. Returns `false` if there's no such account.
+    pub fn delete_account(&self, name: &XorName) -> bool {
+        let mut vault = lock(&self.vault, true);
+        let _ = vault.cache.dirty.insert(Key::Account(*name));
+        vault.cache.client_manager.remove(name).is_some()
+    }
+
+    /// Delete the coin balance with the given name. Returns `false` if there's no such balance.
+    pub fn delete_balance(&self, name: &XorName) -> bool {
+        let mut vault = lock(&self.vault, true);
+        let _ = vault.cache.dirty.insert(Key::CoinBalance(*name));
+        vault.cache.coin_balances.remove(name).is_some()
+    }
+
+    /// Delete the login packet with the given name. Returns `false` if there's no such login
+    /// packet.
+    pub fn delete_login_packet(&self, name: &XorName) -> bool {
+        let mut vault = lock(&self.vault, true);
+        let _ = vault.cache.dirty.insert(Key::LoginPacket(*name));
+        vault.cache.login_packets.remove(name).is_some()
+    }
+
+    /// Delete the data with the given name and, if given, type tag. Returns the number of
+    /// deleted items.
+    pub fn delete_data(&self, name: &XorName, tag: Option<u64>) -> usize {
+        let mut vault = lock(&self.vault, true);
+        let ids = find_data(&vault, name, tag);
+        for id in &ids {
+            vault.delete_data(*id);
+        }
+        ids.len()
+    }
+}
+
+// Ids of the data with the given name and, if given, type tag.
+fn find_data(vault: &Vault, name: &XorName, tag: Option<u64>) -> Vec<DataId> {
+    vault
+        .cache
+        .nae_manager
+        .keys()
+        .filter(|id| {
+            let (id_name, id_tag) = match id {
+                DataId::Immutable(address) => (address.name(), None),
+                DataId::Mutable(address) => (address.name(), Some(address.tag())),
+                DataId::AppendOnly(address) => (address.name(), Some(address.tag())),
+            };
+            id_name == name && (tag.is_none() || tag == id_tag)
+        })
+        .cloned()
+        .collect()
+}
+
+fn adata_entries(data: &AData) -> Vec<safe_nd::ADataEntry> {
+    data.in_range(ADataIndex::FromStart(0), ADataIndex::FromEnd(0))
+        .unwrap_or_default()
+}
+
+fn data_info(id: &DataId) -> DataInfo {
+    match id {
+        DataId::Immutable(address) => DataInfo {
+            name: hex(address.name()),
+            tag: None,
+            kind: format!("{:?}", address.kind()),
+        },
+        DataId::Mutable(address) => DataInfo {
+            name: hex(address.name()),
+            tag: Some(address.tag()),
+            kind: format!("{:?}", address.kind()),
+        },
+        DataId::AppendOnly(address) => DataInfo {
+            name: hex(address.name()),
+            tag: Some(address.tag()),
+            kind: format!("{:?}", address.kind()),
+        },
+    }
+}
+
+fn hex(name: &XorName) -> String {
+    HEXLOWER.encode(&name.0)
+}
+
+fn display_bytes(bytes: &[u8]) -> String {
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(text) => text,
+        Err(_) => HEXLOWER.encode(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_handler::DevConfig;
+    use safe_nd::{PublicKey, SeqMutableData};
+    use std::collections::BTreeMap;
+    use threshold_crypto::SecretKey;
+
+    // Test that the inspector lists and deletes the contents of the vault store.
+    #[test]
+    fn inspect() {
+        let dir = unwrap!(tempfile::tempdir());
+        let config = Config {
+            dev: Some(DevConfig {
+                mock_journal_storage: true,
+                mock_vault_path: Some(unwrap!(dir.path().to_str()).to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let owner = PublicKey::from(SecretKey::random().public_key());
+        let name: XorName = new_rand::random();
+        let data =
+            SeqMutableData::new_with_data(name, 100, BTreeMap::new(), BTreeMap::new(), owner);
+        {
+            let vault = Mutex::new(Vault::new(config.clone()));
+            let mut vault = lock(&vault, true);
+            vault.insert_data(
+                DataId::Mutable(*data.address()),
+                Data::Mutable(MData::Seq(data)),
+            );
+        }
+
+        let inspector = VaultInspector::with_config(config);
+        let contents = inspector.contents();
+        assert_eq!(contents.mutable_data.len(), 1);
+        assert_eq!(contents.mutable_data[0].name, hex(&name));
+
+        let mdata = unwrap!(inspector.mdata(&name, 100));
+        assert_eq!(mdata.owner, format!("{:?}", owner));
+        assert!(mdata.entries.is_empty());
+        assert!(inspector.mdata(&name, 101).is_none());
+
+        assert_eq!(inspector.delete_data(&name, None), 1);
+        assert!(inspector.contents().mutable_data.is_empty());
+    }
+}
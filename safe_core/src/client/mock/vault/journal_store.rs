@@ -0,0 +1,370 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Cache, Key, Store, JOURNAL_FILE_NAME};
+use crate::client::mock::{Account, CoinBalance, DataId};
+use crate::utils::files::{open_lock_file, temp_path};
+use fs2::FileExt;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use safe_nd::{Data, LoginPacket, XorName};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::hash::Hash;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+#[cfg(test)]
+use tempfile::{tempdir, TempDir};
+
+// Length of the header holding the generation of the journal.
+const HEADER_LEN: u64 = 8;
+// Length of the prefix holding the size of each record.
+const RECORD_PREFIX_LEN: usize = 8;
+// The journal is never compacted while it holds fewer records than this.
+#[cfg(not(test))]
+const MIN_COMPACTION_RECORDS: usize = 1000;
+#[cfg(test)]
+const MIN_COMPACTION_RECORDS: usize = 10;
+
+// Record of the journal, as written.
+#[derive(Serialize)]
+enum RecordRef<'a> {
+    Snapshot(&'a Cache),
+    CoinBalance(&'a XorName, Option<&'a CoinBalance>),
+    Account(&'a XorName, Option<&'a Account>),
+    LoginPacket(&'a XorName, Option<&'a LoginPacket>),
+    Data(&'a DataId, Option<&'a Data>),
+}
+
+impl<'a> RecordRef<'a> {
+    // Record of the current value of the given cache entry, `None` meaning it has been removed.
+    fn new(cache: &'a Cache, key: &'a Key) -> Self {
+        match key {
+            Key::CoinBalance(name) => RecordRef::CoinBalance(name, cache.coin_balances.get(name)),
+            Key::Account(name) => RecordRef::Account(name, cache.client_manager.get(name)),
+            Key::LoginPacket(name) => RecordRef::LoginPacket(name, cache.login_packets.get(name)),
+            Key::Data(id) => RecordRef::Data(id, cache.nae_manager.get(id)),
+        }
+    }
+}
+
+// Record of the journal, as read. Must match the layout of `RecordRef`.
+#[derive(Deserialize)]
+enum Record {
+    Snapshot(Cache),
+    CoinBalance(XorName, Option<CoinBalance>),
+    Account(XorName, Option<Account>),
+    LoginPacket(XorName, Option<LoginPacket>),
+    Data(DataId, Option<Data>),
+}
+
+impl Record {
+    fn apply(self, cache: &mut Cache) {
+        match self {
+            Record::Snapshot(snapshot) => *cache = snapshot,
+            Record::CoinBalance(name, value) => update(&mut cache.coin_balances, name, value),
+            Record::Account(name, value) => update(&mut cache.client_manager, name, value),
+            Record::LoginPacket(name, value) => update(&mut cache.login_packets, name, value),
+            Record::Data(id, value) => update(&mut cache.nae_manager, id, value),
+        }
+    }
+}
+
+fn update<K: Eq + Hash, V>(map: &mut HashMap<K, V>, key: K, value: Option<V>) {
+    match value {
+        Some(value) => {
+            let _ = map.insert(key, value);
+        }
+        None => {
+            let _ = map.remove(&key);
+        }
+    }
+}
+
+// Store keeping an append-only journal of the changes to the cache. Every process only reads the
+// records appended since it last synchronised. Once the journal has grown large compared to the
+// cache, it's compacted into a single snapshot of the cache, which bumps its generation so that
+// other processes know to reload it. The snapshot is written to a new file which then replaces the
+// journal, so that the journal is never left incomplete.
+//
+// File layout: the generation (u64, little endian), then the records, each prefixed with its
+// length (u64, little endian).
+pub struct JournalStore {
+    path: PathBuf,
+    // Locked while the journal is in use, as the journal is replaced whenever it's compacted.
+    lock: File,
+    file: File,
+    // Whether the store is being written to.
+    writing: bool,
+    // Generation of the journal the cache is synchronised with, `None` if the journal is empty.
+    generation: Option<u64>,
+    // Position in the file up to which the records have been applied to the cache.
+    offset: u64,
+    // Number of records in the journal.
+    records: usize,
+    // Directory of the journal, removed once the store is dropped.
+    #[cfg(test)]
+    temp_dir: Option<TempDir>,
+}
+
+impl JournalStore {
+    pub fn new(path: &Path) -> Self {
+        let path = path.join(JOURNAL_FILE_NAME);
+        let lock = unwrap!(open_lock_file(&path));
+        let file = open_journal(&path);
+
+        Self {
+            path,
+            lock,
+            file,
+            writing: false,
+            generation: None,
+            offset: HEADER_LEN,
+            records: 0,
+            #[cfg(test)]
+            temp_dir: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn new_with_temp() -> Self {
+        let temp_dir = unwrap!(tempdir());
+        let mut store = Self::new(temp_dir.path());
+        store.temp_dir = Some(temp_dir);
+        store
+    }
+
+    fn read_generation(&mut self) -> Option<u64> {
+        let mut header = [0; HEADER_LEN as usize];
+        let _ = unwrap!(self.file.seek(SeekFrom::Start(0)));
+        match self.file.read_exact(&mut header) {
+            Ok(()) => Some(u64::from_le_bytes(header)),
+            Err(_) => None,
+        }
+    }
+
+    // Apply the records following `offset` to the cache.
+    fn read_records(&mut self, cache: &mut Cache) {
+        let mut raw_data = Vec::new();
+        let _ = unwrap!(self.file.seek(SeekFrom::Start(self.offset)));
+        if let Err(e) = self.file.read_to_end(&mut raw_data) {
+            warn!("Can't read the mock vault journal: {:?}", e);
+            return;
+        }
+
+        let mut pos = 0;
+        while pos + RECORD_PREFIX_LEN <= raw_data.len() {
+            let mut len = [0; RECORD_PREFIX_LEN];
+            len.copy_from_slice(&raw_data[pos..pos + RECORD_PREFIX_LEN]);
+            let start = pos + RECORD_PREFIX_LEN;
+            let end = start + u64::from_le_bytes(len) as usize;
+
+            if end > raw_data.len() {
+                warn!("Mock vault journal ends with an incomplete record");
+                break;
+            }
+            match deserialise::<Record>(&raw_data[start..end]) {
+                Ok(record) => record.apply(cache),
+                Err(e) => {
+                    warn!("Can't read the mock vault journal: {:?}", e);
+                    break;
+                }
+            }
+
+            pos = end;
+            self.records += 1;
+        }
+
+        self.offset += pos as u64;
+    }
+
+    fn append(&mut self, record: &RecordRef) {
+        let raw_data = unwrap!(serialise(record));
+        let mut buffer = Vec::with_capacity(RECORD_PREFIX_LEN + raw_data.len());
+        buffer.extend_from_slice(&(raw_data.len() as u64).to_le_bytes());
+        buffer.extend_from_slice(&raw_data);
+
+        let _ = unwrap!(self.file.seek(SeekFrom::Start(self.offset)));
+        unwrap!(self.file.write_all(&buffer));
+        self.offset += buffer.len() as u64;
+        self.records += 1;
+    }
+
+    // Replace the journal with a snapshot of the cache.
+    fn compact(&mut self, cache: &Cache) {
+        let generation = self
+            .generation
+            .map_or(0, |generation| generation.wrapping_add(1));
+        trace!(
+            "Compacting the mock vault journal (generation {})",
+            generation
+        );
+
+        let temp_path = temp_path(&self.path);
+        self.file = unwrap!(OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&temp_path));
+        unwrap!(self.file.write_all(&generation.to_le_bytes()));
+
+        self.generation = Some(generation);
+        self.offset = HEADER_LEN;
+        self.records = 0;
+        self.append(&RecordRef::Snapshot(cache));
+
+        unwrap!(self.file.sync_all());
+        unwrap!(fs::rename(&temp_path, &self.path));
+    }
+}
+
+impl Store for JournalStore {
+    fn load(&mut self, cache: &mut Cache, writing: bool) {
+        if writing {
+            unwrap!(self.lock.lock_exclusive());
+        } else {
+            unwrap!(self.lock.lock_shared());
+        }
+        self.writing = writing;
+
+        // Another process may have replaced the journal since we last read it.
+        self.file = open_journal(&self.path);
+
+        let generation = self.read_generation();
+        if generation != self.generation {
+            // The journal has been compacted by another process: reload it from the start.
+            *cache = Cache::default();
+            self.generation = generation;
+            self.offset = HEADER_LEN;
+            self.records = 0;
+        }
+
+        if self.generation.is_some() {
+            self.read_records(cache);
+        }
+    }
+
+    fn save(&mut self, cache: &Cache) {
+        if self.writing {
+            if self.generation.is_none() || cache.replaced {
+                self.compact(cache);
+            } else if !cache.dirty.is_empty() {
+                // Drop any incomplete record left behind by a crashed process.
+                unwrap!(self.file.set_len(self.offset));
+
+                for key in &cache.dirty {
+                    self.append(&RecordRef::new(cache, key));
+                }
+
+                let entries = cache.coin_balances.len()
+                    + cache.client_manager.len()
+                    + cache.login_packets.len()
+                    + cache.nae_manager.len();
+                if self.records > MIN_COMPACTION_RECORDS.max(2 * entries) {
+                    self.compact(cache);
+                }
+            }
+
+            unwrap!(self.file.sync_data());
+            self.writing = false;
+        }
+
+        let _ = self.lock.unlock();
+    }
+}
+
+fn open_journal(path: &Path) -> File {
+    unwrap!(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use safe_nd::{Coins, PublicKey};
+    use std::str::FromStr;
+    use threshold_crypto::SecretKey;
+
+    fn balance_key() -> (XorName, PublicKey) {
+        let owner = PublicKey::from(SecretKey::random().public_key());
+        (XorName::from(owner), owner)
+    }
+
+    fn set_balance(store: &mut JournalStore, cache: &mut Cache, owner: PublicKey, coins: &str) {
+        store.load(cache, true);
+        let name = XorName::from(owner);
+        let _ = cache.coin_balances.insert(
+            name,
+            CoinBalance::new(unwrap!(Coins::from_str(coins)), owner),
+        );
+        let _ = cache.dirty.insert(Key::CoinBalance(name));
+        store.save(cache);
+        cache.dirty.clear();
+    }
+
+    fn balance(store: &mut JournalStore, cache: &mut Cache, name: &XorName) -> Option<Coins> {
+        store.load(cache, false);
+        let balance = cache.coin_balances.get(name).map(CoinBalance::balance);
+        store.save(cache);
+        balance
+    }
+
+    // Test that changes made through one store are picked up by another one sharing the journal,
+    // including after the journal has been compacted.
+    #[test]
+    fn shared_journal() {
+        let dir = unwrap!(tempfile::tempdir());
+        let mut store0 = JournalStore::new(dir.path());
+        let mut store1 = JournalStore::new(dir.path());
+        let mut cache0 = Cache::default();
+        let mut cache1 = Cache::default();
+
+        let (name, owner) = balance_key();
+        set_balance(&mut store0, &mut cache0, owner, "1");
+        set_balance(&mut store0, &mut cache0, owner, "2");
+        assert_eq!(
+            balance(&mut store1, &mut cache1, &name),
+            Some(unwrap!(Coins::from_str("2")))
+        );
+
+        // Grow the journal until it gets compacted.
+        for _ in 0..MIN_COMPACTION_RECORDS {
+            set_balance(&mut store1, &mut cache1, owner, "3");
+        }
+        assert!(store1.records < MIN_COMPACTION_RECORDS);
+        assert_eq!(store1.generation, Some(1));
+        // Only the journal and its lock file are left once the snapshot has replaced the journal.
+        assert_eq!(unwrap!(fs::read_dir(dir.path())).count(), 2);
+
+        let (other_name, other_owner) = balance_key();
+        set_balance(&mut store1, &mut cache1, other_owner, "4");
+
+        assert_eq!(
+            balance(&mut store0, &mut cache0, &name),
+            Some(unwrap!(Coins::from_str("3")))
+        );
+        assert_eq!(
+            balance(&mut store0, &mut cache0, &other_name),
+            Some(unwrap!(Coins::from_str("4")))
+        );
+
+        // A new store reads the whole journal.
+        let mut store2 = JournalStore::new(dir.path());
+        let mut cache2 = Cache::default();
+        assert_eq!(cache2.coin_balances.len(), 0);
+        assert_eq!(
+            balance(&mut store2, &mut cache2, &other_name),
+            Some(unwrap!(Coins::from_str("4")))
+        );
+        assert_eq!(cache2.coin_balances.len(), 2);
+    }
+}
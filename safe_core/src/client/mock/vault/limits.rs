@@ -0,0 +1,168 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Vault;
+use crate::config_handler::DataLimits;
+use maidsafe_utilities::serialisation::serialise;
+use safe_nd::{AData, Data, Error as SndError, IData, MData, Result as SndResult};
+use serde::Serialize;
+
+impl Vault {
+    // Check that the data doesn't exceed the limits enforced by the network.
+    pub fn check_data_limits(&self, data: &Data) -> SndResult<()> {
+        match data {
+            Data::Immutable(data) => self.check_idata_limits(data),
+            Data::Mutable(data) => self.check_mdata_limits(data),
+            Data::AppendOnly(data) => self.check_adata_limits(data),
+        }
+    }
+
+    pub fn check_idata_limits(&self, data: &IData) -> SndResult<()> {
+        match self.data_limits().max_idata_size {
+            Some(max_size) => check_size(data, max_size),
+            None if data.validate_size() => Ok(()),
+            None => Err(SndError::ExceededSize),
+        }
+    }
+
+    pub fn check_mdata_limits(&self, data: &MData) -> SndResult<()> {
+        let limits = self.data_limits();
+
+        let entries = match data {
+            MData::Seq(data) => data.entries().len(),
+            MData::Unseq(data) => data.entries().len(),
+        };
+        if entries as u64 > limits.max_mdata_entries
+            || data.permissions().len() as u64 > limits.max_mdata_permissions
+        {
+            return Err(SndError::TooManyEntries);
+        }
+
+        check_size(data, limits.max_mdata_size)
+    }
+
+    pub fn check_adata_limits(&self, data: &AData) -> SndResult<()> {
+        let limits = self.data_limits();
+
+        if data.permissions_index() > limits.max_adata_permissions {
+            return Err(SndError::TooManyEntries);
+        }
+
+        check_size(data, limits.max_adata_size)
+    }
+
+    fn data_limits(&self) -> DataLimits {
+        self.config
+            .dev
+            .as_ref()
+            .map(|dev| dev.mock_data_limits)
+            .unwrap_or_default()
+    }
+}
+
+fn check_size<T: Serialize>(data: &T, max_size: u64) -> SndResult<()> {
+    let size = serialise(data).map_err(|error| SndError::NetworkOther(error.to_string()))?;
+    if size.len() as u64 > max_size {
+        Err(SndError::ExceededSize)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::mock::vault::Vault;
+    use crate::config_handler::{Config, DataLimits, DevConfig};
+    use safe_nd::{
+        AData, Error as SndError, IData, MData, MDataPermissionSet, MDataSeqValue,
+        PubImmutableData, PubSeqAppendOnlyData, PublicKey, SeqMutableData, XorName,
+    };
+    use std::collections::BTreeMap;
+    use threshold_crypto::SecretKey;
+
+    fn new_vault(limits: DataLimits) -> Vault {
+        Vault::new(Config {
+            dev: Some(DevConfig {
+                mock_in_memory_storage: true,
+                mock_data_limits: limits,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn random_key() -> PublicKey {
+        PublicKey::from(SecretKey::random().public_key())
+    }
+
+    fn value() -> MDataSeqValue {
+        MDataSeqValue {
+            data: vec![],
+            version: 0,
+        }
+    }
+
+    // Test that the data exceeding the configured limits is rejected with the same errors as on
+    // the real network.
+    #[test]
+    fn data_limits() {
+        let vault = new_vault(DataLimits {
+            max_idata_size: Some(100),
+            max_mdata_entries: 2,
+            max_mdata_permissions: 1,
+            max_adata_permissions: 1,
+            ..Default::default()
+        });
+
+        // Immutable data
+        let small = IData::Pub(PubImmutableData::new(vec![0; 10]));
+        let large = IData::Pub(PubImmutableData::new(vec![0; 200]));
+        unwrap!(vault.check_idata_limits(&small));
+        match vault.check_idata_limits(&large) {
+            Err(SndError::ExceededSize) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Mutable data
+        let name: XorName = new_rand::random();
+        let owner = random_key();
+        let mut entries = BTreeMap::new();
+        for key in 0..2u8 {
+            let _ = entries.insert(vec![key], value());
+        }
+        let mut permissions = BTreeMap::new();
+        let _ = permissions.insert(random_key(), MDataPermissionSet::new());
+        let data =
+            SeqMutableData::new_with_data(name, 1000, entries.clone(), permissions.clone(), owner);
+        unwrap!(vault.check_mdata_limits(&MData::Seq(data)));
+
+        let _ = entries.insert(vec![2], value());
+        let data =
+            SeqMutableData::new_with_data(name, 1000, entries.clone(), permissions.clone(), owner);
+        match vault.check_mdata_limits(&MData::Seq(data)) {
+            Err(SndError::TooManyEntries) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let _ = entries.remove(&vec![2]);
+        let _ = permissions.insert(random_key(), MDataPermissionSet::new());
+        let data = SeqMutableData::new_with_data(name, 1000, entries, permissions, owner);
+        match vault.check_mdata_limits(&MData::Seq(data)) {
+            Err(SndError::TooManyEntries) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Append-only data
+        let data = AData::PubSeq(PubSeqAppendOnlyData::new(name, 1000));
+        unwrap!(vault.check_adata_limits(&data));
+
+        // The default limits of the network apply if none are configured.
+        let vault = new_vault(Default::default());
+        unwrap!(vault.check_idata_limits(&large));
+    }
+}
@@ -0,0 +1,838 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Cache, Vault};
+use crate::client::mock::{Account, CoinBalance, DataId};
+use crate::errors::CoreError;
+use data_encoding::HEXLOWER;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use safe_nd::{
+    AData, ADataAddress, ADataEntry, ADataIndex, ADataOwner, ADataPubPermissionSet,
+    ADataPubPermissions, ADataUnpubPermissionSet, ADataUnpubPermissions, ADataUser, Data, IData,
+    IDataAddress, LoginPacket, MData, MDataAction, MDataAddress, MDataPermissionSet, MDataSeqValue,
+    PubImmutableData, PubSeqAppendOnlyData, PubUnseqAppendOnlyData, PublicKey, SeqMutableData,
+    UnpubImmutableData, UnpubSeqAppendOnlyData, UnpubUnseqAppendOnlyData, UnseqMutableData,
+    XorName,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::str;
+
+const MDATA_ACTIONS: [MDataAction; 5] = [
+    MDataAction::Read,
+    MDataAction::Insert,
+    MDataAction::Update,
+    MDataAction::Delete,
+    MDataAction::ManagePermissions,
+];
+
+// Entry of one of the maps of the vault.
+#[derive(Deserialize, Serialize)]
+struct Entry<K, V> {
+    name: K,
+    value: V,
+}
+
+// Stored data.
+#[derive(Deserialize, Serialize)]
+struct DataEntry {
+    id: DataId,
+    data: DataFixture,
+}
+
+// Contents of stored data, with its maps written as lists as JSON only allows string keys. The
+// name and type tag are taken from the id of the entry.
+#[derive(Deserialize, Serialize)]
+enum DataFixture {
+    Immutable {
+        value: Bytes,
+        // Owner of unpublished data.
+        owner: Option<PublicKey>,
+    },
+    Mutable {
+        owner: PublicKey,
+        version: u64,
+        entries: Vec<MDataEntryFixture>,
+        permissions: Vec<UserPermissions<PublicKey, Vec<MDataAction>>>,
+    },
+    AppendOnly {
+        owners: Vec<ADataOwner>,
+        permissions: ADataPermissionsFixture,
+        entries: Vec<ADataEntryFixture>,
+    },
+}
+
+// Key or value, written as text if it's valid UTF-8 and in hex otherwise.
+#[derive(Deserialize, Serialize)]
+enum Bytes {
+    Text(String),
+    Hex(String),
+}
+
+#[derive(Deserialize, Serialize)]
+struct MDataEntryFixture {
+    key: Bytes,
+    value: Bytes,
+    // Version of the entries of sequenced data.
+    version: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ADataEntryFixture {
+    key: Bytes,
+    value: Bytes,
+}
+
+#[derive(Deserialize, Serialize)]
+struct UserPermissions<U, P> {
+    user: U,
+    permissions: P,
+}
+
+// History of the permissions of append-only data.
+#[derive(Deserialize, Serialize)]
+enum ADataPermissionsFixture {
+    Pub(Vec<ADataPermissionsEntry<ADataUser, ADataPubPermissionSet>>),
+    Unpub(Vec<ADataPermissionsEntry<PublicKey, ADataUnpubPermissionSet>>),
+}
+
+#[derive(Deserialize, Serialize)]
+struct ADataPermissionsEntry<U, S> {
+    users: Vec<UserPermissions<U, S>>,
+    entries_index: u64,
+    owners_index: u64,
+}
+
+// Step in the history of append-only data, with the index it must be appended at.
+enum ADataStep<P> {
+    Permissions(P, u64),
+    Owner(ADataOwner, u64),
+    Entries(Vec<ADataEntry>, u64),
+}
+
+// Applies the steps of the history of append-only data to `$data`, appending entries with
+// `$append`.
+macro_rules! replay_adata {
+    ($data:ident, $steps:expr, |$entries:ident, $index:ident| $append:expr) => {
+        for step in $steps {
+            match step {
+                ADataStep::Permissions(permissions, index) => {
+                    $data.append_permissions(permissions, index)?
+                }
+                ADataStep::Owner(owner, index) => $data.append_owner(owner, index)?,
+                ADataStep::Entries($entries, $index) => $append?,
+            }
+        }
+    };
+}
+
+// JSON fixture, as written.
+#[derive(Serialize)]
+struct FixtureRef<'a> {
+    coin_balances: Vec<Entry<&'a XorName, &'a CoinBalance>>,
+    accounts: Vec<Entry<&'a XorName, &'a Account>>,
+    login_packets: Vec<&'a LoginPacket>,
+    data: Vec<DataEntry>,
+}
+
+// JSON fixture, as read. Must match `FixtureRef`.
+#[derive(Deserialize)]
+struct Fixture {
+    coin_balances: Vec<Entry<XorName, CoinBalance>>,
+    accounts: Vec<Entry<XorName, Account>>,
+    login_packets: Vec<LoginPacket>,
+    data: Vec<DataEntry>,
+}
+
+impl Vault {
+    /// Save a copy of the current state of the vault under the given name, replacing any previous
+    /// snapshot with the same name.
+    pub fn take_snapshot(&mut self, name: &str) -> Result<(), CoreError> {
+        let snapshot = self.state()?;
+        let _ = self.snapshots.insert(name.to_string(), snapshot);
+        Ok(())
+    }
+
+    /// Returns a serialised copy of the current state of the vault.
+    pub fn state(&self) -> Result<Vec<u8>, CoreError> {
+        Ok(serialise(&self.cache)?)
+    }
+
+    /// Replace the state of the vault with a copy returned by `state`.
+    pub fn restore_state(&mut self, state: &[u8]) -> Result<(), CoreError> {
+        let cache = deserialise(state)?;
+        self.replace_cache(cache);
+        Ok(())
+    }
+
+    /// Restore the state saved by `take_snapshot` under the given name.
+    pub fn restore_snapshot(&mut self, name: &str) -> Result<(), CoreError> {
+        let cache = match self.snapshots.get(name) {
+            Some(snapshot) => deserialise(snapshot)?,
+            None => {
+                return Err(CoreError::Unexpected(format!(
+                    "No mock vault snapshot named {:?}",
+                    name
+                )))
+            }
+        };
+        self.replace_cache(cache);
+        Ok(())
+    }
+
+    /// Export the state of the vault as a JSON fixture. Keys and values of stored data are written
+    /// as text if they are valid UTF-8, and in hex otherwise.
+    pub fn export_json(&self) -> Result<String, CoreError> {
+        let mut coin_balances: Vec<_> = self
+            .cache
+            .coin_balances
+            .iter()
+            .map(|(name, value)| Entry { name, value })
+            .collect();
+        coin_balances.sort_by_key(|entry| entry.name);
+
+        let mut accounts: Vec<_> = self
+            .cache
+            .client_manager
+            .iter()
+            .map(|(name, value)| Entry { name, value })
+            .collect();
+        accounts.sort_by_key(|entry| entry.name);
+
+        let mut login_packets: Vec<_> = self.cache.login_packets.values().collect();
+        login_packets.sort_by_key(|login_packet| *login_packet.destination());
+
+        let mut data = self
+            .cache
+            .nae_manager
+            .iter()
+            .map(|(id, data)| {
+                Ok(DataEntry {
+                    id: *id,
+                    data: data_fixture(data)?,
+                })
+            })
+            .collect::<Result<Vec<_>, CoreError>>()?;
+        data.sort_by_key(|entry| entry.id);
+
+        let fixture = FixtureRef {
+            coin_balances,
+            accounts,
+            login_packets,
+            data,
+        };
+        Ok(serde_json::to_string_pretty(&fixture)?)
+    }
+
+    /// Replace the state of the vault with a JSON fixture written by `export_json`.
+    pub fn import_json(&mut self, json: &str) -> Result<(), CoreError> {
+        let fixture: Fixture = serde_json::from_str(json)?;
+        let mut cache = Cache::default();
+
+        for Entry { name, value } in fixture.coin_balances {
+            let _ = cache.coin_balances.insert(name, value);
+        }
+        for Entry { name, value } in fixture.accounts {
+            let _ = cache.client_manager.insert(name, value);
+        }
+        for login_packet in fixture.login_packets {
+            let _ = cache
+                .login_packets
+                .insert(*login_packet.destination(), login_packet);
+        }
+        for DataEntry { id, data } in fixture.data {
+            let _ = cache.nae_manager.insert(id, data_from_fixture(id, data)?);
+        }
+
+        self.replace_cache(cache);
+        Ok(())
+    }
+
+    fn replace_cache(&mut self, cache: Cache) {
+        self.cache = cache;
+        self.cache.replaced = true;
+    }
+}
+
+impl Bytes {
+    fn new(bytes: &[u8]) -> Self {
+        match str::from_utf8(bytes) {
+            Ok(text) => Bytes::Text(text.to_string()),
+            Err(_) => Bytes::Hex(HEXLOWER.encode(bytes)),
+        }
+    }
+
+    fn into_vec(self) -> Result<Vec<u8>, CoreError> {
+        match self {
+            Bytes::Text(text) => Ok(text.into_bytes()),
+            Bytes::Hex(hex) => HEXLOWER
+                .decode(hex.as_bytes())
+                .map_err(|error| CoreError::Unexpected(error.to_string())),
+        }
+    }
+}
+
+impl<U: Ord, S> ADataPermissionsEntry<U, S> {
+    fn new(permissions: &BTreeMap<U, S>, entries_index: u64, owners_index: u64) -> Self
+    where
+        U: Clone,
+        S: Clone,
+    {
+        Self {
+            users: permissions
+                .iter()
+                .map(|(user, permissions)| UserPermissions {
+                    user: user.clone(),
+                    permissions: permissions.clone(),
+                })
+                .collect(),
+            entries_index,
+            owners_index,
+        }
+    }
+
+    fn permissions(self) -> BTreeMap<U, S> {
+        self.users
+            .into_iter()
+            .map(|UserPermissions { user, permissions }| (user, permissions))
+            .collect()
+    }
+}
+
+fn data_fixture(data: &Data) -> Result<DataFixture, CoreError> {
+    Ok(match data {
+        Data::Immutable(IData::Pub(data)) => DataFixture::Immutable {
+            value: Bytes::new(data.value()),
+            owner: None,
+        },
+        Data::Immutable(IData::Unpub(data)) => DataFixture::Immutable {
+            value: Bytes::new(data.value()),
+            owner: Some(*data.owner()),
+        },
+        Data::Mutable(data) => mdata_fixture(data),
+        Data::AppendOnly(data) => adata_fixture(data)?,
+    })
+}
+
+fn mdata_fixture(data: &MData) -> DataFixture {
+    let entries = match data {
+        MData::Seq(data) => data
+            .entries()
+            .iter()
+            .map(|(key, value)| MDataEntryFixture {
+                key: Bytes::new(key),
+                value: Bytes::new(&value.data),
+                version: Some(value.version),
+            })
+            .collect(),
+        MData::Unseq(data) => data
+            .entries()
+            .iter()
+            .map(|(key, value)| MDataEntryFixture {
+                key: Bytes::new(key),
+                value: Bytes::new(value),
+                version: None,
+            })
+            .collect(),
+    };
+    let permissions = data
+        .permissions()
+        .iter()
+        .map(|(user, set)| UserPermissions {
+            user: *user,
+            permissions: MDATA_ACTIONS
+                .iter()
+                .filter(|action| set.is_allowed(**action))
+                .cloned()
+                .collect(),
+        })
+        .collect();
+
+    DataFixture::Mutable {
+        owner: data.owner(),
+        version: data.version(),
+        entries,
+        permissions,
+    }
+}
+
+fn adata_fixture(data: &AData) -> Result<DataFixture, CoreError> {
+    let owners = (0..data.owners_index())
+        .filter_map(|index| data.owner(index).cloned())
+        .collect();
+
+    let indices = 0..data.permissions_index();
+    let permissions = if data.is_pub() {
+        ADataPermissionsFixture::Pub(
+            indices
+                .map(|index| {
+                    let permissions = data.pub_permissions(ADataIndex::FromStart(index))?;
+                    Ok(ADataPermissionsEntry::new(
+                        &permissions.permissions,
+                        permissions.entries_index,
+                        permissions.owners_index,
+                    ))
+                })
+                .collect::<Result<_, CoreError>>()?,
+        )
+    } else {
+        ADataPermissionsFixture::Unpub(
+            indices
+                .map(|index| {
+                    let permissions = data.unpub_permissions(ADataIndex::FromStart(index))?;
+                    Ok(ADataPermissionsEntry::new(
+                        &permissions.permissions,
+                        permissions.entries_index,
+                        permissions.owners_index,
+                    ))
+                })
+                .collect::<Result<_, CoreError>>()?,
+        )
+    };
+
+    let entries = data
+        .in_range(ADataIndex::FromStart(0), ADataIndex::FromEnd(0))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| ADataEntryFixture {
+            key: Bytes::new(&entry.key),
+            value: Bytes::new(&entry.value),
+        })
+        .collect();
+
+    Ok(DataFixture::AppendOnly {
+        owners,
+        permissions,
+        entries,
+    })
+}
+
+fn data_from_fixture(id: DataId, data: DataFixture) -> Result<Data, CoreError> {
+    let data = match (id, data) {
+        (
+            DataId::Immutable(IDataAddress::Pub(_)),
+            DataFixture::Immutable { value, owner: None },
+        ) => Data::Immutable(IData::Pub(PubImmutableData::new(value.into_vec()?))),
+        (
+            DataId::Immutable(IDataAddress::Unpub(_)),
+            DataFixture::Immutable {
+                value,
+                owner: Some(owner),
+            },
+        ) => Data::Immutable(IData::Unpub(UnpubImmutableData::new(
+            value.into_vec()?,
+            owner,
+        ))),
+        (
+            DataId::Mutable(address),
+            DataFixture::Mutable {
+                owner,
+                version,
+                entries,
+                permissions,
+            },
+        ) => Data::Mutable(mdata_from_fixture(
+            address,
+            owner,
+            version,
+            entries,
+            permissions,
+        )?),
+        (
+            DataId::AppendOnly(address),
+            DataFixture::AppendOnly {
+                owners,
+                permissions,
+                entries,
+            },
+        ) => Data::AppendOnly(adata_from_fixture(address, owners, permissions, entries)?),
+        (id, _) => return Err(fixture_mismatch(id)),
+    };
+
+    // The name of immutable data is derived from its value, which might have been edited.
+    match (id, &data) {
+        (DataId::Immutable(address), Data::Immutable(idata)) if address != *idata.address() => {
+            Err(fixture_mismatch(id))
+        }
+        _ => Ok(data),
+    }
+}
+
+fn mdata_from_fixture(
+    address: MDataAddress,
+    owner: PublicKey,
+    version: u64,
+    entries: Vec<MDataEntryFixture>,
+    permissions: Vec<UserPermissions<PublicKey, Vec<MDataAction>>>,
+) -> Result<MData, CoreError> {
+    let mut permissions: BTreeMap<_, _> = permissions
+        .into_iter()
+        .map(|UserPermissions { user, permissions }| {
+            let set = permissions
+                .into_iter()
+                .fold(MDataPermissionSet::new(), |set, action| set.allow(action));
+            (user, set)
+        })
+        .collect();
+
+    // The version of mutable data can only be brought up by changing its permissions, so the
+    // permissions of one of the users are set again until the version is reached. The owner is
+    // given permissions which are deleted at the last version if there are no users.
+    let placeholder = permissions.is_empty() && version > 0;
+    if placeholder {
+        let _ = permissions.insert(owner, MDataPermissionSet::new());
+    }
+    let repeated = permissions
+        .iter()
+        .next()
+        .map(|(user, set)| (*user, set.clone()));
+
+    let mut data = match address {
+        MDataAddress::Seq { name, tag } => {
+            let entries = entries
+                .into_iter()
+                .map(|entry| {
+                    let version = entry.version.ok_or_else(|| fixture_mismatch(address))?;
+                    let value = MDataSeqValue {
+                        data: entry.value.into_vec()?,
+                        version,
+                    };
+                    Ok((entry.key.into_vec()?, value))
+                })
+                .collect::<Result<_, CoreError>>()?;
+            MData::Seq(SeqMutableData::new_with_data(
+                name,
+                tag,
+                entries,
+                permissions,
+                owner,
+            ))
+        }
+        MDataAddress::Unseq { name, tag } => {
+            let entries = entries
+                .into_iter()
+                .map(|entry| Ok((entry.key.into_vec()?, entry.value.into_vec()?)))
+                .collect::<Result<_, CoreError>>()?;
+            MData::Unseq(UnseqMutableData::new_with_data(
+                name,
+                tag,
+                entries,
+                permissions,
+                owner,
+            ))
+        }
+    };
+
+    if let Some((user, set)) = repeated {
+        for next_version in 1..=version {
+            if placeholder && next_version == version {
+                data.del_user_permissions(user, next_version)?;
+            } else {
+                data.set_user_permissions(user, set.clone(), next_version)?;
+            }
+        }
+    }
+
+    Ok(data)
+}
+
+fn adata_from_fixture(
+    address: ADataAddress,
+    owners: Vec<ADataOwner>,
+    permissions: ADataPermissionsFixture,
+    entries: Vec<ADataEntryFixture>,
+) -> Result<AData, CoreError> {
+    let entries = entries
+        .into_iter()
+        .map(|entry| {
+            Ok(ADataEntry::new(
+                entry.key.into_vec()?,
+                entry.value.into_vec()?,
+            ))
+        })
+        .collect::<Result<Vec<_>, CoreError>>()?;
+
+    Ok(match (address, permissions) {
+        (ADataAddress::PubSeq { name, tag }, ADataPermissionsFixture::Pub(permissions)) => {
+            let mut data = PubSeqAppendOnlyData::new(name, tag);
+            let steps = adata_history(permissions, owners, entries, pub_permissions)?;
+            replay_adata!(data, steps, |entries, index| data.append(entries, index));
+            AData::PubSeq(data)
+        }
+        (ADataAddress::PubUnseq { name, tag }, ADataPermissionsFixture::Pub(permissions)) => {
+            let mut data = PubUnseqAppendOnlyData::new(name, tag);
+            let steps = adata_history(permissions, owners, entries, pub_permissions)?;
+            replay_adata!(data, steps, |entries, _index| data.append(entries));
+            AData::PubUnseq(data)
+        }
+        (ADataAddress::UnpubSeq { name, tag }, ADataPermissionsFixture::Unpub(permissions)) => {
+            let mut data = UnpubSeqAppendOnlyData::new(name, tag);
+            let steps = adata_history(permissions, owners, entries, unpub_permissions)?;
+            replay_adata!(data, steps, |entries, index| data.append(entries, index));
+            AData::UnpubSeq(data)
+        }
+        (ADataAddress::UnpubUnseq { name, tag }, ADataPermissionsFixture::Unpub(permissions)) => {
+            let mut data = UnpubUnseqAppendOnlyData::new(name, tag);
+            let steps = adata_history(permissions, owners, entries, unpub_permissions)?;
+            replay_adata!(data, steps, |entries, _index| data.append(entries));
+            AData::UnpubUnseq(data)
+        }
+        (address, _) => return Err(fixture_mismatch(address)),
+    })
+}
+
+fn pub_permissions(
+    permissions: BTreeMap<ADataUser, ADataPubPermissionSet>,
+    entries_index: u64,
+    owners_index: u64,
+) -> ADataPubPermissions {
+    ADataPubPermissions {
+        permissions,
+        entries_index,
+        owners_index,
+    }
+}
+
+fn unpub_permissions(
+    permissions: BTreeMap<PublicKey, ADataUnpubPermissionSet>,
+    entries_index: u64,
+    owners_index: u64,
+) -> ADataUnpubPermissions {
+    ADataUnpubPermissions {
+        permissions,
+        entries_index,
+        owners_index,
+    }
+}
+
+// Orders the permissions, owners and entries of append-only data the way they must be appended,
+// given the indices each permissions and owner was appended at.
+fn adata_history<U: Ord, S, P>(
+    permissions: Vec<ADataPermissionsEntry<U, S>>,
+    owners: Vec<ADataOwner>,
+    entries: Vec<ADataEntry>,
+    new_permissions: fn(BTreeMap<U, S>, u64, u64) -> P,
+) -> Result<Vec<ADataStep<P>>, CoreError> {
+    let entries_count = entries.len() as u64;
+    let mut permissions = permissions.into_iter().peekable();
+    let mut owners = owners.into_iter().peekable();
+    let mut entries = entries.into_iter();
+
+    let mut steps = Vec::new();
+    let (mut entries_index, mut permissions_index, mut owners_index) = (0, 0, 0);
+
+    loop {
+        match permissions.peek() {
+            Some(next)
+                if next.entries_index == entries_index && next.owners_index == owners_index =>
+            {
+                if let Some(next) = permissions.next() {
+                    let (next_entries_index, next_owners_index) =
+                        (next.entries_index, next.owners_index);
+                    let next =
+                        new_permissions(next.permissions(), next_entries_index, next_owners_index);
+                    steps.push(ADataStep::Permissions(next, permissions_index));
+                    permissions_index += 1;
+                }
+                continue;
+            }
+            _ => (),
+        }
+
+        match owners.peek() {
+            Some(next)
+                if next.entries_index == entries_index
+                    && next.permissions_index == permissions_index =>
+            {
+                if let Some(next) = owners.next() {
+                    steps.push(ADataStep::Owner(next, owners_index));
+                    owners_index += 1;
+                }
+                continue;
+            }
+            _ => (),
+        }
+
+        // Append the entries up to the next permissions or owner.
+        let next_index = permissions
+            .peek()
+            .map(|next| next.entries_index)
+            .into_iter()
+            .chain(owners.peek().map(|next| next.entries_index))
+            .min()
+            .unwrap_or(entries_count)
+            .min(entries_count);
+
+        if next_index > entries_index {
+            let next = entries
+                .by_ref()
+                .take((next_index - entries_index) as usize)
+                .collect();
+            steps.push(ADataStep::Entries(next, entries_index));
+            entries_index = next_index;
+        } else if permissions.peek().is_none() && owners.peek().is_none() {
+            return Ok(steps);
+        } else {
+            return Err(CoreError::Unexpected(
+                "Inconsistent history of append-only data in fixture".to_string(),
+            ));
+        }
+    }
+}
+
+fn fixture_mismatch<T: Debug>(address: T) -> CoreError {
+    CoreError::Unexpected(format!("Fixture data doesn't match {:?}", address))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::mock::vault::Vault;
+    use crate::client::mock::DataId;
+    use crate::config_handler::{Config, DevConfig};
+    use safe_nd::{
+        AData, ADataEntry, ADataOwner, ADataPubPermissionSet, ADataPubPermissions, ADataUser,
+        AppPermissions, Coins, Data, IData, MData, MDataAction, MDataPermissionSet, MDataSeqValue,
+        PubImmutableData, PubSeqAppendOnlyData, PublicKey, SeqMutableData, XorName,
+    };
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+    use threshold_crypto::SecretKey;
+
+    fn new_vault() -> Vault {
+        Vault::new(Config {
+            dev: Some(DevConfig {
+                mock_in_memory_storage: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn coins(amount: &str) -> Coins {
+        unwrap!(Coins::from_str(amount))
+    }
+
+    // Test that restoring a snapshot undoes the changes made since it was taken.
+    #[test]
+    fn snapshots() {
+        let mut vault = new_vault();
+        let owner = PublicKey::from(SecretKey::random().public_key());
+        let name = XorName::from(owner);
+
+        vault.mock_create_balance(owner, coins("10"));
+        unwrap!(vault.take_snapshot("initial"));
+
+        unwrap!(vault.mock_increment_balance(&name, coins("5")));
+        assert_eq!(unwrap!(vault.get_balance(&name)), coins("15"));
+
+        unwrap!(vault.restore_snapshot("initial"));
+        assert_eq!(unwrap!(vault.get_balance(&name)), coins("10"));
+
+        assert!(vault.restore_snapshot("unknown").is_err());
+    }
+
+    // Test that the state of the vault survives a round trip through a JSON fixture.
+    #[test]
+    fn json_fixtures() {
+        let mut vault = new_vault();
+        let owner = PublicKey::from(SecretKey::random().public_key());
+        let app = PublicKey::from(SecretKey::random().public_key());
+        let name = XorName::from(owner);
+
+        vault.mock_create_balance(owner, coins("10"));
+        vault.insert_account(name);
+        unwrap!(unwrap!(vault.get_account_mut(&name)).ins_auth_key(
+            app,
+            AppPermissions {
+                transfer_coins: true,
+            },
+            1
+        ));
+
+        let data = PubImmutableData::new(vec![1, 2, 3]);
+        let id = DataId::Immutable(*data.address());
+        vault.insert_data(id, Data::Immutable(IData::Pub(data)));
+
+        // Sequenced mutable data with a text and a binary entry, at version 2.
+        let mut entries = BTreeMap::new();
+        let _ = entries.insert(
+            b"text".to_vec(),
+            MDataSeqValue {
+                data: b"value".to_vec(),
+                version: 3,
+            },
+        );
+        let _ = entries.insert(
+            vec![0xff, 0x00],
+            MDataSeqValue {
+                data: vec![0xfe],
+                version: 0,
+            },
+        );
+        let mut permissions = BTreeMap::new();
+        let _ = permissions.insert(app, MDataPermissionSet::new().allow(MDataAction::Read));
+        let mut mdata = MData::Seq(SeqMutableData::new_with_data(
+            XorName(new_rand::random()),
+            100,
+            entries,
+            permissions,
+            owner,
+        ));
+        let set = MDataPermissionSet::new().allow(MDataAction::Insert);
+        unwrap!(mdata.set_user_permissions(owner, set, 1));
+        unwrap!(mdata.del_user_permissions(owner, 2));
+        let mdata_id = DataId::Mutable(*mdata.address());
+        let mdata = Data::Mutable(mdata);
+        vault.insert_data(mdata_id, mdata.clone());
+
+        // Append-only data with entries appended before and after its owner.
+        let mut adata = PubSeqAppendOnlyData::new(XorName(new_rand::random()), 100);
+        let mut permissions = BTreeMap::new();
+        let _ = permissions.insert(ADataUser::Anyone, ADataPubPermissionSet::new(true, false));
+        unwrap!(adata.append_permissions(
+            ADataPubPermissions {
+                permissions,
+                entries_index: 0,
+                owners_index: 0,
+            },
+            0
+        ));
+        unwrap!(adata.append(vec![ADataEntry::new(b"a".to_vec(), b"1".to_vec())], 0));
+        unwrap!(adata.append_owner(
+            ADataOwner {
+                public_key: owner,
+                entries_index: 1,
+                permissions_index: 1,
+            },
+            0
+        ));
+        unwrap!(adata.append(vec![ADataEntry::new(b"b".to_vec(), vec![0xff])], 1));
+        let adata_id = DataId::AppendOnly(*adata.address());
+        let adata = Data::AppendOnly(AData::PubSeq(adata));
+        vault.insert_data(adata_id, adata.clone());
+
+        let json = unwrap!(vault.export_json());
+        assert!(json.contains("\"Text\": \"value\""));
+        assert!(json.contains("\"Hex\": \"ff00\""));
+
+        let mut vault = new_vault();
+        unwrap!(vault.import_json(&json));
+
+        assert_eq!(unwrap!(vault.get_balance(&name)), coins("10"));
+        let account = unwrap!(vault.get_account(&name));
+        assert_eq!(account.version(), 1);
+        assert!(account.auth_keys().contains_key(&app));
+        assert!(vault.contains_data(&id));
+        assert_eq!(vault.get_data(&mdata_id), Some(mdata));
+        assert_eq!(vault.get_data(&adata_id), Some(adata));
+
+        // Exporting the imported state gives the same fixture.
+        assert_eq!(unwrap!(vault.export_json()), json);
+    }
+}
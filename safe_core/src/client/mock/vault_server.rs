@@ -0,0 +1,586 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::load_profile;
+use super::pricing::Charge;
+use super::vault::{self, Vault};
+use crate::config_handler::Config;
+use crate::errors::CoreError;
+use crate::event::NotificationTx;
+use futures::sync::mpsc;
+use futures::Stream;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use safe_nd::{Coins, Message, PublicId, PublicKey, RequestType, Result as SndResult, XorName};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// How often the server checks whether it's been asked to stop.
+const POLL_INTERVAL_MS: u64 = 50;
+// Length of the prefix holding the size of each frame.
+const FRAME_PREFIX_LEN: usize = 8;
+// Frames claiming to be larger than this are rejected rather than allocated.
+const MAX_FRAME_LEN: u64 = 128 * 1024 * 1024;
+
+/// Serves a mock vault to other processes over a Unix domain socket, so that they share its state
+/// without going through the vault file. Every frame is a serialised value prefixed with its
+/// length (u64, little endian): the clients send requests and receive their responses in order,
+/// except on the connections they subscribed to notifications on.
+pub struct MockVaultServer {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockVaultServer {
+    /// Start serving the mock vault configured by `config` on the socket at `path`. A stale
+    /// socket file left behind by a server which is no longer running is replaced.
+    pub fn start(path: &Path, config: Config) -> Result<Self, CoreError> {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(CoreError::from(format!(
+                    "A mock vault server is already listening on {}",
+                    path.display()
+                )));
+            }
+            fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+
+        let vault = Arc::new(Mutex::new(Vault::new(config)));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = run(listener, vault, stop.clone());
+
+        trace!("Mock vault server listening on {}", path.display());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Path of the socket the server listens on.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Block until the server stops, which only happens once it's been dropped by another thread
+    /// or the socket has failed.
+    pub fn wait(mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MockVaultServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Accept connections until asked to stop, serving each of them on its own thread.
+fn run(listener: UnixListener, vault: Arc<Mutex<Vault>>, stop: Arc<AtomicBool>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        while !stop.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let vault = vault.clone();
+                    let _ = thread::spawn(move || serve(stream, &vault));
+                }
+                Err(ref error) if error.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(POLL_INTERVAL_MS))
+                }
+                Err(error) => {
+                    warn!("Mock vault server: can't accept connections: {:?}", error);
+                    break;
+                }
+            }
+        }
+    })
+}
+
+// Request sent to a `MockVaultServer`, with the type of its response.
+#[derive(Deserialize, Serialize)]
+enum ServerRequest {
+    // Have the vault process a message: `SndResult<Message>`.
+    Process(PublicId, Message),
+    // Send the notifications concerning a coin balance on this connection, which then no longer
+    // takes requests: `()`, followed by the notifications.
+    Subscribe(XorName),
+    // Whether the vault allows unlimited mutations: `bool`.
+    UnlimitedMutations,
+    // `()`.
+    CreateBalance(PublicKey, Coins),
+    // `SndResult<()>`.
+    IncrementBalance(XorName, Coins),
+    // `Vec<Charge>`.
+    Charges(XorName),
+    // `Result<(), String>`.
+    TakeSnapshot(String),
+    // `Result<(), String>`.
+    RestoreSnapshot(String),
+    // `Result<String, String>`.
+    ExportJson,
+    // `Result<(), String>`.
+    ImportJson(String),
+}
+
+// Process the requests received on the stream until it's closed.
+fn serve(mut stream: UnixStream, vault: &Mutex<Vault>) {
+    if let Err(error) = stream.set_nonblocking(false) {
+        warn!("Mock vault server: {:?}", error);
+        return;
+    }
+
+    loop {
+        let request: ServerRequest = match read_frame(&mut stream) {
+            Ok(request) => request,
+            Err(ref error) if error.kind() == ErrorKind::UnexpectedEof => break,
+            Err(error) => {
+                trace!("Mock vault server: invalid request: {:?}", error);
+                break;
+            }
+        };
+
+        let result = match request {
+            ServerRequest::Process(requester, message) => {
+                let writing = match &message {
+                    Message::Request { request, .. } => {
+                        let req_type = request.get_type();
+                        req_type == RequestType::Mutation || req_type == RequestType::Transaction
+                    }
+                    _ => false,
+                };
+                let response = vault::lock(vault, writing).process_request(requester, &message);
+                write_frame(&mut stream, &response)
+            }
+            ServerRequest::Subscribe(coin_balance_id) => {
+                return forward_notifications(stream, vault, coin_balance_id)
+            }
+            ServerRequest::UnlimitedMutations => {
+                let unlimited = vault::lock(vault, false).unlimited_muts();
+                write_frame(&mut stream, &unlimited)
+            }
+            ServerRequest::CreateBalance(owner, amount) => {
+                vault::lock(vault, true).mock_create_balance(owner, amount);
+                write_frame(&mut stream, &())
+            }
+            ServerRequest::IncrementBalance(coin_balance_name, amount) => {
+                let result =
+                    vault::lock(vault, true).mock_increment_balance(&coin_balance_name, amount);
+                write_frame(&mut stream, &result)
+            }
+            ServerRequest::Charges(account) => {
+                let charges = vault::lock(vault, false).charges(&account);
+                write_frame(&mut stream, &charges)
+            }
+            ServerRequest::TakeSnapshot(name) => {
+                let result = vault::lock(vault, false).take_snapshot(&name);
+                write_frame(&mut stream, &result.map_err(|error| error.to_string()))
+            }
+            ServerRequest::RestoreSnapshot(name) => {
+                let result = vault::lock(vault, true).restore_snapshot(&name);
+                write_frame(&mut stream, &result.map_err(|error| error.to_string()))
+            }
+            ServerRequest::ExportJson => {
+                let result = vault::lock(vault, false).export_json();
+                write_frame(&mut stream, &result.map_err(|error| error.to_string()))
+            }
+            ServerRequest::ImportJson(json) => {
+                let result = vault::lock(vault, true).import_json(&json);
+                write_frame(&mut stream, &result.map_err(|error| error.to_string()))
+            }
+        };
+
+        if let Err(error) = result {
+            trace!("Mock vault server: can't send the response: {:?}", error);
+            break;
+        }
+    }
+}
+
+// Send the notifications concerning the coin balance on the stream until it's closed.
+fn forward_notifications(mut stream: UnixStream, vault: &Mutex<Vault>, coin_balance_id: XorName) {
+    let (notification_tx, notification_rx) = mpsc::unbounded();
+    vault::lock(vault, false).subscribe(coin_balance_id, notification_tx);
+
+    // Acknowledge the subscription, so that the client doesn't miss the notifications caused by
+    // the requests it sends next.
+    if write_frame(&mut stream, &()).is_err() {
+        return;
+    }
+
+    // The client doesn't send anything more on this connection, so reading from it only returns
+    // once it's closed. Stop waiting for notifications then, rather than once the next one fails
+    // to be sent.
+    let (closed_tx, closed_rx) = mpsc::unbounded();
+    match stream.try_clone() {
+        Ok(mut reader) => {
+            let _ = thread::spawn(move || {
+                let _ = reader.read(&mut [0; 1]);
+                let _ = closed_tx.unbounded_send(None);
+            });
+        }
+        Err(error) => {
+            warn!("Mock vault server: {:?}", error);
+            return;
+        }
+    }
+
+    for event in notification_rx.map(Some).select(closed_rx).wait() {
+        match event {
+            Ok(Some(notification)) => {
+                if write_frame(&mut stream, &notification).is_err() {
+                    break;
+                }
+            }
+            Ok(None) | Err(()) => break,
+        }
+    }
+
+    // Wake up the thread waiting for the connection to close.
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+/// Connection to a `MockVaultServer`, shared by the clones of a connection manager.
+pub struct VaultConnection {
+    path: PathBuf,
+    // Taken while a request is in progress, and only put back once its response has been read.
+    // A request which failed or timed out thus doesn't leave a late response on the stream for
+    // the next one to read, as the next request opens a new connection.
+    stream: Mutex<Option<UnixStream>>,
+}
+
+impl VaultConnection {
+    /// Connect to the server listening on the socket at `path`.
+    pub fn connect(path: &Path) -> Result<Self, CoreError> {
+        let stream = UnixStream::connect(path)?;
+        trace!("Connected to the mock vault server on {}", path.display());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            stream: Mutex::new(Some(stream)),
+        })
+    }
+
+    /// Have the server process the message and return its response, failing with
+    /// `CoreError::RequestTimeout` if it doesn't arrive within `timeout`.
+    pub fn process_request(
+        &self,
+        requester: &PublicId,
+        message: &Message,
+        timeout: Duration,
+    ) -> Result<Message, CoreError> {
+        let request = ServerRequest::Process(requester.clone(), message.clone());
+        let response: SndResult<Message> = self.call(&request, Some(timeout))?;
+        Ok(response?)
+    }
+
+    /// Forward the notifications concerning the coin balance to `notification_tx`, until either
+    /// the server stops or the receiver is dropped.
+    pub fn subscribe(
+        &self,
+        coin_balance_id: XorName,
+        notification_tx: NotificationTx,
+    ) -> Result<(), CoreError> {
+        let mut stream = UnixStream::connect(&self.path)?;
+        write_frame(&mut stream, &ServerRequest::Subscribe(coin_balance_id))?;
+        read_frame::<()>(&mut stream)?;
+
+        let _ = thread::spawn(move || {
+            while let Ok(notification) = read_frame(&mut stream) {
+                if notification_tx.unbounded_send(notification).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Whether the vault of the server allows unlimited mutations.
+    pub fn unlimited_muts(&self) -> Result<bool, CoreError> {
+        self.call(&ServerRequest::UnlimitedMutations, None)
+    }
+
+    /// Create a coin balance in the vault of the server.
+    pub fn create_balance(&self, owner: PublicKey, amount: Coins) -> Result<(), CoreError> {
+        self.call(&ServerRequest::CreateBalance(owner, amount), None)
+    }
+
+    /// Add coins to a coin balance in the vault of the server.
+    pub fn increment_balance(
+        &self,
+        coin_balance_name: &XorName,
+        amount: Coins,
+    ) -> Result<SndResult<()>, CoreError> {
+        self.call(
+            &ServerRequest::IncrementBalance(*coin_balance_name, amount),
+            None,
+        )
+    }
+
+    /// Returns the charges made by the vault of the server to the given account.
+    pub fn charges(&self, account: &XorName) -> Result<Vec<Charge>, CoreError> {
+        self.call(&ServerRequest::Charges(*account), None)
+    }
+
+    /// Save the state of the vault of the server under the given name.
+    pub fn take_snapshot(&self, name: &str) -> Result<(), CoreError> {
+        let result: Result<(), String> =
+            self.call(&ServerRequest::TakeSnapshot(name.to_string()), None)?;
+        result.map_err(CoreError::Unexpected)
+    }
+
+    /// Restore the state of the vault of the server saved under the given name.
+    pub fn restore_snapshot(&self, name: &str) -> Result<(), CoreError> {
+        let result: Result<(), String> =
+            self.call(&ServerRequest::RestoreSnapshot(name.to_string()), None)?;
+        result.map_err(CoreError::Unexpected)
+    }
+
+    /// Export the state of the vault of the server as a JSON fixture.
+    pub fn export_json(&self) -> Result<String, CoreError> {
+        let result: Result<String, String> = self.call(&ServerRequest::ExportJson, None)?;
+        result.map_err(CoreError::Unexpected)
+    }
+
+    /// Replace the state of the vault of the server with a JSON fixture.
+    pub fn import_json(&self, json: &str) -> Result<(), CoreError> {
+        let result: Result<(), String> =
+            self.call(&ServerRequest::ImportJson(json.to_string()), None)?;
+        result.map_err(CoreError::Unexpected)
+    }
+
+    // Send the request and wait for its response, for at most `timeout` if given.
+    fn call<T: DeserializeOwned>(
+        &self,
+        request: &ServerRequest,
+        timeout: Option<Duration>,
+    ) -> Result<T, CoreError> {
+        let mut stream = unwrap!(self.stream.lock());
+        let mut current = match stream.take() {
+            Some(current) => current,
+            None => UnixStream::connect(&self.path)?,
+        };
+
+        // A zero timeout isn't accepted, and would mean that the response has already timed out.
+        if timeout == Some(Duration::from_millis(0)) {
+            return Err(CoreError::RequestTimeout);
+        }
+        current.set_read_timeout(timeout)?;
+
+        write_frame(&mut current, request)?;
+        match read_frame(&mut current) {
+            Ok(response) => {
+                *stream = Some(current);
+                Ok(response)
+            }
+            Err(ref error)
+                if error.kind() == ErrorKind::WouldBlock || error.kind() == ErrorKind::TimedOut =>
+            {
+                Err(CoreError::RequestTimeout)
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+// Initializes the mock vault server socket with the following precedence:
+// 1. "SAFE_MOCK_VAULT_SOCKET" env var
+// 2. DevConfig `mock_vault_socket` option
+pub fn init_vault_socket(config: &Config) -> Option<PathBuf> {
+    load_profile(
+        config,
+        "SAFE_MOCK_VAULT_SOCKET",
+        |path| Ok(PathBuf::from(path)),
+        |dev| dev.mock_vault_socket.as_ref().map(PathBuf::from),
+    )
+}
+
+fn write_frame<T: Serialize>(stream: &mut UnixStream, value: &T) -> io::Result<()> {
+    let raw_data =
+        serialise(value).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+    let mut buffer = Vec::with_capacity(FRAME_PREFIX_LEN + raw_data.len());
+    buffer.extend_from_slice(&(raw_data.len() as u64).to_le_bytes());
+    buffer.extend_from_slice(&raw_data);
+    stream.write_all(&buffer)
+}
+
+fn read_frame<T: DeserializeOwned>(stream: &mut UnixStream) -> io::Result<T> {
+    let mut len = [0; FRAME_PREFIX_LEN];
+    stream.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Frame of {} bytes exceeds the limit", len),
+        ));
+    }
+    let mut raw_data = vec![0; len as usize];
+    stream.read_exact(&mut raw_data)?;
+    deserialise(&raw_data).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::ConnectionManager;
+    use crate::client::{req, SafeKey, REQUEST_TIMEOUT_SECS};
+    use crate::config_handler::{DevConfig, Pricing};
+    use crate::event::NetworkNotification;
+    use futures::sync::mpsc;
+    use futures::Future;
+    use safe_nd::{Coins, Error as SndError, Request, Response};
+    use std::str::FromStr;
+    use threshold_crypto::SecretKey as BlsSecretKey;
+    use tokio::runtime::current_thread::block_on_all;
+
+    fn timeout() -> Duration {
+        Duration::from_secs(REQUEST_TIMEOUT_SECS)
+    }
+
+    fn in_memory_config() -> Config {
+        Config {
+            dev: Some(DevConfig {
+                mock_in_memory_storage: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn get_balance(cm: &mut ConnectionManager, full_id: &SafeKey) -> SndResult<Coins> {
+        match unwrap!(req(cm, Request::GetBalance, full_id, timeout())) {
+            Response::GetBalance(res) => res,
+            res => panic!("Unexpected response {:?}", res),
+        }
+    }
+
+    // 1. Start a server and create a balance through a connection manager connected to it.
+    // 2. Verify that another connection manager connected to the server sees the balance, and
+    //    one which isn't connected doesn't.
+    #[test]
+    fn shared_vault() {
+        let dir = unwrap!(tempfile::tempdir());
+        let path = dir.path().join("vault.sock");
+        let config = in_memory_config();
+        let server = unwrap!(MockVaultServer::start(&path, config.clone()));
+        assert!(MockVaultServer::start(&path, config).is_err());
+
+        let (net_tx, _net_rx) = mpsc::unbounded();
+        let full_id = SafeKey::client_from_bls_key(BlsSecretKey::random());
+        let owner = *full_id.public_id().public_key();
+        let amount = unwrap!(Coins::from_str("10"));
+
+        let mut cm0 = unwrap!(ConnectionManager::new(Default::default(), &net_tx));
+        unwrap!(cm0.connect_to_vault_server(server.path()));
+        unwrap!(block_on_all(cm0.bootstrap(full_id.clone())));
+        let request = Request::CreateBalance {
+            new_balance_owner: owner,
+            amount,
+            transaction_id: new_rand::random(),
+        };
+        match unwrap!(req(&mut cm0, request, &full_id, timeout())) {
+            Response::Transaction(res) => {
+                let _ = unwrap!(res);
+            }
+            res => panic!("Unexpected response {:?}", res),
+        }
+
+        let mut cm1 = unwrap!(ConnectionManager::new(Default::default(), &net_tx));
+        unwrap!(cm1.connect_to_vault_server(server.path()));
+        unwrap!(block_on_all(cm1.bootstrap(full_id.clone())));
+        assert_eq!(unwrap!(get_balance(&mut cm1, &full_id)), amount);
+
+        let mut cm2 = unwrap!(ConnectionManager::new(Default::default(), &net_tx));
+        unwrap!(block_on_all(cm2.bootstrap(full_id.clone())));
+        match get_balance(&mut cm2, &full_id) {
+            Err(SndError::NoSuchBalance) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+
+        // The socket is removed once the server stops.
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    // 1. Subscribe to the notifications of a client through a server, and transfer coins to it.
+    // 2. Verify that the notification is forwarded by the server.
+    // 3. Verify that the pricing model of the server can't be replaced.
+    // 4. Verify that the server drops connections announcing frames which are too large.
+    #[test]
+    fn notifications() {
+        let dir = unwrap!(tempfile::tempdir());
+        let path = dir.path().join("vault.sock");
+        let server = unwrap!(MockVaultServer::start(&path, in_memory_config()));
+
+        let (net_tx, _net_rx) = mpsc::unbounded();
+        let sender = SafeKey::client_from_bls_key(BlsSecretKey::random());
+        let recipient = SafeKey::client_from_bls_key(BlsSecretKey::random());
+        let recipient_key = *recipient.public_id().public_key();
+        let amount = unwrap!(Coins::from_str("1"));
+
+        let mut cm = unwrap!(ConnectionManager::new(Default::default(), &net_tx));
+        unwrap!(cm.connect_to_vault_server(server.path()));
+        unwrap!(block_on_all(cm.bootstrap(sender.clone())));
+        unwrap!(cm.create_balance(
+            *sender.public_id().public_key(),
+            unwrap!(Coins::from_str("10"))
+        ));
+        unwrap!(cm.create_balance(recipient_key, unwrap!(Coins::from_str("0"))));
+        let notifications = unwrap!(cm.subscribe(&recipient.public_id()));
+
+        let request = Request::TransferCoins {
+            destination: recipient_key.into(),
+            amount,
+            transaction_id: 1,
+        };
+        match unwrap!(req(&mut cm, request, &sender, timeout())) {
+            Response::Transaction(res) => {
+                let _ = unwrap!(res);
+            }
+            res => panic!("Unexpected response {:?}", res),
+        }
+
+        let notification = notifications
+            .into_future()
+            .wait()
+            .ok()
+            .and_then(|(notification, _)| notification);
+        assert_eq!(
+            notification,
+            Some(NetworkNotification::TransferReceived {
+                transaction_id: 1,
+                amount,
+            })
+        );
+
+        assert!(cm.set_pricing_model(Box::new(Pricing::default())).is_err());
+
+        let mut stream = unwrap!(UnixStream::connect(&path));
+        unwrap!(stream.write_all(&(MAX_FRAME_LEN + 1).to_le_bytes()));
+        let mut buffer = [0; 1];
+        assert_eq!(unwrap!(stream.read(&mut buffer)), 0);
+    }
+}
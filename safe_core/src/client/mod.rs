@@ -16,29 +16,53 @@ pub mod mdata_info;
 /// Operations with recovery.
 pub mod recovery;
 
+mod data_cache;
+mod dry_run;
 mod id;
-#[cfg(feature = "mock-network")]
-mod mock;
+mod idata_disk_cache;
+// Only the mock vault is compiled without `mock-network`: it backs the local test vault used
+// with the real network stack.
+#[cfg(any(feature = "mock-network", test, feature = "testing"))]
+pub(crate) mod mock;
 
 pub use self::account::ClientKeys;
+pub use self::data_cache::DataCacheStats;
+use self::data_cache::{same_indices, CachedAData, CachedEntries, CachedMData, DataCache};
+pub use self::dry_run::DryRunTally;
 pub use self::id::SafeKey;
+use self::idata_disk_cache::IDataDiskCache;
 pub use self::mdata_info::MDataInfo;
 #[cfg(feature = "mock-network")]
+pub use self::mock::vault::inspect::{
+    ADataContents, AccountInfo, AuthKeyInfo, BalanceInfo, DataInfo, EntryInfo, LoginPacketInfo,
+    MDataContents, PermissionInfo, VaultContents, VaultInspector,
+};
+#[cfg(feature = "mock-network")]
 pub use self::mock::vault::mock_vault_path;
 #[cfg(feature = "mock-network")]
 pub use self::mock::ConnectionManager as MockConnectionManager;
 #[cfg(feature = "mock-network")]
 use self::mock::ConnectionManager;
-use crate::config_handler::Config;
+#[cfg(all(feature = "mock-network", unix))]
+pub use self::mock::MockVaultServer;
+#[cfg(feature = "mock-network")]
+pub use self::mock::{Charge, Exchange, Mismatch, Operation, PricingModel, Recording};
+use crate::config_handler::{get_config, Config, DataCacheConfig, IDataCacheConfig};
+#[cfg(any(
+    all(test, feature = "mock-network"),
+    all(feature = "testing", feature = "mock-network")
+))]
+use crate::config_handler::{FaultProfile, SectionEvent, SectionsProfile};
 #[cfg(not(feature = "mock-network"))]
 use crate::connection_manager::ConnectionManager;
 use crate::crypto::{shared_box, shared_secretbox, shared_sign};
 use crate::errors::CoreError;
-use crate::event::{NetworkEvent, NetworkTx};
+use crate::event::{NetworkTx, NotificationRx};
 use crate::event_loop::{CoreFuture, CoreMsgTx};
 use crate::ipc::BootstrapConfig;
+use crate::quorum::Quorum;
 use crate::utils::FutureExt;
-use futures::{future, sync::mpsc, Future};
+use futures::{future, stream, sync::mpsc, Future, Stream};
 use lazy_static::lazy_static;
 use lru_cache::LruCache;
 use rust_sodium::crypto::{box_, sign};
@@ -54,12 +78,16 @@ use safe_nd::{
 use std::cell::RefCell;
 use std::collections::{BTreeMap, BTreeSet};
 use std::rc::Rc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use threshold_crypto::SecretKey as BlsSecretKey;
 use tokio::runtime::current_thread::{block_on_all, Handle};
 
 /// Capacity of the immutable data cache.
 pub const IMMUT_DATA_CACHE_SIZE: usize = 300;
+/// Default request timeout in seconds.
+pub const REQUEST_TIMEOUT_SECS: u64 = 180;
+/// Maximum number of requests of a batch waiting for a response at any time.
+pub const MAX_BATCH_REQUESTS_IN_FLIGHT: usize = 32;
 
 // FIXME: move to conn manager
 // const CONNECTION_TIMEOUT_SECS: u64 = 40;
@@ -159,20 +187,160 @@ pub trait Client: Clone + 'static {
         inner.borrow_mut().timeout = duration;
     }
 
+    /// Send a signed request to the network and return the response. If `timeout` is given, it
+    /// is used instead of the one set with `set_timeout` for this request only.
+    fn send_request(
+        &self,
+        request: Request,
+        timeout: Option<Duration>,
+    ) -> Box<CoreFuture<Response>> {
+        send_with_timeout(self, request, true, timeout)
+    }
+
+    /// Send several signed requests to the network without waiting for the response to one before
+    /// sending the next, and return their results in the same order. A request failing doesn't
+    /// affect the others.
+    fn send_batch(
+        &self,
+        requests: Vec<Request>,
+    ) -> Box<CoreFuture<Vec<Result<Response, CoreError>>>> {
+        let responses: Vec<_> = requests
+            .into_iter()
+            .map(|request| send(self, request, true).then(Ok))
+            .collect();
+        future::join_all(responses).into_box()
+    }
+
+    /// Like `send_batch`, but keeps at most `max_in_flight` requests waiting for a response at
+    /// any time, which suits large batches.
+    fn send_batch_buffered(
+        &self,
+        requests: Vec<Request>,
+        max_in_flight: usize,
+    ) -> Box<CoreFuture<Vec<Result<Response, CoreError>>>> {
+        let client = self.clone();
+        stream::iter_ok(requests)
+            .map(move |request| send(&client, request, true).then(Ok))
+            .buffered(max_in_flight.max(1))
+            .collect()
+            .into_box()
+    }
+
+    /// Subscribe to the notifications the network sends to this client, such as incoming coin
+    /// transfers. Connects to the network first if the client isn't connected yet.
+    /// The returned stream ends once the connection to the network is dropped.
+    fn subscribe_notifications(&self) -> Box<CoreFuture<NotificationRx>> {
+        let full_id = self.full_id();
+        let pub_id = full_id.public_id();
+
+        let mut cm = self.inner().borrow().connection_manager.clone();
+        let mut cm2 = cm.clone();
+
+        cm.bootstrap(full_id)
+            .and_then(move |()| cm2.subscribe(&pub_id))
+            .into_box()
+    }
+
+    /// Returns the number of requests which have been sent but not responded to yet.
+    fn requests_in_flight(&self) -> usize {
+        self.inner()
+            .borrow()
+            .connection_manager
+            .requests_in_flight()
+    }
+
     /// Restart the client and reconnect to the network.
-    fn restart_network(&self) -> Result<(), CoreError> {
+    /// `NetworkEvent::Connected` is sent once the connection has been restored.
+    fn restart_network(&self) -> Box<CoreFuture<()>> {
         trace!("Restarting the network connection");
 
         let inner = self.inner();
         let mut inner = inner.borrow_mut();
 
-        inner.connection_manager.restart_network();
+        inner.connection_manager.restart_network()
+    }
+
+    /// Enable or disable automatic reconnection after `NetworkEvent::Disconnected`.
+    /// Disabled by default, in which case `restart_network` has to be called explicitly.
+    /// The reconnections are scheduled on the event loop, so this has to be called from it.
+    fn set_auto_reconnect(&self, enabled: bool) {
+        let inner = self.inner();
+        inner
+            .borrow_mut()
+            .connection_manager
+            .set_auto_reconnect(enabled);
+    }
+
+    /// Set the number of elders that have to agree on a response before it's accepted.
+    /// Defaults to `Quorum::Majority`.
+    fn set_quorum(&self, quorum: Quorum) {
+        let inner = self.inner();
+        inner.borrow_mut().connection_manager.set_quorum(quorum);
+    }
+
+    /// Returns the number of elders that have to agree on a response before it's accepted.
+    fn quorum(&self) -> Quorum {
+        self.inner().borrow().connection_manager.quorum()
+    }
+
+    /// Replace the options of the cache of mutable and append-only data, dropping the data cached
+    /// so far.
+    fn set_data_cache_config(&self, config: DataCacheConfig) {
+        let inner = self.inner();
+        let mut inner = inner.borrow_mut();
+        let stats = inner.data_cache.stats();
+        inner.data_cache = DataCache::new(config);
+        inner.data_cache.set_stats(stats);
+    }
+
+    /// Drop all the mutable and append-only data cached by this client.
+    fn clear_data_cache(&self) {
+        self.inner().borrow_mut().data_cache.clear();
+    }
 
-        inner.net_tx.unbounded_send(NetworkEvent::Connected)?;
+    /// Returns the hit and miss counts of the cache of mutable and append-only data.
+    fn data_cache_stats(&self) -> DataCacheStats {
+        self.inner().borrow().data_cache.stats()
+    }
 
+    /// Replace the options of the on-disk cache of immutable data for this client. The options
+    /// used by the clients created afterwards are set with
+    /// `config_handler::write_idata_cache_config`.
+    fn set_idata_cache_config(&self, config: &IDataCacheConfig) -> Result<(), CoreError> {
+        let cache = IDataDiskCache::open(config)?;
+        self.inner().borrow_mut().idata_disk_cache = cache;
         Ok(())
     }
 
+    /// Remove all the chunks from the on-disk cache of immutable data, which is shared with the
+    /// other clients of the same user.
+    fn clear_idata_cache(&self) -> Result<(), CoreError> {
+        match self.inner().borrow().idata_disk_cache {
+            Some(ref cache) => cache.clear(),
+            None => Ok(()),
+        }
+    }
+
+    /// Start the dry-run mode, in which the mutations and transactions are answered as if they
+    /// succeeded instead of being sent to the network, so that a whole workflow can be run to
+    /// find out what it would cost. The mutations are priced with the pricing of the mock-vault
+    /// from the config file, which defaults to the flat `COST_OF_PUT`. The immutable data the
+    /// workflow puts can be read back, but the other reads are still sent, so the mutable and
+    /// append-only data read as they were before the dry run.
+    fn start_dry_run(&self) {
+        let pricing = get_config()
+            .dev
+            .and_then(|dev| dev.mock_pricing)
+            .unwrap_or_default();
+        self.inner().borrow_mut().dry_run = Some(DryRunTally::new(pricing));
+    }
+
+    /// Stop the dry-run mode and return the requests tallied since it was started, or `None` if
+    /// it wasn't.
+    fn stop_dry_run(&self) -> Option<DryRunTally> {
+        self.inner().borrow_mut().dry_run.take()
+    }
+
     /// Put unsequenced mutable data to the network
     fn put_unseq_mutable_data(&self, data: UnseqMutableData) -> Box<CoreFuture<()>> {
         trace!("Put Unsequenced MData at {:?}", data.name());
@@ -299,6 +467,18 @@ pub trait Client: Clone + 'static {
             return future::ok(data.clone()).into_box();
         }
 
+        let requester = self.public_key();
+        let cached = inner
+            .borrow()
+            .idata_disk_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&address, &requester));
+        if let Some(data) = cached {
+            trace!("ImmutableData found in disk cache.");
+            let _ = inner.borrow_mut().cache.insert(address, data.clone());
+            return future::ok(data).into_box();
+        }
+
         let inner = Rc::downgrade(&self.inner());
         send(self, Request::GetIData(address), address.is_unpub())
             .and_then(|res| match res {
@@ -307,11 +487,18 @@ pub trait Client: Clone + 'static {
             })
             .map(move |data| {
                 if let Some(inner) = inner.upgrade() {
+                    let mut inner = inner.borrow_mut();
+                    // The data read back in dry-run mode isn't stored on the network.
+                    if inner.dry_run.is_some() {
+                        return data;
+                    }
+                    if let Some(cache) = inner.idata_disk_cache.as_ref() {
+                        if let Err(error) = cache.insert(&data) {
+                            warn!("Could not cache ImmutableData on disk: {:?}", error);
+                        }
+                    }
                     // Put to cache
-                    let _ = inner
-                        .borrow_mut()
-                        .cache
-                        .insert(*data.address(), data.clone());
+                    let _ = inner.cache.insert(*data.address(), data.clone());
                 }
                 data
             })
@@ -329,6 +516,14 @@ pub trait Client: Clone + 'static {
         {
             trace!("Deleted UnpubImmutableData from cache.");
         }
+        if let Some(cache) = inner.borrow().idata_disk_cache.as_ref() {
+            if let Err(error) = cache.remove(&IDataAddress::Unpub(name)) {
+                warn!(
+                    "Could not remove ImmutableData from the disk cache: {:?}",
+                    error
+                );
+            }
+        }
 
         let _ = Rc::downgrade(&self.inner());
         trace!("Delete Unpublished IData at {:?}", name);
@@ -369,24 +564,30 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<MDataSeqValue>> {
         trace!("Fetch MDataValue for {:?}", name);
 
-        send(
+        let address = MDataAddress::Seq { name, tag };
+        let key2 = key.clone();
+        let key3 = key.clone();
+        cached_mdata_entries(
             self,
-            Request::GetMDataValue {
-                address: MDataAddress::Seq { name, tag },
-                key,
+            address,
+            |entries, max_age| entries.value(&key, max_age),
+            move |client| {
+                send(client, Request::GetMDataValue { address, key: key2 }, true)
+                    .and_then(|res| match res {
+                        Response::GetMDataValue(res) => {
+                            res.map_err(CoreError::from).and_then(|value| match value {
+                                MDataValue::Seq(val) => Ok(val),
+                                MDataValue::Unseq(_) => Err(CoreError::ReceivedUnexpectedData),
+                            })
+                        }
+                        _ => Err(CoreError::ReceivedUnexpectedEvent),
+                    })
+                    .into_box()
+            },
+            move |entries, value| {
+                let _ = entries.values.insert(key3, (Instant::now(), value));
             },
-            true,
         )
-        .and_then(|res| match res {
-            Response::GetMDataValue(res) => {
-                res.map_err(CoreError::from).and_then(|value| match value {
-                    MDataValue::Seq(val) => Ok(val),
-                    MDataValue::Unseq(_) => Err(CoreError::ReceivedUnexpectedData),
-                })
-            }
-            _ => Err(CoreError::ReceivedUnexpectedEvent),
-        })
-        .into_box()
     }
 
     /// Fetch the value for a given key in a sequenced mutable data
@@ -446,10 +647,12 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<()>> {
         trace!("Mutate MData for {:?}", name);
 
-        send_mutation(
+        let address = MDataAddress::Seq { name, tag };
+        send_mdata_mutation(
             self,
+            address,
             Request::MutateMDataEntries {
-                address: MDataAddress::Seq { name, tag },
+                address,
                 actions: MDataEntryActions::Seq(actions),
             },
         )
@@ -473,25 +676,61 @@ pub trait Client: Clone + 'static {
         )
     }
 
+    /// Mutates the entries of several `MutableData` at once, pipelining the requests, and returns
+    /// the result of each mutation in the same order.
+    fn mutate_mdata_entries_batch(
+        &self,
+        mutations: Vec<(MDataAddress, MDataEntryActions)>,
+    ) -> Box<CoreFuture<Vec<Result<(), CoreError>>>> {
+        trace!("Mutate {} MData in a batch", mutations.len());
+
+        let addresses: Vec<_> = mutations.iter().map(|(address, _)| *address).collect();
+        let requests = mutations
+            .into_iter()
+            .map(|(address, actions)| Request::MutateMDataEntries { address, actions })
+            .collect();
+        let inner = Rc::downgrade(&self.inner());
+
+        self.send_batch_buffered(requests, MAX_BATCH_REQUESTS_IN_FLIGHT)
+            .map(move |results| {
+                if let Some(inner) = inner.upgrade() {
+                    let mut inner = inner.borrow_mut();
+                    for address in &addresses {
+                        inner.data_cache.invalidate_mdata(address);
+                    }
+                }
+                results
+                    .into_iter()
+                    .map(|result| result.and_then(mutation_result))
+                    .collect()
+            })
+            .into_box()
+    }
+
     /// Get a shell (bare bones) version of `MutableData` from the network.
     fn get_seq_mdata_shell(&self, name: XorName, tag: u64) -> Box<CoreFuture<SeqMutableData>> {
         trace!("GetMDataShell for {:?}", name);
 
-        send(
+        let address = MDataAddress::Seq { name, tag };
+        cached_mdata(
             self,
-            Request::GetMDataShell(MDataAddress::Seq { name, tag }),
-            true,
+            address,
+            |cached| cached.shell.clone(),
+            move |client| {
+                send(client, Request::GetMDataShell(address), true)
+                    .and_then(|res| match res {
+                        Response::GetMDataShell(res) => {
+                            res.map_err(CoreError::from).and_then(|mdata| match mdata {
+                                MData::Seq(data) => Ok(data),
+                                _ => Err(CoreError::ReceivedUnexpectedData),
+                            })
+                        }
+                        _ => Err(CoreError::ReceivedUnexpectedEvent),
+                    })
+                    .into_box()
+            },
+            |cached, shell| cached.shell = Some(shell),
         )
-        .and_then(|res| match res {
-            Response::GetMDataShell(res) => {
-                res.map_err(CoreError::from).and_then(|mdata| match mdata {
-                    MData::Seq(data) => Ok(data),
-                    _ => Err(CoreError::ReceivedUnexpectedData),
-                })
-            }
-            _ => Err(CoreError::ReceivedUnexpectedEvent),
-        })
-        .into_box()
     }
 
     /// Get a shell (bare bones) version of `MutableData` from the network.
@@ -557,22 +796,29 @@ pub trait Client: Clone + 'static {
     fn list_seq_mdata_entries(&self, name: XorName, tag: u64) -> Box<CoreFuture<MDataSeqEntries>> {
         trace!("ListSeqMDataEntries for {:?}", name);
 
-        send(
+        let address = MDataAddress::Seq { name, tag };
+        cached_mdata_entries(
             self,
-            Request::ListMDataEntries(MDataAddress::Seq { name, tag }),
-            true,
-        )
-        .and_then(|res| match res {
-            Response::ListMDataEntries(res) => {
-                res.map_err(CoreError::from)
-                    .and_then(|entries| match entries {
-                        MDataEntries::Seq(data) => Ok(data),
-                        MDataEntries::Unseq(_) => Err(CoreError::ReceivedUnexpectedData),
+            address,
+            |entries, max_age| entries.all(max_age),
+            move |client| {
+                send(client, Request::ListMDataEntries(address), true)
+                    .and_then(|res| match res {
+                        Response::ListMDataEntries(res) => {
+                            res.map_err(CoreError::from)
+                                .and_then(|entries| match entries {
+                                    MDataEntries::Seq(data) => Ok(data),
+                                    MDataEntries::Unseq(_) => {
+                                        Err(CoreError::ReceivedUnexpectedData)
+                                    }
+                                })
+                        }
+                        _ => Err(CoreError::ReceivedUnexpectedEvent),
                     })
-            }
-            _ => Err(CoreError::ReceivedUnexpectedEvent),
-        })
-        .into_box()
+                    .into_box()
+            },
+            |entries, all| entries.all = Some((Instant::now(), all)),
+        )
     }
 
     /// Return a list of keys in `MutableData` stored on the network.
@@ -704,16 +950,28 @@ pub trait Client: Clone + 'static {
             address.name()
         );
 
-        send(
+        let cached_key = key.clone();
+        let fetched_key = key.clone();
+        cached_adata(
             self,
-            Request::GetADataValue { address, key },
-            address.is_unpub(),
+            address,
+            move |cached| cached.values.get(&cached_key).cloned(),
+            move |client| {
+                send(
+                    client,
+                    Request::GetADataValue { address, key },
+                    address.is_unpub(),
+                )
+                .and_then(|res| match res {
+                    Response::GetADataValue(res) => res.map_err(CoreError::from),
+                    _ => Err(CoreError::ReceivedUnexpectedEvent),
+                })
+                .into_box()
+            },
+            move |cached, value| {
+                let _ = cached.values.insert(fetched_key, value);
+            },
         )
-        .and_then(|res| match res {
-            Response::GetADataValue(res) => res.map_err(CoreError::from),
-            _ => Err(CoreError::ReceivedUnexpectedEvent),
-        })
-        .into_box()
     }
 
     /// Get a Set of Entries for the requested range from an AData.
@@ -761,16 +1019,24 @@ pub trait Client: Clone + 'static {
             address.name()
         );
 
-        send(
+        cached_adata(
             self,
-            Request::GetADataLastEntry(address),
-            address.is_unpub(),
+            address,
+            |cached| cached.last_entry.clone(),
+            move |client| {
+                send(
+                    client,
+                    Request::GetADataLastEntry(address),
+                    address.is_unpub(),
+                )
+                .and_then(|res| match res {
+                    Response::GetADataLastEntry(res) => res.map_err(CoreError::from),
+                    _ => Err(CoreError::ReceivedUnexpectedEvent),
+                })
+                .into_box()
+            },
+            |cached, entry| cached.last_entry = Some(entry),
         )
-        .and_then(|res| match res {
-            Response::GetADataLastEntry(res) => res.map_err(CoreError::from),
-            _ => Err(CoreError::ReceivedUnexpectedEvent),
-        })
-        .into_box()
     }
 
     /// Get permissions at the provided index.
@@ -905,8 +1171,9 @@ pub trait Client: Clone + 'static {
             address.name()
         );
 
-        send_mutation(
+        send_adata_mutation(
             self,
+            address,
             Request::AddUnpubADataPermissions {
                 address,
                 permissions,
@@ -924,8 +1191,9 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<()>> {
         trace!("Add Permissions to AppendOnly Data {:?}", address.name());
 
-        send_mutation(
+        send_adata_mutation(
             self,
+            address,
             Request::AddPubADataPermissions {
                 address,
                 permissions,
@@ -943,8 +1211,9 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<()>> {
         trace!("Set Owners to AppendOnly Data {:?}", address.name());
 
-        send_mutation(
+        send_adata_mutation(
             self,
+            address,
             Request::SetADataOwner {
                 address,
                 owner,
@@ -978,12 +1247,12 @@ pub trait Client: Clone + 'static {
 
     /// Append to Published Seq AppendOnly Data
     fn append_seq_adata(&self, append: ADataAppendOperation, index: u64) -> Box<CoreFuture<()>> {
-        send_mutation(self, Request::AppendSeq { append, index })
+        send_adata_mutation(self, append.address, Request::AppendSeq { append, index })
     }
 
     /// Append to Unpublished Unseq AppendOnly Data
     fn append_unseq_adata(&self, append: ADataAppendOperation) -> Box<CoreFuture<()>> {
-        send_mutation(self, Request::AppendUnseq(append))
+        send_adata_mutation(self, append.address, Request::AppendUnseq(append))
     }
 
     /// Return a list of permissions in `MutableData` stored on the network.
@@ -1011,8 +1280,9 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<()>> {
         trace!("SetMDataUserPermissions for {:?}", address);
 
-        send_mutation(
+        send_mdata_mutation(
             self,
+            address,
             Request::SetMDataUserPermissions {
                 address,
                 user,
@@ -1031,8 +1301,9 @@ pub trait Client: Clone + 'static {
     ) -> Box<CoreFuture<()>> {
         trace!("DelMDataUserPermissions for {:?}", address);
 
-        send_mutation(
+        send_mdata_mutation(
             self,
+            address,
             Request::DelMDataUserPermissions {
                 address,
                 user,
@@ -1089,6 +1360,79 @@ pub trait Client: Clone + 'static {
             .set_simulate_timeout(enabled);
     }
 
+    #[cfg(any(
+        all(test, feature = "mock-network"),
+        all(feature = "testing", feature = "mock-network")
+    ))]
+    #[doc(hidden)]
+    fn set_fault_profile(&self, profile: Option<FaultProfile>) {
+        let inner = self.inner();
+        inner
+            .borrow_mut()
+            .connection_manager
+            .set_fault_profile(profile);
+    }
+
+    #[cfg(any(
+        all(test, feature = "mock-network"),
+        all(feature = "testing", feature = "mock-network")
+    ))]
+    #[doc(hidden)]
+    fn set_sections_profile(&self, profile: Option<SectionsProfile>) {
+        self.inner()
+            .borrow_mut()
+            .connection_manager
+            .set_sections_profile(profile);
+    }
+
+    #[cfg(any(
+        all(test, feature = "mock-network"),
+        all(feature = "testing", feature = "mock-network")
+    ))]
+    #[doc(hidden)]
+    fn simulate_section_event(&self, event: SectionEvent) -> Result<(), CoreError> {
+        self.inner()
+            .borrow()
+            .connection_manager
+            .simulate_section_event(event)
+    }
+
+    /// Start recording the requests sent by this client, together with the responses they receive.
+    #[cfg(feature = "mock-network")]
+    fn start_recording(&self) {
+        self.inner()
+            .borrow_mut()
+            .connection_manager
+            .start_recording();
+    }
+
+    /// Stop recording the requests sent by this client and return the recording.
+    #[cfg(feature = "mock-network")]
+    fn stop_recording(&self) -> Option<Recording> {
+        self.inner()
+            .borrow_mut()
+            .connection_manager
+            .stop_recording()
+    }
+
+    /// Replace the model deciding how much the mock vault charges for the mutations.
+    #[cfg(feature = "mock-network")]
+    fn set_pricing_model(&self, pricing: Box<dyn PricingModel>) -> Result<(), CoreError> {
+        self.inner()
+            .borrow()
+            .connection_manager
+            .set_pricing_model(pricing)
+    }
+
+    /// Returns the charges made by the mock vault to the account of this client, oldest first.
+    #[cfg(feature = "mock-network")]
+    fn charges(&self) -> Result<Vec<Charge>, CoreError> {
+        self.inner()
+            .borrow()
+            .connection_manager
+            .charges(&XorName::from(self.owner_key()))
+    }
+
     /// Set the coin balance to a specific value for testing
     #[cfg(any(test, feature = "testing"))]
     fn test_set_balance(
@@ -1130,7 +1474,7 @@ where
     let full_id = SafeKey::client_from_bls_key(identity.clone());
     let (net_tx, _net_rx) = mpsc::unbounded();
 
-    let mut cm = ConnectionManager::new(Config::new().quic_p2p, &net_tx.clone())?;
+    let mut cm = ConnectionManager::new(Config::new(), &net_tx.clone())?;
     block_on_all(cm.bootstrap(full_id.clone()).map_err(CoreError::from))?;
 
     let res = func(&mut cm, &full_id);
@@ -1159,6 +1503,7 @@ pub fn test_create_balance(owner: &BlsSecretKey, amount: Coins) -> Result<(), Co
                 transaction_id: new_rand::random(),
             },
             &full_id,
+            Duration::from_secs(REQUEST_TIMEOUT_SECS),
         )?;
 
         match response {
@@ -1173,7 +1518,12 @@ pub fn wallet_get_balance(wallet_sk: &BlsSecretKey) -> Result<Coins, CoreError>
     trace!("Get balance for {:?}", wallet_sk);
 
     temp_client(wallet_sk, move |mut cm, full_id| {
-        match req(&mut cm, Request::GetBalance, &full_id)? {
+        match req(
+            &mut cm,
+            Request::GetBalance,
+            &full_id,
+            Duration::from_secs(REQUEST_TIMEOUT_SECS),
+        )? {
             Response::GetBalance(res) => res.map_err(CoreError::from),
             _ => Err(CoreError::from("Unexpected response")),
         }
@@ -1204,6 +1554,7 @@ pub fn wallet_create_balance(
                 transaction_id,
             },
             &full_id,
+            Duration::from_secs(REQUEST_TIMEOUT_SECS),
         )?;
         match response {
             Response::Transaction(res) => res.map_err(CoreError::from),
@@ -1232,6 +1583,7 @@ pub fn wallet_transfer_coins(
                 transaction_id,
             },
             &full_id,
+            Duration::from_secs(REQUEST_TIMEOUT_SECS),
         )?;
         match response {
             Response::Transaction(res) => res.map_err(CoreError::from),
@@ -1288,14 +1640,14 @@ pub trait AuthActions: Client + Clone + 'static {
     fn delete_mdata(&self, address: MDataAddress) -> Box<CoreFuture<()>> {
         trace!("Delete entire Mutable Data at {:?}", address);
 
-        send_mutation(self, Request::DeleteMData(address))
+        send_mdata_mutation(self, address, Request::DeleteMData(address))
     }
 
     /// Delete AData from network.
     fn delete_adata(&self, address: ADataAddress) -> Box<CoreFuture<()>> {
         trace!("Delete entire Unpublished AppendOnly Data at {:?}", address);
 
-        send_mutation(self, Request::DeleteAData(address))
+        send_adata_mutation(self, address, Request::DeleteAData(address))
     }
 }
 
@@ -1322,6 +1674,9 @@ pub struct ClientInner<C: Client, T> {
     connection_manager: ConnectionManager,
     el_handle: Handle,
     cache: LruCache<IDataAddress, IData>,
+    data_cache: DataCache,
+    idata_disk_cache: Option<IDataDiskCache>,
+    dry_run: Option<DryRunTally>,
     timeout: Duration,
     core_tx: CoreMsgTx<C, T>,
     net_tx: NetworkTx,
@@ -1338,10 +1693,14 @@ impl<C: Client, T> ClientInner<C, T> {
         core_tx: CoreMsgTx<C, T>,
         net_tx: NetworkTx,
     ) -> ClientInner<C, T> {
+        let config = get_config();
         ClientInner {
             el_handle,
             connection_manager,
             cache,
+            data_cache: DataCache::new(config.data_cache),
+            idata_disk_cache: open_idata_disk_cache(&config.idata_cache),
+            dry_run: None,
             timeout,
             core_tx,
             net_tx,
@@ -1361,6 +1720,10 @@ fn send_as(
     request: Request,
     secret_key: Option<&BlsSecretKey>,
 ) -> Box<CoreFuture<Response>> {
+    if let Some(response) = dry_run(client, &request) {
+        return ok!(response);
+    }
+
     let (message, identity) = match secret_key {
         Some(key) => (
             sign_request_with_key(request, key),
@@ -1372,89 +1735,393 @@ fn send_as(
     let pub_id = identity.public_id();
 
     let inner = client.inner();
+    let mut inner = inner.borrow_mut();
+    let timeout = inner.timeout;
 
-    let cm = &mut inner.borrow_mut().connection_manager;
+    let cm = &mut inner.connection_manager;
     let mut cm2 = cm.clone();
 
     Box::new(
         cm.bootstrap(identity.clone())
-            .and_then(move |_| cm2.send(&pub_id, &message)),
+            .and_then(move |_| cm2.send(&pub_id, &message, timeout)),
     )
 }
 
 // `sign` should be false for GETs on published data, true otherwise.
 fn send(client: &impl Client, request: Request, sign: bool) -> Box<CoreFuture<Response>> {
+    send_with_timeout(client, request, sign, None)
+}
+
+// Like `send`, but overrides the client's request timeout if `timeout` is given.
+fn send_with_timeout(
+    client: &impl Client,
+    request: Request,
+    sign: bool,
+    timeout: Option<Duration>,
+) -> Box<CoreFuture<Response>> {
+    if let Some(response) = dry_run(client, &request) {
+        return ok!(response);
+    }
+
     let request = client.compose_message(request, sign);
     let inner = client.inner();
-    let cm = &mut inner.borrow_mut().connection_manager;
-    cm.send(&client.public_id(), &request)
+    let mut inner = inner.borrow_mut();
+    let timeout = timeout.unwrap_or(inner.timeout);
+    inner
+        .connection_manager
+        .send(&client.public_id(), &request, timeout)
+}
+
+// Tallies the request if the client is in dry-run mode, and returns the response to give in place
+// of the network if the request mustn't be sent.
+fn dry_run(client: &impl Client, request: &Request) -> Option<Response> {
+    let inner = client.inner();
+    let mut inner = inner.borrow_mut();
+    inner
+        .dry_run
+        .as_mut()
+        .and_then(|tally| tally.record(request))
 }
 
 /// Sends a mutation request to a new routing.
 fn send_mutation(client: &impl Client, req: Request) -> Box<CoreFuture<()>> {
-    Box::new(send(client, req, true).and_then(move |res| {
-        trace!("mutation res: {:?}", res);
-        match res {
-            Response::Mutation(res) => res.map_err(CoreError::from),
-            _ => Err(CoreError::ReceivedUnexpectedEvent),
-        }
-    }))
+    Box::new(send(client, req, true).and_then(mutation_result))
 }
 
-/// Send a request and wait for a response.
-/// This function is blocking.
-pub fn req(
-    cm: &mut ConnectionManager,
-    request: Request,
-    full_id_new: &SafeKey,
-) -> Result<Response, CoreError> {
-    let message_id = MessageId::new();
-    let signature = full_id_new.sign(&unwrap!(bincode::serialize(&(&request, message_id))));
-
-    block_on_all(cm.send(
-        &full_id_new.public_id(),
-        &Message::Request {
-            request,
-            message_id,
-            signature: Some(signature),
-        },
-    ))
+/// Opens the on-disk cache of immutable data, if it's enabled. Failing to open it only disables it.
+fn open_idata_disk_cache(config: &IDataCacheConfig) -> Option<IDataDiskCache> {
+    IDataDiskCache::open(config).unwrap_or_else(|error| {
+        warn!("Could not open the ImmutableData disk cache: {:?}", error);
+        None
+    })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::utils::generate_random_vector;
-    use crate::utils::test_utils::random_client;
-    use safe_nd::{
-        ADataAction, ADataEntry, ADataKind, ADataOwner, ADataUnpubPermissionSet,
-        ADataUnpubPermissions, AppendOnlyData, Coins, Error as SndError, MDataAction, MDataKind,
-        PubImmutableData, PubSeqAppendOnlyData, SeqAppendOnly, UnpubImmutableData,
-        UnpubSeqAppendOnlyData, UnpubUnseqAppendOnlyData, UnseqAppendOnly, XorName,
-    };
-    use std::str::FromStr;
-    use BlsSecretKey;
+// Sends a mutation of the given mutable data, dropping the data from the cache once it's done.
+fn send_mdata_mutation(
+    client: &impl Client,
+    address: MDataAddress,
+    req: Request,
+) -> Box<CoreFuture<()>> {
+    let inner = Rc::downgrade(&client.inner());
+    send_mutation(client, req)
+        .then(move |result| {
+            if let Some(inner) = inner.upgrade() {
+                inner.borrow_mut().data_cache.invalidate_mdata(&address);
+            }
+            result
+        })
+        .into_box()
+}
 
-    // Test putting and getting pub idata.
-    #[test]
-    fn pub_idata_test() {
-        random_client(move |client| {
-            let client2 = client.clone();
-            let client3 = client.clone();
-            let client4 = client.clone();
+// Sends a mutation of the given append-only data, dropping the data from the cache once it's
+// done.
+fn send_adata_mutation(
+    client: &impl Client,
+    address: ADataAddress,
+    req: Request,
+) -> Box<CoreFuture<()>> {
+    let inner = Rc::downgrade(&client.inner());
+    send_mutation(client, req)
+        .then(move |result| {
+            if let Some(inner) = inner.upgrade() {
+                inner.borrow_mut().data_cache.invalidate_adata(&address);
+            }
+            result
+        })
+        .into_box()
+}
 
-            let value = unwrap!(generate_random_vector::<u8>(10));
-            let data = PubImmutableData::new(value.clone());
-            let address = *data.address();
+// Reads the part of a sequenced mutable data selected by `get` through the data cache. The cached
+// part is used as it is if it was cached recently, otherwise only if the data is still at the
+// version it was cached at. Failing that, it's fetched again.
+fn cached_mdata<C, T, G, F, S>(
+    client: &C,
+    address: MDataAddress,
+    get: G,
+    fetch: F,
+    set: S,
+) -> Box<CoreFuture<T>>
+where
+    C: Client,
+    T: Clone + 'static,
+    G: FnOnce(&CachedMData) -> Option<T>,
+    F: FnOnce(&C) -> Box<CoreFuture<T>> + 'static,
+    S: FnOnce(&mut CachedMData, T) + 'static,
+{
+    let inner = client.inner();
+    if !inner.borrow().data_cache.is_enabled() {
+        return fetch(client);
+    }
 
-            let test_data = UnpubImmutableData::new(
-                value.clone(),
-                PublicKey::Bls(BlsSecretKey::random().public_key()),
-            );
+    let cached = inner.borrow_mut().data_cache.mdata(&address, get);
+    match cached {
+        Some(ref cached) if cached.fresh => {
+            trace!("MData {:?} found in cache.", address);
+            inner.borrow_mut().data_cache.record_hit();
+            ok!(cached.value.clone())
+        }
+        Some(cached) => {
+            let client2 = client.clone();
             client
-                // Get inexistent idata
-                .get_idata(address)
-                .then(|res| -> Result<(), CoreError> {
+                .get_mdata_version(address)
+                .and_then(move |version| {
+                    if version == cached.version {
+                        trace!("MData {:?} found in cache.", address);
+                        let inner = client2.inner();
+                        let mut inner = inner.borrow_mut();
+                        inner.data_cache.checked_mdata(&address, version);
+                        inner.data_cache.record_hit();
+                        ok!(cached.value)
+                    } else {
+                        fetch_mdata(&client2, address, version, fetch, set)
+                    }
+                })
+                .into_box()
+        }
+        None => {
+            let client2 = client.clone();
+            client
+                .get_mdata_version(address)
+                .and_then(move |version| fetch_mdata(&client2, address, version, fetch, set))
+                .into_box()
+        }
+    }
+}
+
+// Fetches a part of a sequenced mutable data and caches it at the version read before fetching
+// it. If the data is mutated in between, the newer part gets cached at the older version and is
+// simply fetched again once the version is checked.
+fn fetch_mdata<C, T, F, S>(
+    client: &C,
+    address: MDataAddress,
+    version: u64,
+    fetch: F,
+    set: S,
+) -> Box<CoreFuture<T>>
+where
+    C: Client,
+    T: Clone + 'static,
+    F: FnOnce(&C) -> Box<CoreFuture<T>>,
+    S: FnOnce(&mut CachedMData, T) + 'static,
+{
+    client.inner().borrow_mut().data_cache.record_miss();
+
+    let inner = Rc::downgrade(&client.inner());
+    fetch(client)
+        .map(move |value| {
+            if let Some(inner) = inner.upgrade() {
+                inner
+                    .borrow_mut()
+                    .data_cache
+                    .update_mdata(address, version, |cached| set(cached, value.clone()));
+            }
+            value
+        })
+        .into_box()
+}
+
+// Reads the part of the entries of a sequenced mutable data selected by `get` through the data
+// cache. Mutating the entries doesn't change the version of the data, so the cached part is only
+// used if it was fetched within the maximum age. Otherwise it's fetched again.
+fn cached_mdata_entries<C, T, G, F, S>(
+    client: &C,
+    address: MDataAddress,
+    get: G,
+    fetch: F,
+    set: S,
+) -> Box<CoreFuture<T>>
+where
+    C: Client,
+    T: Clone + 'static,
+    G: FnOnce(&CachedEntries, Duration) -> Option<T>,
+    F: FnOnce(&C) -> Box<CoreFuture<T>>,
+    S: FnOnce(&mut CachedEntries, T) + 'static,
+{
+    let inner = client.inner();
+    if !inner.borrow().data_cache.is_enabled() {
+        return fetch(client);
+    }
+
+    let cached = inner.borrow_mut().data_cache.mdata_entries(&address, get);
+    if let Some(value) = cached {
+        trace!("Entries of MData {:?} found in cache.", address);
+        inner.borrow_mut().data_cache.record_hit();
+        return ok!(value);
+    }
+
+    let generation = {
+        let mut inner = inner.borrow_mut();
+        inner.data_cache.record_miss();
+        inner.data_cache.mdata_generation()
+    };
+    let inner = Rc::downgrade(&inner);
+    fetch(client)
+        .map(move |value| {
+            if let Some(inner) = inner.upgrade() {
+                inner.borrow_mut().data_cache.update_mdata_entries(
+                    address,
+                    generation,
+                    |entries| set(entries, value.clone()),
+                );
+            }
+            value
+        })
+        .into_box()
+}
+
+// Reads the part of an append-only data selected by `get` through the data cache. The cached part
+// is used as it is if it was cached recently, otherwise only if the indices of the data haven't
+// changed since it was cached. Failing that, it's fetched again.
+fn cached_adata<C, T, G, F, S>(
+    client: &C,
+    address: ADataAddress,
+    get: G,
+    fetch: F,
+    set: S,
+) -> Box<CoreFuture<T>>
+where
+    C: Client,
+    T: Clone + 'static,
+    G: FnOnce(&CachedAData) -> Option<T>,
+    F: FnOnce(&C) -> Box<CoreFuture<T>> + 'static,
+    S: FnOnce(&mut CachedAData, T) + 'static,
+{
+    let inner = client.inner();
+    if !inner.borrow().data_cache.is_enabled() {
+        return fetch(client);
+    }
+
+    let cached = inner.borrow_mut().data_cache.adata(&address, get);
+    match cached {
+        Some(ref cached) if cached.fresh => {
+            trace!("AData {:?} found in cache.", address);
+            inner.borrow_mut().data_cache.record_hit();
+            ok!(cached.value.clone())
+        }
+        Some(cached) => {
+            let client2 = client.clone();
+            client
+                .get_adata_indices(address)
+                .and_then(move |indices| {
+                    if same_indices(cached.version, &indices) {
+                        trace!("AData {:?} found in cache.", address);
+                        let inner = client2.inner();
+                        let mut inner = inner.borrow_mut();
+                        inner.data_cache.checked_adata(&address, &indices);
+                        inner.data_cache.record_hit();
+                        ok!(cached.value)
+                    } else {
+                        fetch_adata(&client2, address, indices, fetch, set)
+                    }
+                })
+                .into_box()
+        }
+        None => {
+            let client2 = client.clone();
+            client
+                .get_adata_indices(address)
+                .and_then(move |indices| fetch_adata(&client2, address, indices, fetch, set))
+                .into_box()
+        }
+    }
+}
+
+// Fetches a part of an append-only data and caches it at the indices read before fetching it.
+fn fetch_adata<C, T, F, S>(
+    client: &C,
+    address: ADataAddress,
+    indices: ADataIndices,
+    fetch: F,
+    set: S,
+) -> Box<CoreFuture<T>>
+where
+    C: Client,
+    T: Clone + 'static,
+    F: FnOnce(&C) -> Box<CoreFuture<T>>,
+    S: FnOnce(&mut CachedAData, T) + 'static,
+{
+    client.inner().borrow_mut().data_cache.record_miss();
+
+    let inner = Rc::downgrade(&client.inner());
+    fetch(client)
+        .map(move |value| {
+            if let Some(inner) = inner.upgrade() {
+                inner
+                    .borrow_mut()
+                    .data_cache
+                    .update_adata(address, &indices, |cached| set(cached, value.clone()));
+            }
+            value
+        })
+        .into_box()
+}
+
+// Extracts the result of a mutation from its response.
+fn mutation_result(res: Response) -> Result<(), CoreError> {
+    trace!("mutation res: {:?}", res);
+    match res {
+        Response::Mutation(res) => res.map_err(CoreError::from),
+        _ => Err(CoreError::ReceivedUnexpectedEvent),
+    }
+}
+
+/// Send a request and wait for a response, failing with `CoreError::RequestTimeout` if none is
+/// received within `timeout`.
+/// This function is blocking.
+pub fn req(
+    cm: &mut ConnectionManager,
+    request: Request,
+    full_id_new: &SafeKey,
+    timeout: Duration,
+) -> Result<Response, CoreError> {
+    let message_id = MessageId::new();
+    let signature = full_id_new.sign(&unwrap!(bincode::serialize(&(&request, message_id))));
+
+    block_on_all(cm.send(
+        &full_id_new.public_id(),
+        &Message::Request {
+            request,
+            message_id,
+            signature: Some(signature),
+        },
+        timeout,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::generate_random_vector;
+    use crate::utils::test_utils::random_client;
+    use safe_nd::{
+        ADataAction, ADataEntry, ADataKind, ADataOwner, ADataUnpubPermissionSet,
+        ADataUnpubPermissions, AppendOnlyData, Coins, Error as SndError, MDataAction, MDataKind,
+        PubImmutableData, PubSeqAppendOnlyData, SeqAppendOnly, UnpubImmutableData,
+        UnpubSeqAppendOnlyData, UnpubUnseqAppendOnlyData, UnseqAppendOnly, XorName,
+    };
+    use std::str::FromStr;
+    use BlsSecretKey;
+
+    // Test putting and getting pub idata.
+    #[test]
+    fn pub_idata_test() {
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+
+            let value = unwrap!(generate_random_vector::<u8>(10));
+            let data = PubImmutableData::new(value.clone());
+            let address = *data.address();
+
+            let test_data = UnpubImmutableData::new(
+                value.clone(),
+                PublicKey::Bls(BlsSecretKey::random().public_key()),
+            );
+            client
+                // Get inexistent idata
+                .get_idata(address)
+                .then(|res| -> Result<(), CoreError> {
                     match res {
                         Ok(data) => panic!("Pub idata should not exist yet: {:?}", data),
                         Err(CoreError::DataError(SndError::NoSuchData)) => Ok(()),
@@ -2000,6 +2667,60 @@ mod tests {
         });
     }
 
+    // Test reusing a transaction ID: the same transfer from the same balance is reported as
+    // already made, a different one is rejected, and other balances can use the ID freely.
+    #[test]
+    fn transaction_id_reuse() {
+        let source_sk = BlsSecretKey::random();
+        let other_sk = BlsSecretKey::random();
+        let destination_sk = BlsSecretKey::random();
+        for secret_key in &[&source_sk, &other_sk, &destination_sk] {
+            unwrap!(test_create_balance(
+                secret_key,
+                unwrap!(Coins::from_str("10"))
+            ));
+        }
+        let destination = XorName::from(PublicKey::from(destination_sk.public_key()));
+        let amount = unwrap!(Coins::from_str("1"));
+        let transaction_id = rand::random();
+
+        random_client(move |client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+            let source_sk2 = source_sk.clone();
+            let source_sk3 = source_sk.clone();
+
+            client
+                .transfer_coins(Some(&source_sk), destination, amount, Some(transaction_id))
+                .and_then(move |_| {
+                    c2.transfer_coins(Some(&source_sk2), destination, amount, Some(transaction_id))
+                })
+                .then(move |res| {
+                    match res {
+                        Err(CoreError::DataError(SndError::TransactionIdExists)) => (),
+                        res => panic!("Unexpected result: {:?}", res),
+                    }
+                    c3.transfer_coins(
+                        Some(&source_sk3),
+                        destination,
+                        unwrap!(Coins::from_str("2")),
+                        Some(transaction_id),
+                    )
+                })
+                .then(move |res| {
+                    match res {
+                        Err(CoreError::DataError(SndError::InvalidOperation)) => (),
+                        res => panic!("Unexpected result: {:?}", res),
+                    }
+                    c4.transfer_coins(Some(&other_sk), destination, amount, Some(transaction_id))
+                })
+                .and_then(move |_| c5.get_balance(Some(&destination_sk)))
+                .map(|balance| assert_eq!(balance, unwrap!(Coins::from_str("12"))))
+        });
+    }
+
     // 1. Create a client that PUTs some mdata on the network
     // 2. Create a different client that tries to delete the data. It should panic.
     #[test]
@@ -2873,4 +3594,733 @@ mod tests {
                 })
         });
     }
+
+    // 1. Require all elders to agree on the responses.
+    // 2. Verify that the setting is kept and requests still succeed.
+    #[test]
+    pub fn quorum() {
+        random_client(move |client| {
+            assert_eq!(client.quorum(), Quorum::Majority);
+
+            client.set_quorum(Quorum::All);
+            assert_eq!(client.quorum(), Quorum::All);
+
+            client.get_balance(None).map(|_| ())
+        });
+    }
+
+    // 1. Enable automatic reconnection.
+    // 2. Simulate a network disconnect.
+    // 3. Verify that the connection has been restored and requests still succeed.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn auto_reconnect() {
+        random_client(move |client| {
+            client.set_auto_reconnect(true);
+            client.simulate_network_disconnect();
+
+            let pub_id = client.public_id();
+            assert!(client
+                .inner()
+                .borrow()
+                .connection_manager
+                .has_connection_to(&pub_id));
+
+            client.get_balance(None).map(|_| ())
+        });
+    }
+
+    // 1. Limit the number of operations the client can perform to one.
+    // 2. Verify that the first request succeeds and the following ones fail.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn network_limits() {
+        random_client(move |client| {
+            let client2 = client.clone();
+            client.set_network_limits(Some(1));
+
+            client
+                .get_balance(None)
+                .then(move |res| {
+                    let _ = unwrap!(res);
+                    client2.get_balance(None)
+                })
+                .then(|res| match res {
+                    Err(CoreError::DataError(SndError::NetworkOther(_))) => Ok::<_, CoreError>(()),
+                    res => panic!("Unexpected {:?}", res),
+                })
+        });
+    }
+
+    // 1. Create a connection manager whose config doesn't allow any operation.
+    // 2. Verify that its requests fail, so the developer options of the passed config apply.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn passed_dev_config() {
+        use crate::config_handler::DevConfig;
+
+        let config = Config {
+            dev: Some(DevConfig {
+                mock_max_ops_count: Some(0),
+                ..Default::default()
+            }),
+            ..Config::new()
+        };
+        let (net_tx, _net_rx) = mpsc::unbounded();
+        let full_id = SafeKey::client_from_bls_key(BlsSecretKey::random());
+
+        let mut cm = unwrap!(ConnectionManager::new(config, &net_tx));
+        unwrap!(block_on_all(cm.bootstrap(full_id.clone())));
+        match unwrap!(req(
+            &mut cm,
+            Request::GetBalance,
+            &full_id,
+            Duration::from_secs(REQUEST_TIMEOUT_SECS)
+        )) {
+            Response::GetBalance(Err(SndError::NetworkOther(_))) => (),
+            res => panic!("Unexpected {:?}", res),
+        }
+    }
+
+    // 1. Clone a connection manager, then inject failures into the `GetBalance` requests of the
+    //    original.
+    // 2. Verify that the requests sent through the clone fail as well.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn fault_profile_shared_with_clones() {
+        use crate::config_handler::FailureRule;
+
+        let (net_tx, _net_rx) = mpsc::unbounded();
+        let full_id = SafeKey::client_from_bls_key(BlsSecretKey::random());
+
+        let mut cm = unwrap!(ConnectionManager::new(Config::new(), &net_tx));
+        unwrap!(block_on_all(cm.bootstrap(full_id.clone())));
+        let mut cm2 = cm.clone();
+        cm.set_fault_profile(Some(FaultProfile {
+            failures: vec![FailureRule {
+                requests: vec!["GetBalance".to_string()],
+                probability: 1.0,
+                errors: vec![SndError::AccessDenied],
+            }],
+            ..Default::default()
+        }));
+
+        match unwrap!(req(
+            &mut cm2,
+            Request::GetBalance,
+            &full_id,
+            Duration::from_secs(REQUEST_TIMEOUT_SECS)
+        )) {
+            Response::GetBalance(Err(SndError::AccessDenied)) => (),
+            res => panic!("Unexpected {:?}", res),
+        }
+    }
+
+    // 1. Simulate a network which never responds.
+    // 2. Verify that a request sent on behalf of another identity times out too, as it goes
+    //    through a clone of the connection manager sharing its settings.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn simulate_timeout_as_other_identity() {
+        random_client(move |client| {
+            let client2 = client.clone();
+            client.set_timeout(Duration::from_millis(200));
+            client.set_simulate_timeout(true);
+
+            client
+                .get_balance(Some(&BlsSecretKey::random()))
+                .then(move |res| {
+                    match res {
+                        Err(CoreError::RequestTimeout) => (),
+                        res => panic!("Unexpected {:?}", res),
+                    }
+                    client2.set_simulate_timeout(false);
+                    Ok::<_, CoreError>(())
+                })
+        });
+    }
+
+    // 1. Inject failures into all `GetBalance` requests.
+    // 2. Verify that the request fails with the injected error, and succeeds once the faults have
+    //    been cleared.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn fault_injection() {
+        use crate::config_handler::FailureRule;
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            client.set_fault_profile(Some(FaultProfile {
+                failures: vec![FailureRule {
+                    requests: vec!["GetBalance".to_string()],
+                    probability: 1.0,
+                    errors: vec![SndError::AccessDenied],
+                }],
+                ..Default::default()
+            }));
+
+            client
+                .get_balance(None)
+                .then(move |res| {
+                    match res {
+                        Err(CoreError::DataError(SndError::AccessDenied)) => (),
+                        res => panic!("Unexpected {:?}", res),
+                    }
+                    client2.set_fault_profile(None);
+                    client3.get_balance(None)
+                })
+                .map(|_| ())
+        });
+    }
+
+    // 1. Simulate a network of two sections and put a piece of immutable data.
+    // 2. Make the section managing the data unavailable for one request, which times out.
+    // 3. Verify that the data can be fetched once the section is available again.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn section_outage() {
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            client.set_sections_profile(Some(SectionsProfile::default()));
+
+            let data = PubImmutableData::new(unwrap!(generate_random_vector(100)));
+            let address = *data.address();
+            let prefix = if address.name().0[0] & 0x80 == 0 {
+                "0"
+            } else {
+                "1"
+            };
+
+            client
+                .put_idata(data)
+                .and_then(move |_| {
+                    unwrap!(client2.simulate_section_event(SectionEvent::Unavailable {
+                        prefix: prefix.to_string(),
+                        requests: 1,
+                        error: None,
+                    }));
+                    client2
+                        .send_request(Request::GetIData(address), Some(Duration::from_millis(100)))
+                })
+                .then(move |res| {
+                    match res {
+                        Err(CoreError::RequestTimeout) => (),
+                        res => panic!("Unexpected {:?}", res),
+                    }
+                    client3.get_idata(address)
+                })
+                .map(move |_| client4.set_sections_profile(None))
+        });
+    }
+
+    // 1. Set a response hook replacing the responses to `GetBalance` requests.
+    // 2. Verify that the client receives the replaced response.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn response_hook() {
+        random_client(move |client| {
+            client
+                .inner()
+                .borrow_mut()
+                .connection_manager
+                .set_response_hook(|response| match response {
+                    Response::GetBalance(_) => Response::GetBalance(Err(SndError::NoSuchBalance)),
+                    response => response,
+                });
+
+            client.get_balance(None).then(|res| match res {
+                Err(CoreError::DataError(SndError::NoSuchBalance)) => Ok::<_, CoreError>(()),
+                res => panic!("Unexpected {:?}", res),
+            })
+        });
+    }
+
+    // 1. Record a client putting and getting a piece of immutable data.
+    // 2. Write the recording to a file and read it back.
+    // 3. Replay it against the state of the vault when the recording started and verify that all
+    //    the responses match.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn recording() {
+        let recording = random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+
+            let data =
+                UnpubImmutableData::new(unwrap!(generate_random_vector(100)), client.public_key());
+            let address = *data.address();
+
+            client.start_recording();
+            client
+                .get_idata(address)
+                .then(move |res| {
+                    match res {
+                        Err(CoreError::DataError(SndError::NoSuchData)) => (),
+                        res => panic!("Unexpected {:?}", res),
+                    }
+                    client2.put_idata(data)
+                })
+                .and_then(move |_| client3.get_idata(address))
+                .map(move |_| unwrap!(client.stop_recording()))
+        });
+        assert_eq!(recording.exchanges().len(), 3);
+        assert!(recording
+            .exchanges()
+            .iter()
+            .all(|exchange| exchange.processed));
+
+        let dir = unwrap!(tempfile::tempdir());
+        let path = dir.path().join("recording");
+        unwrap!(recording.write_to_file(&path));
+        let recording = unwrap!(Recording::read_from_file(&path));
+
+        // The vault is restored to its state when the recording started, so that the put is
+        // charged to the client's balance just as when it was recorded.
+        let mismatches = unwrap!(recording.replay());
+        assert!(mismatches.is_empty(), "Mismatches: {:?}", mismatches);
+    }
+
+    // 1. Put a piece of immutable data.
+    // 2. Verify that the charge for it is recorded in the ledger of the client's account.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn charges() {
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+
+            let charges = unwrap!(client.charges()).len();
+            let data = PubImmutableData::new(unwrap!(generate_random_vector(100)));
+
+            client
+                .put_idata(data)
+                .and_then(move |_| client2.get_balance(None))
+                .map(move |balance| {
+                    let ledger = unwrap!(client3.charges());
+                    assert_eq!(ledger.len(), charges + 1);
+
+                    let charge = unwrap!(ledger.last());
+                    assert_eq!(charge.request, "PutIData");
+                    assert_eq!(charge.cost, *COST_OF_PUT);
+                    assert_eq!(charge.balance, balance);
+                })
+        });
+    }
+
+    // 1. Start the dry-run mode and put a piece of immutable data.
+    // 2. Verify that the balance is unchanged, and the data can be read back.
+    // 3. Stop the dry-run mode and verify that the put and the reads were tallied, and that the
+    //    data isn't stored.
+    #[test]
+    pub fn dry_run_mode() {
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let client5 = client.clone();
+
+            let data = PubImmutableData::new(unwrap!(generate_random_vector(100)));
+            let address = *data.address();
+
+            client
+                .get_balance(None)
+                .and_then(move |balance| {
+                    client2.start_dry_run();
+                    client2.put_idata(data).map(move |_| balance)
+                })
+                .and_then(move |balance| {
+                    client3
+                        .get_balance(None)
+                        .map(move |new_balance| assert_eq!(new_balance, balance))
+                })
+                .and_then(move |_| client4.get_idata(address))
+                .and_then(move |fetched_data| {
+                    assert_eq!(*fetched_data.address(), address);
+
+                    let tally = unwrap!(client5.stop_dry_run());
+                    assert_eq!(tally.mutations, 1);
+                    assert_eq!(tally.requests.get("PutIData"), Some(&1));
+                    assert_eq!(tally.requests.get("GetBalance"), Some(&1));
+                    assert_eq!(tally.requests.get("GetIData"), Some(&1));
+                    assert_eq!(unwrap!(tally.estimate()).cost, *COST_OF_PUT);
+                    assert!(client5.stop_dry_run().is_none());
+
+                    client5.get_idata(address).then(|res| {
+                        match res {
+                            Err(CoreError::DataError(SndError::NoSuchData)) => (),
+                            res => panic!("Unexpected result {:?}", res),
+                        }
+                        Ok::<_, CoreError>(())
+                    })
+                })
+        });
+    }
+
+    // 1. Simulate a network which never responds, and send a request overriding the client's
+    //    timeout.
+    // 2. Verify that the request is in flight until it times out, and no longer afterwards.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn requests_in_flight() {
+        random_client(move |client| {
+            let client2 = client.clone();
+            client.set_simulate_timeout(true);
+
+            let response =
+                client.send_request(Request::GetBalance, Some(Duration::from_millis(200)));
+            assert_eq!(client.requests_in_flight(), 1);
+
+            response.then(move |result| {
+                match result {
+                    Err(CoreError::RequestTimeout) => (),
+                    res => panic!("Unexpected result {:?}", res),
+                }
+                assert_eq!(client2.requests_in_flight(), 0);
+                client2.set_simulate_timeout(false);
+                Ok::<_, CoreError>(())
+            })
+        });
+    }
+
+    // 1. Enable the data cache and put a sequenced mutable data.
+    // 2. Get its shell twice and verify that the second read is served from the cache once the
+    //    version of the data has been checked.
+    // 3. Set the permissions of a user and verify that the cached shell is dropped, so that the
+    //    new version is read from the network.
+    // 4. Update an entry and verify that the new value is read, as the cached entries are dropped
+    //    along with the shell.
+    #[test]
+    pub fn data_cache() {
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let client5 = client.clone();
+
+            client.set_data_cache_config(DataCacheConfig {
+                enabled: true,
+                max_age_secs: 0,
+                ..Default::default()
+            });
+
+            let name = XorName(rand::random());
+            let tag = 15003;
+            let address = MDataAddress::Seq { name, tag };
+            let mut entries: MDataSeqEntries = Default::default();
+            let _ = entries.insert(
+                b"key".to_vec(),
+                MDataSeqValue {
+                    data: b"value".to_vec(),
+                    version: 0,
+                },
+            );
+            let mut permissions: BTreeMap<_, _> = Default::default();
+            let permission_set = MDataPermissionSet::new()
+                .allow(MDataAction::Read)
+                .allow(MDataAction::Update)
+                .allow(MDataAction::ManagePermissions);
+            let _ = permissions.insert(client.public_key(), permission_set.clone());
+            let data =
+                SeqMutableData::new_with_data(name, tag, entries, permissions, client.public_key());
+
+            client
+                .put_seq_mutable_data(data)
+                .and_then(move |_| client2.get_seq_mdata_shell(name, tag))
+                .and_then(move |shell| {
+                    assert_eq!(shell.version(), 0);
+                    client3.get_seq_mdata_shell(name, tag)
+                })
+                .and_then(move |shell| {
+                    assert_eq!(shell.version(), 0);
+                    let stats = client4.data_cache_stats();
+                    assert_eq!((stats.hits, stats.misses), (1, 1));
+
+                    client4.set_mdata_user_permissions(
+                        address,
+                        client4.public_key(),
+                        permission_set,
+                        1,
+                    )
+                })
+                .and_then(move |_| {
+                    assert_eq!(client5.data_cache_stats().invalidations, 1);
+                    client5
+                        .get_seq_mdata_shell(name, tag)
+                        .map(move |shell| (shell, client5))
+                })
+                .and_then(|(shell, client)| {
+                    assert_eq!(shell.version(), 1);
+                    assert_eq!(client.data_cache_stats().misses, 2);
+
+                    let actions = MDataSeqEntryActions::new().update(
+                        b"key".to_vec(),
+                        b"new value".to_vec(),
+                        1,
+                    );
+                    let client2 = client.clone();
+                    client
+                        .mutate_seq_mdata_entries(name, tag, actions)
+                        .and_then(move |_| client2.get_seq_mdata_value(name, tag, b"key".to_vec()))
+                })
+                .map(|value| assert_eq!(value.data, b"new value".to_vec()))
+        });
+    }
+
+    // 1. Enable the data cache and put a sequenced mutable data.
+    // 2. Get the value of an entry twice and verify that only the first read reaches the network.
+    // 3. Update the entry and verify that the new value is then read from the network, as the
+    //    client's own mutations drop the cached entries.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn data_cache_entries() {
+        fn value_requests(recording: &Recording) -> usize {
+            recording
+                .exchanges()
+                .iter()
+                .filter(|exchange| match exchange.request {
+                    Message::Request {
+                        request: Request::GetMDataValue { .. },
+                        ..
+                    } => true,
+                    _ => false,
+                })
+                .count()
+        }
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let client5 = client.clone();
+
+            client.set_data_cache_config(DataCacheConfig {
+                enabled: true,
+                max_age_secs: 60,
+                ..Default::default()
+            });
+
+            let name = XorName(rand::random());
+            let tag = 15003;
+            let mut entries: MDataSeqEntries = Default::default();
+            let _ = entries.insert(
+                b"key".to_vec(),
+                MDataSeqValue {
+                    data: b"value".to_vec(),
+                    version: 0,
+                },
+            );
+            let mut permissions: BTreeMap<_, _> = Default::default();
+            let permission_set = MDataPermissionSet::new()
+                .allow(MDataAction::Read)
+                .allow(MDataAction::Update);
+            let _ = permissions.insert(client.public_key(), permission_set);
+            let data =
+                SeqMutableData::new_with_data(name, tag, entries, permissions, client.public_key());
+
+            client
+                .put_seq_mutable_data(data)
+                .and_then(move |_| {
+                    client2.start_recording();
+                    client2.get_seq_mdata_value(name, tag, b"key".to_vec())
+                })
+                .and_then(move |value| {
+                    assert_eq!(value.data, b"value".to_vec());
+                    client3.get_seq_mdata_value(name, tag, b"key".to_vec())
+                })
+                .and_then(move |value| {
+                    assert_eq!(value.data, b"value".to_vec());
+                    let recording = unwrap!(client4.stop_recording());
+                    assert_eq!(value_requests(&recording), 1);
+                    assert_eq!(client4.data_cache_stats().hits, 1);
+
+                    let actions = MDataSeqEntryActions::new().update(
+                        b"key".to_vec(),
+                        b"new value".to_vec(),
+                        1,
+                    );
+                    client4.mutate_seq_mdata_entries(name, tag, actions)
+                })
+                .and_then(move |_| {
+                    client5.start_recording();
+                    client5
+                        .get_seq_mdata_value(name, tag, b"key".to_vec())
+                        .map(move |value| (value, client5))
+                })
+                .map(|(value, client)| {
+                    assert_eq!(value.data, b"new value".to_vec());
+                    let recording = unwrap!(client.stop_recording());
+                    assert_eq!(value_requests(&recording), 1);
+                })
+        });
+    }
+
+    // 1. Cache a chunk which isn't stored on the network through another instance of the on-disk
+    //    cache of immutable data, as another process of the user would.
+    // 2. Enable the on-disk cache for a client and verify that it reads the chunk from the cache.
+    // 3. Put and get another chunk and verify that it's been cached on disk.
+    #[test]
+    pub fn idata_disk_cache() {
+        let dir = unwrap!(tempfile::tempdir());
+        let config = IDataCacheConfig {
+            enabled: true,
+            path: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let other_cache = unwrap!(unwrap!(IDataDiskCache::open(&config)));
+        let cached_data: IData = PubImmutableData::new(unwrap!(generate_random_vector(100))).into();
+        unwrap!(other_cache.insert(&cached_data));
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+
+            unwrap!(client.set_idata_cache_config(&config));
+
+            let data: IData = PubImmutableData::new(unwrap!(generate_random_vector(100))).into();
+            let address = *data.address();
+
+            client
+                .get_idata(*cached_data.address())
+                .and_then(move |fetched_data| {
+                    assert_eq!(fetched_data, cached_data);
+                    client2.put_idata(data)
+                })
+                .and_then(move |_| {
+                    client3
+                        .get_idata(address)
+                        .map(move |fetched_data| (fetched_data, client3))
+                })
+                .map(move |(fetched_data, client)| {
+                    let cached_data = other_cache.get(&address, &client.public_key());
+                    assert_eq!(cached_data, Some(fetched_data));
+                })
+        });
+    }
+
+    // 1. Put two sequenced mutable data.
+    // 2. Insert an entry into both of them and into a non-existent one in a single batch.
+    // 3. Verify that the results are in the order of the mutations, and only the mutation of the
+    //    non-existent data failed.
+    #[test]
+    pub fn batch_mutations() {
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+
+            let tag = 15002;
+            let names: Vec<XorName> = (0..3).map(|_| XorName(rand::random())).collect();
+            let mut permissions: BTreeMap<_, _> = Default::default();
+            let permission_set = MDataPermissionSet::new()
+                .allow(MDataAction::Read)
+                .allow(MDataAction::Insert);
+            let _ = permissions.insert(client.public_key(), permission_set);
+            let puts: Vec<_> = names[..2]
+                .iter()
+                .map(|name| {
+                    let data = SeqMutableData::new_with_data(
+                        *name,
+                        tag,
+                        Default::default(),
+                        permissions.clone(),
+                        client.public_key(),
+                    );
+                    client.put_seq_mutable_data(data)
+                })
+                .collect();
+
+            future::join_all(puts)
+                .and_then(move |_| {
+                    let mutations = names
+                        .iter()
+                        .map(|name| {
+                            let actions = MDataSeqEntryActions::new().ins(
+                                b"key".to_vec(),
+                                b"value".to_vec(),
+                                0,
+                            );
+                            (
+                                MDataAddress::Seq { name: *name, tag },
+                                MDataEntryActions::Seq(actions),
+                            )
+                        })
+                        .collect();
+
+                    client2
+                        .mutate_mdata_entries_batch(mutations)
+                        .map(move |results| (results, names))
+                })
+                .and_then(move |(results, names)| {
+                    assert_eq!(results.len(), 3);
+                    let _ = unwrap!(results[0].as_ref());
+                    let _ = unwrap!(results[1].as_ref());
+                    match results[2] {
+                        Err(CoreError::DataError(SndError::NoSuchData)) => (),
+                        ref res => panic!("Unexpected result {:?}", res),
+                    }
+
+                    client3.get_seq_mdata_value(names[1], tag, b"key".to_vec())
+                })
+                .map(|value| assert_eq!(value.data, b"value".to_vec()))
+        });
+    }
+
+    // 1. Subscribe to the client's notifications.
+    // 2. Transfer coins to the client's wallet from another wallet.
+    // 3. Verify that a notification about the incoming transfer is received.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    pub fn transfer_notification() {
+        use crate::event::NetworkNotification;
+        use futures::Stream;
+
+        let bls_sk = BlsSecretKey::random();
+        unwrap!(test_create_balance(&bls_sk, unwrap!(Coins::from_str("50"))));
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let wallet: XorName = client.owner_key().into();
+            let amount = unwrap!(Coins::from_str("10"));
+
+            client
+                .subscribe_notifications()
+                .and_then(move |notifications| {
+                    client2
+                        .transfer_coins(Some(&bls_sk), wallet, amount, Some(42))
+                        .map(move |_| notifications)
+                })
+                .and_then(move |notifications| {
+                    notifications
+                        .into_future()
+                        .map_err(|_| CoreError::from("Notification stream failed"))
+                })
+                .map(move |(notification, _)| {
+                    assert_eq!(
+                        notification,
+                        Some(NetworkNotification::TransferReceived {
+                            transaction_id: 42,
+                            amount,
+                        })
+                    );
+                })
+        });
+    }
+
+    // 1. Disconnect the client from the network.
+    // 2. Subscribe to its notifications before sending any other request.
+    // 3. Verify that the subscription connects the client again.
+    #[test]
+    pub fn subscribe_notifications_connects() {
+        random_client(move |client| {
+            let client2 = client.clone();
+            let pub_id = client.public_id();
+            let mut cm = client.inner().borrow().connection_manager.clone();
+
+            cm.disconnect(&pub_id)
+                .and_then(move |()| client2.subscribe_notifications())
+                .map(move |_notifications| assert!(cm.has_connection_to(&pub_id)))
+        });
+    }
 }
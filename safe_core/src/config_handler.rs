@@ -6,21 +6,32 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::CoreError;
+use crate::{client::COST_OF_PUT, section_map::SectionMap, utils::files, CoreError};
+use chrono::{DateTime, Utc};
 use directories::ProjectDirs;
-use quic_p2p::Config as QuicP2pConfig;
+use quic_p2p::{Config as QuicP2pConfig, NodeInfo};
+use safe_nd::Error as SndError;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-#[cfg(test)]
-use std::{fs, path::PathBuf};
 use std::{
-    fs::File,
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
     io::{self, BufReader},
+    path::PathBuf,
 };
 
 const CONFIG_DIR_QUALIFIER: &str = "net";
 const CONFIG_DIR_ORGANISATION: &str = "MaidSafe";
 const CONFIG_DIR_APPLICATION: &str = "safe_core";
 const CONFIG_FILE: &str = "safe_core.config";
+const SECTIONS_FILE: &str = "safe_core_sections.config";
+const DATA_CACHE_FILE: &str = "safe_core_data_cache.config";
+const IDATA_CACHE_FILE: &str = "safe_core_idata_cache.config";
+const IDATA_CACHE_DIR: &str = "idata";
+const TRANSFER_JOURNAL_FILE: &str = "transfers.journal";
+const BOOTSTRAP_CACHE_FILE: &str = "safe_core_bootstrap_cache.config";
+
+/// Maximum number of contacts kept in the bootstrap cache.
+pub const MAX_BOOTSTRAP_CACHE_SIZE: usize = 100;
 
 const VAULT_CONFIG_DIR_APPLICATION: &str = "safe_vault";
 const VAULT_CONNECTION_INFO_FILE: &str = "vault_connection_info.config";
@@ -32,15 +43,37 @@ pub struct Config {
     pub quic_p2p: QuicP2pConfig,
     /// Developer options.
     pub dev: Option<DevConfig>,
+    /// Known sections along with the contact info of their elders.
+    #[serde(default)]
+    pub sections: SectionMap,
+    /// Cache of mutable and append-only data.
+    #[serde(default)]
+    pub data_cache: DataCacheConfig,
+    /// On-disk cache of immutable data.
+    #[serde(default)]
+    pub idata_cache: IDataCacheConfig,
 }
 
 impl Config {
-    /// Returns a new `Config` instance. Tries to read quic-p2p config from file.
+    /// Returns a new `Config` instance. Tries to read quic-p2p config, the known sections and
+    /// the options of the data caches from file.
     pub fn new() -> Self {
         let quic_p2p = Self::read_qp2p_from_file().unwrap_or_default();
+        let sections = dirs()
+            .and_then(|dirs| read_config_file(dirs, SECTIONS_FILE))
+            .unwrap_or_default();
+        let data_cache = dirs()
+            .and_then(|dirs| read_config_file(dirs, DATA_CACHE_FILE))
+            .unwrap_or_default();
+        let idata_cache = dirs()
+            .and_then(|dirs| read_config_file(dirs, IDATA_CACHE_FILE))
+            .unwrap_or_default();
         Self {
             quic_p2p,
             dev: None,
+            sections,
+            data_cache,
+            idata_cache,
         }
     }
 
@@ -67,6 +100,62 @@ impl Config {
     }
 }
 
+/// Options of the cache of mutable and append-only data kept by the clients. Cached data is
+/// checked against the version of the data on the network before it's used once it's older than
+/// `max_age_secs`, and dropped when the client mutates the data. The entries of sequenced mutable
+/// data can change without their version changing, so they're fetched again instead.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DataCacheConfig {
+    /// Whether the data is cached.
+    pub enabled: bool,
+    /// Maximum number of mutable data cached.
+    pub max_mdata: usize,
+    /// Maximum number of append-only data cached.
+    pub max_adata: usize,
+    /// Number of seconds cached data is used for without checking its version on the network.
+    /// Mutations by other clients can go unnoticed for that long.
+    pub max_age_secs: u64,
+}
+
+impl Default for DataCacheConfig {
+    fn default() -> Self {
+        DataCacheConfig {
+            enabled: false,
+            max_mdata: 100,
+            max_adata: 100,
+            max_age_secs: 5,
+        }
+    }
+}
+
+/// Options of the on-disk cache of immutable data, which is shared by the clients of the same
+/// user and kept across sessions.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct IDataCacheConfig {
+    /// Whether the data is cached on disk.
+    pub enabled: bool,
+    /// Directory of the cache. A directory in the user's cache directory is used if not set.
+    pub path: Option<PathBuf>,
+    /// Maximum total size of the cached data, in bytes.
+    pub max_size: u64,
+    /// Only cache published data. Otherwise the unpublished data cached by any account of the
+    /// user is kept in the cache too, but only read back by the account which owns it.
+    pub published_only: bool,
+}
+
+impl Default for IDataCacheConfig {
+    fn default() -> Self {
+        IDataCacheConfig {
+            enabled: false,
+            path: None,
+            max_size: 100 * 1024 * 1024,
+            published_only: true,
+        }
+    }
+}
+
 /// Extra configuration options intended for developers.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct DevConfig {
@@ -76,6 +165,282 @@ pub struct DevConfig {
     pub mock_in_memory_storage: bool,
     /// Set the mock-vault path if using file store (`mock_in_memory_storage` is `false`).
     pub mock_vault_path: Option<String>,
+    /// Use an append-only journal, compacted periodically, as the mock-vault file store instead
+    /// of rewriting the whole file on every mutation.
+    #[serde(default)]
+    pub mock_journal_storage: bool,
+    /// Make requests to the mock-vault time out instead of getting a response.
+    #[serde(default)]
+    pub mock_simulate_timeout: bool,
+    /// Number of requests the mock-vault processes before failing every further one.
+    #[serde(default)]
+    pub mock_max_ops_count: Option<u64>,
+    /// Faults injected by the mock network.
+    #[serde(default)]
+    pub mock_fault_profile: Option<FaultProfile>,
+    /// Limits on the size of the data stored by the mock-vault.
+    #[serde(default)]
+    pub mock_data_limits: DataLimits,
+    /// Prices charged by the mock-vault for the mutations. A flat `COST_OF_PUT` is charged for
+    /// every mutation if not set.
+    #[serde(default)]
+    pub mock_pricing: Option<Pricing>,
+    /// Simulate a network of several sections, with scripted splits, churn and outages.
+    #[serde(default)]
+    pub mock_sections: Option<SectionsProfile>,
+    /// Socket of a mock vault server shared with other processes. The mock-vault of this process
+    /// is used if not set.
+    #[serde(default)]
+    pub mock_vault_socket: Option<String>,
+}
+
+/// Limits on the size of the data the mock-vault accepts. The defaults match the limits enforced
+/// by the real vaults.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DataLimits {
+    /// Maximum serialised size of an immutable data chunk, in bytes. The limit of the real
+    /// network (checked by `IData::validate_size`) applies if not set.
+    pub max_idata_size: Option<u64>,
+    /// Maximum number of entries in a mutable data.
+    pub max_mdata_entries: u64,
+    /// Maximum serialised size of a mutable data, in bytes.
+    pub max_mdata_size: u64,
+    /// Maximum number of users with permissions on a mutable data.
+    pub max_mdata_permissions: u64,
+    /// Maximum serialised size of an append-only data, in bytes.
+    pub max_adata_size: u64,
+    /// Maximum number of permission sets in the history of an append-only data.
+    pub max_adata_permissions: u64,
+}
+
+impl Default for DataLimits {
+    fn default() -> Self {
+        DataLimits {
+            max_idata_size: None,
+            max_mdata_entries: 1000,
+            max_mdata_size: 1024 * 1024,
+            max_mdata_permissions: 50,
+            max_adata_size: 1024 * 1024,
+            max_adata_permissions: 50,
+        }
+    }
+}
+
+/// Pricing of the mutations by the mock-vault, also used to estimate the cost of the mutations
+/// tallied in dry-run mode. The cost of a mutation is the cost of its request type plus the cost
+/// of its size, multiplied by a factor rising with the fullness of the network.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Pricing {
+    /// Cost in nano coins of the request types not listed in `request_costs`.
+    pub base_cost: u64,
+    /// Cost in nano coins of the requests, by name of the `Request` variant such as
+    /// `"PutIData"`.
+    pub request_costs: BTreeMap<String, u64>,
+    /// Additional cost in nano coins of every started KiB of the serialised request.
+    pub cost_per_kib: u64,
+    /// Number of data the simulated network can store. If set, the cost is divided by the
+    /// fraction of the network which is still free, so that it doubles once the network is half
+    /// full.
+    pub network_capacity: Option<u64>,
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        Pricing {
+            base_cost: COST_OF_PUT.as_nano(),
+            request_costs: BTreeMap::new(),
+            cost_per_kib: 0,
+            network_capacity: None,
+        }
+    }
+}
+
+/// Sections simulated by the mock network. The data is still stored by a single mock-vault, but
+/// the requests are routed to the section managing the data they are about (or the section of
+/// the client for the other requests), and fail if that section can't handle them.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SectionsProfile {
+    /// Length of the prefixes of the initial sections, so that there are `2^prefix_len` of them.
+    pub prefix_len: u8,
+    /// Number of elders in every section.
+    pub elder_count: usize,
+    /// Events applied once the given number of requests have been sent.
+    pub events: Vec<ScriptedSectionEvent>,
+}
+
+impl Default for SectionsProfile {
+    fn default() -> Self {
+        SectionsProfile {
+            prefix_len: 1,
+            elder_count: 7,
+            events: Vec::new(),
+        }
+    }
+}
+
+/// Section event scheduled by a `SectionsProfile`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScriptedSectionEvent {
+    /// Number of requests sent before the event happens.
+    pub after_requests: u64,
+    /// The event.
+    pub event: SectionEvent,
+}
+
+/// Change to the sections simulated by the mock network. Sections are designated by their prefix,
+/// written as a string of bits such as `"01"`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum SectionEvent {
+    /// The section splits into two sections, whose elders are half the elders of the original
+    /// section plus new ones.
+    Split {
+        /// Prefix of the section.
+        prefix: String,
+    },
+    /// Elders of the section are replaced with new ones, the oldest first.
+    Churn {
+        /// Prefix of the section.
+        prefix: String,
+        /// Number of elders replaced.
+        count: usize,
+    },
+    /// The section doesn't handle the next requests it receives.
+    Unavailable {
+        /// Prefix of the section.
+        prefix: String,
+        /// Number of requests not handled.
+        requests: u64,
+        /// Error the requests fail with. They time out if not set.
+        #[serde(default)]
+        error: Option<SndError>,
+    },
+}
+
+/// Faults injected by the mock network to reproduce the behaviour of an unreliable network.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FaultProfile {
+    /// Seed of the random number generator deciding which faults occur, so that they can be
+    /// reproduced. A random seed is used (and logged) if not set.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Requests failing with an error instead of being processed.
+    #[serde(default)]
+    pub failures: Vec<FailureRule>,
+    /// Delay before a response is received.
+    #[serde(default)]
+    pub latency: Latency,
+    /// Probability that a response is lost after the request has been processed, so that the
+    /// request times out.
+    #[serde(default)]
+    pub drop_probability: f64,
+    /// Probability that a request is delivered twice, as when it's sent again after its response
+    /// has been lost. The response to the second delivery is the one received.
+    #[serde(default)]
+    pub duplicate_probability: f64,
+}
+
+/// Failure injected into the matching requests.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FailureRule {
+    /// Names of the `Request` variants the rule applies to, such as `"MutateMDataEntries"`. The
+    /// rule applies to all requests if empty.
+    #[serde(default)]
+    pub requests: Vec<String>,
+    /// Probability that a matching request fails.
+    pub probability: f64,
+    /// Errors to fail with, one of them being chosen at random. Requests fail with
+    /// `NetworkOther` if empty.
+    #[serde(default)]
+    pub errors: Vec<SndError>,
+}
+
+/// Distribution of the delay before a response is received.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Latency {
+    /// No delay.
+    None,
+    /// The same delay for every response.
+    Fixed {
+        /// Delay in milliseconds.
+        millis: u64,
+    },
+    /// Delay uniformly distributed within a range.
+    Uniform {
+        /// Minimum delay in milliseconds.
+        min_millis: u64,
+        /// Maximum delay in milliseconds.
+        max_millis: u64,
+    },
+    /// Exponentially distributed delay.
+    Exponential {
+        /// Mean delay in milliseconds.
+        mean_millis: u64,
+    },
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Latency::None
+    }
+}
+
+/// A contact we've successfully bootstrapped to.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct CachedContact {
+    /// Connection info of the contact.
+    pub node_info: NodeInfo,
+    /// When we last bootstrapped to the contact.
+    pub last_success: DateTime<Utc>,
+}
+
+/// Contacts we've successfully bootstrapped to, most recent first. They're tried before the
+/// hard-coded contacts, as they're more likely to be up to date.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BootstrapCache {
+    contacts: Vec<CachedContact>,
+}
+
+impl BootstrapCache {
+    /// Returns the cached contacts, most recent first.
+    pub fn contacts(&self) -> &[CachedContact] {
+        &self.contacts
+    }
+
+    /// Returns the connection info of all cached contacts.
+    pub fn node_infos(&self) -> HashSet<NodeInfo> {
+        self.contacts
+            .iter()
+            .map(|contact| contact.node_info.clone())
+            .collect()
+    }
+
+    /// Returns `true` if there are no cached contacts.
+    pub fn is_empty(&self) -> bool {
+        self.contacts.is_empty()
+    }
+
+    /// Record a successful bootstrap to `node_info`. The least recently successful contact is
+    /// dropped if there are more than `MAX_BOOTSTRAP_CACHE_SIZE`.
+    pub fn insert(&mut self, node_info: NodeInfo) {
+        self.contacts
+            .retain(|contact| contact.node_info.peer_addr != node_info.peer_addr);
+        self.contacts.insert(
+            0,
+            CachedContact {
+                node_info,
+                last_success: Utc::now(),
+            },
+        );
+        self.contacts.truncate(MAX_BOOTSTRAP_CACHE_SIZE);
+    }
+
+    /// Remove all contacts.
+    pub fn clear(&mut self) {
+        self.contacts.clear();
+    }
 }
 
 /// Reads the `safe_core` config file and returns it or a default if this fails.
@@ -83,6 +448,66 @@ pub fn get_config() -> Config {
     Config::new()
 }
 
+/// Reads the bootstrap cache file. Returns an empty cache if there's no file yet.
+pub fn read_bootstrap_cache() -> Result<BootstrapCache, CoreError> {
+    match read_config_file(dirs()?, BOOTSTRAP_CACHE_FILE) {
+        Err(CoreError::IoError(ref err)) if err.kind() == io::ErrorKind::NotFound => {
+            Ok(BootstrapCache::default())
+        }
+        result => result,
+    }
+}
+
+/// Writes the bootstrap cache file, returning its path.
+pub fn write_bootstrap_cache(cache: &BootstrapCache) -> Result<PathBuf, CoreError> {
+    let dirs = dirs()?;
+    let _lock = lock_config_file(&dirs, BOOTSTRAP_CACHE_FILE)?;
+    write_config_file_to(dirs, BOOTSTRAP_CACHE_FILE, cache)
+}
+
+/// Records a successful bootstrap to `node_info` in the bootstrap cache file, returning its path.
+/// The file stays locked in between reading and writing it, so that the clients of other
+/// processes don't drop each other's contacts.
+pub fn update_bootstrap_cache(node_info: NodeInfo) -> Result<PathBuf, CoreError> {
+    let dirs = dirs()?;
+    let _lock = lock_config_file(&dirs, BOOTSTRAP_CACHE_FILE)?;
+    let mut cache = read_bootstrap_cache()?;
+    cache.insert(node_info);
+    write_config_file_to(dirs, BOOTSTRAP_CACHE_FILE, &cache)
+}
+
+/// Removes all contacts from the bootstrap cache file.
+pub fn clear_bootstrap_cache() -> Result<(), CoreError> {
+    let _ = write_bootstrap_cache(&BootstrapCache::default())?;
+    Ok(())
+}
+
+/// Writes the known sections, which are read by the clients created afterwards.
+pub fn write_sections(sections: &SectionMap) -> Result<PathBuf, CoreError> {
+    let dirs = dirs()?;
+    let _lock = lock_config_file(&dirs, SECTIONS_FILE)?;
+    write_config_file_to(dirs, SECTIONS_FILE, sections)
+}
+
+/// Writes the options of the on-disk cache of immutable data, which apply to the clients created
+/// afterwards.
+pub fn write_idata_cache_config(config: &IDataCacheConfig) -> Result<PathBuf, CoreError> {
+    write_config_file_to(dirs()?, IDATA_CACHE_FILE, config)
+}
+
+/// Directory of the on-disk cache of immutable data, if none is configured.
+pub fn default_idata_cache_dir() -> Result<PathBuf, CoreError> {
+    Ok(dirs()?.cache_dir().join(IDATA_CACHE_DIR))
+}
+
+/// File of the `TransferJournal` wallet transfers are sent through by the FFI. Its directory is
+/// created if needed.
+pub fn default_transfer_journal_path() -> Result<PathBuf, CoreError> {
+    let dirs = dirs()?;
+    fs::create_dir_all(dirs.data_dir())?;
+    Ok(dirs.data_dir().join(TRANSFER_JOURNAL_FILE))
+}
+
 fn dirs() -> Result<ProjectDirs, CoreError> {
     ProjectDirs::from(
         CONFIG_DIR_QUALIFIER,
@@ -132,19 +557,31 @@ where
 #[cfg(test)]
 #[allow(unused)]
 pub fn write_config_file(config: &Config) -> Result<PathBuf, CoreError> {
-    let dirs = dirs()?;
+    write_config_file_to(dirs()?, CONFIG_FILE, config)
+}
+
+fn write_config_file_to<T>(dirs: ProjectDirs, file: &str, value: &T) -> Result<PathBuf, CoreError>
+where
+    T: Serialize,
+{
     let dir = dirs.config_dir();
     fs::create_dir_all(dir)?;
 
-    let path = dir.join(CONFIG_FILE);
-    dbg!(&path);
-    let mut file = File::create(&path)?;
-    serde_json::to_writer_pretty(&mut file, config)?;
-    file.sync_all()?;
-
+    let path = dir.join(file);
+    files::write_atomically(&path, |temp_file| {
+        serde_json::to_writer_pretty(temp_file, value)?;
+        Ok(())
+    })?;
     Ok(path)
 }
 
+// Take an exclusive lock on the given config file, held until the returned file is dropped.
+fn lock_config_file(dirs: &ProjectDirs, file: &str) -> Result<File, CoreError> {
+    let dir = dirs.config_dir();
+    fs::create_dir_all(dir)?;
+    files::lock_exclusive(&dir.join(file))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -157,4 +594,27 @@ mod test {
         let config = Config::default();
         unwrap!(write_config_file(&config));
     }
+
+    // Test that the most recently successful contacts are kept first, without duplicates.
+    #[test]
+    fn bootstrap_cache() {
+        let node_info = |port| NodeInfo {
+            peer_addr: ([127, 0, 0, 1], port).into(),
+            peer_cert_der: vec![],
+        };
+
+        let mut cache = BootstrapCache::default();
+        for port in 0..MAX_BOOTSTRAP_CACHE_SIZE as u16 + 1 {
+            cache.insert(node_info(port));
+        }
+        assert_eq!(cache.contacts().len(), MAX_BOOTSTRAP_CACHE_SIZE);
+        assert!(!cache.node_infos().contains(&node_info(0)));
+
+        cache.insert(node_info(1));
+        assert_eq!(cache.contacts().len(), MAX_BOOTSTRAP_CACHE_SIZE);
+        assert_eq!(cache.contacts()[0].node_info, node_info(1));
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
 }
@@ -6,40 +6,77 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod accumulator;
 mod connection_group;
 
-use crate::{client::SafeKey, event::NetworkEvent, event::NetworkTx, CoreError, CoreFuture};
-use connection_group::ConnectionGroup;
-use futures::{future, Future};
-use quic_p2p::Config as QuicP2pConfig;
+use crate::{
+    client::SafeKey,
+    config_handler::{write_sections, Config},
+    event::NetworkEvent,
+    event::NetworkTx,
+    event::NotificationRx,
+    section_map::{data_name, Prefix, SectionMap},
+    CoreError, CoreFuture, Quorum,
+};
+use connection_group::{ConnectionGroup, DEFAULT_MAX_RETRIES};
+use futures::{
+    future,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Future, Stream,
+};
+use quic_p2p::{Config as QuicP2pConfig, NodeInfo};
 use safe_nd::{Message, PublicId, Response};
 use std::{
     cell::RefCell,
-    collections::{hash_map::Entry, HashMap},
-    rc::Rc,
+    collections::{HashMap, HashSet},
+    rc::{Rc, Weak},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 use tokio::prelude::FutureExt;
+use tokio::runtime::current_thread::TaskExecutor;
 
 const CONNECTION_TIMEOUT_SECS: u64 = 30;
 
 /// Initialises QuicP2p instance. Establishes new connections.
 /// Contains a reference to crossbeam channel provided by quic-p2p for capturing the events.
+/// Keeps a map of the known sections, starting from the ones in the config, so that it can
+/// connect to all elders of a section and route data requests to the section managing the data.
 #[derive(Clone)]
 pub struct ConnectionManager {
     inner: Rc<RefCell<Inner>>,
 }
 
 impl ConnectionManager {
-    /// Create a new connection manager.
-    pub fn new(mut config: QuicP2pConfig, net_tx: &NetworkTx) -> Result<Self, CoreError> {
+    /// Create a new connection manager, starting from the quic-p2p options and the known sections
+    /// of `config`.
+    pub fn new(config: Config, net_tx: &NetworkTx) -> Result<Self, CoreError> {
+        let Config {
+            quic_p2p: mut config,
+            sections,
+            ..
+        } = config;
         config.port = None; // Make sure we always use a random port for client connections.
 
+        let (lost_tx, lost_rx) = mpsc::unbounded();
         let inner = Rc::new(RefCell::new(Inner {
             config,
             groups: HashMap::default(),
+            section_groups: HashMap::default(),
+            this: Weak::new(),
+            section_map: Arc::new(Mutex::new(sections)),
+            use_bootstrap_cache: true,
             net_tx: net_tx.clone(),
+            auto_reconnect: Arc::new(AtomicBool::new(false)),
+            lost_tx,
+            lost_rx: Some(lost_rx),
+            quorum: Quorum::default(),
+            max_retries: DEFAULT_MAX_RETRIES,
         }));
+        inner.borrow_mut().this = Rc::downgrade(&inner);
 
         Ok(Self { inner })
     }
@@ -51,9 +88,42 @@ impl ConnectionManager {
         inner.groups.contains_key(&pub_id)
     }
 
-    /// Send `message` via the `ConnectionGroup` specified by our given `pub_id`.
-    pub fn send(&mut self, pub_id: &PublicId, msg: &Message) -> Box<CoreFuture<Response>> {
-        self.inner.borrow_mut().send(pub_id, msg)
+    /// Send `message` via the `ConnectionGroup` specified by our given `pub_id`. Fails with
+    /// `CoreError::RequestTimeout` if no response is received within `timeout`.
+    pub fn send(
+        &mut self,
+        pub_id: &PublicId,
+        msg: &Message,
+        timeout: Duration,
+    ) -> Box<CoreFuture<Response>> {
+        self.inner.borrow_mut().send(pub_id, msg, timeout)
+    }
+
+    /// Subscribe to the notifications sent by the `ConnectionGroup` specified by `pub_id`, such
+    /// as incoming coin transfers.
+    pub fn subscribe(&mut self, pub_id: &PublicId) -> Result<NotificationRx, CoreError> {
+        self.inner.borrow_mut().subscribe(pub_id)
+    }
+
+    /// Returns the number of requests which are still waiting for a response.
+    pub fn requests_in_flight(&self) -> usize {
+        let inner = self.inner.borrow();
+        inner
+            .groups
+            .values()
+            .chain(inner.section_groups.values().map(|section| &section.group))
+            .map(ConnectionGroup::requests_in_flight)
+            .sum()
+    }
+
+    /// Returns the sections known so far, including the elders learned from the network.
+    pub fn section_map(&self) -> SectionMap {
+        unwrap!(self.inner.borrow().section_map.lock()).clone()
+    }
+
+    /// Replace the known sections. Only affects the groups connected from now on.
+    pub fn set_section_map(&mut self, section_map: SectionMap) {
+        *unwrap!(self.inner.borrow().section_map.lock()) = section_map;
     }
 
     /// Connect to Client Handlers that manage the provided ID.
@@ -61,9 +131,77 @@ impl ConnectionManager {
         self.inner.borrow_mut().bootstrap(full_id)
     }
 
-    /// Reconnect to the network.
-    pub fn restart_network(&mut self) {
-        unimplemented!();
+    /// Reconnect to the network. Re-bootstraps every group that has lost its connection, backing
+    /// off exponentially between the attempts. `NetworkEvent::Connected` is sent once all the
+    /// groups are connected again.
+    pub fn restart_network(&mut self) -> Box<CoreFuture<()>> {
+        self.inner.borrow_mut().restart_network()
+    }
+
+    /// Enable or disable automatic reconnection after the connection to a group has been lost.
+    /// The reconnections run on the event loop, so they have to be enabled from it.
+    pub fn set_auto_reconnect(&mut self, enabled: bool) {
+        self.inner
+            .borrow()
+            .auto_reconnect
+            .store(enabled, Ordering::SeqCst);
+
+        if enabled {
+            self.watch_lost_connections();
+        }
+    }
+
+    // Restart the network whenever a group reports it's lost its connection, as long as automatic
+    // reconnection is enabled.
+    fn watch_lost_connections(&mut self) {
+        let lost_rx = match self.inner.borrow_mut().lost_rx.take() {
+            Some(lost_rx) => lost_rx,
+            None => return, // Already watching.
+        };
+        let inner_weak = Rc::downgrade(&self.inner);
+
+        let watcher = lost_rx.for_each(move |()| {
+            if let Some(restarted) = restart_lost_connections(&inner_weak) {
+                let restarted =
+                    restarted.map_err(|error| warn!("Failed to reconnect: {:?}", error));
+                TaskExecutor::current()
+                    .spawn_local(Box::new(restarted))
+                    .map_err(|error| warn!("Could not schedule reconnection: {:?}", error))?;
+            }
+            Ok(())
+        });
+
+        if let Err(error) = TaskExecutor::current().spawn_local(Box::new(watcher)) {
+            warn!(
+                "Automatic reconnection is only available on the event loop: {:?}",
+                error
+            );
+        }
+    }
+
+    /// Set the number of elders that have to agree on a response before it's accepted.
+    /// Defaults to `Quorum::Majority`.
+    pub fn set_quorum(&mut self, quorum: Quorum) {
+        self.inner.borrow_mut().set_quorum(quorum)
+    }
+
+    /// Returns the number of elders that have to agree on a response before it's accepted.
+    pub fn quorum(&self) -> Quorum {
+        self.inner.borrow().quorum
+    }
+
+    /// Set how many times a request which couldn't be delivered to an elder is sent again before
+    /// it fails with `CoreError::EldersUnreachable`.
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.inner.borrow_mut().set_max_retries(max_retries)
+    }
+
+    /// Enable or disable the bootstrap cache. When enabled (the default), the contacts we've
+    /// bootstrapped to before are tried first, and new ones are added to the cache. The sections
+    /// are saved to the config directory as well once a group has connected, so that the elders
+    /// learned from the network are known to the next clients.
+    pub fn set_use_bootstrap_cache(&mut self, enabled: bool) {
+        self.inner.borrow_mut().use_bootstrap_cache = enabled;
     }
 
     /// Disconnect from a group.
@@ -75,7 +213,26 @@ impl ConnectionManager {
 struct Inner {
     config: QuicP2pConfig,
     groups: HashMap<PublicId, ConnectionGroup>,
+    // Groups connected to the sections managing data, other than the section serving the ID.
+    section_groups: HashMap<(PublicId, Prefix), SectionGroup>,
+    this: Weak<RefCell<Inner>>,
+    section_map: Arc<Mutex<SectionMap>>,
+    use_bootstrap_cache: bool,
     net_tx: NetworkTx,
+    auto_reconnect: Arc<AtomicBool>,
+    // Groups report having lost their connection through this channel.
+    lost_tx: UnboundedSender<()>,
+    // Taken once automatic reconnection is enabled for the first time.
+    lost_rx: Option<UnboundedReceiver<()>>,
+    quorum: Quorum,
+    max_retries: u32,
+}
+
+// Group connected to the elders of another section.
+struct SectionGroup {
+    group: ConnectionGroup,
+    // Elders of the section at the time the group was created.
+    elders: HashSet<NodeInfo>,
 }
 
 impl Drop for Inner {
@@ -90,31 +247,80 @@ impl Inner {
     fn bootstrap(&mut self, full_id: SafeKey) -> Box<CoreFuture<()>> {
         trace!("Trying to bootstrap with group {:?}", full_id.public_id());
 
-        let elders = Default::default();
+        if self.groups.contains_key(&full_id.public_id()) {
+            trace!("Group {} is already connected", full_id.public_id());
+            return ok!(());
+        }
+
+        let (prefix, elders) = {
+            let section_map = unwrap!(self.section_map.lock());
+            section_map
+                .section_for(full_id.public_id().name())
+                .map(|(prefix, elders)| (prefix, elders.clone()))
+                .unwrap_or_default()
+        };
+
+        let pub_id = full_id.public_id();
+        let net_tx = self.net_tx.clone();
+        let (group, connected) = fry!(self.connect_group(full_id, prefix, elders, Some(net_tx)));
+        let _ = self.groups.insert(pub_id, group);
+        connected
+    }
+
+    // Create a group connecting to the given elders of the section with the given prefix.
+    // Returns the group along with a future resolving once it's connected. The group reports
+    // losing its connection through `net_tx`, if set.
+    fn connect_group(
+        &self,
+        full_id: SafeKey,
+        prefix: Prefix,
+        elders: HashSet<NodeInfo>,
+        net_tx: Option<NetworkTx>,
+    ) -> Result<(ConnectionGroup, Box<CoreFuture<()>>), CoreError> {
+        trace!(
+            "Connecting to {} elder(s) of section {:?}",
+            elders.len(),
+            prefix
+        );
 
         let (connected_tx, connected_rx) = futures::oneshot();
+        let group = ConnectionGroup::new(
+            self.config.clone(),
+            full_id,
+            prefix,
+            self.section_map.clone(),
+            elders,
+            self.use_bootstrap_cache,
+            connected_tx,
+            net_tx,
+            self.auto_reconnect.clone(),
+            self.lost_tx.clone(),
+            self.quorum,
+            self.max_retries,
+        )?;
+        let connected = connected_rx
+            .map_err(|err| CoreError::from(format!("{}", err)))
+            .and_then(|res| res)
+            .timeout(Duration::from_secs(CONNECTION_TIMEOUT_SECS))
+            .map_err(|_e| CoreError::RequestTimeout);
 
-        if let Entry::Vacant(value) = self.groups.entry(full_id.public_id()) {
-            let _ = value.insert(fry!(ConnectionGroup::new(
-                self.config.clone(),
-                full_id,
-                elders,
-                connected_tx
-            )));
-            Box::new(
-                connected_rx
-                    .map_err(|err| CoreError::from(format!("{}", err)))
-                    .and_then(|res| res)
-                    .timeout(Duration::from_secs(CONNECTION_TIMEOUT_SECS))
-                    .map_err(|_e| CoreError::RequestTimeout),
-            )
+        if self.use_bootstrap_cache {
+            let section_map = self.section_map.clone();
+            Ok((
+                group,
+                Box::new(connected.map(move |()| save_sections(&section_map))),
+            ))
         } else {
-            trace!("Group {} is already connected", full_id.public_id());
-            ok!(())
+            Ok((group, Box::new(connected)))
         }
     }
 
-    fn send(&mut self, pub_id: &PublicId, msg: &Message) -> Box<CoreFuture<Response>> {
+    fn send(
+        &mut self,
+        pub_id: &PublicId,
+        msg: &Message,
+        timeout: Duration,
+    ) -> Box<CoreFuture<Response>> {
         let msg_id = if let Message::Request { message_id, .. } = msg {
             *message_id
         } else {
@@ -123,19 +329,159 @@ impl Inner {
             )));
         };
 
+        if let Some(section_group) = self.section_group(pub_id, msg) {
+            return section_group.send(msg_id, msg, timeout);
+        }
+
         let conn_group = fry!(self.groups.get_mut(&pub_id).ok_or_else(|| {
             CoreError::Unexpected(
                 "No connection group found - did you call `bootstrap`?".to_string(),
             )
         }));
 
-        conn_group.send(msg_id, msg)
+        conn_group.send(msg_id, msg, timeout)
+    }
+
+    // Returns the group connected to the section managing the data the request is about, if it's
+    // known and isn't the section serving `pub_id`. The group is created the first time it's
+    // needed, and created again once the section has elders it didn't know about. Requests go
+    // through the group serving `pub_id` until it's connected. A group which fails to connect
+    // within `CONNECTION_TIMEOUT_SECS` is dropped, so that the next request creates a new one.
+    fn section_group(&mut self, pub_id: &PublicId, msg: &Message) -> Option<&mut ConnectionGroup> {
+        let name = data_name(msg)?;
+        let (full_id, own_prefix) = {
+            let group = self.groups.get(pub_id)?;
+            (group.full_id(), group.prefix())
+        };
+
+        let (prefix, elders) = {
+            let section_map = unwrap!(self.section_map.lock());
+            let (prefix, elders) = section_map.section_for(&name)?;
+            (prefix, elders.clone())
+        };
+        if prefix == own_prefix || prefix.matches(pub_id.name()) {
+            return None;
+        }
+
+        let key = (pub_id.clone(), prefix);
+        let is_current = self
+            .section_groups
+            .get(&key)
+            .map_or(false, |section| elders.is_subset(&section.elders));
+        if !is_current {
+            if self.section_groups.remove(&key).is_some() {
+                trace!("Elders of section {:?} changed - reconnecting", prefix);
+            }
+
+            // Losing the connection to another section doesn't disconnect the client, so it isn't
+            // reported.
+            let (group, connected) = match self.connect_group(full_id, prefix, elders.clone(), None)
+            {
+                Ok(result) => result,
+                Err(error) => {
+                    warn!("Could not connect to section {:?}: {:?}", prefix, error);
+                    return None;
+                }
+            };
+            self.watch_section_group(key.clone(), group.id(), connected);
+            let _ = self
+                .section_groups
+                .insert(key.clone(), SectionGroup { group, elders });
+        }
+
+        self.section_groups
+            .get_mut(&key)
+            .map(|section| &mut section.group)
+            .filter(|group| group.is_connected())
+    }
+
+    // Drive the future connecting the section group with the given ID on the event loop, and drop
+    // the group if it fails to connect.
+    fn watch_section_group(
+        &self,
+        key: (PublicId, Prefix),
+        id: u64,
+        connected: Box<CoreFuture<()>>,
+    ) {
+        let inner_weak = self.this.clone();
+        let watcher = connected.map_err(move |error| {
+            warn!("Could not connect to section {:?}: {:?}", key.1, error);
+            if let Some(inner) = inner_weak.upgrade() {
+                let mut inner = inner.borrow_mut();
+                if inner
+                    .section_groups
+                    .get(&key)
+                    .map_or(false, |section| section.group.id() == id)
+                {
+                    let _ = inner.section_groups.remove(&key);
+                }
+            }
+        });
+
+        if let Err(error) = TaskExecutor::current().spawn_local(Box::new(watcher)) {
+            warn!("Could not watch the connection to a section: {:?}", error);
+        }
+    }
+
+    fn subscribe(&mut self, pub_id: &PublicId) -> Result<NotificationRx, CoreError> {
+        let conn_group = self.groups.get_mut(&pub_id).ok_or_else(|| {
+            CoreError::Unexpected(
+                "No connection group found - did you call `bootstrap`?".to_string(),
+            )
+        })?;
+
+        Ok(conn_group.subscribe())
+    }
+
+    fn set_quorum(&mut self, quorum: Quorum) {
+        self.quorum = quorum;
+        for group in self.groups.values_mut().chain(
+            self.section_groups
+                .values_mut()
+                .map(|section| &mut section.group),
+        ) {
+            group.set_quorum(quorum);
+        }
+    }
+
+    fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+        for group in self.groups.values_mut().chain(
+            self.section_groups
+                .values_mut()
+                .map(|section| &mut section.group),
+        ) {
+            group.set_max_retries(max_retries);
+        }
+    }
+
+    fn restart_network(&mut self) -> Box<CoreFuture<()>> {
+        trace!("Restarting connections to {} group(s)", self.groups.len());
+
+        let reconnections: Vec<_> = self
+            .groups
+            .values_mut()
+            .chain(
+                self.section_groups
+                    .values_mut()
+                    .map(|section| &mut section.group),
+            )
+            .map(ConnectionGroup::reconnect)
+            .collect();
+        let net_tx = self.net_tx.clone();
+
+        Box::new(future::join_all(reconnections).map(move |_| {
+            let _ = net_tx.unbounded_send(NetworkEvent::Connected);
+        }))
     }
 
     /// Disconnect from a group.
     pub fn disconnect(&mut self, pub_id: &PublicId) -> Box<CoreFuture<()>> {
         trace!("Disconnecting group {:?}", pub_id);
 
+        // Dropping the groups connected to other sections on behalf of `pub_id` disconnects them.
+        self.section_groups.retain(|(id, _), _| id != pub_id);
+
         let group = self.groups.remove(&pub_id);
 
         if let Some(mut group) = group {
@@ -150,3 +496,23 @@ impl Inner {
         }
     }
 }
+
+// Returns the future restarting the network if automatic reconnection is still enabled.
+fn restart_lost_connections(inner: &Weak<RefCell<Inner>>) -> Option<Box<CoreFuture<()>>> {
+    let inner = inner.upgrade()?;
+    let mut inner = inner.borrow_mut();
+
+    if inner.auto_reconnect.load(Ordering::SeqCst) {
+        Some(inner.restart_network())
+    } else {
+        None
+    }
+}
+
+// Save the known sections, including the elders learned from the network, to the config directory.
+fn save_sections(section_map: &Mutex<SectionMap>) {
+    let sections = unwrap!(section_map.lock()).clone();
+    if let Err(error) = write_sections(&sections) {
+        warn!("Failed to save the known sections: {:?}", error);
+    }
+}
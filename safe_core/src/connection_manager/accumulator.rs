@@ -0,0 +1,338 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{CoreError, Quorum};
+use bincode::serialize;
+use safe_nd::{MessageId, Response};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
+
+// Responses received for a single request.
+struct Pending {
+    // Elders the request has been sent to and which haven't turned out to be unreachable.
+    recipients: HashSet<SocketAddr>,
+    // Elders the request couldn't be delivered to.
+    unreachable: HashSet<SocketAddr>,
+    // Number of matching responses required, fixed by the elders the request was first sent to
+    // so that unreachable elders don't lower it.
+    threshold: usize,
+    // Distinct responses (keyed by their serialised form) along with the elders that sent them.
+    votes: Vec<(Vec<u8>, Response, Vec<SocketAddr>)>,
+}
+
+impl Pending {
+    fn has_voted(&self, elder: &SocketAddr) -> bool {
+        self.votes
+            .iter()
+            .any(|(_, _, voters)| voters.contains(elder))
+    }
+
+    // Number of elders whose response is still expected.
+    fn remaining(&self) -> usize {
+        self.recipients
+            .iter()
+            .filter(|elder| !self.has_voted(elder))
+            .count()
+    }
+
+    // `Some(true)` once a quorum of elders agree on a response, `Some(false)` once that's no
+    // longer possible with the responses still expected, `None` while it's undecided.
+    fn decision(&self) -> Option<bool> {
+        let most_votes = self.most_voters();
+
+        if most_votes >= self.threshold {
+            Some(true)
+        } else if most_votes + self.remaining() < self.threshold {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    // Number of elders which sent the most common response.
+    fn most_voters(&self) -> usize {
+        self.votes
+            .iter()
+            .map(|(_, _, voters)| voters.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Returns `true` if the quorum could still have been reached had the unreachable elders
+    // responded, so its failure is down to them rather than to disagreeing elders.
+    fn failed_on_unreachable(&self) -> bool {
+        self.most_voters() + self.remaining() + self.unreachable.len() >= self.threshold
+    }
+
+    // Index of the response sent by the most elders. Ties go to the one received first.
+    fn most_votes(&self) -> Option<usize> {
+        (0..self.votes.len())
+            .rev()
+            .max_by_key(|index| self.votes[*index].2.len())
+    }
+
+    // Elders which sent a response different from the one at `index`.
+    fn dissenters(&self, index: usize) -> Vec<SocketAddr> {
+        self.votes
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .flat_map(|(_, (_, _, voters))| voters.iter().cloned())
+            .collect()
+    }
+}
+
+/// Accumulates the responses sent by the elders until a quorum of them agree.
+#[derive(Default)]
+pub(super) struct Accumulator {
+    requests: HashMap<MessageId, Pending>,
+}
+
+impl Accumulator {
+    /// Start accumulating responses to a request which has been sent to the given elders. The
+    /// number of matching responses required is computed from these elders and doesn't change
+    /// afterwards.
+    pub fn insert<I>(&mut self, msg_id: MessageId, recipients: I, quorum: Quorum)
+    where
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        let recipients: HashSet<_> = recipients.into_iter().collect();
+        let threshold = quorum.threshold(recipients.len());
+        let _ = self.requests.insert(
+            msg_id,
+            Pending {
+                recipients,
+                unreachable: HashSet::new(),
+                threshold,
+                votes: Vec::new(),
+            },
+        );
+    }
+
+    /// Stop accumulating responses to the given request.
+    pub fn remove(&mut self, msg_id: &MessageId) {
+        let _ = self.requests.remove(msg_id);
+    }
+
+    /// Record that the request has been sent to another elder as well.
+    pub fn add_recipient(&mut self, msg_id: MessageId, elder: SocketAddr) {
+        if let Some(pending) = self.requests.get_mut(&msg_id) {
+            let _ = pending.recipients.insert(elder);
+        }
+    }
+
+    /// Returns `true` if the request has been sent to the elder and it hasn't turned out to be
+    /// unreachable.
+    pub fn is_recipient(&self, msg_id: &MessageId, elder: &SocketAddr) -> bool {
+        self.requests
+            .get(msg_id)
+            .map_or(false, |pending| pending.recipients.contains(elder))
+    }
+
+    /// Number of elders whose response to the request is still expected.
+    pub fn remaining(&self, msg_id: &MessageId) -> usize {
+        self.requests.get(msg_id).map_or(0, Pending::remaining)
+    }
+
+    /// Number of further matching responses required to reach the quorum.
+    pub fn needed(&self, msg_id: &MessageId) -> usize {
+        self.requests.get(msg_id).map_or(0, |pending| {
+            pending.threshold.saturating_sub(pending.most_voters())
+        })
+    }
+
+    /// Returns `true` if the request couldn't be delivered to the elder.
+    pub fn is_unreachable(&self, msg_id: &MessageId, elder: &SocketAddr) -> bool {
+        self.requests
+            .get(msg_id)
+            .map_or(false, |pending| pending.unreachable.contains(elder))
+    }
+
+    /// Stop expecting a response from an elder the request couldn't be delivered to. Returns the
+    /// outcome if it's known without that elder's response: `EldersUnreachable` once the elders
+    /// left can no longer reach the quorum. Once none of the elders are left and none have
+    /// responded, the outcome is left to the caller, which can send the request to other elders.
+    pub fn remove_recipient(
+        &mut self,
+        msg_id: MessageId,
+        elder: &SocketAddr,
+    ) -> Option<Result<Response, CoreError>> {
+        {
+            let pending = self.requests.get_mut(&msg_id)?;
+            if pending.has_voted(elder) || !pending.recipients.remove(elder) {
+                return None;
+            }
+            let _ = pending.unreachable.insert(*elder);
+            if pending.recipients.is_empty() && pending.votes.is_empty() {
+                return None;
+            }
+        }
+
+        self.decide(msg_id)
+    }
+
+    /// Add a response sent by the elder at `sender`. Returns the outcome once it's known: either
+    /// the response a quorum of elders agreed on, or an error listing the elders which disagreed
+    /// if the quorum can no longer be reached.
+    pub fn add(
+        &mut self,
+        msg_id: MessageId,
+        sender: SocketAddr,
+        response: Response,
+    ) -> Option<Result<Response, CoreError>> {
+        {
+            let pending = self.requests.get_mut(&msg_id)?;
+
+            if pending.has_voted(&sender) {
+                trace!("Ignoring duplicate response from {}", sender);
+                return None;
+            }
+
+            let key = unwrap!(serialize(&response));
+            let index = match pending.votes.iter().position(|(other, ..)| *other == key) {
+                Some(index) => index,
+                None => {
+                    pending.votes.push((key, response, Vec::new()));
+                    pending.votes.len() - 1
+                }
+            };
+            pending.votes[index].2.push(sender);
+        }
+
+        self.decide(msg_id)
+    }
+
+    // Finish accumulating the responses to the request if its outcome is known.
+    fn decide(&mut self, msg_id: MessageId) -> Option<Result<Response, CoreError>> {
+        let reached_quorum = self.requests.get(&msg_id)?.decision()?;
+
+        let mut pending = self.requests.remove(&msg_id)?;
+        let index = match pending.most_votes() {
+            Some(index) if reached_quorum || !pending.failed_on_unreachable() => index,
+            _ => return Some(Err(CoreError::EldersUnreachable)),
+        };
+        let dissenters = pending.dissenters(index);
+
+        if reached_quorum {
+            if !dissenters.is_empty() {
+                warn!(
+                    "Elders {:?} disagreed with the quorum on {:?}",
+                    dissenters, msg_id
+                );
+            }
+            Some(Ok(pending.votes.swap_remove(index).1))
+        } else {
+            Some(Err(CoreError::ElderDisagreement(dissenters)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use safe_nd::Error as SndError;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn ok() -> Response {
+        Response::Mutation(Ok(()))
+    }
+
+    fn err() -> Response {
+        Response::Mutation(Err(SndError::AccessDenied))
+    }
+
+    // Test that a response is accepted only once a majority of elders agree on it.
+    #[test]
+    fn majority() {
+        let mut accumulator = Accumulator::default();
+        let msg_id = MessageId::new();
+        accumulator.insert(msg_id, vec![addr(1), addr(2), addr(3)], Quorum::Majority);
+
+        assert!(accumulator.add(msg_id, addr(1), err()).is_none());
+        assert!(accumulator.add(msg_id, addr(2), ok()).is_none());
+        // Duplicate responses from the same elder don't count.
+        assert!(accumulator.add(msg_id, addr(2), ok()).is_none());
+
+        match accumulator.add(msg_id, addr(3), ok()) {
+            Some(Ok(Response::Mutation(Ok(())))) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+        // The request is done, so further responses are ignored.
+        assert!(accumulator.add(msg_id, addr(4), ok()).is_none());
+    }
+
+    // Test that disagreeing elders are reported once the quorum can't be reached anymore.
+    #[test]
+    fn disagreement() {
+        let mut accumulator = Accumulator::default();
+        let msg_id = MessageId::new();
+        accumulator.insert(msg_id, vec![addr(1), addr(2), addr(3)], Quorum::All);
+
+        assert!(accumulator.add(msg_id, addr(1), ok()).is_none());
+        match accumulator.add(msg_id, addr(2), err()) {
+            Some(Err(CoreError::ElderDisagreement(elders))) => assert_eq!(elders, vec![addr(2)]),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+
+    // Test that elders the request couldn't be delivered to don't lower the quorum.
+    #[test]
+    fn unreachable_elders() {
+        let mut accumulator = Accumulator::default();
+        let msg_id = MessageId::new();
+        accumulator.insert(msg_id, vec![addr(1), addr(2), addr(3)], Quorum::Majority);
+
+        assert!(accumulator.add(msg_id, addr(1), ok()).is_none());
+        assert!(accumulator.remove_recipient(msg_id, &addr(2)).is_none());
+        assert_eq!(accumulator.remaining(&msg_id), 1);
+        assert_eq!(accumulator.needed(&msg_id), 1);
+
+        match accumulator.add(msg_id, addr(3), ok()) {
+            Some(Ok(Response::Mutation(Ok(())))) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        // A single response isn't accepted once the other elders turn out to be unreachable.
+        let msg_id = MessageId::new();
+        accumulator.insert(msg_id, vec![addr(1), addr(2), addr(3)], Quorum::Majority);
+
+        assert!(accumulator.add(msg_id, addr(1), ok()).is_none());
+        assert!(accumulator.remove_recipient(msg_id, &addr(2)).is_none());
+        match accumulator.remove_recipient(msg_id, &addr(3)) {
+            Some(Err(CoreError::EldersUnreachable)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        // The request fails as soon as the quorum is out of reach.
+        let msg_id = MessageId::new();
+        accumulator.insert(msg_id, vec![addr(1), addr(2), addr(3)], Quorum::All);
+
+        assert!(accumulator.add(msg_id, addr(1), ok()).is_none());
+        match accumulator.remove_recipient(msg_id, &addr(2)) {
+            Some(Err(CoreError::EldersUnreachable)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        // Once no elder is left, the caller decides what to do.
+        let msg_id = MessageId::new();
+        accumulator.insert(msg_id, vec![addr(1)], Quorum::All);
+        assert!(accumulator.remove_recipient(msg_id, &addr(1)).is_none());
+        assert_eq!(accumulator.remaining(&msg_id), 0);
+
+        accumulator.add_recipient(msg_id, addr(2));
+        match accumulator.add(msg_id, addr(2), ok()) {
+            Some(Ok(Response::Mutation(Ok(())))) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+}
@@ -6,33 +6,57 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{client::SafeKey, utils, CoreError, CoreFuture};
+use super::accumulator::Accumulator;
+use crate::{
+    client::SafeKey,
+    config_handler::{read_bootstrap_cache, update_bootstrap_cache},
+    event::{NetworkEvent, NetworkNotification, NetworkTx, NotificationRx, NotificationTx},
+    section_map::{Prefix, SectionMap},
+    utils, CoreError, CoreFuture, Quorum,
+};
 use bincode::{deserialize, serialize};
 use bytes::Bytes;
 use crossbeam_channel::{self, Receiver};
 use futures::{
-    sync::oneshot::{self, Sender},
+    future::{self, Loop},
+    sync::{
+        mpsc::{self, UnboundedSender},
+        oneshot::{self, Sender},
+    },
     Future,
 };
 use lazy_static::lazy_static;
+use lru_cache::LruCache;
 use new_rand::Rng;
 use quic_p2p::{
     self, Builder, Config as QuicP2pConfig, Error as QuicP2pError, Event, NodeInfo, Peer, QuicP2p,
     Token,
 };
-use safe_nd::{Challenge, Message, MessageId, NodePublicId, PublicId, Request, Response};
-use std::sync::atomic::{AtomicU64, Ordering};
+use safe_nd::{Challenge, Message, MessageId, NodePublicId, Notification, PublicId, Response};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::{
     collections::{HashMap, HashSet},
     net::SocketAddr,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, Weak},
     thread::{self, JoinHandle},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::prelude::FutureExt;
+use tokio::runtime::current_thread::TaskExecutor;
+use tokio::timer::Delay;
+
+/// Maximum number of bootstrap attempts made while reconnecting a group.
+pub const MAX_RECONNECT_ATTEMPTS: u32 = 10;
+/// Default number of times a request which couldn't be delivered to an elder is sent again.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+// Number of recent transaction IDs remembered to filter out duplicate notifications.
+const NOTIFIED_TRANSACTIONS_CAPACITY: usize = 100;
 
-/// Request timeout in seconds.
-pub const REQUEST_TIMEOUT_SECS: u64 = 180;
+// Delay before the first reconnection attempt. It is doubled with every failed attempt.
+const RECONNECT_BASE_DELAY_MS: u64 = 500;
+// Upper bound for the delay between two reconnection attempts.
+const RECONNECT_MAX_DELAY_MS: u64 = 30_000;
 
 lazy_static! {
     static ref GROUP_COUNTER: AtomicU64 = AtomicU64::new(0);
@@ -60,59 +84,205 @@ impl Elder {
 }
 
 /// Encapsulates multiple QUIC connections with a group of Client Handlers.
-/// Accumulates responses. During Phase 1 connects only to a single vault.
+/// Accumulates responses until a quorum of the elders agree on them.
+/// Connects to all the known elders of the section with the given prefix, or bootstraps through
+/// quic-p2p if none are known. Elders we've connected to are recorded in the section map.
+/// When bootstrapping, the contacts in the bootstrap cache are tried before the hard-coded ones.
 pub(super) struct ConnectionGroup {
     inner: Arc<Mutex<Inner>>,
 }
 
 impl ConnectionGroup {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         config: QuicP2pConfig,
         full_id: SafeKey,
-        mut elders: HashSet<NodeInfo>,
+        prefix: Prefix,
+        section_map: Arc<Mutex<SectionMap>>,
+        contacts: HashSet<NodeInfo>,
+        use_bootstrap_cache: bool,
         connection_hook: Sender<Result<(), CoreError>>,
+        net_tx: Option<NetworkTx>,
+        auto_reconnect: Arc<AtomicBool>,
+        lost_tx: UnboundedSender<()>,
+        quorum: Quorum,
+        max_retries: u32,
     ) -> Result<Self, CoreError> {
         let (event_tx, event_rx) = crossbeam_channel::unbounded();
 
-        let mut quic_p2p = Builder::new(event_tx).with_config(config).build()?;
-        quic_p2p.bootstrap();
+        let quic_p2p = Builder::new(event_tx).with_config(config.clone()).build()?;
 
         let inner = Arc::new(Mutex::new(Inner {
             quic_p2p,
+            config,
+            use_bootstrap_cache,
+            bootstrapping_from_cache: false,
             full_id,
+            prefix,
+            section_map,
+            contacts,
             hooks: Default::default(),
+            sent: Default::default(),
+            accumulator: Default::default(),
+            quorum,
+            max_retries,
+            retries: Default::default(),
+            queued: Vec::new(),
+            subscribers: Vec::new(),
+            notified_transactions: LruCache::new(NOTIFIED_TRANSACTIONS_CAPACITY),
             connection_hook: Some(connection_hook),
             disconnect_tx: None,
-            elders: elders
-                .drain()
-                .map(|node_info| (node_info.peer_addr, Elder::new(node_info)))
-                .collect(),
+            elders: Default::default(),
             id: GROUP_COUNTER.fetch_add(1, Ordering::SeqCst),
+            net_tx,
+            auto_reconnect,
+            lost_tx,
+            reconnecting: false,
+            reconnect_attempt: None,
+            attempt_hook: None,
+            reconnect_hooks: Vec::new(),
+            this: Weak::new(),
         }));
+        {
+            let mut inner_locked = unwrap!(inner.lock());
+            inner_locked.this = Arc::downgrade(&inner);
+            inner_locked.connect();
+        }
 
         let _ = setup_quic_p2p_event_loop(inner.clone(), event_rx);
 
         Ok(Self { inner })
     }
 
-    pub fn send(&mut self, msg_id: MessageId, msg: &Message) -> Box<CoreFuture<Response>> {
-        unwrap!(self.inner.lock()).send(msg_id, msg)
+    pub fn send(
+        &mut self,
+        msg_id: MessageId,
+        msg: &Message,
+        timeout: Duration,
+    ) -> Box<CoreFuture<Response>> {
+        unwrap!(self.inner.lock()).send(msg_id, msg, timeout)
+    }
+
+    /// Subscribe to the notifications sent by the elders.
+    pub fn subscribe(&mut self) -> NotificationRx {
+        let (notification_tx, notification_rx) = mpsc::unbounded();
+        unwrap!(self.inner.lock()).subscribers.push(notification_tx);
+        notification_rx
+    }
+
+    /// Our identity in this group.
+    pub fn full_id(&self) -> SafeKey {
+        unwrap!(self.inner.lock()).full_id.clone()
+    }
+
+    /// Identifier distinguishing this group from the other ones, for as long as the process runs.
+    pub fn id(&self) -> u64 {
+        unwrap!(self.inner.lock()).id
+    }
+
+    /// Prefix of the section whose elders this group connects to.
+    pub fn prefix(&self) -> Prefix {
+        unwrap!(self.inner.lock()).prefix
+    }
+
+    /// Returns `true` if we've been accepted by at least one of the elders.
+    pub fn is_connected(&self) -> bool {
+        unwrap!(self.inner.lock()).is_connected()
+    }
+
+    /// Number of requests still waiting for a response.
+    pub fn requests_in_flight(&self) -> usize {
+        unwrap!(self.inner.lock()).hooks.len()
+    }
+
+    /// Set the number of matching elder responses required to accept a response.
+    pub fn set_quorum(&mut self, quorum: Quorum) {
+        unwrap!(self.inner.lock()).quorum = quorum;
+    }
+
+    /// Set the number of times a request which couldn't be delivered is sent again.
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        unwrap!(self.inner.lock()).max_retries = max_retries;
     }
 
     /// Terminate the QUIC connections gracefully.
     pub fn close(&mut self) -> Box<CoreFuture<()>> {
         unwrap!(self.inner.lock()).close()
     }
+
+    /// Re-bootstrap the group if the connection to its elders has been lost. The returned future
+    /// resolves once we're connected again, or immediately if the group is still connected.
+    /// The bootstrap attempts are scheduled on the event loop polling the future.
+    pub fn reconnect(&mut self) -> Box<CoreFuture<()>> {
+        let inner = self.inner.clone();
+
+        let reconnected = future::lazy(move || {
+            let (reconnect_tx, reconnect_rx) = oneshot::channel();
+            if unwrap!(inner.lock()).reconnect(reconnect_tx) {
+                let attempts = reconnect_attempts(Arc::downgrade(&inner));
+                if let Err(error) = TaskExecutor::current().spawn_local(attempts) {
+                    unwrap!(inner.lock()).finish_reconnecting();
+                    return Err(CoreError::Unexpected(format!(
+                        "Could not schedule reconnection: {:?}",
+                        error
+                    )));
+                }
+            }
+            Ok(reconnect_rx)
+        });
+
+        Box::new(reconnected.and_then(|reconnect_rx| {
+            reconnect_rx
+                .map_err(|err| CoreError::from(format!("{}", err)))
+                .and_then(|res| res)
+        }))
+    }
 }
 
 struct Inner {
     quic_p2p: QuicP2p,
+    config: QuicP2pConfig,
+    use_bootstrap_cache: bool,
+    // Whether `quic_p2p` has been started with the cached contacts instead of the hard-coded ones.
+    bootstrapping_from_cache: bool,
     full_id: SafeKey,
+    prefix: Prefix,
+    section_map: Arc<Mutex<SectionMap>>,
+    // Elders of the section known when the group was created. Empty if we bootstrap instead.
+    contacts: HashSet<NodeInfo>,
     elders: HashMap<SocketAddr, Elder>,
-    hooks: HashMap<MessageId, Sender<Response>>, // to be replaced with Accumulator for multiple vaults.
+    hooks: HashMap<MessageId, Sender<Result<Response, CoreError>>>,
+    // Serialised pending requests, so that they can be sent to other elders.
+    sent: HashMap<MessageId, Bytes>,
+    accumulator: Accumulator,
+    quorum: Quorum,
+    max_retries: u32,
+    // Number of times each of the pending requests has been sent again.
+    retries: HashMap<MessageId, u32>,
+    // Requests waiting to be sent again once we're reconnected.
+    queued: Vec<(MessageId, Bytes)>,
+    subscribers: Vec<NotificationTx>,
+    // Every elder sends us the same notification, so we only pass on the first one.
+    notified_transactions: LruCache<u64, ()>,
     connection_hook: Option<Sender<Result<(), CoreError>>>,
     disconnect_tx: Option<Sender<()>>,
     id: u64,
+    // Where losing the connection is reported, unless the group is connected to another section
+    // on behalf of the client.
+    net_tx: Option<NetworkTx>,
+    auto_reconnect: Arc<AtomicBool>,
+    // Tells the connection manager we've lost the connection, so that it reconnects us if
+    // automatic reconnection is enabled.
+    lost_tx: UnboundedSender<()>,
+    // Whether the bootstrap attempts of a reconnection are being scheduled.
+    reconnecting: bool,
+    // `Some(n)` while we're trying to reconnect, `n` being the number of failed attempts so far.
+    reconnect_attempt: Option<u32>,
+    // Resolved with the outcome of the current bootstrap attempt while reconnecting.
+    attempt_hook: Option<Sender<Result<(), CoreError>>>,
+    reconnect_hooks: Vec<Sender<Result<(), CoreError>>>,
+    // Used by the quic-p2p event loops and the pending requests to refer back to the group.
+    this: Weak<Mutex<Inner>>,
 }
 
 impl Drop for Inner {
@@ -131,30 +301,234 @@ impl Inner {
         }
     }
 
-    fn send(&mut self, msg_id: MessageId, msg: &Message) -> Box<CoreFuture<Response>> {
+    fn is_connected(&self) -> bool {
+        self.elders.values().any(|elder| elder.public_id.is_some())
+    }
+
+    // Connect to every known elder of the section, or bootstrap if we don't know any.
+    fn connect(&mut self) {
+        if self.contacts.is_empty() {
+            self.bootstrap();
+            return;
+        }
+
+        for node_info in &self.contacts {
+            let _ = self
+                .elders
+                .entry(node_info.peer_addr)
+                .or_insert_with(|| Elder::new(node_info.clone()));
+            self.quic_p2p.connect_to(node_info.clone());
+        }
+    }
+
+    // Registers the hook to be resolved once we're reconnected. Returns `true` if the bootstrap
+    // attempts have to be scheduled, as no reconnection is in progress yet.
+    fn reconnect(&mut self, hook: Sender<Result<(), CoreError>>) -> bool {
+        if !self.reconnecting && self.reconnect_attempt.is_none() && self.is_connected() {
+            trace!("{}: Already connected", self.id);
+            let _ = hook.send(Ok(()));
+            return false;
+        }
+
+        self.reconnect_hooks.push(hook);
+
+        if self.reconnecting {
+            return false;
+        }
+
+        trace!("{}: Reconnecting", self.id);
+        self.reconnecting = true;
+        if self.reconnect_attempt.is_none() {
+            self.reconnect_attempt = Some(0);
+        }
+        true
+    }
+
+    // Make another bootstrap attempt. Returns the receiver of its outcome.
+    fn attempt_reconnect(&mut self, attempt: u32) -> oneshot::Receiver<Result<(), CoreError>> {
+        let (attempt_tx, attempt_rx) = oneshot::channel();
+
+        if self.reconnect_attempt.is_none() {
+            // Reconnected in the meantime.
+            let _ = attempt_tx.send(Ok(()));
+        } else {
+            trace!("{}: Bootstrap attempt {}", self.id, attempt + 1);
+            self.reconnect_attempt = Some(attempt);
+            self.attempt_hook = Some(attempt_tx);
+            self.connect();
+        }
+
+        attempt_rx
+    }
+
+    // Stop scheduling bootstrap attempts. Fails the requests waiting for the reconnection if
+    // we haven't reconnected.
+    fn finish_reconnecting(&mut self) {
+        self.reconnecting = false;
+        self.attempt_hook = None;
+
+        if let Some(attempt) = self.reconnect_attempt.take() {
+            trace!("{}: Giving up after {} attempts", self.id, attempt + 1);
+            for hook in self.reconnect_hooks.drain(..) {
+                let _ = hook.send(Err(CoreError::from("Reconnection failure".to_string())));
+            }
+            self.fail_pending_requests();
+        }
+    }
+
+    // Bootstrap through the cached contacts if there are any, or the hard-coded ones otherwise.
+    // quic-p2p only bootstraps to the contacts it's been started with, so it's restarted whenever
+    // we switch between the two.
+    fn bootstrap(&mut self) {
+        let cached = if self.use_bootstrap_cache {
+            read_bootstrap_cache()
+                .map(|cache| cache.node_infos())
+                .unwrap_or_default()
+        } else {
+            Default::default()
+        };
+
+        let started = if !cached.is_empty() {
+            trace!(
+                "{}: Bootstrapping through {} cached contact(s)",
+                self.id,
+                cached.len()
+            );
+            let config = QuicP2pConfig {
+                hard_coded_contacts: cached,
+                ..self.config.clone()
+            };
+            self.restart_quic_p2p(config, true)
+        } else if self.bootstrapping_from_cache {
+            let config = self.config.clone();
+            self.restart_quic_p2p(config, false)
+        } else {
+            true
+        };
+
+        if started {
+            self.quic_p2p.bootstrap();
+        } else {
+            self.handle_bootstrap_failure();
+        }
+    }
+
+    // Replace `quic_p2p` with a new instance using the given config. Events from the old instance
+    // are still handled until it's shut down.
+    fn restart_quic_p2p(&mut self, config: QuicP2pConfig, from_cache: bool) -> bool {
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        match Builder::new(event_tx).with_config(config).build() {
+            Ok(quic_p2p) => {
+                self.quic_p2p = quic_p2p;
+                self.bootstrapping_from_cache = from_cache;
+                if let Some(inner) = self.this.upgrade() {
+                    let _ = setup_quic_p2p_event_loop(inner, event_rx);
+                }
+                true
+            }
+            Err(error) => {
+                warn!("{}: Failed to start quic-p2p: {}", self.id, error);
+                false
+            }
+        }
+    }
+
+    fn handle_disconnected(&mut self) {
+        trace!("{}: Lost connection to all elders", self.id);
+        if let Some(ref net_tx) = self.net_tx {
+            let _ = net_tx.unbounded_send(NetworkEvent::Disconnected);
+        }
+
+        if self.auto_reconnect.load(Ordering::SeqCst) {
+            // Keep the pending requests until the connection manager has reconnected us.
+            if self.reconnect_attempt.is_none() {
+                self.reconnect_attempt = Some(0);
+            }
+            let _ = self.lost_tx.unbounded_send(());
+        } else if self.reconnect_attempt.is_none() {
+            self.fail_pending_requests();
+        }
+    }
+
+    // Fail every request still waiting for a response, as none of the elders can be reached.
+    fn fail_pending_requests(&mut self) {
+        self.queued.clear();
+        let msg_ids: Vec<_> = self.hooks.keys().cloned().collect();
+        for msg_id in msg_ids {
+            self.resolve(msg_id, Err(CoreError::EldersUnreachable));
+        }
+    }
+
+    // Complete the request with the given result and forget about it.
+    fn resolve(&mut self, msg_id: MessageId, result: Result<Response, CoreError>) {
+        let _ = self.forget(&msg_id).map(|sender| sender.send(result));
+    }
+
+    // Drop all the state kept for the request, returning its hook if it was still pending.
+    fn forget(&mut self, msg_id: &MessageId) -> Option<Sender<Result<Response, CoreError>>> {
+        self.accumulator.remove(msg_id);
+        let _ = self.sent.remove(msg_id);
+        let _ = self.retries.remove(msg_id);
+        self.queued.retain(|(queued_id, _)| queued_id != msg_id);
+        self.hooks.remove(msg_id)
+    }
+
+    fn send(
+        &mut self,
+        msg_id: MessageId,
+        msg: &Message,
+        timeout: Duration,
+    ) -> Box<CoreFuture<Response>> {
         trace!("Sending message {:?}", msg_id);
         let mut rng = new_rand::thread_rng();
 
+        // Elders which haven't accepted us yet would ignore the request, so only the connected
+        // ones count towards the quorum - unless none of them are connected yet.
+        let mut peers: Vec<_> = self
+            .elders
+            .values()
+            .filter(|elder| elder.public_id.is_some())
+            .map(Elder::peer)
+            .collect();
+        if peers.is_empty() {
+            peers = self.elders.values().map(Elder::peer).collect();
+        }
+
         let (future_tx, future_rx) = oneshot::channel();
         let _ = self.hooks.insert(msg_id, future_tx);
+        self.accumulator
+            .insert(msg_id, peers.iter().map(Peer::peer_addr), self.quorum);
 
         let bytes = Bytes::from(unwrap!(serialize(msg)));
-        {
-            for peer in self.elders.values().map(Elder::peer) {
-                let token = rng.gen();
-                self.quic_p2p.send(peer, bytes.clone(), token);
+        let _ = self.sent.insert(msg_id, bytes.clone());
+
+        if peers.is_empty() {
+            if self.reconnect_attempt.is_some() {
+                trace!("{}: Sending {:?} after reconnecting", self.id, msg_id);
+                self.queued.push((msg_id, bytes));
+            } else {
+                self.resolve(msg_id, Err(CoreError::EldersUnreachable));
             }
         }
+        for peer in peers {
+            let token = rng.gen();
+            self.quic_p2p.send(peer, bytes.clone(), token);
+        }
+
+        let guard = PendingRequest {
+            inner: self.this.clone(),
+            msg_id,
+        };
 
         Box::new(
             future_rx
-                .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
-                .map_err(|_e| CoreError::RequestTimeout), // .then(move |result| {
-                                                          //     if let Some(inner) = inner_weak.upgrade() {
-                                                          //         let _ = inner.borrow_mut().hooks.remove(&msg_id);
-                                                          //     }
-                                                          //     result
-                                                          // }),
+                .timeout(timeout)
+                .map_err(|_e| CoreError::RequestTimeout)
+                .and_then(|res| res)
+                .then(move |result| {
+                    drop(guard);
+                    result
+                }),
         )
     }
 
@@ -191,19 +565,62 @@ impl Inner {
                 info!("Received unexpected event: {}", event);
             }
             ConnectionFailure { peer_addr, err } => self.handle_connection_failure(peer_addr, err),
-            // We don't connect to peers yet, so we ignore this event.
-            ConnectedTo { peer: _peer } => (),
+            ConnectedTo {
+                peer: Peer::Node { node_info },
+            } => self.handle_connected_to(node_info),
+            ConnectedTo { .. } => info!("Received unexpected event: {}", event),
         }
     }
 
     fn handle_bootstrapped_to(&mut self, node_info: NodeInfo) {
         trace!("{}: Bootstrapped", self.id);
+
+        if self.use_bootstrap_cache {
+            // Updating the cache may have to wait for the clients of other processes, so it's done
+            // without holding on to the group.
+            let id = self.id;
+            let node_info = node_info.clone();
+            let _ = thread::spawn(move || {
+                if let Err(error) = update_bootstrap_cache(node_info) {
+                    warn!("{}: Failed to update the bootstrap cache: {:?}", id, error);
+                }
+            });
+        }
+
         let _ = self
             .elders
             .insert(node_info.peer_addr, Elder::new(node_info));
     }
 
+    fn handle_connected_to(&mut self, node_info: NodeInfo) {
+        trace!("{}: Connected to {}", self.id, node_info.peer_addr);
+        let _ = self
+            .elders
+            .entry(node_info.peer_addr)
+            .or_insert_with(|| Elder::new(node_info));
+    }
+
     fn handle_bootstrap_failure(&mut self) {
+        if self.bootstrapping_from_cache {
+            trace!(
+                "{}: Cached contacts unreachable - trying the hard-coded ones",
+                self.id
+            );
+            let config = self.config.clone();
+            if self.restart_quic_p2p(config, false) {
+                self.quic_p2p.bootstrap();
+                return;
+            }
+        }
+
+        if self.reconnect_attempt.is_some() {
+            let _ = self
+                .attempt_hook
+                .take()
+                .map(|hook| hook.send(Err(CoreError::from("Bootstrap failure".to_string()))));
+            return;
+        }
+
         let _ = self
             .connection_hook
             .take()
@@ -219,25 +636,113 @@ impl Inner {
         // TODO: check if we have handled the challenge?
 
         match deserialize(&msg) {
-            Ok(Message::Request {
-                request,
-                message_id,
-                ..
-            }) => self.handle_unsent_request(peer_addr, request, message_id, token),
-            Ok(_) => println!("Unexpected message type"),
-            Err(e) => println!("Unexpected error {:?}", e),
+            Ok(Message::Request { message_id, .. }) => {
+                self.handle_unsent_request(peer_addr, message_id, msg, token)
+            }
+            Ok(_) => warn!("{}: Unexpected message type", self.id),
+            Err(e) => warn!("{}: Unexpected error {:?}", self.id, e),
         }
     }
 
+    /// Send the request again with the same message ID to the elder it couldn't be delivered
+    /// to, as long as that elder is still connected. Otherwise, or once the retry budget is used
+    /// up, the elder no longer counts towards the quorum.
     fn handle_unsent_request(
         &mut self,
-        _peer_addr: SocketAddr,
-        _request: Request,
-        _message_id: MessageId,
-        _token: Token,
+        peer_addr: SocketAddr,
+        msg_id: MessageId,
+        msg: Bytes,
+        token: Token,
     ) {
-        trace!("{}: Not sent user message", self.id);
-        // TODO: unimplemented
+        trace!("{}: Not sent user message {:?}", self.id, msg_id);
+
+        if !self.hooks.contains_key(&msg_id) {
+            trace!("{}: {:?} is no longer pending", self.id, msg_id);
+            return;
+        }
+
+        let peer = self
+            .elders
+            .get(&peer_addr)
+            .filter(|elder| elder.public_id.is_some())
+            .map(Elder::peer);
+
+        match peer {
+            Some(peer) if self.retry(msg_id) => {
+                trace!("{}: Resending {:?} to {}", self.id, msg_id, peer_addr);
+                self.quic_p2p.send(peer, msg, token);
+            }
+            _ => self.elder_unreachable(msg_id, peer_addr),
+        }
+    }
+
+    // Stop expecting a response to the request from the given elder. If none of the elders the
+    // request has been sent to are left, send it to as many other connected elders as the quorum
+    // needs, or once we're reconnected. Fails the request once the retry budget is used up or not
+    // enough elders can be reached.
+    fn elder_unreachable(&mut self, msg_id: MessageId, peer_addr: SocketAddr) {
+        if !self.accumulator.is_recipient(&msg_id, &peer_addr) {
+            return;
+        }
+        if let Some(result) = self.accumulator.remove_recipient(msg_id, &peer_addr) {
+            self.resolve(msg_id, result);
+            return;
+        }
+        if self.accumulator.remaining(&msg_id) > 0 {
+            trace!(
+                "{}: Waiting for the other elders to respond to {:?}",
+                self.id,
+                msg_id
+            );
+            return;
+        }
+
+        let msg = match self.sent.get(&msg_id) {
+            Some(msg) => msg.clone(),
+            None => return,
+        };
+        let needed = self.accumulator.needed(&msg_id);
+        let accumulator = &self.accumulator;
+        let peers: Vec<_> = self
+            .elders
+            .values()
+            .filter(|elder| elder.public_id.is_some())
+            .map(Elder::peer)
+            .filter(|peer| !accumulator.is_unreachable(&msg_id, &peer.peer_addr()))
+            .take(needed)
+            .collect();
+
+        if !self.retry(msg_id) {
+            trace!("{}: Giving up on {:?}", self.id, msg_id);
+            self.resolve(msg_id, Err(CoreError::EldersUnreachable));
+        } else if !peers.is_empty() && peers.len() >= needed {
+            let mut rng = new_rand::thread_rng();
+            for peer in peers {
+                trace!(
+                    "{}: Sending {:?} to {} instead",
+                    self.id,
+                    msg_id,
+                    peer.peer_addr()
+                );
+                self.accumulator.add_recipient(msg_id, peer.peer_addr());
+                self.quic_p2p.send(peer, msg.clone(), rng.gen());
+            }
+        } else if self.reconnect_attempt.is_some() {
+            trace!("{}: Resending {:?} after reconnecting", self.id, msg_id);
+            self.queued.push((msg_id, msg));
+        } else {
+            self.resolve(msg_id, Err(CoreError::EldersUnreachable));
+        }
+    }
+
+    // Count another attempt at sending the request. Returns `false` if the retry budget is used up.
+    fn retry(&mut self, msg_id: MessageId) -> bool {
+        let attempts = self.retries.entry(msg_id).or_insert(0);
+        if *attempts >= self.max_retries {
+            return false;
+        }
+        *attempts += 1;
+        true
     }
 
     fn handle_new_message(&mut self, peer_addr: SocketAddr, msg: Bytes) {
@@ -262,7 +767,7 @@ impl Inner {
                     message_id,
                 }) => self.handle_response(peer_addr, message_id, response),
                 Ok(Message::Notification { notification }) => {
-                    trace!("Got transaction notification: {:?}", notification);
+                    self.handle_notification(notification)
                 }
                 Ok(_msg) => error!("Unexpected message type, expected response."),
                 Err(e) => {
@@ -285,8 +790,33 @@ impl Inner {
         }
     }
 
-    /// Handle a response from one of the elders.
-    /// `_sender` is unused because we don't need to handle elder groups during the Phase 1.
+    /// Handle a transaction notification from one of the elders.
+    fn handle_notification(&mut self, notification: Notification) {
+        let Notification(transaction) = notification;
+        trace!(
+            "{}: Got transaction notification: {:?}",
+            self.id,
+            transaction
+        );
+
+        if self
+            .notified_transactions
+            .insert(transaction.id, ())
+            .is_some()
+        {
+            return;
+        }
+
+        let notification = NetworkNotification::TransferReceived {
+            transaction_id: transaction.id,
+            amount: transaction.amount,
+        };
+        self.subscribers
+            .retain(|subscriber| subscriber.unbounded_send(notification.clone()).is_ok());
+    }
+
+    /// Handle a response from one of the elders. The request is resolved once a quorum of the
+    /// elders have sent matching responses, or failed once the quorum can't be reached anymore.
     fn handle_response(&mut self, sender_addr: SocketAddr, msg_id: MessageId, response: Response) {
         trace!(
             "{}: Response from: {:?}, msg_id: {:?}, resp: {:?}",
@@ -295,19 +825,21 @@ impl Inner {
             msg_id,
             response
         );
-        let _ = self
-            .hooks
-            .remove(&msg_id)
-            .map(|sender| sender.send(response))
-            .or_else(|| {
-                info!(
-                    "{}: {:?} - No hook found for message ID {:?}",
-                    self.id,
-                    self.full_id.public_id(),
-                    msg_id
-                );
-                None
-            });
+        if !self.hooks.contains_key(&msg_id) {
+            info!(
+                "{}: {:?} - No hook found for message ID {:?}",
+                self.id,
+                self.full_id.public_id(),
+                msg_id
+            );
+            return;
+        }
+
+        if let Some(result) = self.accumulator.add(msg_id, sender_addr, response) {
+            self.resolve(msg_id, result);
+        } else {
+            trace!("{}: Waiting for more responses to {:?}", self.id, msg_id);
+        }
     }
 
     /// Handle a challenge request from a newly-connected vault.
@@ -320,12 +852,31 @@ impl Inner {
         // safe to unwrap as we just found this elder before calling this method.
         let mut elder = unwrap!(self.elders.get_mut(&sender_addr));
         elder.public_id = Some(sender_id);
+        if let Peer::Node { ref node_info } = elder.peer {
+            unwrap!(self.section_map.lock()).add_elder(self.prefix, node_info.clone());
+        }
         let token = new_rand::thread_rng().gen();
         let response = Challenge::Response(self.full_id.public_id(), self.full_id.sign(&challenge));
         let msg = Bytes::from(unwrap!(serialize(&response)));
         self.quic_p2p.send(elder.peer.clone(), msg, token);
         // trigger the connection future
         let _ = self.connection_hook.take().map(|hook| hook.send(Ok(())));
+
+        if self.reconnect_attempt.take().is_some() {
+            trace!("{}: Reconnected", self.id);
+            let _ = self.attempt_hook.take().map(|hook| hook.send(Ok(())));
+            for hook in self.reconnect_hooks.drain(..) {
+                let _ = hook.send(Ok(()));
+            }
+
+            let peer = unwrap!(self.elders.get(&sender_addr)).peer();
+            let mut rng = new_rand::thread_rng();
+            for (msg_id, msg) in self.queued.drain(..) {
+                trace!("{}: Resending {:?}", self.id, msg_id);
+                self.accumulator.add_recipient(msg_id, sender_addr);
+                self.quic_p2p.send(peer.clone(), msg, rng.gen());
+            }
+        }
     }
 
     fn handle_connection_failure(&mut self, peer_addr: SocketAddr, err: quic_p2p::Error) {
@@ -342,9 +893,91 @@ impl Inner {
             peer_addr,
             err
         );
+
+        let was_connected = self.is_connected();
+        let elder = match self.elders.remove(&peer_addr) {
+            Some(elder) if self.disconnect_tx.is_none() => elder,
+            _ => return,
+        };
+        if elder.public_id.is_some() {
+            // The elder has gone away, so the next groups shouldn't try to connect to it.
+            unwrap!(self.section_map.lock()).remove_elder(&self.prefix, &peer_addr);
+        }
+
+        if was_connected && !self.is_connected() {
+            self.handle_disconnected();
+        } else if !was_connected && self.elders.is_empty() && !self.contacts.is_empty() {
+            // None of the known elders could be reached. There's no `BootstrapFailure` event when
+            // connecting to them directly, so we report it ourselves.
+            self.handle_bootstrap_failure();
+        }
+
+        // The requests sent to the elder won't get its response anymore.
+        let msg_ids: Vec<_> = self.hooks.keys().cloned().collect();
+        for msg_id in msg_ids {
+            self.elder_unreachable(msg_id, peer_addr);
+        }
+    }
+}
+
+// Forgets about the request once its future completes, times out or gets dropped, so that the
+// hooks of requests nobody's waiting for anymore don't pile up.
+struct PendingRequest {
+    inner: Weak<Mutex<Inner>>,
+    msg_id: MessageId,
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.upgrade() {
+            if let Ok(mut inner) = inner.lock() {
+                let _ = inner.forget(&self.msg_id);
+            }
+        }
     }
 }
 
+// Bootstraps the group until it's reconnected or `MAX_RECONNECT_ATTEMPTS` attempts have failed,
+// waiting for a growing delay before each of them.
+fn reconnect_attempts(inner: Weak<Mutex<Inner>>) -> Box<dyn Future<Item = (), Error = ()>> {
+    let inner_weak = inner.clone();
+
+    let attempts = future::loop_fn(0, move |attempt| {
+        let delay = reconnect_delay(attempt);
+        let inner = inner.clone();
+        trace!("Next bootstrap attempt in {:?}", delay);
+
+        Delay::new(Instant::now() + delay)
+            .map_err(|error| warn!("Reconnection timer failed: {}", error))
+            .and_then(move |()| {
+                let inner = inner.upgrade().ok_or(())?;
+                let outcome = unwrap!(inner.lock()).attempt_reconnect(attempt);
+                Ok(outcome)
+            })
+            .and_then(|outcome| outcome.map_err(|_| ()))
+            .map(move |result| match result {
+                Err(_) if attempt + 1 < MAX_RECONNECT_ATTEMPTS => Loop::Continue(attempt + 1),
+                _ => Loop::Break(()),
+            })
+    });
+
+    Box::new(attempts.then(move |_| {
+        if let Some(inner) = inner_weak.upgrade() {
+            unwrap!(inner.lock()).finish_reconnecting();
+        }
+        Ok(())
+    }))
+}
+
+// Returns the delay before the given reconnection attempt: exponential backoff capped at
+// `RECONNECT_MAX_DELAY_MS`, with half of it randomised so that clients don't reconnect in lockstep.
+fn reconnect_delay(attempt: u32) -> Duration {
+    let ceiling = RECONNECT_BASE_DELAY_MS
+        .saturating_mul(1 << attempt.min(16))
+        .min(RECONNECT_MAX_DELAY_MS);
+    Duration::from_millis(new_rand::thread_rng().gen_range(ceiling / 2, ceiling + 1))
+}
+
 fn setup_quic_p2p_event_loop(
     inner: Arc<Mutex<Inner>>,
     event_rx: Receiver<Event>,
@@ -369,3 +1002,26 @@ fn setup_quic_p2p_event_loop(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the delay between reconnection attempts doubles with every attempt until it
+    // reaches the upper bound, and that only its upper half is randomised.
+    #[test]
+    fn reconnect_backoff() {
+        for attempt in 0..MAX_RECONNECT_ATTEMPTS + 10 {
+            let ceiling = (RECONNECT_BASE_DELAY_MS << attempt.min(16)).min(RECONNECT_MAX_DELAY_MS);
+
+            for _ in 0..10 {
+                let delay = reconnect_delay(attempt);
+                assert!(delay >= Duration::from_millis(ceiling / 2));
+                assert!(delay <= Duration::from_millis(ceiling));
+            }
+        }
+
+        assert!(reconnect_delay(0) <= Duration::from_millis(RECONNECT_BASE_DELAY_MS));
+        assert!(reconnect_delay(100) >= Duration::from_millis(RECONNECT_MAX_DELAY_MS / 2));
+    }
+}
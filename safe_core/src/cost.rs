@@ -0,0 +1,305 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::config_handler::Pricing;
+use crate::errors::CoreError;
+use crate::immutable_data;
+use crate::nfs::File;
+use maidsafe_utilities::serialisation::serialise;
+use safe_nd::{
+    Coins, MData, MDataAddress, MDataEntryActions, MDataSeqEntryActions, MDataSeqValue,
+    PubImmutableData, PublicKey, Request, Result as SndResult, SeqMutableData, XorName,
+    XOR_NAME_LEN,
+};
+use std::collections::BTreeMap;
+use threshold_crypto::SecretKey as BlsSecretKey;
+
+// The network is never considered fuller than this, which caps the cost of a mutation at a hundred
+// times its price on an empty network.
+const MAX_FULLNESS: f64 = 0.99;
+
+/// Operation planned by an app, whose cost can be estimated before carrying it out.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PlannedOperation {
+    /// Storing a value of `len` bytes with `immutable_data::create` and putting the result.
+    PutIData {
+        /// Length of the value before self-encryption.
+        len: u64,
+    },
+    /// Putting a sequenced mutable data holding `entries` entries and no permissions.
+    PutMData {
+        /// Number of entries the data is created with.
+        entries: u64,
+        /// Total length of the keys and values of the entries.
+        size: u64,
+    },
+    /// Writing a file of `size` bytes through `nfs::Writer`, then inserting or updating its entry
+    /// in the parent directory.
+    WriteFile {
+        /// Size of the file content.
+        size: u64,
+    },
+}
+
+/// Mutation priced by a `Pricing`.
+#[derive(Clone, Copy, Debug)]
+pub struct Operation<'a> {
+    /// Name of the `Request` variant, such as `"PutIData"`.
+    pub request: &'a str,
+    /// Serialised size of the request in bytes.
+    pub size: u64,
+    /// Number of data stored by the vault.
+    pub stored_data: u64,
+}
+
+impl Pricing {
+    /// Returns the cost of the given mutation, or an error if it exceeds the coins in existence.
+    pub fn cost_of(&self, operation: &Operation) -> SndResult<Coins> {
+        let request_cost = self
+            .request_costs
+            .get(operation.request)
+            .cloned()
+            .unwrap_or(self.base_cost);
+        let kibs = (operation.size + 1023) / 1024;
+        let mut cost = request_cost.saturating_add(self.cost_per_kib.saturating_mul(kibs));
+
+        if let Some(capacity) = self.network_capacity {
+            let fullness =
+                (operation.stored_data as f64 / capacity.max(1) as f64).min(MAX_FULLNESS);
+            cost = (cost as f64 / (1.0 - fullness))
+                .round()
+                .min(u64::max_value() as f64) as u64;
+        }
+
+        Coins::from_nano(cost)
+    }
+}
+
+/// Number of mutations made by an operation and the coins they're expected to cost.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CostEstimate {
+    /// Number of charged mutations.
+    pub mutations: u64,
+    /// Expected cost of the mutations.
+    pub cost: Coins,
+}
+
+/// Estimate the mutations the operation makes and what they cost with `pricing`, as priced by a
+/// dry run. Reads are free, and so is deleting data.
+pub fn estimate(operation: PlannedOperation, pricing: &Pricing) -> Result<CostEstimate, CoreError> {
+    // Name and serialised size of each request the operation makes.
+    let requests = match operation {
+        PlannedOperation::PutIData { len } => {
+            put_idata_requests(immutable_data::chunk_sizes(len)?)?
+        }
+        PlannedOperation::PutMData { entries, size } => {
+            // The entries are stored by the same request as the data.
+            vec![("PutMData", put_mdata_size(entries, size)?)]
+        }
+        PlannedOperation::WriteFile { size } => {
+            // The chunks of the content, then the data map stored as immutable data, then the
+            // entry of the file in its directory.
+            let data_map = serialise(&immutable_data::sample_data_map(size))?;
+            let mut chunk_sizes = immutable_data::self_encrypted_chunk_sizes(size);
+            chunk_sizes.extend(immutable_data::chunk_sizes(data_map.len() as u64)?);
+            let mut requests = put_idata_requests(chunk_sizes)?;
+            requests.push(("MutateMDataEntries", file_entry_size()?));
+            requests
+        }
+    };
+
+    let mut cost: u64 = 0;
+    for &(request, size) in &requests {
+        let operation = Operation {
+            request,
+            size,
+            stored_data: 0,
+        };
+        cost = cost.saturating_add(pricing.cost_of(&operation)?.as_nano());
+    }
+
+    Ok(CostEstimate {
+        mutations: requests.len() as u64,
+        cost: Coins::from_nano(cost)?,
+    })
+}
+
+// Returns the requests putting published chunks with values of the given lengths. The length of a
+// value is serialised with a fixed size, so each request is as large as an empty chunk's plus the
+// length of its value.
+fn put_idata_requests(chunk_sizes: Vec<u64>) -> Result<Vec<(&'static str, u64)>, CoreError> {
+    let empty = serialise(&Request::PutIData(PubImmutableData::new(Vec::new()).into()))?;
+    Ok(chunk_sizes
+        .into_iter()
+        .map(|len| ("PutIData", empty.len() as u64 + len))
+        .collect())
+}
+
+// Returns the serialised size of the request putting sequenced mutable data with `entries` entries
+// whose keys and values add up to `size` bytes, from the sizes of the requests putting data with
+// none and one empty entry.
+fn put_mdata_size(entries: u64, size: u64) -> Result<u64, CoreError> {
+    let owner = PublicKey::Bls(BlsSecretKey::random().public_key());
+    let request_size = |data_entries| -> Result<u64, CoreError> {
+        let data = SeqMutableData::new_with_data(
+            XorName([0; XOR_NAME_LEN]),
+            0,
+            data_entries,
+            BTreeMap::new(),
+            owner,
+        );
+        Ok(serialise(&Request::PutMData(MData::Seq(data)))?.len() as u64)
+    };
+
+    let mut entry = BTreeMap::new();
+    let _ = entry.insert(
+        Vec::new(),
+        MDataSeqValue {
+            data: Vec::new(),
+            version: 0,
+        },
+    );
+    let empty = request_size(BTreeMap::new())?;
+    let entry_overhead = request_size(entry)? - empty;
+
+    Ok(empty + entries * entry_overhead + size)
+}
+
+// Returns the serialised size of the request inserting a file in its directory. The name of the
+// file isn't known, and neither is whether the directory encrypts its entries, so the entry is
+// priced with an empty key and the plain serialised file as its value.
+fn file_entry_size() -> Result<u64, CoreError> {
+    let file = serialise(&File::new(Vec::new(), false))?;
+    let request = Request::MutateMDataEntries {
+        address: MDataAddress::Seq {
+            name: XorName([0; XOR_NAME_LEN]),
+            tag: 0,
+        },
+        actions: MDataEntryActions::Seq(MDataSeqEntryActions::new().ins(Vec::new(), file, 0)),
+    };
+    Ok(serialise(&request)?.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{DryRunTally, COST_OF_PUT};
+    use crate::utils::test_utils::random_client;
+    use futures::Future;
+
+    fn mutations(operation: PlannedOperation) -> u64 {
+        unwrap!(estimate(operation, &Pricing::default())).mutations
+    }
+
+    fn sized_pricing() -> Pricing {
+        let mut request_costs = BTreeMap::new();
+        let _ = request_costs.insert("PutIData".to_string(), 10);
+        Pricing {
+            base_cost: 2,
+            request_costs,
+            cost_per_kib: 1,
+            network_capacity: Some(100),
+        }
+    }
+
+    // Test the number of mutations estimated for each kind of operation.
+    #[test]
+    fn estimates() {
+        // Small values are kept in the data map, so only the data map is stored.
+        assert_eq!(mutations(PlannedOperation::PutIData { len: 100 }), 1);
+        // Values are split into at least three chunks...
+        assert_eq!(mutations(PlannedOperation::PutIData { len: 10 * 1024 }), 4);
+        // ...and chunks are at most 1 MiB.
+        assert_eq!(
+            mutations(PlannedOperation::PutIData {
+                len: 5 * 1024 * 1024 + 1
+            }),
+            7
+        );
+
+        assert_eq!(
+            mutations(PlannedOperation::PutMData {
+                entries: 0,
+                size: 0
+            }),
+            1
+        );
+        assert_eq!(
+            mutations(PlannedOperation::PutMData {
+                entries: 100,
+                size: 10 * 1024
+            }),
+            1
+        );
+
+        assert_eq!(mutations(PlannedOperation::WriteFile { size: 0 }), 2);
+        assert_eq!(
+            mutations(PlannedOperation::WriteFile { size: 10 * 1024 }),
+            5
+        );
+
+        let operation = PlannedOperation::WriteFile { size: 10 * 1024 };
+        let flat = unwrap!(estimate(operation, &Pricing::default()));
+        assert_eq!(flat.cost.as_nano(), 5 * COST_OF_PUT.as_nano());
+
+        // The chunks are priced at 10 coins plus 1 per started KiB: three chunks of a bit over
+        // 3 KiB and a data map of less than 1 KiB. The entry of the file costs 2 coins plus 1 KiB.
+        let sized = unwrap!(estimate(operation, &sized_pricing()));
+        assert_eq!(sized.cost.as_nano(), 3 * 14 + 11 + 3);
+    }
+
+    // Test that the estimate of putting mutable data adds up to what a dry run charges for it.
+    #[test]
+    fn mdata_estimate_matches_dry_run() {
+        let pricing = sized_pricing();
+        let entries = (0..20u8)
+            .map(|index| {
+                let value = MDataSeqValue {
+                    data: vec![index; 100],
+                    version: 0,
+                };
+                (vec![index; 10], value)
+            })
+            .collect();
+        let data = SeqMutableData::new_with_data(
+            XorName(rand::random()),
+            15_000,
+            entries,
+            BTreeMap::new(),
+            PublicKey::Bls(BlsSecretKey::random().public_key()),
+        );
+
+        let mut tally = DryRunTally::new(pricing.clone());
+        let _ = tally.record(&Request::PutMData(MData::Seq(data)));
+
+        let operation = PlannedOperation::PutMData {
+            entries: 20,
+            size: 20 * 110,
+        };
+        assert_eq!(
+            unwrap!(tally.estimate()),
+            unwrap!(estimate(operation, &pricing))
+        );
+    }
+
+    // Test that the estimate of storing a value adds up to what a dry run charges for the data
+    // `immutable_data::create` returns.
+    #[test]
+    fn idata_estimate_matches_dry_run() {
+        let pricing = sized_pricing();
+        let expected = unwrap!(estimate(PlannedOperation::PutIData { len: 2000 }, &pricing));
+
+        random_client(move |client| {
+            immutable_data::create(client, &[1; 2000], true, None).map(move |data| {
+                let mut tally = DryRunTally::new(pricing);
+                let _ = tally.record(&Request::PutIData(data));
+                assert_eq!(unwrap!(tally.estimate()), expected);
+            })
+        });
+    }
+}
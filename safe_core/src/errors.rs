@@ -14,6 +14,7 @@ use self_encryption::SelfEncryptionError;
 use std::error::Error as StdError;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::io;
+use std::net::SocketAddr;
 use std::sync::mpsc;
 
 /// Client Errors
@@ -58,6 +59,10 @@ pub enum CoreError {
     IoError(io::Error),
     /// QuicP2p error.
     QuicP2p(quic_p2p::Error),
+    /// The elders couldn't agree on a response. Contains the addresses of the dissenting elders.
+    ElderDisagreement(Vec<SocketAddr>),
+    /// The request couldn't be delivered to any of the elders.
+    EldersUnreachable,
 }
 
 impl<'a> From<&'a str> for CoreError {
@@ -165,6 +170,10 @@ impl Debug for CoreError {
             }
             Self::IoError(ref error) => write!(formatter, "CoreError::IoError -> {:?}", error),
             Self::QuicP2p(ref error) => write!(formatter, "CoreError::QuicP2p -> {:?}", error),
+            Self::ElderDisagreement(ref elders) => {
+                write!(formatter, "CoreError::ElderDisagreement -> {:?}", elders)
+            }
+            Self::EldersUnreachable => write!(formatter, "CoreError::EldersUnreachable"),
         }
     }
 }
@@ -210,6 +219,10 @@ impl Display for CoreError {
             Self::ConfigError(ref error) => write!(formatter, "Config file error: {}", error),
             Self::IoError(ref error) => write!(formatter, "Io error: {}", error),
             Self::QuicP2p(ref error) => write!(formatter, "QuicP2P error: {}", error),
+            Self::ElderDisagreement(ref elders) => {
+                write!(formatter, "Elders {:?} disagreed on the response", elders)
+            }
+            Self::EldersUnreachable => write!(formatter, "None of the elders could be reached"),
         }
     }
 }
@@ -236,6 +249,8 @@ impl StdError for CoreError {
             Self::ConfigError(ref error) => error.description(),
             Self::IoError(ref error) => error.description(),
             Self::QuicP2p(ref error) => error.description(),
+            Self::ElderDisagreement(_) => "Elders disagreed on the response",
+            Self::EldersUnreachable => "None of the elders could be reached",
         }
     }
 
@@ -7,6 +7,8 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use futures::sync::mpsc;
+use safe_nd::Coins;
+use serde::{Deserialize, Serialize};
 
 /// Network Events will be translated into values starting from this number for
 /// propagating them beyond the FFI boudaries when required
@@ -32,7 +34,23 @@ impl Into<i32> for NetworkEvent {
     }
 }
 
+/// Notifications pushed by the network to the clients which subscribed to them.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub enum NetworkNotification {
+    /// Coins have been transferred to the client's balance.
+    TransferReceived {
+        /// ID of the transaction.
+        transaction_id: u64,
+        /// Amount of coins that have been received.
+        amount: Coins,
+    },
+}
+
 /// `NetworkEvent` receiver stream.
 pub type NetworkRx = mpsc::UnboundedReceiver<NetworkEvent>;
 /// `NetworkEvent` transmitter.
 pub type NetworkTx = mpsc::UnboundedSender<NetworkEvent>;
+/// `NetworkNotification` receiver stream.
+pub type NotificationRx = mpsc::UnboundedReceiver<NetworkNotification>;
+/// `NetworkNotification` transmitter.
+pub type NotificationTx = mpsc::UnboundedSender<NetworkNotification>;
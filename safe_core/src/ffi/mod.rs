@@ -16,9 +16,44 @@ pub mod arrays;
 pub mod ipc;
 /// NFS API.
 pub mod nfs;
+/// Wallet API.
+pub mod wallet;
+/// XOR-URL API.
+pub mod xor_url;
 
 use self::arrays::*;
+use crate::client::Client;
+use crate::errors::CoreError;
+use crate::ipc::IpcError;
+use ffi_utils::{ErrorCode, StringError};
+use futures::Future;
+use maidsafe_utilities::serialisation::SerialisationError;
 use safe_nd::MDataKind as NativeMDataKind;
+use std::ffi::NulError;
+use std::fmt::{Debug, Display};
+use std::os::raw::c_char;
+
+/// Front end the FFI functions shared by apps and the authenticator are called through. The FFI
+/// functions of each front end are thin wrappers around the shared ones.
+pub trait FfiHost {
+    /// Client of the front end.
+    type Client: Client;
+    /// Error reported to the callbacks.
+    type Error: Debug
+        + Display
+        + ErrorCode
+        + From<CoreError>
+        + From<IpcError>
+        + From<NulError>
+        + From<SerialisationError>
+        + From<StringError>
+        + 'static;
+
+    /// Run `f` on the event loop of the front end.
+    fn send<F>(&self, f: F) -> Result<(), Self::Error>
+    where
+        F: FnOnce(&Self::Client) -> Option<Box<dyn Future<Item = (), Error = ()>>> + Send + 'static;
+}
 
 /// FFI wrapper for `MDataInfo`.
 #[repr(C)]
@@ -50,6 +85,15 @@ pub struct MDataInfo {
     pub new_enc_nonce: SymNonce,
 }
 
+/// FFI wrapper for a contact in the bootstrap cache.
+#[repr(C)]
+pub struct BootstrapContact {
+    /// Socket address of the contact, such as "127.0.0.1:5000".
+    pub peer_addr: *const c_char,
+    /// When we last bootstrapped to the contact, in seconds since the UNIX epoch.
+    pub last_success: i64,
+}
+
 // TODO: Implement `into_repr_c` for MDataKind once we move FfiMDataKind to safe-nd.
 /// Convert from native to FFI representation for MDataKind.
 pub fn md_kind_into_repr_c(kind: NativeMDataKind) -> bool {
@@ -0,0 +1,434 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::arrays::{BlsPublicKey, XorNameArray};
+use crate::client::Client;
+use crate::config_handler::default_transfer_journal_path;
+use crate::errors::CoreError;
+use crate::event_loop::CoreFuture;
+use crate::ffi::FfiHost;
+use crate::utils::FutureExt;
+use crate::wallet::{
+    self, HistoryFormat, TransferDirection, TransferJournal,
+    TransferRecord as NativeTransferRecord, Wallet, WalletStore,
+};
+use ffi_utils::{
+    catch_unwind_cb, from_c_str, vec_clone_from_raw_parts, FfiResult, OpaqueCtx, SafePtr,
+    StringError, FFI_RESULT_OK,
+};
+use futures::Future;
+use maidsafe_utilities::serialisation::deserialise;
+use safe_nd::{Coins, XorName};
+use std::ffi::{CString, NulError};
+use std::os::raw::{c_char, c_void};
+use std::path::PathBuf;
+use std::ptr;
+use threshold_crypto::SecretKey as BlsSecretKey;
+
+/// FFI-wrapper for `TransferRecord`.
+#[repr(C)]
+pub struct TransferRecord {
+    /// ID of the transaction.
+    pub transaction_id: u64,
+    /// Whether the coins were received by the wallet rather than sent from it.
+    pub received: bool,
+    /// Name of the wallet's balance the coins were sent from or received by.
+    pub balance: *const c_char,
+    /// Flag indicating whether `counterparty` is set. It isn't for received transfers.
+    pub has_counterparty: bool,
+    /// Balance the coins were sent to. Meaningful only if `has_counterparty` is `true`.
+    pub counterparty: XorNameArray,
+    /// Amount of coins transferred, in nano coins.
+    pub amount: u64,
+    /// Note attached to the transfer, or null if there's none.
+    pub memo: *const c_char,
+    /// When the transfer was recorded, in seconds since the UNIX epoch.
+    pub timestamp: i64,
+    /// Whether the transfer was recorded before being sent and its outcome isn't known yet.
+    pub pending: bool,
+}
+
+/// Balance of a wallet.
+#[repr(C)]
+pub struct WalletBalance {
+    /// Name of the balance in the wallet.
+    pub name: *const c_char,
+    /// Public key of the balance.
+    pub public_key: BlsPublicKey,
+}
+
+/// Calls `f` with the FFI-wrappers of the transfers, which are only valid during the call.
+pub fn with_transfer_records<F, R>(records: &[NativeTransferRecord], f: F) -> Result<R, NulError>
+where
+    F: FnOnce(&[TransferRecord]) -> R,
+{
+    let strings = records
+        .iter()
+        .map(|record| {
+            let balance = CString::new(record.balance.clone())?;
+            let memo = record.memo.clone().map(CString::new).transpose()?;
+            Ok((balance, memo))
+        })
+        .collect::<Result<Vec<_>, NulError>>()?;
+
+    let records: Vec<_> = records
+        .iter()
+        .zip(&strings)
+        .map(|(record, (balance, memo))| TransferRecord {
+            transaction_id: record.transaction_id,
+            received: record.direction == TransferDirection::Received,
+            balance: balance.as_ptr(),
+            has_counterparty: record.counterparty.is_some(),
+            counterparty: record.counterparty.map(|name| name.0).unwrap_or_default(),
+            amount: record.amount.as_nano(),
+            memo: memo.as_ref().map_or(ptr::null(), |memo| memo.as_ptr()),
+            timestamp: record.timestamp.timestamp(),
+            pending: record.pending,
+        })
+        .collect();
+
+    Ok(f(&records))
+}
+
+/// Calls `f` with the FFI-wrappers of the balances of the wallet, which are only valid during
+/// the call.
+pub fn with_wallet_balances<F, R>(wallet: &Wallet, f: F) -> Result<R, NulError>
+where
+    F: FnOnce(&[WalletBalance]) -> R,
+{
+    let balances = wallet
+        .balances()
+        .into_iter()
+        .map(|(name, public_key)| Ok((CString::new(name)?, public_key.to_bytes())))
+        .collect::<Result<Vec<_>, NulError>>()?;
+
+    let balances: Vec<_> = balances
+        .iter()
+        .map(|(name, public_key)| WalletBalance {
+            name: name.as_ptr(),
+            public_key: *public_key,
+        })
+        .collect();
+
+    Ok(f(&balances))
+}
+
+/// Add a balance to the wallet of `host`. See `app_wallet_add_balance`.
+pub unsafe fn add_balance<H: FfiHost>(
+    host: *const H,
+    store_path: *const c_char,
+    name: *const c_char,
+    secret_key: *const u8,
+    secret_key_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        public_key: *const BlsPublicKey,
+    ),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, H::Error> {
+        let store = wallet_store(store_path)?;
+        let name = from_c_str(name)?;
+        let secret_key: BlsSecretKey = if secret_key.is_null() || secret_key_len == 0 {
+            BlsSecretKey::random()
+        } else {
+            deserialise(&vec_clone_from_raw_parts(secret_key, secret_key_len))?
+        };
+
+        (*host).send(move |client| {
+            wallet::update(client, store, move |wallet| {
+                wallet.add_balance(&name, secret_key.clone())?;
+                Ok(secret_key.public_key())
+            })
+            .map_err(H::Error::from)
+            .map(move |public_key| {
+                o_cb(user_data.0, FFI_RESULT_OK, &public_key.to_bytes());
+            })
+            .map_err(move |e| {
+                call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+            })
+            .into_box()
+            .into()
+        })
+    })
+}
+
+/// List the balances of the wallet of `host`. See `app_wallet_balances`.
+pub unsafe fn balances<H: FfiHost>(
+    host: *const H,
+    store_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        balances: *const WalletBalance,
+        balances_len: usize,
+    ),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, H::Error> {
+        let store = wallet_store(store_path)?;
+
+        (*host).send(move |client| {
+            Wallet::load(client, &store)
+                .map_err(H::Error::from)
+                .and_then(move |wallet| {
+                    with_wallet_balances(&wallet, |balances| {
+                        o_cb(
+                            user_data.0,
+                            FFI_RESULT_OK,
+                            balances.as_safe_ptr(),
+                            balances.len(),
+                        );
+                    })?;
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get the amount of nano coins held by a balance of the wallet of `host`. See
+/// `app_wallet_get_balance`.
+pub unsafe fn get_balance<H: FfiHost>(
+    host: *const H,
+    store_path: *const c_char,
+    name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, amount: u64),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, H::Error> {
+        let store = wallet_store(store_path)?;
+        let name = from_c_str(name)?;
+
+        (*host).send(move |client| {
+            let client = client.clone();
+
+            Wallet::load(&client, &store)
+                .and_then(move |wallet| wallet.get_balance(&client, &name))
+                .map_err(H::Error::from)
+                .map(move |amount| {
+                    o_cb(user_data.0, FFI_RESULT_OK, amount.as_nano());
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Transfer coins from a balance of the wallet of `host`. See `app_wallet_transfer_coins`.
+pub unsafe fn transfer_coins<H: FfiHost>(
+    host: *const H,
+    store_path: *const c_char,
+    from: *const c_char,
+    destination: *const XorNameArray,
+    amount: u64,
+    memo: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, transaction_id: u64),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, H::Error> {
+        let store = wallet_store(store_path)?;
+        let from = from_c_str(from)?;
+        let destination = XorName(*destination);
+        let amount = Coins::from_nano(amount).map_err(CoreError::from)?;
+        let memo = optional_c_str(memo)?;
+        let journal = TransferJournal::new(default_transfer_journal_path()?);
+
+        (*host).send(move |client| {
+            wallet::transfer_coins(client, store, journal, from, destination, amount, memo)
+                .map_err(H::Error::from)
+                .map(move |record| {
+                    o_cb(user_data.0, FFI_RESULT_OK, record.transaction_id);
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Record coins received by a balance of the wallet of `host`. See
+/// `app_wallet_record_received`.
+pub unsafe fn record_received<H: FfiHost>(
+    host: *const H,
+    store_path: *const c_char,
+    balance: *const c_char,
+    transaction_id: u64,
+    amount: u64,
+    memo: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, H::Error> {
+        let store = wallet_store(store_path)?;
+        let balance = from_c_str(balance)?;
+        let amount = Coins::from_nano(amount).map_err(CoreError::from)?;
+        let memo = optional_c_str(memo)?;
+
+        (*host).send(move |client| {
+            wallet::update(client, store, move |wallet| {
+                wallet.record_received(&balance, transaction_id, amount, memo.clone())
+            })
+            .map_err(H::Error::from)
+            .then(move |res| {
+                call_result_cb!(res, user_data, o_cb);
+                Ok(())
+            })
+            .into_box()
+            .into()
+        })
+    })
+}
+
+/// Resolve the pending transfers of the wallet of `host`. See `app_wallet_reconcile`.
+pub unsafe fn reconcile<H: FfiHost>(
+    host: *const H,
+    store_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, H::Error> {
+        let store = wallet_store(store_path)?;
+        let journal = TransferJournal::new(default_transfer_journal_path()?);
+
+        (*host).send(move |client| {
+            wallet::reconcile(client, store, journal)
+                .map(|_| ())
+                .map_err(H::Error::from)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get the transfers recorded in the wallet of `host`. See `app_wallet_history`.
+pub unsafe fn history<H: FfiHost>(
+    host: *const H,
+    store_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        transfers: *const TransferRecord,
+        transfers_len: usize,
+    ),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, H::Error> {
+        let store = wallet_store(store_path)?;
+
+        (*host).send(move |client| {
+            load_history(client, &store)
+                .map_err(H::Error::from)
+                .and_then(move |history| {
+                    with_transfer_records(&history, |transfers| {
+                        o_cb(
+                            user_data.0,
+                            FFI_RESULT_OK,
+                            transfers.as_safe_ptr(),
+                            transfers.len(),
+                        );
+                    })?;
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Export the transfers recorded in the wallet of `host`. See `app_wallet_export_history`.
+pub unsafe fn export_history<H: FfiHost>(
+    host: *const H,
+    store_path: *const c_char,
+    json: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, history: *const c_char),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, H::Error> {
+        let store = wallet_store(store_path)?;
+        let format = if json {
+            HistoryFormat::Json
+        } else {
+            HistoryFormat::Csv
+        };
+
+        (*host).send(move |client| {
+            load_history(client, &store)
+                .map_err(H::Error::from)
+                .and_then(move |history| {
+                    let history = CString::new(wallet::export_history(&history, format)?)?;
+                    o_cb(user_data.0, FFI_RESULT_OK, history.as_ptr());
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+fn load_history(
+    client: &impl Client,
+    store: &WalletStore,
+) -> Box<CoreFuture<Vec<NativeTransferRecord>>> {
+    let client = client.clone();
+    Wallet::load(&client, store)
+        .and_then(move |wallet| wallet.load_history(&client))
+        .into_box()
+}
+
+unsafe fn wallet_store(store_path: *const c_char) -> Result<WalletStore, StringError> {
+    Ok(if store_path.is_null() {
+        WalletStore::Network
+    } else {
+        WalletStore::Local(PathBuf::from(from_c_str(store_path)?))
+    })
+}
+
+unsafe fn optional_c_str(ptr: *const c_char) -> Result<Option<String>, StringError> {
+    Ok(if ptr.is_null() {
+        None
+    } else {
+        Some(from_c_str(ptr)?)
+    })
+}
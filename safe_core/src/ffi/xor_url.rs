@@ -0,0 +1,128 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::ffi::arrays::XorNameArray;
+use crate::ffi::FfiHost;
+use crate::ipc::IpcError;
+use crate::utils::FutureExt;
+use crate::xor_url::{self, ResolvedData, XorUrl as NativeXorUrl};
+use ffi_utils::{
+    catch_unwind_cb, from_c_str, ErrorCode, FfiResult, OpaqueCtx, ReprC, StringError, FFI_RESULT_OK,
+};
+use futures::Future;
+use maidsafe_utilities::serialisation::serialise;
+use std::ffi::{CString, NulError};
+use std::fmt::{Debug, Display};
+use std::os::raw::{c_char, c_void};
+
+/// FFI wrapper for `XorUrl`.
+#[repr(C)]
+pub struct XorUrl {
+    // NOTE: `repr[C]` enums don't seem to be supported by JNI right now, so we use a byte.
+    /// Kind of the data: 0 for immutable, 1 for mutable and 2 for append-only data.
+    pub data_kind: u8,
+    /// Name of the data.
+    pub name: XorNameArray,
+    /// Type tag of the data. Always zero for immutable data.
+    pub type_tag: u64,
+    /// Whether the data is published. Mutable data never is.
+    pub published: bool,
+    /// Whether the data is sequenced. Immutable data never is.
+    pub sequenced: bool,
+    /// MIME type of the content, or null if there's no hint.
+    pub content_type: *const c_char,
+}
+
+impl Drop for XorUrl {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.content_type.is_null() {
+                let _ = CString::from_raw(self.content_type as *mut _);
+            }
+        }
+    }
+}
+
+/// Encode the address of data into a `safe://` XOR-URL, reporting errors as `E`. See
+/// `encode_xor_url`.
+pub unsafe fn encode<E>(
+    xor_url: *const XorUrl,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, encoded: *const c_char),
+) where
+    E: Debug + Display + ErrorCode + From<IpcError> + From<NulError>,
+{
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, E> {
+        let xor_url = NativeXorUrl::clone_from_repr_c(xor_url)?;
+        let encoded = CString::new(xor_url.encode()?)?;
+        o_cb(user_data, FFI_RESULT_OK, encoded.as_ptr());
+        Ok(())
+    })
+}
+
+/// Decode a `safe://` XOR-URL, reporting errors as `E`. See `decode_xor_url`.
+pub unsafe fn decode<E>(
+    encoded: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, xor_url: *const XorUrl),
+) where
+    E: Debug + Display + ErrorCode + From<IpcError> + From<StringError>,
+{
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, E> {
+        let encoded = from_c_str(encoded)?;
+        let xor_url = NativeXorUrl::decode(&encoded)?.into_repr_c()?;
+        o_cb(user_data, FFI_RESULT_OK, &xor_url);
+        Ok(())
+    })
+}
+
+/// Fetch the data referenced by a `safe://` XOR-URL through `host`. See `app_resolve_xor_url`.
+pub unsafe fn resolve<H: FfiHost>(
+    host: *const H,
+    encoded: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        xor_url: *const XorUrl,
+        data: *const u8,
+        data_len: usize,
+    ),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, H::Error> {
+        let native_url = NativeXorUrl::decode(&from_c_str(encoded)?)?;
+
+        (*host).send(move |client| {
+            xor_url::resolve(client, &native_url)
+                .map_err(H::Error::from)
+                .and_then(move |resolved| {
+                    let data = match resolved {
+                        ResolvedData::IData(value) => value,
+                        ResolvedData::MData(data) => serialise(&data)?,
+                        ResolvedData::AData(data) => serialise(&data)?,
+                    };
+                    let xor_url = native_url.into_repr_c()?;
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        &xor_url,
+                        data.as_ptr(),
+                        data.len(),
+                    );
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
@@ -8,15 +8,19 @@
 
 use crate::client::Client;
 use crate::crypto::shared_secretbox;
+use crate::errors::CoreError;
 use crate::event_loop::CoreFuture;
 use crate::self_encryption_storage::SelfEncryptionStorage;
 use crate::utils::{self, FutureExt};
 use futures::Future;
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use safe_nd::{IData, IDataAddress, PubImmutableData, UnpubImmutableData};
-use self_encryption::{DataMap, SelfEncryptor};
+use self_encryption::{ChunkDetails, DataMap, SelfEncryptor, MAX_CHUNK_SIZE, MIN_CHUNK_SIZE};
 use serde::{Deserialize, Serialize};
 
+// Length of the hashes self-encryption names the chunks with.
+const HASH_LEN: usize = 32;
+
 #[derive(Serialize, Deserialize)]
 enum DataTypeEncoding {
     Serialised(Vec<u8>),
@@ -100,6 +104,91 @@ pub fn get_value(
         .into_box()
 }
 
+/// Returns whether the data holds a value encoded by `create`, rather than being a chunk stored
+/// otherwise.
+pub(crate) fn is_encoded(data: &IData) -> bool {
+    deserialise::<DataTypeEncoding>(data.value()).is_ok()
+}
+
+/// Returns the number of chunks `create` stores on the network for a value of `len` bytes,
+/// counting the returned data which the caller puts itself. The few bytes added by an encryption
+/// key are not taken into account.
+pub fn count_chunks(len: u64) -> Result<u64, CoreError> {
+    Ok(chunk_sizes(len)?.len() as u64)
+}
+
+/// Returns the length of the values of the chunks `create` stores on the network for a value of
+/// `len` bytes, the returned data coming last. Self-encrypted chunks are assumed to be as large as
+/// the part of the value they hold, as compressing and encrypting them changes their length by an
+/// amount which can't be known in advance.
+pub(crate) fn chunk_sizes(len: u64) -> Result<Vec<u64>, CoreError> {
+    let mut sizes = self_encrypted_chunk_sizes(len);
+    let mut value = serialise(&DataTypeEncoding::Serialised(serialise(&sample_data_map(
+        len,
+    ))?))?;
+
+    // Mirrors `pack`, which self-encrypts the data again for as long as it's too large.
+    loop {
+        let value_len = value.len() as u64;
+        let data: IData = PubImmutableData::new(value).into();
+        if data.validate_size() {
+            sizes.push(value_len);
+            return Ok(sizes);
+        }
+        let serialised_len = serialise(&data)?.len() as u64;
+        sizes.extend(self_encrypted_chunk_sizes(serialised_len));
+        value = serialise(&DataTypeEncoding::DataMap(sample_data_map(serialised_len)))?;
+    }
+}
+
+/// Returns the number of chunks self-encryption splits `len` bytes into. Values too small to be
+/// split are kept in the data map itself.
+pub(crate) fn self_encrypted_chunks(len: u64) -> u64 {
+    let min_chunk_size = u64::from(MIN_CHUNK_SIZE);
+    let max_chunk_size = u64::from(MAX_CHUNK_SIZE);
+    if len < 3 * min_chunk_size {
+        0
+    } else if len < 3 * max_chunk_size {
+        3
+    } else {
+        (len + max_chunk_size - 1) / max_chunk_size
+    }
+}
+
+/// Returns the number of bytes of `len` held by each of the chunks self-encryption splits them
+/// into, the last chunk holding what's left over.
+pub(crate) fn self_encrypted_chunk_sizes(len: u64) -> Vec<u64> {
+    let chunks = self_encrypted_chunks(len);
+    (0..chunks)
+        .map(|chunk_num| {
+            if chunk_num + 1 < chunks {
+                len / chunks
+            } else {
+                len - (chunks - 1) * (len / chunks)
+            }
+        })
+        .collect()
+}
+
+/// Returns a data map of the same serialised size as the one self-encryption produces for `len`
+/// bytes.
+pub(crate) fn sample_data_map(len: u64) -> DataMap {
+    let chunks = self_encrypted_chunks(len);
+    if chunks == 0 {
+        return DataMap::Content(vec![0; len as usize]);
+    }
+    DataMap::Chunks(
+        (0..chunks)
+            .map(|chunk_num| ChunkDetails {
+                chunk_num: chunk_num as u32,
+                hash: vec![0; HASH_LEN],
+                pre_hash: vec![0; HASH_LEN],
+                source_size: len / chunks,
+            })
+            .collect(),
+    )
+}
+
 // TODO: consider rewriting these two function to not use recursion.
 
 fn pack(client: impl Client, value: Vec<u8>, published: bool) -> Box<CoreFuture<IData>> {
@@ -179,6 +268,32 @@ mod tests {
         create_and_retrieve(10 * 1024 * 1024)
     }
 
+    // Test that `count_chunks` predicts the number of chunks stored, by comparing it with the
+    // charges made for storing values of various sizes.
+    #[cfg(feature = "mock-network")]
+    #[test]
+    fn count_chunks_matches_charges() {
+        for &size in &[100, 10 * 1024, 3 * 1024 * 1024 + 1] {
+            let value = unwrap!(utils::generate_random_vector(size));
+            let expected = unwrap!(count_chunks(size as u64));
+
+            random_client(move |client| {
+                let client2 = client.clone();
+                let client3 = client.clone();
+                let charges = unwrap!(client.charges()).len();
+
+                create(client, &value, true, None)
+                    .and_then(move |data| client2.put_idata(data))
+                    .map(move |_| {
+                        assert_eq!(
+                            (unwrap!(client3.charges()).len() - charges) as u64,
+                            expected
+                        );
+                    })
+            })
+        }
+    }
+
     fn create_and_retrieve(size: usize) {
         let value = unwrap!(utils::generate_random_vector(size));
 
@@ -68,6 +68,8 @@
     variant_size_differences
 )]
 
+#[macro_use]
+extern crate ffi_utils;
 #[cfg(feature = "mock-network")]
 #[macro_use]
 extern crate lazy_static;
@@ -92,6 +94,8 @@ pub mod utils;
 pub mod client;
 /// Config file handling.
 pub mod config_handler;
+/// Estimation of the cost of operations before carrying them out.
+pub mod cost;
 /// Cryptographic utilities.
 pub mod crypto;
 /// Event loop handling.
@@ -102,24 +106,41 @@ pub mod immutable_data;
 pub mod ipc;
 /// NFS utilities.
 pub mod nfs;
+/// Known sections of the network and their elders.
+pub mod section_map;
 /// Implements the Self Encryption storage trait.
 pub mod self_encryption_storage;
+/// Coin balances and the history of their transfers.
+pub mod wallet;
+/// `safe://` URLs referencing data by its address.
+pub mod xor_url;
 
 #[cfg(not(feature = "mock-network"))]
 mod connection_manager;
 mod errors;
 mod event;
+mod quorum;
 
+#[cfg(all(feature = "mock-network", unix))]
+pub use self::client::MockVaultServer;
 pub use self::client::{
-    mdata_info, recovery, test_create_balance, AuthActions, Client, ClientKeys, MDataInfo,
+    mdata_info, recovery, test_create_balance, AuthActions, Client, ClientKeys, DryRunTally,
+    MDataInfo,
 };
 #[cfg(feature = "mock-network")]
-pub use self::client::{mock_vault_path, MockConnectionManager as ConnectionManager};
+pub use self::client::{
+    mock_vault_path, Charge, Exchange, Mismatch, MockConnectionManager as ConnectionManager,
+    Operation, PricingModel, Recording,
+};
 #[cfg(not(feature = "mock-network"))]
 pub use self::connection_manager::ConnectionManager;
 pub use self::errors::CoreError;
-pub use self::event::{NetworkEvent, NetworkRx, NetworkTx};
+pub use self::event::{
+    NetworkEvent, NetworkNotification, NetworkRx, NetworkTx, NotificationRx, NotificationTx,
+};
 pub use self::event_loop::{CoreFuture, CoreMsg, CoreMsgRx, CoreMsgTx};
+pub use self::quorum::Quorum;
+pub use self::section_map::{Prefix, SectionMap};
 pub use self::self_encryption_storage::{SelfEncryptionStorage, SelfEncryptionStorageError};
 pub use self::utils::FutureExt;
 pub use quic_p2p::Config as QuicP2pConfig;
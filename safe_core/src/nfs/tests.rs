@@ -7,7 +7,9 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::client::core_client::CoreClient;
-use crate::client::MDataInfo;
+use crate::client::{Client, MDataInfo};
+use crate::config_handler::Pricing;
+use crate::cost::{self, PlannedOperation};
 use crate::crypto::shared_secretbox;
 use crate::errors::CoreError;
 use crate::nfs::file_helper::{self, Version};
@@ -914,3 +916,50 @@ fn encryption() {
         })
     })
 }
+
+// Test that writing a file in dry-run mode tallies the mutations its cost estimate predicts.
+// 1. Create a directory, then start the dry-run mode.
+// 2. Write a file and insert it in the directory.
+// 3. Stop the dry-run mode and compare the tally with the estimate.
+#[test]
+fn dry_run_file_write() {
+    const SIZE: usize = 10 * 1024;
+
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let c4 = client.clone();
+        let root = unwrap!(MDataInfo::random_private(MDataKind::Seq, DIR_TAG));
+        let root2 = root.clone();
+
+        create_dir(client, &root, btree_map![], btree_map![])
+            .then(move |res| {
+                unwrap!(res);
+                c2.start_dry_run();
+
+                file_helper::write(
+                    c2.clone(),
+                    File::new(Vec::new(), false),
+                    Mode::Overwrite,
+                    root.enc_key().cloned(),
+                )
+            })
+            .then(move |res| {
+                let writer = unwrap!(res);
+                writer.write(&[0u8; SIZE]).and_then(move |_| writer.close())
+            })
+            .then(move |res| {
+                let file = unwrap!(res);
+                file_helper::insert(c3, root2, "hello.txt", &file)
+            })
+            .then(move |res| -> Result<_, NfsError> {
+                unwrap!(res);
+                let tally = unwrap!(c4.stop_dry_run());
+                let operation = PlannedOperation::WriteFile { size: SIZE as u64 };
+                let estimate = unwrap!(cost::estimate(operation, &Pricing::default()));
+                assert_eq!(tally.mutations, estimate.mutations);
+                assert_eq!(unwrap!(tally.estimate()), estimate);
+                Ok(())
+            })
+    })
+}
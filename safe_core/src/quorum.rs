@@ -0,0 +1,39 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+/// Number of matching elder responses required before a response is accepted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Quorum {
+    /// Accept the first response received.
+    One,
+    /// Require more than half of the elders the request was sent to.
+    Majority,
+    /// Require all elders the request was sent to.
+    All,
+    /// Require the given number of elders (or all of them, if the request was sent to fewer).
+    Count(usize),
+}
+
+impl Default for Quorum {
+    fn default() -> Self {
+        Quorum::Majority
+    }
+}
+
+impl Quorum {
+    /// Number of matching responses required when a request has been sent to `elders` elders.
+    pub fn threshold(self, elders: usize) -> usize {
+        let threshold = match self {
+            Quorum::One => 1,
+            Quorum::Majority => elders / 2 + 1,
+            Quorum::All => elders,
+            Quorum::Count(count) => count.min(elders),
+        };
+        threshold.max(1)
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use quic_p2p::NodeInfo;
+use safe_nd::{Message, Request, XorName, XOR_NAME_LEN};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Debug, Formatter};
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+/// The first `bit_count` bits of a name. A section manages all the names matching its prefix.
+#[derive(Clone, Copy, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
+pub struct Prefix {
+    bit_count: u16,
+    name: XorName,
+}
+
+impl Prefix {
+    /// Create a prefix of the first `bit_count` bits of `name`.
+    pub fn new(bit_count: usize, name: XorName) -> Self {
+        let bit_count = bit_count.min(8 * XOR_NAME_LEN);
+        let mut bytes = name.0;
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            let leading = bit_count.saturating_sub(8 * index).min(8);
+            *byte &= !(0xff_u16 >> leading) as u8;
+        }
+
+        Self {
+            bit_count: bit_count as u16,
+            name: XorName(bytes),
+        }
+    }
+
+    /// Number of bits in the prefix.
+    pub fn bit_count(&self) -> usize {
+        usize::from(self.bit_count)
+    }
+
+    /// Returns `true` if the first `bit_count` bits of `name` match the prefix.
+    pub fn matches(&self, name: &XorName) -> bool {
+        *self == Self::new(self.bit_count(), *name)
+    }
+
+    /// Returns `true` if `other` is this prefix or one of its descendants, managing a subset of
+    /// its names.
+    pub fn is_ancestor_of(&self, other: &Prefix) -> bool {
+        self.bit_count() <= other.bit_count() && self.matches(&other.name)
+    }
+
+    /// Returns the prefix extended with one more bit, as when a section splits in two.
+    pub fn pushed(&self, bit: bool) -> Self {
+        let bit_count = self.bit_count();
+        if bit_count >= 8 * XOR_NAME_LEN {
+            return *self;
+        }
+
+        let mut name = self.name;
+        if bit {
+            name.0[bit_count / 8] |= 0x80 >> (bit_count % 8);
+        }
+        Self::new(bit_count + 1, name)
+    }
+}
+
+impl FromStr for Prefix {
+    type Err = String;
+
+    /// Parse a prefix written as a string of bits, such as `"0110"`.
+    fn from_str(bits: &str) -> Result<Self, Self::Err> {
+        bits.chars()
+            .try_fold(Self::default(), |prefix, bit| match bit {
+                '0' if prefix.bit_count() < 8 * XOR_NAME_LEN => Ok(prefix.pushed(false)),
+                '1' if prefix.bit_count() < 8 * XOR_NAME_LEN => Ok(prefix.pushed(true)),
+                _ => Err(format!("Invalid prefix: {:?}", bits)),
+            })
+    }
+}
+
+impl Debug for Prefix {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "Prefix(")?;
+        for index in 0..self.bit_count() {
+            let bit = (self.name.0[index / 8] >> (7 - index % 8)) & 1;
+            write!(formatter, "{}", bit)?;
+        }
+        write!(formatter, ")")
+    }
+}
+
+/// Contact info of the elders of the sections known to the client.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(from = "Vec<Section>", into = "Vec<Section>")]
+pub struct SectionMap {
+    sections: BTreeMap<Prefix, HashSet<NodeInfo>>,
+}
+
+// Serialised form of a map entry, as JSON only supports strings as map keys.
+#[derive(Deserialize, Serialize)]
+struct Section {
+    prefix: Prefix,
+    elders: HashSet<NodeInfo>,
+}
+
+impl From<Vec<Section>> for SectionMap {
+    fn from(sections: Vec<Section>) -> Self {
+        let mut map = Self::default();
+        for Section { prefix, elders } in sections {
+            map.insert(prefix, elders);
+        }
+        map
+    }
+}
+
+impl Into<Vec<Section>> for SectionMap {
+    fn into(self) -> Vec<Section> {
+        self.sections
+            .into_iter()
+            .map(|(prefix, elders)| Section { prefix, elders })
+            .collect()
+    }
+}
+
+impl SectionMap {
+    /// Returns `true` if no sections are known.
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Set the elders of the section with the given prefix, replacing any previously known ones.
+    /// Sections which are covered by the new prefix are forgotten, as they've been merged into it.
+    pub fn insert(&mut self, prefix: Prefix, elders: HashSet<NodeInfo>) {
+        self.sections.retain(|other, _| {
+            other.bit_count() < prefix.bit_count() || !prefix.matches(&other.name)
+        });
+        let _ = self.sections.insert(prefix, elders);
+    }
+
+    /// Forget about the section with the given prefix.
+    pub fn remove(&mut self, prefix: &Prefix) -> Option<HashSet<NodeInfo>> {
+        self.sections.remove(prefix)
+    }
+
+    /// Add an elder to the section with the given prefix, adding the section if it's not known.
+    pub fn add_elder(&mut self, prefix: Prefix, elder: NodeInfo) {
+        let _ = self.sections.entry(prefix).or_default().insert(elder);
+    }
+
+    /// Remove the elder listening on `peer_addr` from the section with the given prefix. The
+    /// section is forgotten once it has no elders left.
+    pub fn remove_elder(&mut self, prefix: &Prefix, peer_addr: &SocketAddr) {
+        let is_empty = match self.sections.get_mut(prefix) {
+            Some(elders) => {
+                elders.retain(|elder| elder.peer_addr != *peer_addr);
+                elders.is_empty()
+            }
+            None => false,
+        };
+        if is_empty {
+            let _ = self.sections.remove(prefix);
+        }
+    }
+
+    /// Returns the prefix and the elders of the known section which manages `name`, that is the
+    /// one with the longest prefix matching it.
+    pub fn section_for(&self, name: &XorName) -> Option<(Prefix, &HashSet<NodeInfo>)> {
+        self.sections
+            .iter()
+            .filter(|(prefix, _)| prefix.matches(name))
+            .max_by_key(|(prefix, _)| prefix.bit_count())
+            .map(|(prefix, elders)| (*prefix, elders))
+    }
+
+    /// Iterate over the known sections.
+    pub fn iter(&self) -> impl Iterator<Item = (&Prefix, &HashSet<NodeInfo>)> {
+        self.sections.iter()
+    }
+}
+
+// Name of the data the request is about, if it's a data request.
+pub(crate) fn data_name(msg: &Message) -> Option<XorName> {
+    let request = match msg {
+        Message::Request { request, .. } => request,
+        _ => return None,
+    };
+
+    let name = match request {
+        Request::PutIData(data) => *data.name(),
+        Request::GetIData(address) | Request::DeleteUnpubIData(address) => *address.name(),
+        Request::PutMData(data) => *data.name(),
+        Request::GetMData(address)
+        | Request::GetMDataShell(address)
+        | Request::GetMDataVersion(address)
+        | Request::ListMDataEntries(address)
+        | Request::ListMDataKeys(address)
+        | Request::ListMDataValues(address)
+        | Request::ListMDataPermissions(address)
+        | Request::DeleteMData(address)
+        | Request::GetMDataValue { address, .. }
+        | Request::SetMDataUserPermissions { address, .. }
+        | Request::DelMDataUserPermissions { address, .. }
+        | Request::ListMDataUserPermissions { address, .. }
+        | Request::MutateMDataEntries { address, .. } => *address.name(),
+        Request::PutAData(data) => *data.name(),
+        Request::GetAData(address)
+        | Request::DeleteAData(address)
+        | Request::GetADataIndices(address)
+        | Request::GetADataLastEntry(address)
+        | Request::GetADataShell { address, .. }
+        | Request::GetADataRange { address, .. }
+        | Request::GetADataValue { address, .. }
+        | Request::GetADataPermissions { address, .. }
+        | Request::GetPubADataUserPermissions { address, .. }
+        | Request::GetUnpubADataUserPermissions { address, .. }
+        | Request::GetADataOwners { address, .. }
+        | Request::AddPubADataPermissions { address, .. }
+        | Request::AddUnpubADataPermissions { address, .. }
+        | Request::SetADataOwner { address, .. } => *address.name(),
+        Request::AppendSeq { append, .. } | Request::AppendUnseq(append) => *append.address.name(),
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_info(port: u16) -> NodeInfo {
+        NodeInfo {
+            peer_addr: SocketAddr::from(([127, 0, 0, 1], port)),
+            peer_cert_der: vec![],
+        }
+    }
+
+    fn name(first_byte: u8) -> XorName {
+        let mut name = XorName([0; XOR_NAME_LEN]);
+        name.0[0] = first_byte;
+        name
+    }
+
+    // Test that names are looked up in the section with the longest matching prefix.
+    #[test]
+    fn section_for() {
+        let mut map = SectionMap::default();
+        assert!(map.section_for(&name(0)).is_none());
+
+        let root = Prefix::default();
+        let left = Prefix::new(1, name(0b0000_0000));
+        let right = Prefix::new(1, name(0b1111_1111));
+
+        map.insert(root, vec![node_info(1)].into_iter().collect());
+        map.insert(right, vec![node_info(2)].into_iter().collect());
+
+        assert_eq!(unwrap!(map.section_for(&name(0b0100_0000))).0, root);
+        assert_eq!(unwrap!(map.section_for(&name(0b1000_0000))).0, right);
+
+        // Splitting the root section into two.
+        map.insert(left, vec![node_info(3)].into_iter().collect());
+        assert_eq!(unwrap!(map.section_for(&name(0b0100_0000))).0, left);
+
+        // Merging them back replaces both.
+        map.insert(root, vec![node_info(4)].into_iter().collect());
+        assert_eq!(map.iter().count(), 1);
+        assert_eq!(unwrap!(map.section_for(&name(0b1000_0000))).0, root);
+
+        // Sections are forgotten once they lose all their elders.
+        map.add_elder(right, node_info(5));
+        map.remove_elder(&right, &node_info(5).peer_addr);
+        assert_eq!(unwrap!(map.section_for(&name(0b1000_0000))).0, root);
+
+        // The map survives a round trip through the config file format.
+        let json = unwrap!(serde_json::to_string(&map));
+        let map: SectionMap = unwrap!(serde_json::from_str(&json));
+        assert_eq!(unwrap!(map.section_for(&name(0b1000_0000))).1.len(), 1);
+    }
+
+    // Test that only the first `bit_count` bits of a prefix are significant.
+    #[test]
+    fn prefix() {
+        let prefix = Prefix::new(3, name(0b1011_0110));
+        assert_eq!(prefix, Prefix::new(3, name(0b1010_0000)));
+        assert!(prefix.matches(&name(0b1011_1111)));
+        assert!(!prefix.matches(&name(0b1110_0000)));
+        assert_eq!(format!("{:?}", prefix), "Prefix(101)");
+        assert_eq!(unwrap!(Prefix::from_str("101")), prefix);
+        assert!(Prefix::from_str("102").is_err());
+
+        assert_eq!(prefix.pushed(true), Prefix::new(4, name(0b1011_0000)));
+        assert_eq!(prefix.pushed(false), Prefix::new(4, name(0b1010_0000)));
+        assert!(prefix.is_ancestor_of(&prefix.pushed(true)));
+        assert!(prefix.is_ancestor_of(&prefix));
+        assert!(!prefix.pushed(true).is_ancestor_of(&prefix));
+    }
+}
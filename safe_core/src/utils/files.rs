@@ -0,0 +1,63 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::errors::CoreError;
+use fs2::FileExt;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Open the lock file guarding the file at `path`, creating it if needed. Files which are replaced
+/// whenever they're written can't be locked themselves, so they're locked through this file.
+pub(crate) fn open_lock_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(with_suffix(path, ".lock"))
+}
+
+/// Take an exclusive lock on the file at `path`, held until the returned lock file is dropped.
+pub(crate) fn lock_exclusive(path: &Path) -> Result<File, CoreError> {
+    let lock = open_lock_file(path)?;
+    lock.lock_exclusive()?;
+    Ok(lock)
+}
+
+/// Path of a temporary file next to `path`, unique to the caller.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    with_suffix(path, &format!(".{:016x}.tmp", new_rand::random::<u64>()))
+}
+
+/// Write the file at `path` through `write`, which writes a temporary file first. The temporary
+/// file is synced to the disk and then moved in place, so that readers never see a partially
+/// written file, even when several processes write it. It's removed if writing fails.
+pub(crate) fn write_atomically<F>(path: &Path, write: F) -> Result<(), CoreError>
+where
+    F: FnOnce(&mut File) -> Result<(), CoreError>,
+{
+    let temp_path = temp_path(path);
+    let result = File::create(&temp_path)
+        .map_err(CoreError::from)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.sync_all()?;
+            Ok(())
+        })
+        .and_then(|()| fs::rename(&temp_path, path).map_err(CoreError::from));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
@@ -8,6 +8,7 @@
 
 #[macro_use]
 mod futures;
+pub(crate) mod files;
 
 /// Seed utilities.
 pub mod seed;
@@ -21,6 +22,7 @@ use maidsafe_utilities::serialisation::{deserialise, serialise};
 use rand::Rng;
 use rust_sodium::crypto::hash::sha512::{self, Digest, DIGESTBYTES};
 use rust_sodium::crypto::secretbox;
+use safe_nd::Request;
 use serde::{Deserialize, Serialize};
 
 /// Easily create a BTreeSet.
@@ -143,6 +145,56 @@ pub fn derive_secrets(acc_locator: &[u8], acc_password: &[u8]) -> (Vec<u8>, Vec<
     (password, keyword, pin)
 }
 
+/// Returns the name of the `Request` variant, such as `"GetIData"`.
+pub fn request_name(request: &Request) -> &'static str {
+    match request {
+        Request::PutIData(_) => "PutIData",
+        Request::GetIData(_) => "GetIData",
+        Request::DeleteUnpubIData(_) => "DeleteUnpubIData",
+        Request::PutMData(_) => "PutMData",
+        Request::GetMData(_) => "GetMData",
+        Request::GetMDataValue { .. } => "GetMDataValue",
+        Request::DeleteMData(_) => "DeleteMData",
+        Request::GetMDataShell(_) => "GetMDataShell",
+        Request::GetMDataVersion(_) => "GetMDataVersion",
+        Request::ListMDataEntries(_) => "ListMDataEntries",
+        Request::ListMDataKeys(_) => "ListMDataKeys",
+        Request::ListMDataValues(_) => "ListMDataValues",
+        Request::SetMDataUserPermissions { .. } => "SetMDataUserPermissions",
+        Request::DelMDataUserPermissions { .. } => "DelMDataUserPermissions",
+        Request::ListMDataPermissions(_) => "ListMDataPermissions",
+        Request::ListMDataUserPermissions { .. } => "ListMDataUserPermissions",
+        Request::MutateMDataEntries { .. } => "MutateMDataEntries",
+        Request::PutAData(_) => "PutAData",
+        Request::GetAData(_) => "GetAData",
+        Request::GetADataShell { .. } => "GetADataShell",
+        Request::DeleteAData(_) => "DeleteAData",
+        Request::GetADataRange { .. } => "GetADataRange",
+        Request::GetADataIndices(_) => "GetADataIndices",
+        Request::GetADataLastEntry(_) => "GetADataLastEntry",
+        Request::GetADataPermissions { .. } => "GetADataPermissions",
+        Request::GetPubADataUserPermissions { .. } => "GetPubADataUserPermissions",
+        Request::GetUnpubADataUserPermissions { .. } => "GetUnpubADataUserPermissions",
+        Request::GetADataOwners { .. } => "GetADataOwners",
+        Request::AddPubADataPermissions { .. } => "AddPubADataPermissions",
+        Request::AddUnpubADataPermissions { .. } => "AddUnpubADataPermissions",
+        Request::SetADataOwner { .. } => "SetADataOwner",
+        Request::AppendSeq { .. } => "AppendSeq",
+        Request::AppendUnseq(_) => "AppendUnseq",
+        Request::GetADataValue { .. } => "GetADataValue",
+        Request::TransferCoins { .. } => "TransferCoins",
+        Request::GetBalance => "GetBalance",
+        Request::CreateBalance { .. } => "CreateBalance",
+        Request::CreateLoginPacket(_) => "CreateLoginPacket",
+        Request::CreateLoginPacketFor { .. } => "CreateLoginPacketFor",
+        Request::UpdateLoginPacket(_) => "UpdateLoginPacket",
+        Request::GetLoginPacket(_) => "GetLoginPacket",
+        Request::ListAuthKeysAndVersion => "ListAuthKeysAndVersion",
+        Request::InsAuthKey { .. } => "InsAuthKey",
+        Request::DelAuthKey { .. } => "DelAuthKey",
+    }
+}
+
 /// Convert binary data to a diplay-able format
 #[inline]
 pub fn bin_data_format(data: &[u8]) -> String {
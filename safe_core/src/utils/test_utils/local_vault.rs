@@ -0,0 +1,558 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::client::mock::vault::{self, Vault};
+use crate::client::mock::DataId;
+use crate::config_handler::{Config, DevConfig};
+use crate::CoreError;
+use bincode::{deserialize, serialize};
+use bytes::Bytes;
+use crossbeam_channel::{self, Receiver, RecvTimeoutError};
+use new_rand::Rng;
+use quic_p2p::{Builder, Config as QuicP2pConfig, Event, NodeInfo, OurType, Peer, QuicP2p};
+use safe_nd::{Challenge, Data, IData, Message, NodeFullId, PublicId, RequestType};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// How often the elder threads check whether they've been asked to stop.
+const POLL_INTERVAL_MS: u64 = 50;
+
+/// Vault stand-in listening on localhost, speaking the same quic-p2p protocol as the real vaults.
+/// Every elder is a separate quic-p2p node; all of them share the state of a single mock vault.
+/// This lets the real `ConnectionManager` be tested end-to-end without any outside services.
+pub struct LocalVault {
+    vault: Arc<Mutex<Vault>>,
+    elders: Vec<Elder>,
+}
+
+impl LocalVault {
+    /// Start a vault with `elders` elders, using in-memory storage and unlimited mutations.
+    pub fn new(elders: usize) -> Result<Self, CoreError> {
+        let config = Config {
+            quic_p2p: Default::default(),
+            dev: Some(DevConfig {
+                mock_unlimited_mutations: true,
+                mock_in_memory_storage: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        Self::with_config(config, elders)
+    }
+
+    /// Start a vault with `elders` elders. `config` is passed on to the mock vault.
+    pub fn with_config(config: Config, elders: usize) -> Result<Self, CoreError> {
+        let vault = Arc::new(Mutex::new(Vault::new(config)));
+        let elders = (0..elders)
+            .map(|_| Elder::start(vault.clone()))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { vault, elders })
+    }
+
+    /// Store the immutable data in the vault directly, without going through a client.
+    pub fn insert_idata(&self, data: IData) {
+        let mut vault = vault::lock(&self.vault, true);
+        vault.insert_data(DataId::Immutable(*data.address()), Data::Immutable(data));
+    }
+
+    /// Connection info of the elders which are still running.
+    pub fn connection_info(&self) -> HashSet<NodeInfo> {
+        self.elders
+            .iter()
+            .filter(|elder| elder.is_running())
+            .map(|elder| elder.node_info.clone())
+            .collect()
+    }
+
+    /// Client quic-p2p config listing the running elders as the hard-coded contacts. As the
+    /// contacts in the bootstrap cache are tried first, clients should disable it with
+    /// `ConnectionManager::set_use_bootstrap_cache`.
+    pub fn quic_p2p_config(&self) -> QuicP2pConfig {
+        QuicP2pConfig {
+            hard_coded_contacts: self.connection_info(),
+            our_type: OurType::Client,
+            ..Default::default()
+        }
+    }
+
+    /// Stop the elder with the given index, dropping all of its connections.
+    pub fn stop_elder(&mut self, index: usize) {
+        if let Some(elder) = self.elders.get_mut(index) {
+            elder.stop();
+        }
+    }
+}
+
+impl Drop for LocalVault {
+    fn drop(&mut self) {
+        for elder in &mut self.elders {
+            elder.stop();
+        }
+    }
+}
+
+struct Elder {
+    node_info: NodeInfo,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Elder {
+    fn start(vault: Arc<Mutex<Vault>>) -> Result<Self, CoreError> {
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        let config = QuicP2pConfig {
+            ip: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            port: None,
+            our_type: OurType::Node,
+            ..Default::default()
+        };
+
+        let mut quic_p2p = Builder::new(event_tx).with_config(config).build()?;
+        let node_info = quic_p2p.our_connection_info()?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let session = Session {
+            quic_p2p,
+            vault,
+            full_id: NodeFullId::new(&mut new_rand::thread_rng()),
+            clients: HashMap::new(),
+        };
+        let thread = session.run(event_rx, stop.clone());
+
+        trace!("Local vault elder listening on {}", node_info.peer_addr);
+
+        Ok(Self {
+            node_info,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn is_running(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// State of a client connection: waiting for the response to our challenge, or authenticated.
+enum ClientState {
+    Challenged(Vec<u8>),
+    Authenticated(PublicId),
+}
+
+// The quic-p2p node of an elder, along with the clients connected to it.
+struct Session {
+    quic_p2p: QuicP2p,
+    vault: Arc<Mutex<Vault>>,
+    full_id: NodeFullId,
+    clients: HashMap<SocketAddr, ClientState>,
+}
+
+impl Session {
+    fn run(mut self, event_rx: Receiver<Event>, stop: Arc<AtomicBool>) -> JoinHandle<()> {
+        thread::spawn(move || {
+            while !stop.load(Ordering::SeqCst) {
+                match event_rx.recv_timeout(Duration::from_millis(POLL_INTERVAL_MS)) {
+                    Ok(event) => self.handle_event(event),
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            // Dropping the session drops the quic-p2p node along with all of its connections.
+        })
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::ConnectedTo {
+                peer: Peer::Client { peer_addr },
+            } => self.handle_client_connected(peer_addr),
+            Event::NewMessage { peer_addr, msg } => self.handle_new_message(peer_addr, msg),
+            Event::ConnectionFailure { peer_addr, .. } => {
+                trace!("Local vault: lost connection to {}", peer_addr);
+                let _ = self.clients.remove(&peer_addr);
+            }
+            event => trace!("Local vault: ignoring event {}", event),
+        }
+    }
+
+    fn handle_client_connected(&mut self, peer_addr: SocketAddr) {
+        let challenge: Vec<u8> = (0..8).map(|_| new_rand::thread_rng().gen()).collect();
+        let request = Challenge::Request(
+            PublicId::Node(self.full_id.public_id().clone()),
+            challenge.clone(),
+        );
+
+        let _ = self
+            .clients
+            .insert(peer_addr, ClientState::Challenged(challenge));
+        self.send(peer_addr, unwrap!(serialize(&request)));
+    }
+
+    fn handle_new_message(&mut self, peer_addr: SocketAddr, msg: Bytes) {
+        match self.clients.remove(&peer_addr) {
+            Some(ClientState::Challenged(challenge)) => match deserialize(&msg) {
+                Ok(Challenge::Response(public_id, signature)) => {
+                    if public_id
+                        .public_key()
+                        .verify(&signature, &challenge)
+                        .is_ok()
+                    {
+                        trace!("Local vault: {} is {:?}", peer_addr, public_id);
+                        let _ = self
+                            .clients
+                            .insert(peer_addr, ClientState::Authenticated(public_id));
+                    } else {
+                        trace!("Local vault: invalid challenge response from {}", peer_addr);
+                        self.quic_p2p.disconnect_from(peer_addr);
+                    }
+                }
+                _ => {
+                    trace!("Local vault: expected a challenge response");
+                    let _ = self
+                        .clients
+                        .insert(peer_addr, ClientState::Challenged(challenge));
+                }
+            },
+            Some(ClientState::Authenticated(public_id)) => {
+                match deserialize(&msg) {
+                    Ok(message) => self.handle_request(peer_addr, public_id.clone(), &message),
+                    Err(error) => trace!("Local vault: invalid message: {:?}", error),
+                }
+                let _ = self
+                    .clients
+                    .insert(peer_addr, ClientState::Authenticated(public_id));
+            }
+            None => trace!("Local vault: message from unknown peer {}", peer_addr),
+        }
+    }
+
+    fn handle_request(&mut self, peer_addr: SocketAddr, requester: PublicId, message: &Message) {
+        let writing = match message {
+            Message::Request { request, .. } => {
+                let req_type = request.get_type();
+                req_type == RequestType::Mutation || req_type == RequestType::Transaction
+            }
+            _ => false,
+        };
+
+        let response = {
+            let mut vault = vault::lock(&self.vault, writing);
+            vault.process_request(requester, message)
+        };
+
+        match response {
+            Ok(response) => self.send(peer_addr, unwrap!(serialize(&response))),
+            Err(error) => trace!("Local vault: failed to process request: {:?}", error),
+        }
+    }
+
+    fn send(&mut self, peer_addr: SocketAddr, msg: Vec<u8>) {
+        let token = new_rand::thread_rng().gen();
+        self.quic_p2p
+            .send(Peer::Client { peer_addr }, Bytes::from(msg), token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{req, SafeKey, REQUEST_TIMEOUT_SECS};
+    use crate::event::NetworkEvent;
+    use crate::section_map::{Prefix, SectionMap};
+    use crate::utils::generate_random_vector;
+    use crate::{ConnectionManager, Quorum};
+    use futures::sync::mpsc;
+    use futures::{Future, Stream};
+    use safe_nd::{Coins, Error as SndError, PubImmutableData, Request, Response};
+    use std::str::FromStr;
+    use threshold_crypto::SecretKey as BlsSecretKey;
+    use tokio::prelude::FutureExt;
+    use tokio::runtime::current_thread::block_on_all;
+
+    fn timeout() -> Duration {
+        Duration::from_secs(REQUEST_TIMEOUT_SECS)
+    }
+
+    // Client config with the given quic-p2p options and no known sections.
+    fn client_config(quic_p2p: QuicP2pConfig) -> Config {
+        Config {
+            quic_p2p,
+            ..Default::default()
+        }
+    }
+
+    // Create a balance and read it back through the real connection manager.
+    fn create_and_get_balance(cm: &mut ConnectionManager, full_id: &SafeKey) {
+        let owner = match full_id.public_id() {
+            PublicId::Client(id) => *id.public_key(),
+            id => panic!("Unexpected ID {:?}", id),
+        };
+        let amount = unwrap!(Coins::from_str("10"));
+
+        let request = Request::CreateBalance {
+            new_balance_owner: owner,
+            amount,
+            transaction_id: new_rand::random(),
+        };
+        match unwrap!(req(cm, request, full_id, timeout())) {
+            Response::Transaction(res) => {
+                let _ = unwrap!(res);
+            }
+            res => panic!("Unexpected response {:?}", res),
+        }
+
+        match unwrap!(req(cm, Request::GetBalance, full_id, timeout())) {
+            Response::GetBalance(res) => assert_eq!(unwrap!(res), amount),
+            res => panic!("Unexpected response {:?}", res),
+        }
+    }
+
+    // 1. Start a local vault and bootstrap a client to it, going through the challenge handshake.
+    // 2. Verify that requests are processed and routed back to the client.
+    #[test]
+    fn requests() {
+        let vault = unwrap!(LocalVault::new(1));
+        let (net_tx, _net_rx) = mpsc::unbounded();
+        let config = client_config(vault.quic_p2p_config());
+        let mut cm = unwrap!(ConnectionManager::new(config, &net_tx));
+        cm.set_use_bootstrap_cache(false);
+
+        let full_id = SafeKey::client_from_bls_key(BlsSecretKey::random());
+        unwrap!(block_on_all(cm.bootstrap(full_id.clone())));
+
+        create_and_get_balance(&mut cm, &full_id);
+    }
+
+    // 1. Start a local vault with several elders and list them in the section map only.
+    // 2. Verify that the client connects to them without any hard-coded contacts.
+    #[test]
+    fn bootstrap_from_section_map() {
+        let vault = unwrap!(LocalVault::new(3));
+        let (net_tx, _net_rx) = mpsc::unbounded();
+        let config = QuicP2pConfig {
+            our_type: OurType::Client,
+            ..Default::default()
+        };
+        let mut sections = SectionMap::default();
+        sections.insert(Prefix::default(), vault.connection_info());
+        let config = Config {
+            quic_p2p: config,
+            sections,
+            ..Default::default()
+        };
+        let mut cm = unwrap!(ConnectionManager::new(config, &net_tx));
+        cm.set_use_bootstrap_cache(false);
+
+        let full_id = SafeKey::client_from_bls_key(BlsSecretKey::random());
+        unwrap!(block_on_all(cm.bootstrap(full_id.clone())));
+        create_and_get_balance(&mut cm, &full_id);
+
+        let section_map = cm.section_map();
+        let (prefix, elders) = unwrap!(section_map.section_for(full_id.public_id().name()));
+        assert_eq!(prefix, Prefix::default());
+        assert_eq!(*elders, vault.connection_info());
+    }
+
+    // 1. Start a local vault with several elders and bootstrap a client to it.
+    // 2. Stop all of the elders.
+    // 3. Verify that the client is notified about the disconnection.
+    #[test]
+    fn elders_drop_out() {
+        let mut vault = unwrap!(LocalVault::new(3));
+        let (net_tx, net_rx) = mpsc::unbounded();
+        let config = client_config(vault.quic_p2p_config());
+        let mut cm = unwrap!(ConnectionManager::new(config, &net_tx));
+        cm.set_use_bootstrap_cache(false);
+
+        let full_id = SafeKey::client_from_bls_key(BlsSecretKey::random());
+        unwrap!(block_on_all(cm.bootstrap(full_id.clone())));
+        create_and_get_balance(&mut cm, &full_id);
+
+        for index in 0..3 {
+            vault.stop_elder(index);
+        }
+        assert!(vault.connection_info().is_empty());
+
+        let event = unwrap!(block_on_all(net_rx.into_future().map_err(|_| ()))).0;
+        match event {
+            Some(NetworkEvent::Disconnected) => (),
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    // 1. Bootstrap a client to a local vault.
+    // 2. Restart the network while still connected.
+    // 3. Verify that the restart completes at once and the client is told it's connected.
+    #[test]
+    fn restart_while_connected() {
+        let vault = unwrap!(LocalVault::new(1));
+        let (net_tx, net_rx) = mpsc::unbounded();
+        let config = client_config(vault.quic_p2p_config());
+        let mut cm = unwrap!(ConnectionManager::new(config, &net_tx));
+        cm.set_use_bootstrap_cache(false);
+
+        let full_id = SafeKey::client_from_bls_key(BlsSecretKey::random());
+        unwrap!(block_on_all(cm.bootstrap(full_id.clone())));
+        unwrap!(block_on_all(cm.restart_network()));
+
+        let event = unwrap!(block_on_all(net_rx.into_future().map_err(|_| ()))).0;
+        match event {
+            Some(NetworkEvent::Connected) => (),
+            event => panic!("Unexpected event {:?}", event),
+        }
+        create_and_get_balance(&mut cm, &full_id);
+    }
+
+    // Connect to all the elders of a local vault through the section map.
+    fn connect_to_all_elders(vault: &LocalVault, cm: &mut ConnectionManager, full_id: &SafeKey) {
+        cm.set_use_bootstrap_cache(false);
+        let mut section_map = SectionMap::default();
+        section_map.insert(Prefix::default(), vault.connection_info());
+        cm.set_section_map(section_map);
+
+        unwrap!(block_on_all(cm.bootstrap(full_id.clone())));
+    }
+
+    // 1. Connect to all three elders of a local vault, requiring all of them to agree.
+    // 2. Stop one of the elders.
+    // 3. Verify that requests still succeed: the elder is retried until it's known to be gone,
+    //    and then it no longer counts towards the quorum.
+    #[test]
+    fn elder_failover() {
+        let mut vault = unwrap!(LocalVault::new(3));
+        let (net_tx, _net_rx) = mpsc::unbounded();
+        let config = QuicP2pConfig {
+            our_type: OurType::Client,
+            ..Default::default()
+        };
+        let mut cm = unwrap!(ConnectionManager::new(client_config(config), &net_tx));
+        cm.set_quorum(Quorum::All);
+
+        let full_id = SafeKey::client_from_bls_key(BlsSecretKey::random());
+        connect_to_all_elders(&vault, &mut cm, &full_id);
+        create_and_get_balance(&mut cm, &full_id);
+
+        vault.stop_elder(0);
+
+        for _ in 0..3 {
+            match unwrap!(req(&mut cm, Request::GetBalance, &full_id, timeout())) {
+                Response::GetBalance(res) => {
+                    let _ = unwrap!(res);
+                }
+                res => panic!("Unexpected response {:?}", res),
+            }
+        }
+        assert_eq!(cm.requests_in_flight(), 0);
+    }
+
+    // 1. Connect to all the elders of a local vault, requiring all of them to agree.
+    // 2. Stop all of the elders.
+    // 3. Verify that requests fail with `EldersUnreachable` rather than waiting for a response.
+    #[test]
+    fn elders_unreachable() {
+        let mut vault = unwrap!(LocalVault::new(3));
+        let (net_tx, net_rx) = mpsc::unbounded();
+        let config = QuicP2pConfig {
+            our_type: OurType::Client,
+            ..Default::default()
+        };
+        let mut cm = unwrap!(ConnectionManager::new(client_config(config), &net_tx));
+        cm.set_quorum(Quorum::All);
+
+        let full_id = SafeKey::client_from_bls_key(BlsSecretKey::random());
+        connect_to_all_elders(&vault, &mut cm, &full_id);
+
+        for index in 0..3 {
+            vault.stop_elder(index);
+        }
+        let _ = unwrap!(block_on_all(net_rx.into_future().map_err(|_| ())));
+
+        match req(&mut cm, Request::GetBalance, &full_id, timeout()) {
+            Err(CoreError::EldersUnreachable) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+        assert_eq!(cm.requests_in_flight(), 0);
+    }
+
+    // 1. Start a local vault for the section of the client and another one for the section
+    //    managing a chunk, which only the second one holds.
+    // 2. Verify that getting the chunk is routed to the second section once it's connected,
+    //    without reporting anything about that connection to the client.
+    #[test]
+    fn route_to_other_section() {
+        let data = PubImmutableData::new(unwrap!(generate_random_vector(100)));
+        let other_prefix = Prefix::new(1, *data.name());
+
+        let own_vault = unwrap!(LocalVault::new(1));
+        let other_vault = unwrap!(LocalVault::new(1));
+        other_vault.insert_idata(data.clone().into());
+
+        let mut sections = SectionMap::default();
+        sections.insert(Prefix::default(), own_vault.connection_info());
+        sections.insert(other_prefix, other_vault.connection_info());
+        let config = Config {
+            quic_p2p: QuicP2pConfig {
+                our_type: OurType::Client,
+                ..Default::default()
+            },
+            sections,
+            ..Default::default()
+        };
+        let (net_tx, net_rx) = mpsc::unbounded();
+        let mut cm = unwrap!(ConnectionManager::new(config, &net_tx));
+        cm.set_use_bootstrap_cache(false);
+
+        let full_id = loop {
+            let full_id = SafeKey::client_from_bls_key(BlsSecretKey::random());
+            if !other_prefix.matches(full_id.public_id().name()) {
+                break full_id;
+            }
+        };
+        unwrap!(block_on_all(cm.bootstrap(full_id.clone())));
+
+        // Requests go through the section of the client until the other one is connected.
+        let mut fetched = None;
+        for _ in 0..50 {
+            let request = Request::GetIData(*data.address());
+            match unwrap!(req(&mut cm, request, &full_id, timeout())) {
+                Response::GetIData(Ok(idata)) => {
+                    fetched = Some(idata);
+                    break;
+                }
+                Response::GetIData(Err(SndError::NoSuchData)) => {
+                    thread::sleep(Duration::from_millis(POLL_INTERVAL_MS))
+                }
+                res => panic!("Unexpected response {:?}", res),
+            }
+        }
+        assert_eq!(unwrap!(fetched), IData::from(data));
+
+        drop(other_vault);
+        let event = block_on_all(
+            net_rx
+                .into_future()
+                .map_err(|_| ())
+                .timeout(Duration::from_secs(1)),
+        );
+        if let Ok((event, _)) = event {
+            panic!("Unexpected event {:?}", event);
+        }
+    }
+}
@@ -6,11 +6,21 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+#[cfg(not(feature = "mock-network"))]
+mod local_vault;
 #[cfg(feature = "mock-network")]
 mod sync;
+#[cfg(feature = "mock-network")]
+mod vault_fixtures;
 
+#[cfg(not(feature = "mock-network"))]
+pub use self::local_vault::LocalVault;
 #[cfg(feature = "mock-network")]
 pub use self::sync::Synchronizer;
+#[cfg(feature = "mock-network")]
+pub use self::vault_fixtures::{
+    export_vault_fixture, import_vault_fixture, restore_vault_snapshot, take_vault_snapshot,
+};
 use crate::client::core_client::CoreClient;
 use crate::client::Client;
 use crate::event::{NetworkEvent, NetworkTx};
@@ -0,0 +1,79 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Snapshots and fixtures of the mock vault. When the clients are configured to use a mock vault
+//! server, with the `SAFE_MOCK_VAULT_SOCKET` env var or the `mock_vault_socket` dev config
+//! option, they apply to the vault of the server rather than to the vault of this process.
+
+use crate::client::mock::{clone_vault, vault};
+#[cfg(unix)]
+use crate::client::mock::{init_vault_socket, VaultConnection};
+#[cfg(unix)]
+use crate::config_handler::get_config;
+use crate::errors::CoreError;
+use std::fs;
+use std::path::Path;
+
+/// Save the current state of the mock vault under the given name. Snapshots are kept in memory
+/// for the lifetime of the process serving the vault.
+pub fn take_vault_snapshot(name: &str) -> Result<(), CoreError> {
+    #[cfg(unix)]
+    {
+        if let Some(server) = vault_server()? {
+            return server.take_snapshot(name);
+        }
+    }
+    vault::lock(&clone_vault(), false).take_snapshot(name)
+}
+
+/// Restore the state of the mock vault saved by `take_vault_snapshot` under the given name.
+pub fn restore_vault_snapshot(name: &str) -> Result<(), CoreError> {
+    #[cfg(unix)]
+    {
+        if let Some(server) = vault_server()? {
+            return server.restore_snapshot(name);
+        }
+    }
+    vault::lock(&clone_vault(), true).restore_snapshot(name)
+}
+
+/// Write the state of the mock vault to a JSON fixture file.
+pub fn export_vault_fixture<P: AsRef<Path>>(path: P) -> Result<(), CoreError> {
+    #[cfg(unix)]
+    let json = match vault_server()? {
+        Some(server) => server.export_json()?,
+        None => vault::lock(&clone_vault(), false).export_json()?,
+    };
+    #[cfg(not(unix))]
+    let json = vault::lock(&clone_vault(), false).export_json()?;
+
+    fs::write(path, json)?;
+    Ok(())
+}
+
+/// Replace the state of the mock vault with a JSON fixture file written by
+/// `export_vault_fixture`, e.g. to start from a prepared account instead of registering one.
+pub fn import_vault_fixture<P: AsRef<Path>>(path: P) -> Result<(), CoreError> {
+    let json = fs::read_to_string(path)?;
+    #[cfg(unix)]
+    {
+        if let Some(server) = vault_server()? {
+            return server.import_json(&json);
+        }
+    }
+    vault::lock(&clone_vault(), true).import_json(&json)
+}
+
+// Connect to the mock vault server the clients are configured to use, if any.
+#[cfg(unix)]
+fn vault_server() -> Result<Option<VaultConnection>, CoreError> {
+    match init_vault_socket(&get_config()) {
+        Some(path) => Ok(Some(VaultConnection::connect(&path)?)),
+        None => Ok(None),
+    }
+}
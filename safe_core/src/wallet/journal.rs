@@ -0,0 +1,387 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::client::Client;
+use crate::errors::CoreError;
+use crate::event_loop::CoreFuture;
+use crate::utils::{files, FutureExt};
+use chrono::{DateTime, Utc};
+use futures::{future, Future};
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use safe_nd::{Coins, PublicKey, Transaction, XorName};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use threshold_crypto::SecretKey as BlsSecretKey;
+
+/// Transfer written to the journal before being sent.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// ID of the transaction, reused by every attempt to send the transfer.
+    pub transaction_id: u64,
+    /// Owner of the balance the coins are sent from, or `None` for the client's own balance.
+    pub source: Option<PublicKey>,
+    /// Balance the coins are sent to.
+    pub destination: XorName,
+    /// Amount of coins transferred.
+    pub amount: Coins,
+    /// Whether the network is known to have carried out the transfer. Until then, the transfer
+    /// is pending.
+    pub completed: bool,
+    /// When the transfer was first attempted.
+    pub created: DateTime<Utc>,
+}
+
+/// Durable journal of coin transfers, kept in a file on the local disk. `wallet::transfer_coins`
+/// sends the transfers of a wallet through it, and other transfers can be sent through it instead
+/// of `Client::transfer_coins`.
+///
+/// When a transfer times out, the client cannot tell whether the network carried it out. Sending
+/// transfers through the journal makes retrying them safe: the transfer and its transaction ID
+/// are written to the journal before the request is sent, and every retry reuses the ID, so the
+/// coins are sent at most once. The network rejecting the ID as already used is reported as a
+/// conflict, as the client can't tell whether it was used by an earlier attempt of the transfer.
+///
+/// The journal file is locked while it's changed, so it can be shared by several processes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransferJournal {
+    path: PathBuf,
+}
+
+impl TransferJournal {
+    /// Journal kept in the given file, which is created by the first transfer.
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Returns the transfers in the journal, ordered by transaction ID.
+    pub fn entries(&self) -> Result<Vec<JournalEntry>, CoreError> {
+        Ok(self.load()?.into_iter().map(|(_, entry)| entry).collect())
+    }
+
+    /// Returns the transfer journaled under the transaction ID, if any.
+    pub fn entry(&self, transaction_id: u64) -> Result<Option<JournalEntry>, CoreError> {
+        Ok(self.load()?.remove(&transaction_id))
+    }
+
+    /// Returns the transfers whose outcome is unknown.
+    pub fn pending(&self) -> Result<Vec<JournalEntry>, CoreError> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter(|entry| !entry.completed)
+            .collect())
+    }
+
+    /// Remove the completed transfers from the journal. Retrying one of them afterwards would
+    /// send it again.
+    pub fn remove_completed(&self) -> Result<(), CoreError> {
+        self.modify(|entries| {
+            entries.retain(|_, entry| !entry.completed);
+            Ok(())
+        })
+    }
+
+    /// Transfer coins like `Client::transfer_coins`, recording the transfer in the journal first.
+    ///
+    /// If `transaction_id` is `None`, a random one is picked and the transfer is always sent. To
+    /// retry a transfer, pass the ID of the first attempt again along with the same destination
+    /// and amount. A transfer already completed is then not sent again, and a pending one is sent
+    /// with the same ID.
+    pub fn transfer_coins(
+        &self,
+        client: &impl Client,
+        secret_key: Option<&BlsSecretKey>,
+        destination: XorName,
+        amount: Coins,
+        transaction_id: Option<u64>,
+    ) -> Box<CoreFuture<Transaction>> {
+        let entry = JournalEntry {
+            transaction_id: transaction_id.unwrap_or_else(new_rand::random),
+            source: secret_key.map(|secret_key| PublicKey::from(secret_key.public_key())),
+            destination,
+            amount,
+            completed: false,
+            created: Utc::now(),
+        };
+        let entry = fry!(self.begin(entry));
+
+        if entry.completed {
+            return ok!(Transaction {
+                id: entry.transaction_id,
+                amount: entry.amount,
+            });
+        }
+        self.send(client, secret_key, &entry)
+    }
+
+    /// Resolve the pending transfers from the balance controlled by `secret_key`, or from the
+    /// client's own balance if it's `None`, by sending each of them again with its transaction
+    /// ID. This should be called at startup, before making new transfers.
+    ///
+    /// Returns the outcome of each transfer. Those that fail because of the network rather than
+    /// being rejected by it stay pending. A transfer whose transaction ID is found to be already
+    /// used fails with `TransactionIdExists` and is removed from the journal, like any other
+    /// rejected transfer.
+    pub fn reconcile(
+        &self,
+        client: &impl Client,
+        secret_key: Option<&BlsSecretKey>,
+    ) -> Box<CoreFuture<Vec<(u64, Result<Transaction, CoreError>)>>> {
+        let source = secret_key.map(|secret_key| PublicKey::from(secret_key.public_key()));
+        let pending = fry!(self.pending());
+
+        let resolutions: Vec<_> = pending
+            .into_iter()
+            .filter(|entry| entry.source == source)
+            .map(|entry| {
+                let transaction_id = entry.transaction_id;
+                self.send(client, secret_key, &entry)
+                    .then(move |res| Ok((transaction_id, res)))
+            })
+            .collect();
+
+        future::join_all(resolutions).into_box()
+    }
+
+    // Send the journaled transfer, then mark it as completed if it went through, or remove it
+    // from the journal if the network rejected it.
+    fn send(
+        &self,
+        client: &impl Client,
+        secret_key: Option<&BlsSecretKey>,
+        entry: &JournalEntry,
+    ) -> Box<CoreFuture<Transaction>> {
+        let journal = self.clone();
+        let transaction_id = entry.transaction_id;
+        let amount = entry.amount;
+
+        client
+            .transfer_coins(secret_key, entry.destination, amount, Some(transaction_id))
+            .then(move |res| {
+                match res {
+                    Ok(transaction) => {
+                        journal.complete(transaction_id)?;
+                        Ok(transaction)
+                    }
+                    // Includes `TransactionIdExists`, as the ID may have been used by another
+                    // transfer.
+                    Err(CoreError::DataError(error)) => {
+                        journal.abandon(transaction_id)?;
+                        Err(CoreError::DataError(error))
+                    }
+                    Err(error) => Err(error),
+                }
+            })
+            .into_box()
+    }
+
+    // Write the transfer to the journal unless it's a retry, in which case the journaled
+    // transfer is returned. A retry must be for the same transfer as the first attempt.
+    fn begin(&self, entry: JournalEntry) -> Result<JournalEntry, CoreError> {
+        self.modify(|entries| {
+            if let Some(existing) = entries.get(&entry.transaction_id) {
+                if existing.source != entry.source
+                    || existing.destination != entry.destination
+                    || existing.amount != entry.amount
+                {
+                    return Err(CoreError::from(format!(
+                        "Transaction {} was journaled for a different transfer",
+                        entry.transaction_id
+                    )));
+                }
+                return Ok(existing.clone());
+            }
+
+            let _ = entries.insert(entry.transaction_id, entry.clone());
+            Ok(entry)
+        })
+    }
+
+    fn complete(&self, transaction_id: u64) -> Result<(), CoreError> {
+        self.modify(|entries| {
+            if let Some(entry) = entries.get_mut(&transaction_id) {
+                entry.completed = true;
+            }
+            Ok(())
+        })
+    }
+
+    fn abandon(&self, transaction_id: u64) -> Result<(), CoreError> {
+        self.modify(|entries| {
+            let _ = entries.remove(&transaction_id);
+            Ok(())
+        })
+    }
+
+    // Every change reads the journal and writes it back with the journal locked, so that
+    // transfers made concurrently, even by other processes, don't overwrite each other's entries.
+    fn modify<F, T>(&self, f: F) -> Result<T, CoreError>
+    where
+        F: FnOnce(&mut BTreeMap<u64, JournalEntry>) -> Result<T, CoreError>,
+    {
+        let _lock = files::lock_exclusive(&self.path)?;
+        let mut entries = self.load()?;
+        let result = f(&mut entries)?;
+        let contents = serialise(&entries)?;
+        files::write_atomically(&self.path, |file| Ok(file.write_all(&contents)?))?;
+        Ok(result)
+    }
+
+    fn load(&self) -> Result<BTreeMap<u64, JournalEntry>, CoreError> {
+        match fs::read(&self.path) {
+            Ok(contents) => Ok(deserialise(&contents)?),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::test_create_balance;
+    use crate::utils::test_utils::random_client;
+    use safe_nd::Error as SndError;
+    use std::str::FromStr;
+
+    // Test that retrying a transfer with the same ID doesn't send the coins twice, and that a
+    // retry must be for the same transfer.
+    #[test]
+    fn retry() {
+        let dir = unwrap!(tempfile::tempdir());
+        let journal = TransferJournal::new(dir.path().join("journal"));
+
+        let source_sk = BlsSecretKey::random();
+        let destination_sk = BlsSecretKey::random();
+        unwrap!(test_create_balance(
+            &source_sk,
+            unwrap!(Coins::from_str("50"))
+        ));
+        unwrap!(test_create_balance(
+            &destination_sk,
+            unwrap!(Coins::from_str("1"))
+        ));
+        let destination = XorName::from(PublicKey::from(destination_sk.public_key()));
+        let amount = unwrap!(Coins::from_str("10"));
+        let transaction_id = new_rand::random();
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let journal2 = journal.clone();
+            let journal3 = journal.clone();
+            let source_sk2 = source_sk.clone();
+            let source_sk3 = source_sk.clone();
+
+            journal
+                .transfer_coins(
+                    client,
+                    Some(&source_sk),
+                    destination,
+                    amount,
+                    Some(transaction_id),
+                )
+                .and_then(move |transaction| {
+                    assert_eq!(transaction.id, transaction_id);
+                    journal2.transfer_coins(
+                        &client2,
+                        Some(&source_sk2),
+                        destination,
+                        amount,
+                        Some(transaction_id),
+                    )
+                })
+                .and_then(move |transaction| {
+                    assert_eq!(transaction.amount, amount);
+                    assert!(unwrap!(journal3.pending()).is_empty());
+
+                    journal3
+                        .transfer_coins(
+                            &client3,
+                            Some(&source_sk3),
+                            destination,
+                            unwrap!(Coins::from_str("20")),
+                            Some(transaction_id),
+                        )
+                        .then(|res| match res {
+                            Err(CoreError::Unexpected(_)) => Ok(()),
+                            res => panic!("Unexpected result {:?}", res),
+                        })
+                })
+                .and_then(move |_| client4.get_balance(Some(&destination_sk)))
+                .map(|balance| assert_eq!(balance, unwrap!(Coins::from_str("11"))))
+        });
+    }
+
+    // Test resolving pending transfers: one that went through before its response was lost, whose
+    // transaction ID is then reported as used, and one rejected by the network. Both are removed
+    // from the journal, and the coins are sent only once.
+    #[test]
+    fn reconcile() {
+        let dir = unwrap!(tempfile::tempdir());
+        let journal = TransferJournal::new(dir.path().join("journal"));
+
+        let source_sk = BlsSecretKey::random();
+        let destination_sk = BlsSecretKey::random();
+        unwrap!(test_create_balance(
+            &source_sk,
+            unwrap!(Coins::from_str("50"))
+        ));
+        unwrap!(test_create_balance(
+            &destination_sk,
+            unwrap!(Coins::from_str("1"))
+        ));
+        let destination = XorName::from(PublicKey::from(destination_sk.public_key()));
+        let amount = unwrap!(Coins::from_str("10"));
+
+        let entry = |transaction_id, amount| JournalEntry {
+            transaction_id,
+            source: Some(PublicKey::from(source_sk.public_key())),
+            destination,
+            amount,
+            completed: false,
+            created: Utc::now(),
+        };
+        let (sent_id, rejected_id) = (new_rand::random(), new_rand::random());
+        let _ = unwrap!(journal.begin(entry(sent_id, amount)));
+        let _ = unwrap!(journal.begin(entry(rejected_id, unwrap!(Coins::from_str("1000")))));
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let journal2 = journal.clone();
+            let source_sk2 = source_sk.clone();
+
+            // The first transfer reaches the network without being marked as completed.
+            client
+                .transfer_coins(Some(&source_sk), destination, amount, Some(sent_id))
+                .and_then(move |_| journal2.reconcile(&client2, Some(&source_sk2)))
+                .and_then(move |resolutions| {
+                    assert_eq!(resolutions.len(), 2);
+                    for (transaction_id, res) in resolutions {
+                        match res {
+                            Err(CoreError::DataError(SndError::TransactionIdExists))
+                                if transaction_id == sent_id => {}
+                            Err(CoreError::DataError(SndError::InsufficientBalance))
+                                if transaction_id == rejected_id => {}
+                            res => panic!("Unexpected result {:?}", res),
+                        }
+                    }
+
+                    assert!(unwrap!(journal.entries()).is_empty());
+
+                    client3.get_balance(Some(&destination_sk))
+                })
+                .map(|balance| assert_eq!(balance, unwrap!(Coins::from_str("11"))))
+        });
+    }
+}
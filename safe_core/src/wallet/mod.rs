@@ -0,0 +1,736 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+mod journal;
+mod store;
+
+pub use self::journal::{JournalEntry, TransferJournal};
+pub use self::store::{WalletStore, WALLET_TAG};
+
+use crate::client::Client;
+use crate::errors::CoreError;
+use crate::event::NetworkNotification;
+use crate::event_loop::CoreFuture;
+use crate::utils::FutureExt;
+use chrono::{DateTime, Utc};
+use data_encoding::HEXLOWER;
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use safe_nd::{Coins, Error as SndError, PublicKey, Transaction, XorName};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use threshold_crypto::serde_impl::SerdeSecret;
+use threshold_crypto::{PublicKey as BlsPublicKey, SecretKey as BlsSecretKey};
+
+/// Whether a transfer was sent from one of the balances of a wallet or received by it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum TransferDirection {
+    /// Coins sent to another balance.
+    Sent,
+    /// Coins received from another balance.
+    Received,
+}
+
+/// Transfer kept in the history of a wallet.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TransferRecord {
+    /// ID of the transaction.
+    pub transaction_id: u64,
+    /// Whether the coins were sent or received.
+    pub direction: TransferDirection,
+    /// Name of the wallet's balance the coins were sent from or received by.
+    pub balance: String,
+    /// Balance the coins were sent to. The network doesn't tell where received coins come from.
+    pub counterparty: Option<XorName>,
+    /// Amount of coins transferred.
+    pub amount: Coins,
+    /// Note attached to the transfer by the user.
+    pub memo: Option<String>,
+    /// When the transfer was recorded.
+    pub timestamp: DateTime<Utc>,
+    /// Whether the transfer was recorded before being sent and its outcome isn't known yet.
+    pub pending: bool,
+}
+
+/// Format of an exported transfer history.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HistoryFormat {
+    /// Comma-separated values, with a header line.
+    Csv,
+    /// JSON array of transfers.
+    Json,
+}
+
+/// Coin balances controlled by the user, along with the history of their transfers. The wallet
+/// is kept encrypted in a `WalletStore`.
+///
+/// The recent transfers are kept in the wallet itself. On the network, older transfers are moved
+/// to pages of immutable data once they take too much space, so that the wallet stays within the
+/// size limits of mutable data entries.
+#[derive(Clone, Default, Deserialize)]
+pub struct Wallet {
+    balances: BTreeMap<String, BlsSecretKey>,
+    history: Vec<TransferRecord>,
+    pages: Vec<XorName>,
+    // Version of the wallet in the store it was loaded from, or `None` if it wasn't stored yet.
+    #[serde(skip)]
+    version: Option<u64>,
+}
+
+// threshold_crypto::SecretKey cannot be serialised directly,
+// hence this trait is implemented
+impl Serialize for Wallet {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let balances: BTreeMap<_, _> = self
+            .balances
+            .iter()
+            .map(|(name, secret_key)| (name, SerdeSecret(secret_key)))
+            .collect();
+
+        let mut state = serializer.serialize_struct("Wallet", 3)?;
+        state.serialize_field("balances", &balances)?;
+        state.serialize_field("history", &self.history)?;
+        state.serialize_field("pages", &self.pages)?;
+        state.end()
+    }
+}
+
+impl Wallet {
+    /// Create an empty wallet.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add the balance controlled by `secret_key` under the given name, which must not be taken
+    /// by another balance of the wallet.
+    pub fn add_balance(&mut self, name: &str, secret_key: BlsSecretKey) -> Result<(), CoreError> {
+        if self.balances.contains_key(name) {
+            return Err(CoreError::from(format!("Balance {} already exists", name)));
+        }
+        let _ = self.balances.insert(name.to_string(), secret_key);
+        Ok(())
+    }
+
+    /// Remove the named balance from the wallet, returning its secret key. Its transfers are kept
+    /// in the history.
+    pub fn remove_balance(&mut self, name: &str) -> Option<BlsSecretKey> {
+        self.balances.remove(name)
+    }
+
+    /// Returns the names of the balances along with their public keys.
+    pub fn balances(&self) -> Vec<(String, BlsPublicKey)> {
+        self.balances
+            .iter()
+            .map(|(name, secret_key)| (name.clone(), secret_key.public_key()))
+            .collect()
+    }
+
+    /// Returns the secret key of the named balance.
+    pub fn secret_key(&self, name: &str) -> Result<&BlsSecretKey, CoreError> {
+        self.balances
+            .get(name)
+            .ok_or_else(|| CoreError::from(format!("No balance named {}", name)))
+    }
+
+    /// Get the amount of coins held by the named balance.
+    pub fn get_balance(&self, client: &impl Client, name: &str) -> Box<CoreFuture<Coins>> {
+        let secret_key = fry!(self.secret_key(name)).clone();
+        client.get_balance(Some(&secret_key))
+    }
+
+    /// Transfer coins from the named balance. The returned transfer has yet to be added to the
+    /// history with `record`.
+    pub fn transfer_coins(
+        &self,
+        client: &impl Client,
+        from: &str,
+        destination: XorName,
+        amount: Coins,
+        memo: Option<String>,
+        transaction_id: Option<u64>,
+    ) -> Box<CoreFuture<TransferRecord>> {
+        let secret_key = fry!(self.secret_key(from)).clone();
+        let balance = from.to_string();
+
+        client
+            .transfer_coins(Some(&secret_key), destination, amount, transaction_id)
+            .map(move |transaction| TransferRecord {
+                transaction_id: transaction.id,
+                direction: TransferDirection::Sent,
+                balance,
+                counterparty: Some(destination),
+                amount: transaction.amount,
+                memo,
+                timestamp: Utc::now(),
+                pending: false,
+            })
+            .into_box()
+    }
+
+    /// Create a new balance owned by `new_balance_owner`, funded from the named balance. The
+    /// returned transfer has yet to be added to the history with `record`.
+    pub fn create_balance(
+        &self,
+        client: &impl Client,
+        from: &str,
+        new_balance_owner: PublicKey,
+        amount: Coins,
+        memo: Option<String>,
+        transaction_id: Option<u64>,
+    ) -> Box<CoreFuture<TransferRecord>> {
+        let secret_key = fry!(self.secret_key(from)).clone();
+        let balance = from.to_string();
+
+        client
+            .create_balance(Some(&secret_key), new_balance_owner, amount, transaction_id)
+            .map(move |transaction| TransferRecord {
+                transaction_id: transaction.id,
+                direction: TransferDirection::Sent,
+                balance,
+                counterparty: Some(XorName::from(new_balance_owner)),
+                amount: transaction.amount,
+                memo,
+                timestamp: Utc::now(),
+                pending: false,
+            })
+            .into_box()
+    }
+
+    /// Record a transfer received by the named balance, such as one announced by a
+    /// `NetworkNotification`.
+    pub fn record_received(
+        &mut self,
+        balance: &str,
+        transaction_id: u64,
+        amount: Coins,
+        memo: Option<String>,
+    ) -> Result<(), CoreError> {
+        let _ = self.secret_key(balance)?;
+        self.record(TransferRecord {
+            transaction_id,
+            direction: TransferDirection::Received,
+            balance: balance.to_string(),
+            counterparty: None,
+            amount,
+            memo,
+            timestamp: Utc::now(),
+            pending: false,
+        });
+        Ok(())
+    }
+
+    /// Record the transfer announced by the notification as received by the named balance.
+    pub fn record_notification(
+        &mut self,
+        balance: &str,
+        notification: &NetworkNotification,
+    ) -> Result<(), CoreError> {
+        match *notification {
+            NetworkNotification::TransferReceived {
+                transaction_id,
+                amount,
+            } => self.record_received(balance, transaction_id, amount, None),
+        }
+    }
+
+    /// Add the transfer to the history. A transfer already recorded for the same balance,
+    /// direction and transaction ID is not recorded again.
+    pub fn record(&mut self, record: TransferRecord) {
+        let recorded = self.history.iter().any(|existing| {
+            existing.transaction_id == record.transaction_id
+                && existing.direction == record.direction
+                && existing.balance == record.balance
+        });
+        if !recorded {
+            self.history.push(record);
+        }
+    }
+
+    /// Returns the transfers recorded since the older ones were moved to pages, oldest first.
+    /// Use `load_history` to get all of them.
+    pub fn history(&self) -> &[TransferRecord] {
+        &self.history
+    }
+
+    // Mark the pending transfer as completed, returning it.
+    fn complete(&mut self, transaction_id: u64, balance: &str) -> Option<TransferRecord> {
+        self.history
+            .iter_mut()
+            .find(|record| {
+                record.pending
+                    && record.transaction_id == transaction_id
+                    && record.balance == balance
+            })
+            .map(|record| {
+                record.pending = false;
+                record.clone()
+            })
+    }
+
+    // Remove the pending transfer, rejected by the network.
+    fn discard(&mut self, transaction_id: u64, balance: &str) {
+        self.history.retain(|record| {
+            !(record.pending
+                && record.transaction_id == transaction_id
+                && record.balance == balance)
+        });
+    }
+}
+
+/// Export the transfers, such as those returned by `Wallet::load_history`. Amounts are in coins,
+/// counterparties are hex-encoded and timestamps follow RFC 3339.
+pub fn export_history(
+    history: &[TransferRecord],
+    format: HistoryFormat,
+) -> Result<String, CoreError> {
+    let rows: Vec<_> = history.iter().map(ExportedTransfer::from).collect();
+    match format {
+        HistoryFormat::Csv => {
+            let mut csv =
+                "transaction_id,direction,balance,counterparty,amount,memo,timestamp,pending\n"
+                    .to_string();
+            for row in rows {
+                csv.push_str(&format!(
+                    "{},{},{},{},{},{},{},{}\n",
+                    row.transaction_id,
+                    row.direction,
+                    csv_field(&row.balance),
+                    row.counterparty,
+                    row.amount,
+                    csv_field(&row.memo),
+                    row.timestamp,
+                    row.pending
+                ));
+            }
+            Ok(csv)
+        }
+        HistoryFormat::Json => Ok(serde_json::to_string_pretty(&rows)?),
+    }
+}
+
+/// Load the wallet kept in `store`, apply `f` to it, then save it back unless `f` fails. If the
+/// wallet was changed concurrently in the meantime, `f` is applied again to the new wallet.
+pub fn update<C, F, T>(client: &C, store: WalletStore, f: F) -> Box<CoreFuture<T>>
+where
+    C: Client,
+    F: FnMut(&mut Wallet) -> Result<T, CoreError> + 'static,
+    T: 'static,
+{
+    let client = client.clone();
+
+    future::loop_fn(f, move |mut f| {
+        let client = client.clone();
+        let store = store.clone();
+
+        Wallet::load(&client, &store).and_then(move |mut wallet| {
+            let result = match f(&mut wallet) {
+                Ok(result) => result,
+                Err(error) => return Either::A(future::err(error)),
+            };
+            Either::B(wallet.save(&client, &store).then(move |res| match res {
+                Ok(_) => Ok(Loop::Break(result)),
+                Err(CoreError::DataError(SndError::InvalidSuccessor(_))) => Ok(Loop::Continue(f)),
+                Err(error) => Err(error),
+            }))
+        })
+    })
+    .into_box()
+}
+
+/// Transfer the coins from the named balance of the wallet kept in `store`, through `journal`.
+///
+/// The transfer is recorded as pending in the wallet's history before being sent, so that it
+/// isn't lost if the client stops in the meantime. It's then marked as completed, or removed from
+/// the history if the network rejects it. If sending fails for another reason, the transfer stays
+/// pending, as the network may have carried it out, until it's resolved by `reconcile`.
+pub fn transfer_coins<C: Client>(
+    client: &C,
+    store: WalletStore,
+    journal: TransferJournal,
+    from: String,
+    destination: XorName,
+    amount: Coins,
+    memo: Option<String>,
+) -> Box<CoreFuture<TransferRecord>> {
+    let client = client.clone();
+    let client2 = client.clone();
+    let transaction_id = new_rand::random();
+    let store2 = store.clone();
+    let from2 = from.clone();
+
+    update(&client, store, move |wallet| {
+        let secret_key = wallet.secret_key(&from)?.clone();
+        wallet.record(TransferRecord {
+            transaction_id,
+            direction: TransferDirection::Sent,
+            balance: from.clone(),
+            counterparty: Some(destination),
+            amount,
+            memo: memo.clone(),
+            timestamp: Utc::now(),
+            pending: true,
+        });
+        Ok(secret_key)
+    })
+    .and_then(move |secret_key| {
+        journal
+            .transfer_coins(
+                &client2,
+                Some(&secret_key),
+                destination,
+                amount,
+                Some(transaction_id),
+            )
+            .then(move |res| match res {
+                Ok(_) => Either::A(update(&client2, store2, move |wallet| {
+                    wallet.complete(transaction_id, &from2).ok_or_else(|| {
+                        CoreError::from(format!(
+                            "Transaction {} is missing from the wallet",
+                            transaction_id
+                        ))
+                    })
+                })),
+                Err(error @ CoreError::DataError(_)) => Either::B(
+                    update(&client2, store2, move |wallet| {
+                        wallet.discard(transaction_id, &from2);
+                        Ok(())
+                    })
+                    .and_then(move |_| Err(error)),
+                ),
+                Err(error) => Either::A(err!(error)),
+            })
+    })
+    .into_box()
+}
+
+/// Resolve the pending transfers of the wallet kept in `store`, sent through `journal` by
+/// `transfer_coins`. This should be called at startup, before making new transfers.
+///
+/// The transfers still pending in the journal are sent again with their transaction ID. The
+/// transfers which went through are marked as completed, and those rejected by the network or
+/// missing from the journal, which were never sent, are removed from the history. Those failing
+/// for another reason stay pending. Returns the outcome of each transfer.
+pub fn reconcile<C: Client>(
+    client: &C,
+    store: WalletStore,
+    journal: TransferJournal,
+) -> Box<CoreFuture<Vec<(u64, Result<Transaction, CoreError>)>>> {
+    let client = client.clone();
+    let client2 = client.clone();
+
+    Wallet::load(&client, &store)
+        .and_then(move |wallet| {
+            let resolutions: Vec<_> = wallet
+                .history
+                .iter()
+                .filter(|record| record.pending)
+                .map(|record| {
+                    let transaction_id = record.transaction_id;
+                    let balance = record.balance.clone();
+                    let resolution = match journal.entry(transaction_id) {
+                        Ok(Some(entry)) => resend(&client, &wallet, &journal, &entry, &balance),
+                        // The journal writes the transfers before sending them, so one missing
+                        // from it was never sent.
+                        Ok(None) => ok!((
+                            Outcome::Rejected,
+                            Err(CoreError::from(format!(
+                                "Transaction {} was never sent",
+                                transaction_id
+                            )))
+                        )),
+                        Err(error) => ok!((Outcome::Unknown, Err(error))),
+                    };
+                    resolution.map(move |(outcome, res)| (transaction_id, balance, outcome, res))
+                })
+                .collect();
+            future::join_all(resolutions)
+        })
+        .and_then(move |resolutions| {
+            let outcomes = resolutions
+                .iter()
+                .map(|&(transaction_id, ref balance, outcome, _)| {
+                    (transaction_id, balance.clone(), outcome)
+                })
+                .collect();
+            resolve(&client2, store, outcomes).map(move |_| {
+                resolutions
+                    .into_iter()
+                    .map(|(transaction_id, _, _, res)| (transaction_id, res))
+                    .collect()
+            })
+        })
+        .into_box()
+}
+
+// Send the journaled transfer from the named balance again, unless the journal has it as
+// completed. Never fails, as the outcome is returned instead.
+fn resend(
+    client: &impl Client,
+    wallet: &Wallet,
+    journal: &TransferJournal,
+    entry: &JournalEntry,
+    balance: &str,
+) -> Box<CoreFuture<(Outcome, Result<Transaction, CoreError>)>> {
+    let secret_key = match wallet.secret_key(balance) {
+        Ok(secret_key) => secret_key,
+        Err(error) => return ok!((Outcome::Unknown, Err(error))),
+    };
+    journal
+        .transfer_coins(
+            client,
+            Some(secret_key),
+            entry.destination,
+            entry.amount,
+            Some(entry.transaction_id),
+        )
+        .then(|res| Ok((Outcome::of(&res), res)))
+        .into_box()
+}
+
+// What sending a pending transfer of the wallet means for its record.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Outcome {
+    Completed,
+    Rejected,
+    Unknown,
+}
+
+impl Outcome {
+    fn of(res: &Result<Transaction, CoreError>) -> Self {
+        match *res {
+            Ok(_) => Outcome::Completed,
+            Err(CoreError::DataError(_)) => Outcome::Rejected,
+            Err(_) => Outcome::Unknown,
+        }
+    }
+}
+
+// Mark the pending transfers of the wallet as completed, or remove them if they were rejected.
+fn resolve<C: Client>(
+    client: &C,
+    store: WalletStore,
+    outcomes: Vec<(u64, String, Outcome)>,
+) -> Box<CoreFuture<()>> {
+    update(client, store, move |wallet| {
+        for &(transaction_id, ref balance, outcome) in &outcomes {
+            match outcome {
+                Outcome::Completed => {
+                    let _ = wallet.complete(transaction_id, balance);
+                }
+                Outcome::Rejected => wallet.discard(transaction_id, balance),
+                Outcome::Unknown => (),
+            }
+        }
+        Ok(())
+    })
+}
+
+// Transfer as exported, with every field readable by a human.
+#[derive(Serialize)]
+struct ExportedTransfer {
+    transaction_id: u64,
+    direction: &'static str,
+    balance: String,
+    counterparty: String,
+    amount: String,
+    memo: String,
+    timestamp: String,
+    pending: bool,
+}
+
+impl<'a> From<&'a TransferRecord> for ExportedTransfer {
+    fn from(record: &'a TransferRecord) -> Self {
+        Self {
+            transaction_id: record.transaction_id,
+            direction: match record.direction {
+                TransferDirection::Sent => "sent",
+                TransferDirection::Received => "received",
+            },
+            balance: record.balance.clone(),
+            counterparty: record
+                .counterparty
+                .map(|name| HEXLOWER.encode(&name.0))
+                .unwrap_or_default(),
+            amount: record.amount.to_string(),
+            memo: record.memo.clone().unwrap_or_default(),
+            timestamp: record.timestamp.to_rfc3339(),
+            pending: record.pending,
+        }
+    }
+}
+
+// Quote the field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::test_create_balance;
+    use crate::utils::test_utils::random_client;
+    use maidsafe_utilities::serialisation::{deserialise, serialise};
+    use std::str::FromStr;
+
+    fn record(transaction_id: u64, direction: TransferDirection, memo: &str) -> TransferRecord {
+        TransferRecord {
+            transaction_id,
+            direction,
+            balance: "main".to_string(),
+            counterparty: Some(XorName([1; 32])),
+            amount: unwrap!(Coins::from_nano(1_500_000_000)),
+            memo: Some(memo.to_string()),
+            timestamp: Utc::now(),
+            pending: false,
+        }
+    }
+
+    // Test managing balances, recording transfers without duplicates and serialising the wallet.
+    #[test]
+    fn balances_and_history() {
+        let mut wallet = Wallet::new();
+        let secret_key = BlsSecretKey::random();
+        let public_key = secret_key.public_key();
+
+        unwrap!(wallet.add_balance("main", secret_key.clone()));
+        assert!(wallet.add_balance("main", BlsSecretKey::random()).is_err());
+        assert_eq!(wallet.balances(), vec![("main".to_string(), public_key)]);
+
+        wallet.record(record(1, TransferDirection::Sent, "rent"));
+        wallet.record(record(1, TransferDirection::Sent, "rent"));
+        unwrap!(wallet.record_notification(
+            "main",
+            &NetworkNotification::TransferReceived {
+                transaction_id: 1,
+                amount: unwrap!(Coins::from_nano(10)),
+            }
+        ));
+        assert!(wallet
+            .record_received("savings", 2, unwrap!(Coins::from_nano(10)), None)
+            .is_err());
+        assert_eq!(wallet.history().len(), 2);
+
+        let wallet: Wallet = unwrap!(deserialise(&unwrap!(serialise(&wallet))));
+        assert_eq!(unwrap!(wallet.secret_key("main")), &secret_key);
+        assert_eq!(wallet.history().len(), 2);
+        assert_eq!(wallet.history()[1].direction, TransferDirection::Received);
+    }
+
+    // Test exporting the history in both formats.
+    #[test]
+    fn export() {
+        let mut wallet = Wallet::new();
+        let record = record(7, TransferDirection::Sent, "coffee, \"large\"");
+        let amount = record.amount;
+        wallet.record(record);
+
+        let csv = unwrap!(export_history(wallet.history(), HistoryFormat::Csv));
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with(&format!(
+            "7,sent,main,{},{},\"coffee, \"\"large\"\"\",",
+            HEXLOWER.encode(&[1u8; 32]),
+            amount
+        )));
+
+        let json: serde_json::Value = unwrap!(serde_json::from_str(&unwrap!(export_history(
+            wallet.history(),
+            HistoryFormat::Json
+        ))));
+        assert_eq!(json[0]["transaction_id"], 7);
+        assert_eq!(json[0]["memo"], "coffee, \"large\"");
+    }
+
+    // Test resolving the pending transfers of a wallet: one completed in the journal before the
+    // client stopped, and one which never made it to the journal.
+    #[test]
+    fn reconcile_pending() {
+        let dir = unwrap!(tempfile::tempdir());
+        let store = WalletStore::Local(dir.path().join("wallet"));
+        let journal = TransferJournal::new(dir.path().join("journal"));
+
+        let source_sk = BlsSecretKey::random();
+        let destination_sk = BlsSecretKey::random();
+        unwrap!(test_create_balance(
+            &source_sk,
+            unwrap!(Coins::from_str("50"))
+        ));
+        unwrap!(test_create_balance(
+            &destination_sk,
+            unwrap!(Coins::from_str("1"))
+        ));
+        let destination = XorName::from(PublicKey::from(destination_sk.public_key()));
+        let amount = unwrap!(Coins::from_str("10"));
+        let (sent_id, unsent_id) = (new_rand::random(), new_rand::random());
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let client5 = client.clone();
+            let store2 = store.clone();
+            let store3 = store.clone();
+            let journal2 = journal.clone();
+
+            update(client, store, move |wallet| {
+                wallet.add_balance("main", source_sk.clone())?;
+                for &transaction_id in &[sent_id, unsent_id] {
+                    wallet.record(TransferRecord {
+                        counterparty: Some(destination),
+                        amount,
+                        pending: true,
+                        ..record(transaction_id, TransferDirection::Sent, "rent")
+                    });
+                }
+                Ok(source_sk.clone())
+            })
+            .and_then(move |source_sk| {
+                journal.transfer_coins(
+                    &client2,
+                    Some(&source_sk),
+                    destination,
+                    amount,
+                    Some(sent_id),
+                )
+            })
+            .and_then(move |_| reconcile(&client3, store2, journal2))
+            .and_then(move |resolutions| {
+                assert_eq!(resolutions.len(), 2);
+                for (transaction_id, res) in resolutions {
+                    match res {
+                        Ok(ref transaction) if transaction_id == sent_id => {
+                            assert_eq!(transaction.amount, amount)
+                        }
+                        Err(CoreError::Unexpected(_)) if transaction_id == unsent_id => (),
+                        res => panic!("Unexpected result {:?}", res),
+                    }
+                }
+                Wallet::load(&client4, &store3)
+            })
+            .and_then(move |wallet| {
+                let history: Vec<_> = wallet
+                    .history()
+                    .iter()
+                    .map(|record| (record.transaction_id, record.pending))
+                    .collect();
+                assert_eq!(history, vec![(sent_id, false)]);
+
+                client5.get_balance(Some(&destination_sk))
+            })
+            .map(|balance| assert_eq!(balance, unwrap!(Coins::from_str("11"))))
+        });
+    }
+}
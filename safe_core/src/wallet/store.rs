@@ -0,0 +1,458 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{TransferRecord, Wallet};
+use crate::client::Client;
+use crate::crypto::shared_secretbox;
+use crate::errors::CoreError;
+use crate::event_loop::CoreFuture;
+use crate::immutable_data;
+use crate::utils::{self, files, FutureExt};
+use crate::MAIDSAFE_TAG;
+use futures::{future, Future};
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use safe_nd::{
+    EntryError, Error as SndError, IDataAddress, MDataAction, MDataPermissionSet,
+    MDataSeqEntryActions, MDataSeqValue, SeqMutableData, XorName,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tiny_keccak::sha3_256;
+
+/// Type tag of the mutable data wallets are stored in.
+pub const WALLET_TAG: u64 = MAIDSAFE_TAG + 1;
+
+const WALLET_KEY: &[u8] = b"wallet";
+
+// Serialised size of the history kept in the wallet's entry on the network beyond which the
+// completed transfers are moved to a page.
+const MAX_HISTORY_SIZE: usize = 64 * 1024;
+
+/// Where a wallet is kept. Either way, it's encrypted with the client's symmetric key.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WalletStore {
+    /// File on the local disk.
+    Local(PathBuf),
+    /// Entry of a sequenced mutable data named after the client's public key, so that every app
+    /// has its own wallet.
+    Network,
+}
+
+impl Wallet {
+    /// Load the wallet from the store, or return an empty wallet if the store holds none yet.
+    pub fn load(client: &impl Client, store: &WalletStore) -> Box<CoreFuture<Wallet>> {
+        let key = client.secret_symmetric_key();
+
+        match *store {
+            WalletStore::Local(ref path) => future::result(load_local(path, &key)).into_box(),
+            WalletStore::Network => {
+                let name = fry!(network_name(client));
+                client
+                    .get_seq_mdata_value(name, WALLET_TAG, WALLET_KEY.to_vec())
+                    .then(move |res| match res {
+                        Ok(value) => {
+                            let mut wallet: Wallet = decrypt(&value.data, &key)?;
+                            wallet.version = Some(value.version);
+                            Ok(wallet)
+                        }
+                        Err(CoreError::DataError(SndError::NoSuchData))
+                        | Err(CoreError::DataError(SndError::NoSuchEntry)) => Ok(Wallet::new()),
+                        Err(error) => Err(error),
+                    })
+                    .into_box()
+            }
+        }
+    }
+
+    /// Save the wallet to the store, replacing the one it was loaded from. The mutable data is
+    /// created the first time a wallet is saved to the network.
+    ///
+    /// Fails with `InvalidSuccessor` if the stored wallet was replaced since this one was loaded,
+    /// in which case it should be loaded and changed again. The wallet must also be loaded again
+    /// before being saved a second time.
+    pub fn save(&self, client: &impl Client, store: &WalletStore) -> Box<CoreFuture<()>> {
+        let key = client.secret_symmetric_key();
+
+        match *store {
+            WalletStore::Local(ref path) => future::result(save_local(path, self, &key)).into_box(),
+            WalletStore::Network => {
+                let name = fry!(network_name(client));
+                let client = client.clone();
+                let version = self.version;
+
+                seal_history(&client, self.clone())
+                    .and_then(move |wallet| {
+                        let cipher_text = fry!(encrypt(&wallet, &key));
+                        let actions = match version {
+                            Some(version) => MDataSeqEntryActions::new().update(
+                                WALLET_KEY.to_vec(),
+                                cipher_text.clone(),
+                                version + 1,
+                            ),
+                            None => MDataSeqEntryActions::new().ins(
+                                WALLET_KEY.to_vec(),
+                                cipher_text.clone(),
+                                0,
+                            ),
+                        };
+
+                        client
+                            .mutate_seq_mdata_entries(name, WALLET_TAG, actions)
+                            .or_else(move |error| match error {
+                                CoreError::DataError(SndError::NoSuchData) if version.is_none() => {
+                                    put_wallet_data(&client, name, cipher_text)
+                                }
+                                error => err!(entry_error(error)),
+                            })
+                            .into_box()
+                    })
+                    .into_box()
+            }
+        }
+    }
+
+    /// Returns all the recorded transfers, oldest first, including those moved to pages.
+    pub fn load_history(&self, client: &impl Client) -> Box<CoreFuture<Vec<TransferRecord>>> {
+        let key = client.secret_symmetric_key();
+        let pages: Vec<_> = self
+            .pages
+            .iter()
+            .map(|name| {
+                immutable_data::get_value(client, IDataAddress::Unpub(*name), Some(key.clone()))
+                    .and_then(|value| Ok(deserialise::<Vec<TransferRecord>>(&value)?))
+            })
+            .collect();
+        let recent = self.history.clone();
+
+        future::join_all(pages)
+            .map(move |pages| {
+                let mut history: Vec<_> = pages.into_iter().flatten().collect();
+                history.extend(recent);
+                history
+            })
+            .into_box()
+    }
+}
+
+// The wallet file holds the encrypted wallet along with its version.
+fn load_local(path: &Path, key: &shared_secretbox::Key) -> Result<Wallet, CoreError> {
+    match fs::read(path) {
+        Ok(cipher_text) => {
+            let (version, mut wallet): (u64, Wallet) = decrypt(&cipher_text, key)?;
+            wallet.version = Some(version);
+            Ok(wallet)
+        }
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(Wallet::new()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+// The file is locked while its version is checked and the wallet written, so that a wallet saved
+// by another process in the meantime isn't overwritten.
+fn save_local(path: &Path, wallet: &Wallet, key: &shared_secretbox::Key) -> Result<(), CoreError> {
+    let _lock = files::lock_exclusive(path)?;
+
+    let current = match fs::read(path) {
+        Ok(cipher_text) => Some(decrypt::<(u64, Wallet)>(&cipher_text, key)?.0),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => return Err(error.into()),
+    };
+    if current != wallet.version {
+        return Err(CoreError::DataError(SndError::InvalidSuccessor(
+            current.unwrap_or_default(),
+        )));
+    }
+
+    let version = wallet.version.map_or(0, |version| version + 1);
+    let cipher_text = encrypt(&(version, wallet), key)?;
+    files::write_atomically(path, |file| Ok(file.write_all(&cipher_text)?))
+}
+
+// Move the completed transfers to a page of unpublished immutable data once the history grows too
+// large to be kept in the wallet's entry. Pending transfers stay in the wallet, as they have yet to
+// be completed.
+fn seal_history(client: &impl Client, mut wallet: Wallet) -> Box<CoreFuture<Wallet>> {
+    if fry!(serialise(&wallet.history)).len() <= MAX_HISTORY_SIZE {
+        return ok!(wallet);
+    }
+    let (pending, page): (Vec<_>, Vec<_>) =
+        wallet.history.drain(..).partition(|record| record.pending);
+    wallet.history = pending;
+    if page.is_empty() {
+        return ok!(wallet);
+    }
+
+    let value = fry!(serialise(&page));
+    let client = client.clone();
+
+    immutable_data::create(&client, &value, false, Some(client.secret_symmetric_key()))
+        .and_then(move |data| {
+            let name = *data.name();
+            client.put_idata(data).map(move |_| {
+                wallet.pages.push(name);
+                wallet
+            })
+        })
+        .into_box()
+}
+
+fn put_wallet_data(
+    client: &impl Client,
+    name: XorName,
+    cipher_text: Vec<u8>,
+) -> Box<CoreFuture<()>> {
+    let mut entries = BTreeMap::new();
+    let _ = entries.insert(
+        WALLET_KEY.to_vec(),
+        MDataSeqValue {
+            data: cipher_text,
+            version: 0,
+        },
+    );
+    let mut permissions = BTreeMap::new();
+    let _ = permissions.insert(
+        client.public_key(),
+        MDataPermissionSet::new()
+            .allow(MDataAction::Read)
+            .allow(MDataAction::Insert)
+            .allow(MDataAction::Update),
+    );
+
+    client
+        .put_seq_mutable_data(SeqMutableData::new_with_data(
+            name,
+            WALLET_TAG,
+            entries,
+            permissions,
+            client.owner_key(),
+        ))
+        .map_err(|error| match error {
+            // Another client created the wallet first.
+            CoreError::DataError(SndError::DataExists) => {
+                CoreError::DataError(SndError::InvalidSuccessor(0))
+            }
+            error => error,
+        })
+        .into_box()
+}
+
+// As only the wallet's entry is mutated, report its error as `InvalidSuccessor` when it was
+// replaced or inserted concurrently.
+fn entry_error(error: CoreError) -> CoreError {
+    if let CoreError::DataError(SndError::InvalidEntryActions(ref errors)) = error {
+        match errors.get(WALLET_KEY) {
+            Some(EntryError::InvalidSuccessor(version))
+            | Some(EntryError::EntryExists(version)) => {
+                return CoreError::DataError(SndError::InvalidSuccessor((*version).into()));
+            }
+            _ => (),
+        }
+    }
+    error
+}
+
+fn network_name(client: &impl Client) -> Result<XorName, CoreError> {
+    let mut seed = serialise(&client.public_key())?;
+    seed.extend_from_slice(WALLET_KEY);
+    Ok(XorName(sha3_256(&seed)))
+}
+
+fn encrypt<T: Serialize>(value: &T, key: &shared_secretbox::Key) -> Result<Vec<u8>, CoreError> {
+    utils::symmetric_encrypt(&serialise(value)?, key, None)
+}
+
+fn decrypt<T: DeserializeOwned>(
+    cipher_text: &[u8],
+    key: &shared_secretbox::Key,
+) -> Result<T, CoreError> {
+    Ok(deserialise(&utils::symmetric_decrypt(cipher_text, key)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::random_client;
+    use crate::wallet::TransferDirection;
+    use threshold_crypto::SecretKey as BlsSecretKey;
+
+    // Test that a wallet saved to either store is loaded back, and that saving again replaces it.
+    #[test]
+    fn save_and_load() {
+        let dir = unwrap!(tempfile::tempdir());
+        let stores = vec![
+            WalletStore::Local(dir.path().join("wallet")),
+            WalletStore::Network,
+        ];
+
+        for store in stores {
+            random_client(move |client| {
+                let client2 = client.clone();
+                let client3 = client.clone();
+                let client4 = client.clone();
+                let client5 = client.clone();
+                let store2 = store.clone();
+                let store3 = store.clone();
+                let store4 = store.clone();
+                let store5 = store.clone();
+
+                Wallet::load(client, &store)
+                    .and_then(move |mut wallet| {
+                        assert!(wallet.balances().is_empty());
+                        unwrap!(wallet.add_balance("main", BlsSecretKey::random()));
+                        wallet.save(&client2, &store2)
+                    })
+                    .and_then(move |_| Wallet::load(&client3, &store3))
+                    .and_then(move |mut wallet| {
+                        assert_eq!(wallet.balances().len(), 1);
+                        unwrap!(wallet.record_received(
+                            "main",
+                            1,
+                            unwrap!(safe_nd::Coins::from_nano(10)),
+                            Some("gift".to_string())
+                        ));
+                        wallet.save(&client4, &store4)
+                    })
+                    .and_then(move |_| Wallet::load(&client5, &store5))
+                    .map(|wallet| {
+                        assert_eq!(wallet.history().len(), 1);
+                        assert_eq!(wallet.history()[0].direction, TransferDirection::Received);
+                    })
+            });
+        }
+    }
+
+    // Test that saving a wallet replaced since it was loaded fails, in either store.
+    #[test]
+    fn concurrent_save() {
+        let dir = unwrap!(tempfile::tempdir());
+        let stores = vec![
+            WalletStore::Local(dir.path().join("wallet")),
+            WalletStore::Network,
+        ];
+
+        for store in stores {
+            random_client(move |client| {
+                let client2 = client.clone();
+                let client3 = client.clone();
+                let store2 = store.clone();
+                let store3 = store.clone();
+
+                Wallet::load(client, &store)
+                    .join(Wallet::load(client, &store))
+                    .and_then(move |(mut first, mut second)| {
+                        unwrap!(first.add_balance("first", BlsSecretKey::random()));
+                        unwrap!(second.add_balance("second", BlsSecretKey::random()));
+
+                        first
+                            .save(&client2, &store2)
+                            .and_then(move |_| second.save(&client2, &store2))
+                    })
+                    .then(|res| match res {
+                        Err(CoreError::DataError(SndError::InvalidSuccessor(_))) => Ok(()),
+                        res => panic!("Unexpected result {:?}", res),
+                    })
+                    .and_then(move |_| Wallet::load(&client3, &store3))
+                    .map(|wallet| {
+                        assert_eq!(wallet.balances().len(), 1);
+                        assert!(wallet.secret_key("first").is_ok());
+                    })
+            });
+        }
+    }
+
+    // Test that older transfers are moved to a page once the history grows too large, and that
+    // they're still part of the loaded history.
+    #[test]
+    fn history_pages() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let memo = "x".repeat(MAX_HISTORY_SIZE);
+
+            Wallet::load(client, &WalletStore::Network)
+                .and_then(move |mut wallet| {
+                    unwrap!(wallet.add_balance("main", BlsSecretKey::random()));
+                    for transaction_id in 0..2 {
+                        unwrap!(wallet.record_received(
+                            "main",
+                            transaction_id,
+                            unwrap!(safe_nd::Coins::from_nano(10)),
+                            Some(memo.clone())
+                        ));
+                    }
+                    wallet.save(&client2, &WalletStore::Network)
+                })
+                .and_then(move |_| Wallet::load(&client3, &WalletStore::Network))
+                .and_then(move |wallet| {
+                    assert!(wallet.history().is_empty());
+                    assert_eq!(wallet.pages.len(), 1);
+                    wallet.load_history(&client4)
+                })
+                .map(|history| {
+                    let ids: Vec<_> = history.iter().map(|record| record.transaction_id).collect();
+                    assert_eq!(ids, vec![0, 1]);
+                })
+        });
+    }
+
+    // Test that a pending transfer recorded before the completed ones stays in the wallet, while
+    // the completed transfers are still moved to a page.
+    #[test]
+    fn history_pages_skip_pending() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let memo = "x".repeat(MAX_HISTORY_SIZE);
+
+            Wallet::load(client, &WalletStore::Network)
+                .and_then(move |mut wallet| {
+                    unwrap!(wallet.add_balance("main", BlsSecretKey::random()));
+                    wallet.record(TransferRecord {
+                        transaction_id: 0,
+                        direction: TransferDirection::Sent,
+                        balance: "main".to_string(),
+                        counterparty: Some(XorName(rand::random())),
+                        amount: unwrap!(safe_nd::Coins::from_nano(10)),
+                        memo: None,
+                        timestamp: chrono::Utc::now(),
+                        pending: true,
+                    });
+                    for transaction_id in 1..3 {
+                        unwrap!(wallet.record_received(
+                            "main",
+                            transaction_id,
+                            unwrap!(safe_nd::Coins::from_nano(10)),
+                            Some(memo.clone())
+                        ));
+                    }
+                    wallet.save(&client2, &WalletStore::Network)
+                })
+                .and_then(move |_| Wallet::load(&client3, &WalletStore::Network))
+                .and_then(move |wallet| {
+                    let ids: Vec<_> = wallet
+                        .history()
+                        .iter()
+                        .map(|record| (record.transaction_id, record.pending))
+                        .collect();
+                    assert_eq!(ids, vec![(0, true)]);
+                    assert_eq!(wallet.pages.len(), 1);
+                    wallet.load_history(&client4)
+                })
+                .map(|history| {
+                    let ids: Vec<_> = history.iter().map(|record| record.transaction_id).collect();
+                    assert_eq!(ids, vec![1, 2, 0]);
+                })
+        });
+    }
+}
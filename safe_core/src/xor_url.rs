@@ -0,0 +1,520 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::client::Client;
+use crate::errors::CoreError;
+use crate::event_loop::CoreFuture;
+use crate::ffi::xor_url::XorUrl as FfiXorUrl;
+use crate::immutable_data;
+use crate::ipc::IpcError;
+use crate::utils::FutureExt;
+use data_encoding::BASE32_NOPAD;
+use ffi_utils::{from_c_str, ReprC};
+use futures::Future;
+use safe_nd::{AData, ADataAddress, IDataAddress, MData, MDataAddress, XorName, XOR_NAME_LEN};
+use std::ffi::CString;
+use std::ptr;
+use std::str;
+
+/// Scheme of XOR-URLs.
+pub const XOR_URL_SCHEME: &str = "safe://";
+/// Version of the XOR-URL encoding, stored in the first byte of every encoded URL.
+pub const XOR_URL_VERSION: u8 = 1;
+
+/// Data kind of XOR-URLs referencing immutable data.
+pub const XOR_URL_IDATA: u8 = 0;
+/// Data kind of XOR-URLs referencing mutable data.
+pub const XOR_URL_MDATA: u8 = 1;
+/// Data kind of XOR-URLs referencing append-only data.
+pub const XOR_URL_ADATA: u8 = 2;
+
+const PUBLISHED_FLAG: u8 = 0b01;
+const SEQUENCED_FLAG: u8 = 0b10;
+
+// Version, kind and flags, followed by the name and the type tag.
+const HEADER_LEN: usize = 3 + XOR_NAME_LEN + 8;
+
+/// Address of the data referenced by an XOR-URL.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum XorUrlAddress {
+    /// Immutable data.
+    IData(IDataAddress),
+    /// Mutable data.
+    MData(MDataAddress),
+    /// Append-only data.
+    AData(ADataAddress),
+}
+
+impl XorUrlAddress {
+    /// Returns the kind of data, as one of the `XOR_URL_*DATA` constants.
+    pub fn kind(&self) -> u8 {
+        match *self {
+            XorUrlAddress::IData(_) => XOR_URL_IDATA,
+            XorUrlAddress::MData(_) => XOR_URL_MDATA,
+            XorUrlAddress::AData(_) => XOR_URL_ADATA,
+        }
+    }
+
+    /// Returns the name of the data.
+    pub fn name(&self) -> XorName {
+        match *self {
+            XorUrlAddress::IData(ref address) => *address.name(),
+            XorUrlAddress::MData(ref address) => *address.name(),
+            XorUrlAddress::AData(ref address) => *address.name(),
+        }
+    }
+
+    /// Returns the type tag of the data, which is zero for immutable data.
+    pub fn type_tag(&self) -> u64 {
+        match *self {
+            XorUrlAddress::IData(_) => 0,
+            XorUrlAddress::MData(ref address) => address.tag(),
+            XorUrlAddress::AData(ref address) => address.tag(),
+        }
+    }
+
+    /// Returns whether the data is published. Mutable data never is.
+    pub fn published(&self) -> bool {
+        match *self {
+            XorUrlAddress::IData(IDataAddress::Pub(_))
+            | XorUrlAddress::AData(ADataAddress::PubSeq { .. })
+            | XorUrlAddress::AData(ADataAddress::PubUnseq { .. }) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns whether the data is sequenced. Immutable data never is.
+    pub fn sequenced(&self) -> bool {
+        match *self {
+            XorUrlAddress::MData(MDataAddress::Seq { .. })
+            | XorUrlAddress::AData(ADataAddress::PubSeq { .. })
+            | XorUrlAddress::AData(ADataAddress::UnpubSeq { .. }) => true,
+            _ => false,
+        }
+    }
+
+    /// Construct the address from its parts, failing if they don't describe any data.
+    pub fn from_parts(
+        kind: u8,
+        name: XorName,
+        type_tag: u64,
+        published: bool,
+        sequenced: bool,
+    ) -> Result<Self, IpcError> {
+        let address = match (kind, published, sequenced) {
+            (XOR_URL_IDATA, true, false) if type_tag == 0 => {
+                XorUrlAddress::IData(IDataAddress::Pub(name))
+            }
+            (XOR_URL_IDATA, false, false) if type_tag == 0 => {
+                XorUrlAddress::IData(IDataAddress::Unpub(name))
+            }
+            (XOR_URL_MDATA, false, true) => XorUrlAddress::MData(MDataAddress::Seq {
+                name,
+                tag: type_tag,
+            }),
+            (XOR_URL_MDATA, false, false) => XorUrlAddress::MData(MDataAddress::Unseq {
+                name,
+                tag: type_tag,
+            }),
+            (XOR_URL_ADATA, true, true) => XorUrlAddress::AData(ADataAddress::PubSeq {
+                name,
+                tag: type_tag,
+            }),
+            (XOR_URL_ADATA, true, false) => XorUrlAddress::AData(ADataAddress::PubUnseq {
+                name,
+                tag: type_tag,
+            }),
+            (XOR_URL_ADATA, false, true) => XorUrlAddress::AData(ADataAddress::UnpubSeq {
+                name,
+                tag: type_tag,
+            }),
+            (XOR_URL_ADATA, false, false) => XorUrlAddress::AData(ADataAddress::UnpubUnseq {
+                name,
+                tag: type_tag,
+            }),
+            _ => return Err(IpcError::InvalidMsg),
+        };
+        Ok(address)
+    }
+}
+
+impl From<IDataAddress> for XorUrlAddress {
+    fn from(address: IDataAddress) -> Self {
+        XorUrlAddress::IData(address)
+    }
+}
+
+impl From<MDataAddress> for XorUrlAddress {
+    fn from(address: MDataAddress) -> Self {
+        XorUrlAddress::MData(address)
+    }
+}
+
+impl From<ADataAddress> for XorUrlAddress {
+    fn from(address: ADataAddress) -> Self {
+        XorUrlAddress::AData(address)
+    }
+}
+
+/// URL referencing data on the network by its address, such as `safe://bae...`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct XorUrl {
+    /// Address of the data.
+    pub address: XorUrlAddress,
+    /// MIME type of the content, as a hint for displaying it.
+    pub content_type: Option<String>,
+}
+
+impl XorUrl {
+    /// Create a URL referencing the data at the given address.
+    pub fn new<A: Into<XorUrlAddress>>(address: A, content_type: Option<String>) -> Self {
+        Self {
+            address: address.into(),
+            content_type,
+        }
+    }
+
+    /// Encode the URL into its canonical form: the scheme followed by the multibase-prefixed,
+    /// lowercase base32 encoding of the version byte, the kind and flags, the name, the type tag
+    /// and the content type.
+    pub fn encode(&self) -> Result<String, IpcError> {
+        let mut flags = 0;
+        if self.address.published() {
+            flags |= PUBLISHED_FLAG;
+        }
+        if self.address.sequenced() {
+            flags |= SEQUENCED_FLAG;
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.push(XOR_URL_VERSION);
+        bytes.push(self.address.kind());
+        bytes.push(flags);
+        bytes.extend_from_slice(&self.address.name().0);
+        bytes.extend_from_slice(&self.address.type_tag().to_be_bytes());
+
+        if let Some(ref content_type) = self.content_type {
+            // An empty content type couldn't be told apart from none.
+            if content_type.is_empty() {
+                return Err(IpcError::from("Empty content type"));
+            }
+            bytes.extend_from_slice(content_type.as_bytes());
+        }
+
+        Ok(format!(
+            "{}b{}",
+            XOR_URL_SCHEME,
+            BASE32_NOPAD.encode(&bytes).to_lowercase()
+        ))
+    }
+
+    /// Decode a URL encoded with `encode`. The scheme and the encoding are case-insensitive.
+    pub fn decode(url: &str) -> Result<Self, IpcError> {
+        if url.len() < XOR_URL_SCHEME.len()
+            || !url.is_char_boundary(XOR_URL_SCHEME.len())
+            || !url[..XOR_URL_SCHEME.len()].eq_ignore_ascii_case(XOR_URL_SCHEME)
+        {
+            return Err(IpcError::InvalidMsg);
+        }
+
+        let mut chars = url[XOR_URL_SCHEME.len()..].chars();
+        let bytes = match chars.next().ok_or(IpcError::InvalidMsg)? {
+            // Encoded as base32
+            'b' | 'B' => BASE32_NOPAD.decode(chars.as_str().to_uppercase().as_bytes())?,
+            // Fail if not encoded as base32
+            _ => return Err(IpcError::EncodeDecodeError),
+        };
+
+        if bytes.len() < HEADER_LEN {
+            return Err(IpcError::InvalidMsg);
+        }
+        if bytes[0] != XOR_URL_VERSION {
+            return Err(IpcError::from(format!(
+                "Unsupported XOR-URL version {}",
+                bytes[0]
+            )));
+        }
+
+        let (kind, flags) = (bytes[1], bytes[2]);
+        if flags & !(PUBLISHED_FLAG | SEQUENCED_FLAG) != 0 {
+            return Err(IpcError::InvalidMsg);
+        }
+        let mut name = [0; XOR_NAME_LEN];
+        name.copy_from_slice(&bytes[3..3 + XOR_NAME_LEN]);
+        let mut type_tag = [0; 8];
+        type_tag.copy_from_slice(&bytes[3 + XOR_NAME_LEN..HEADER_LEN]);
+
+        let address = XorUrlAddress::from_parts(
+            kind,
+            XorName(name),
+            u64::from_be_bytes(type_tag),
+            flags & PUBLISHED_FLAG != 0,
+            flags & SEQUENCED_FLAG != 0,
+        )?;
+        let content_type = if bytes.len() > HEADER_LEN {
+            Some(str::from_utf8(&bytes[HEADER_LEN..])?.to_string())
+        } else {
+            None
+        };
+
+        Ok(Self {
+            address,
+            content_type,
+        })
+    }
+
+    /// Construct FFI wrapper for the native Rust object, consuming self.
+    pub fn into_repr_c(self) -> Result<FfiXorUrl, IpcError> {
+        Ok(FfiXorUrl {
+            data_kind: self.address.kind(),
+            name: self.address.name().0,
+            type_tag: self.address.type_tag(),
+            published: self.address.published(),
+            sequenced: self.address.sequenced(),
+            content_type: match self.content_type {
+                Some(content_type) => CString::new(content_type)?.into_raw(),
+                None => ptr::null(),
+            },
+        })
+    }
+}
+
+impl ReprC for XorUrl {
+    type C = *const FfiXorUrl;
+    type Error = IpcError;
+
+    unsafe fn clone_from_repr_c(repr_c: Self::C) -> Result<Self, Self::Error> {
+        let repr_c = &*repr_c;
+
+        Ok(Self {
+            address: XorUrlAddress::from_parts(
+                repr_c.data_kind,
+                XorName(repr_c.name),
+                repr_c.type_tag,
+                repr_c.published,
+                repr_c.sequenced,
+            )?,
+            content_type: if repr_c.content_type.is_null() {
+                None
+            } else {
+                Some(from_c_str(repr_c.content_type)?)
+            },
+        })
+    }
+}
+
+/// Data fetched by resolving an XOR-URL.
+#[derive(Debug)]
+pub enum ResolvedData {
+    /// Value of immutable data stored by `immutable_data::create`, or the content of a published
+    /// chunk stored otherwise.
+    IData(Vec<u8>),
+    /// Mutable data.
+    MData(MData),
+    /// Append-only data.
+    AData(AData),
+}
+
+/// Fetch the data referenced by the URL. An unregistered client can resolve URLs of published
+/// data; other data is fetched only if the client is allowed to read it. Unpublished immutable
+/// data whose value was encrypted can't be resolved, as the URL doesn't carry the key.
+pub fn resolve(client: &impl Client, url: &XorUrl) -> Box<CoreFuture<ResolvedData>> {
+    match url.address {
+        XorUrlAddress::IData(address) => {
+            let client = client.clone();
+            client
+                .get_idata(address)
+                .and_then(move |data| {
+                    // The data wasn't stored by `immutable_data::create`, so it holds the value
+                    // itself.
+                    if data.is_pub() && !immutable_data::is_encoded(&data) {
+                        return ok!(data.value().clone());
+                    }
+                    let published = data.is_pub();
+                    immutable_data::extract_value(&client, &data, None)
+                        .map_err(move |error| match error {
+                            // The value of unpublished data is encrypted with a key the URL
+                            // doesn't carry.
+                            CoreError::EncodeDecodeError(_) if !published => {
+                                CoreError::SymmetricDecipherFailure
+                            }
+                            error => error,
+                        })
+                        .into_box()
+                })
+                .map(ResolvedData::IData)
+                .into_box()
+        }
+        XorUrlAddress::MData(MDataAddress::Seq { name, tag }) => client
+            .get_seq_mdata(name, tag)
+            .map(|data| ResolvedData::MData(MData::Seq(data)))
+            .into_box(),
+        XorUrlAddress::MData(MDataAddress::Unseq { name, tag }) => client
+            .get_unseq_mdata(name, tag)
+            .map(|data| ResolvedData::MData(MData::Unseq(data)))
+            .into_box(),
+        XorUrlAddress::AData(address) => client
+            .get_adata(address)
+            .map(ResolvedData::AData)
+            .into_box(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::shared_secretbox;
+    use crate::utils::test_utils::random_client;
+    use safe_nd::{MDataAction, MDataPermissionSet, PubImmutableData, UnseqMutableData};
+    use std::collections::BTreeMap;
+
+    // Test that every kind of address survives encoding, with and without a content type, and
+    // that malformed URLs are rejected.
+    #[test]
+    fn encode_decode() {
+        let name = XorName(rand::random());
+        let addresses: Vec<XorUrlAddress> = vec![
+            IDataAddress::Pub(name).into(),
+            IDataAddress::Unpub(name).into(),
+            MDataAddress::Seq { name, tag: 15_000 }.into(),
+            MDataAddress::Unseq { name, tag: 15_001 }.into(),
+            ADataAddress::PubSeq { name, tag: 1 }.into(),
+            ADataAddress::PubUnseq { name, tag: 2 }.into(),
+            ADataAddress::UnpubSeq { name, tag: 3 }.into(),
+            ADataAddress::UnpubUnseq {
+                name,
+                tag: u64::max_value(),
+            }
+            .into(),
+        ];
+
+        for address in addresses {
+            for content_type in vec![None, Some("text/html".to_string())] {
+                let url = XorUrl::new(address.clone(), content_type);
+                let encoded = unwrap!(url.encode());
+                assert!(encoded.starts_with("safe://b"));
+                assert_eq!(encoded, encoded.to_lowercase());
+                assert_eq!(unwrap!(XorUrl::decode(&encoded)), url);
+                assert_eq!(unwrap!(XorUrl::decode(&encoded.to_uppercase())), url);
+            }
+        }
+
+        let encoded = unwrap!(XorUrl::new(IDataAddress::Pub(name), None).encode());
+        assert!(XorUrl::decode(&encoded["safe://".len()..]).is_err());
+        assert!(XorUrl::decode(&encoded.replace("safe://b", "safe://z")).is_err());
+        assert!(XorUrl::decode(&encoded[..encoded.len() - 8]).is_err());
+
+        // A newer version can't be decoded.
+        let mut bytes = unwrap!(BASE32_NOPAD.decode(encoded[8..].to_uppercase().as_bytes()));
+        bytes[0] = XOR_URL_VERSION + 1;
+        let encoded = format!("safe://b{}", BASE32_NOPAD.encode(&bytes));
+        assert!(XorUrl::decode(&encoded).is_err());
+
+        // Immutable data can't be sequenced.
+        bytes[0] = XOR_URL_VERSION;
+        bytes[2] = SEQUENCED_FLAG;
+        let encoded = format!("safe://b{}", BASE32_NOPAD.encode(&bytes));
+        assert!(XorUrl::decode(&encoded).is_err());
+    }
+
+    // Test that the FFI representation converts back to the same URL.
+    #[test]
+    fn repr_c() {
+        let url = XorUrl::new(
+            ADataAddress::PubSeq {
+                name: XorName(rand::random()),
+                tag: 100,
+            },
+            Some("image/png".to_string()),
+        );
+        let repr_c = unwrap!(url.clone().into_repr_c());
+        assert_eq!(unwrap!(unsafe { XorUrl::clone_from_repr_c(&repr_c) }), url);
+    }
+
+    // Test resolving URLs of immutable data, including a chunk put as is, and of mutable data.
+    #[test]
+    fn resolve_urls() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let client5 = client.clone();
+            let client6 = client.clone();
+            let client7 = client.clone();
+            let value = vec![1, 2, 3];
+
+            // A chunk put as is rather than through `immutable_data::create`.
+            let chunk_value = b"raw chunk".to_vec();
+            let chunk = PubImmutableData::new(chunk_value.clone());
+            let chunk_url = XorUrl::new(*chunk.address(), None);
+
+            let mut permissions = BTreeMap::new();
+            let _ = permissions.insert(
+                client.public_key(),
+                MDataPermissionSet::new().allow(MDataAction::Read),
+            );
+            let mdata = UnseqMutableData::new_with_data(
+                XorName(rand::random()),
+                15_000,
+                BTreeMap::new(),
+                permissions,
+                client.owner_key(),
+            );
+            let mdata_url = XorUrl::new(*mdata.address(), None);
+
+            immutable_data::create(client, &value, true, None)
+                .and_then(move |data| {
+                    let url = XorUrl::new(*data.address(), Some("text/plain".to_string()));
+                    client2.put_idata(data).map(move |_| url)
+                })
+                .and_then(move |url| resolve(&client3, &url))
+                .and_then(move |resolved| {
+                    match resolved {
+                        ResolvedData::IData(resolved) => assert_eq!(resolved, value),
+                        resolved => panic!("Unexpected data {:?}", resolved),
+                    }
+                    client4.put_unseq_mutable_data(mdata)
+                })
+                .and_then(move |_| resolve(&client5, &mdata_url))
+                .and_then(move |resolved| {
+                    match resolved {
+                        ResolvedData::MData(MData::Unseq(data)) => assert_eq!(data.tag(), 15_000),
+                        resolved => panic!("Unexpected data {:?}", resolved),
+                    }
+                    client6.put_idata(chunk)
+                })
+                .and_then(move |_| resolve(&client7, &chunk_url))
+                .map(move |resolved| match resolved {
+                    ResolvedData::IData(resolved) => assert_eq!(resolved, chunk_value),
+                    resolved => panic!("Unexpected data {:?}", resolved),
+                })
+        });
+    }
+
+    // Test that resolving unpublished immutable data whose value is encrypted fails rather than
+    // returning the cipher text.
+    #[test]
+    fn resolve_encrypted_url() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let key = shared_secretbox::gen_key();
+
+            immutable_data::create(client, &[1, 2, 3], false, Some(key))
+                .and_then(move |data| {
+                    let url = XorUrl::new(*data.address(), None);
+                    client2.put_idata(data).map(move |_| url)
+                })
+                .and_then(move |url| resolve(&client3, &url))
+                .then(|res| match res {
+                    Err(CoreError::SymmetricDecipherFailure) => Ok::<_, CoreError>(()),
+                    res => panic!("Unexpected result {:?}", res),
+                })
+        });
+    }
+}